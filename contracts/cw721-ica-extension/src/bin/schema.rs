@@ -1,7 +1,7 @@
 use cosmwasm_schema::write_api;
 
 use cw721_base::msg::QueryMsg as BaseQueryMsg;
-use cw721_ica_extension::{ExecuteMsg, Extension, InstantiateMsg};
+use cw721_ica_extension::{ExecuteMsg, Extension, InstantiateMsg, MigrateMsg};
 
 type QueryMsg = BaseQueryMsg<Extension>;
 
@@ -10,5 +10,6 @@ fn main() {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
+        migrate: MigrateMsg,
     }
 }
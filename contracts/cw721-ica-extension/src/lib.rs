@@ -2,8 +2,9 @@
 #![deny(missing_docs)]
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Empty};
+use cosmwasm_std::{Addr, CustomMsg, Empty};
 pub use cw721_base::{ContractError, InstantiateMsg, MinterResponse};
+use cw_storage_plus::Item;
 
 // Version info for migration
 /// Name of the contract for cw2
@@ -11,6 +12,10 @@ pub const CONTRACT_NAME: &str = "crates.io:cw721-ica-extension";
 /// Version of the contract for cw2
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Whether the minter is notified of the transfers of the tokens.
+/// Disabled by default, since the minter must accept the [`MinterHookMsg`].
+pub const TRANSFER_HOOK_ENABLED: Item<bool> = Item::new("transfer_hook_enabled");
+
 /// This is the ICA extension data that is stored with each token
 #[cw_serde]
 pub struct Extension {
@@ -18,13 +23,75 @@ pub struct Extension {
     pub ica_controller_address: Addr,
     /// The ICA address in the counterparty chain.
     pub ica_address: String,
+    /// The alias of the counterparty chain, as registered in the coordinator.
+    #[serde(default)]
+    pub chain: Option<String>,
+    /// All the interchain accounts controlled by this token, including the one above.
+    #[serde(default)]
+    pub icas: Vec<ChainIca>,
+}
+
+/// This is an interchain account on a single counterparty chain
+#[cw_serde]
+pub struct ChainIca {
+    /// The alias of the counterparty chain, as registered in the coordinator.
+    pub chain: String,
+    /// The ICA controller contract's address
+    pub ica_controller_address: Addr,
+    /// The ICA address in the counterparty chain.
+    pub ica_address: String,
+}
+
+/// This is the extension execute message that this contract supports
+#[cw_serde]
+pub enum ExtensionMsg {
+    /// Adds an interchain account to the extension of an existing token.
+    /// Only the minter can call this.
+    AddIca {
+        /// The token ID of the NFT.
+        token_id: String,
+        /// The interchain account to add.
+        ica: ChainIca,
+    },
+    /// Enables or disables the [`MinterHookMsg`] sent to the minter when a token is transferred.
+    /// Only the minter can call this.
+    SetTransferHook {
+        /// Whether the minter is notified of the transfers.
+        enabled: bool,
+    },
+}
+
+impl CustomMsg for ExtensionMsg {}
+
+/// This is the migration message for the contract
+#[cw_serde]
+pub struct MigrateMsg {}
+
+/// This is the execute message sent to the minter of the collection when a token is
+/// transferred, if enabled with [`ExtensionMsg::SetTransferHook`]. The minter must accept this
+/// variant in its execute message, and can reject the transfer by returning an error.
+#[cw_serde]
+pub enum MinterHookMsg {
+    /// NftTransferHook is sent after a token is transferred or sent.
+    NftTransferHook(NftTransferHookMsg),
+}
+
+/// This is the transfer of a token notified to the minter
+#[cw_serde]
+pub struct NftTransferHookMsg {
+    /// The token ID of the NFT.
+    pub token_id: String,
+    /// The previous owner of the NFT.
+    pub from: String,
+    /// The new owner of the NFT.
+    pub to: String,
 }
 
 /// This is a wrapper around the [`cw721_base::Cw721Contract`] that adds the ICA extension
 pub type Cw721IcaExtensionContract<'a> =
-    cw721_base::Cw721Contract<'a, Extension, Empty, Empty, Empty>;
+    cw721_base::Cw721Contract<'a, Extension, Empty, ExtensionMsg, Empty>;
 /// This is the execute message that this contract supports
-pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, Empty>;
+pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, ExtensionMsg>;
 /// This is the query message that this contract supports
 pub type QueryMsg = cw721_base::QueryMsg<Empty>;
 
@@ -34,7 +101,10 @@ pub mod entry {
     use super::*;
 
     use cosmwasm_std::entry_point;
-    use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+    use cosmwasm_std::{
+        to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+        WasmMsg,
+    };
 
     // This makes a conscious choice on the various generics used by the contract
     /// This is the instantiate entry point for the contract
@@ -50,6 +120,14 @@ pub mod entry {
         Cw721IcaExtensionContract::default().instantiate(deps.branch(), env, info, msg)
     }
 
+    /// This is the migrate entry point for the contract
+    #[entry_point]
+    pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+        cw2::ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        Ok(Response::default())
+    }
+
     /// This is the execute entry point for the contract
     #[entry_point]
     pub fn execute(
@@ -58,7 +136,99 @@ pub mod entry {
         info: MessageInfo,
         msg: ExecuteMsg,
     ) -> Result<Response, ContractError> {
-        Cw721IcaExtensionContract::default().execute(deps, env, info, msg)
+        match msg {
+            ExecuteMsg::Extension { msg } => execute_extension(deps, info, msg),
+            ExecuteMsg::TransferNft {
+                ref recipient,
+                ref token_id,
+            } => {
+                let hook = transfer_hook(deps.as_ref(), token_id, recipient)?;
+                let res = Cw721IcaExtensionContract::default().execute(deps, env, info, msg)?;
+                Ok(res.add_messages(hook))
+            }
+            ExecuteMsg::SendNft {
+                ref contract,
+                ref token_id,
+                ..
+            } => {
+                let hook = transfer_hook(deps.as_ref(), token_id, contract)?;
+                let res = Cw721IcaExtensionContract::default().execute(deps, env, info, msg)?;
+                Ok(res.add_messages(hook))
+            }
+            _ => Cw721IcaExtensionContract::default().execute(deps, env, info, msg),
+        }
+    }
+
+    /// This builds the [`MinterHookMsg`] notifying the minter of the transfer of a token.
+    /// The hook is sent after the messages of the transfer, so that a failing minter reverts it.
+    /// No hook is sent if the hook is not enabled or if the collection has no minter.
+    pub fn transfer_hook(
+        deps: Deps,
+        token_id: &str,
+        to: &str,
+    ) -> Result<Option<WasmMsg>, ContractError> {
+        if !TRANSFER_HOOK_ENABLED
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+        {
+            return Ok(None);
+        }
+        let Some(minter) = cw_ownable::get_ownership(deps.storage)?.owner else {
+            return Ok(None);
+        };
+        // The owner is loaded before the transfer, an unknown token is rejected by the transfer.
+        let Some(token) = Cw721IcaExtensionContract::default()
+            .tokens
+            .may_load(deps.storage, token_id)?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(WasmMsg::Execute {
+            contract_addr: minter.to_string(),
+            msg: to_json_binary(&MinterHookMsg::NftTransferHook(NftTransferHookMsg {
+                token_id: token_id.to_string(),
+                from: token.owner.to_string(),
+                to: to.to_string(),
+            }))?,
+            funds: vec![],
+        }))
+    }
+
+    /// This handles the [`ExtensionMsg`] messages
+    pub fn execute_extension(
+        deps: DepsMut,
+        info: MessageInfo,
+        msg: ExtensionMsg,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        match msg {
+            ExtensionMsg::AddIca { token_id, ica } => {
+                Cw721IcaExtensionContract::default().tokens.update(
+                    deps.storage,
+                    &token_id,
+                    |maybe_token| match maybe_token {
+                        Some(mut token) => {
+                            token.extension.icas.push(ica);
+                            Ok(token)
+                        }
+                        None => Err(ContractError::Std(StdError::not_found("token"))),
+                    },
+                )?;
+
+                Ok(Response::new()
+                    .add_attribute("action", "add_ica")
+                    .add_attribute("token_id", token_id))
+            }
+            ExtensionMsg::SetTransferHook { enabled } => {
+                TRANSFER_HOOK_ENABLED.save(deps.storage, &enabled)?;
+
+                Ok(Response::new()
+                    .add_attribute("action", "set_transfer_hook")
+                    .add_attribute("enabled", enabled.to_string()))
+            }
+        }
     }
 
     /// This is the query entry point for the contract
@@ -78,7 +248,7 @@ pub mod helpers {
 
     /// This types wraps the address of the ICA extension contract to provide
     /// a convenient interface for other contracts to use.
-    pub type Cw721IcaExtensionContract = Cw721Contract<Empty, Empty>;
+    pub type Cw721IcaExtensionContract = Cw721Contract<Empty, ExtensionMsg>;
 
     /// Creates a new instance of the ICA extension contract helper
     pub fn new_cw721_ica_extension_helper(
@@ -146,6 +316,14 @@ mod tests {
         let extension = Extension {
             ica_controller_address: Addr::unchecked("0x1234567890123456789012345678901234567890"),
             ica_address: "0x1234567890123456789012345678901234567890".into(),
+            chain: Some("cosmoshub".into()),
+            icas: vec![ChainIca {
+                chain: "cosmoshub".into(),
+                ica_controller_address: Addr::unchecked(
+                    "0x1234567890123456789012345678901234567890",
+                ),
+                ica_address: "0x1234567890123456789012345678901234567890".into(),
+            }],
         };
         let exec_msg = ExecuteMsg::Mint {
             token_id: token_id.to_string(),
@@ -161,4 +339,165 @@ mod tests {
         assert_eq!(res.token_uri, token_uri);
         assert_eq!(res.extension, extension);
     }
+
+    #[test]
+    #[cfg(not(feature = "library"))]
+    fn add_ica_extension_msg() {
+        let mut deps = mock_dependencies();
+        let contract = Cw721IcaExtensionContract::default();
+
+        let info = mock_info(CREATOR, &[]);
+        entry::instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg {
+                name: "SpaceShips".to_string(),
+                symbol: "SPACE".to_string(),
+                minter: CREATOR.to_string(),
+            },
+        )
+        .unwrap();
+
+        let token_id = "Enterprise";
+        let primary_ica = ChainIca {
+            chain: "cosmoshub".into(),
+            ica_controller_address: Addr::unchecked("controller-1"),
+            ica_address: "cosmos1ica".into(),
+        };
+        entry::execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Mint {
+                token_id: token_id.to_string(),
+                owner: "john".to_string(),
+                token_uri: None,
+                extension: Extension {
+                    ica_controller_address: primary_ica.ica_controller_address.clone(),
+                    ica_address: primary_ica.ica_address.clone(),
+                    chain: Some(primary_ica.chain.clone()),
+                    icas: vec![primary_ica.clone()],
+                },
+            },
+        )
+        .unwrap();
+
+        let osmosis_ica = ChainIca {
+            chain: "osmosis".into(),
+            ica_controller_address: Addr::unchecked("controller-2"),
+            ica_address: "osmo1ica".into(),
+        };
+        let add_ica_msg = ExecuteMsg::Extension {
+            msg: ExtensionMsg::AddIca {
+                token_id: token_id.to_string(),
+                ica: osmosis_ica.clone(),
+            },
+        };
+
+        // only the minter can add ICAs
+        entry::execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("john", &[]),
+            add_ica_msg.clone(),
+        )
+        .unwrap_err();
+
+        entry::execute(deps.as_mut(), mock_env(), info, add_ica_msg).unwrap();
+
+        let res = contract.nft_info(deps.as_ref(), token_id.into()).unwrap();
+        assert_eq!(res.extension.icas, vec![primary_ica, osmosis_ica]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "library"))]
+    fn transfer_notifies_minter() {
+        use cosmwasm_std::{to_json_binary, SubMsg, WasmMsg};
+
+        let mut deps = mock_dependencies();
+
+        let info = mock_info(CREATOR, &[]);
+        entry::instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg {
+                name: "SpaceShips".to_string(),
+                symbol: "SPACE".to_string(),
+                minter: CREATOR.to_string(),
+            },
+        )
+        .unwrap();
+
+        let token_id = "Enterprise";
+        entry::execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Mint {
+                token_id: token_id.to_string(),
+                owner: "john".to_string(),
+                token_uri: None,
+                extension: Extension {
+                    ica_controller_address: Addr::unchecked("controller-1"),
+                    ica_address: "cosmos1ica".into(),
+                    chain: Some("cosmoshub".into()),
+                    icas: vec![],
+                },
+            },
+        )
+        .unwrap();
+
+        // the minter is not notified until the hook is enabled
+        let res = entry::execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("john", &[]),
+            ExecuteMsg::TransferNft {
+                recipient: "jane".to_string(),
+                token_id: token_id.to_string(),
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        // only the minter can enable the hook
+        let enable_msg = ExecuteMsg::Extension {
+            msg: ExtensionMsg::SetTransferHook { enabled: true },
+        };
+        entry::execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("jane", &[]),
+            enable_msg.clone(),
+        )
+        .unwrap_err();
+        entry::execute(deps.as_mut(), mock_env(), info, enable_msg).unwrap();
+
+        let res = entry::execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("jane", &[]),
+            ExecuteMsg::TransferNft {
+                recipient: "john".to_string(),
+                token_id: token_id.to_string(),
+            },
+        )
+        .unwrap();
+
+        let hook = MinterHookMsg::NftTransferHook(NftTransferHookMsg {
+            token_id: token_id.to_string(),
+            from: "jane".to_string(),
+            to: "john".to_string(),
+        });
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: CREATOR.to_string(),
+                msg: to_json_binary(&hook).unwrap(),
+                funds: vec![],
+            })]
+        );
+    }
 }
@@ -0,0 +1,4555 @@
+//! This module handles the execution logic of the contract.
+
+use cosmwasm_std::{entry_point, Addr, Reply, StdError};
+use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+
+use crate::types::keys::{
+    self, CALLBACK_RECEIVER_REPLY_ID, CW721_INSTANTIATE_REPLY_ID,
+    CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID, FUND_ICA_REPLY_ID, SUBSCRIBER_HOOK_REPLY_ID,
+};
+use crate::types::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
+use crate::types::state::{
+    history::{AckPayload, TransactionStatus},
+    ContractState, STATE,
+};
+use crate::types::ContractError;
+
+/// Instantiate the contract.
+#[entry_point]
+pub fn instantiate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    cw2::set_contract_version(deps.storage, keys::CONTRACT_NAME, keys::CONTRACT_VERSION)?;
+
+    let owner = msg.owner.unwrap_or(info.sender.to_string());
+    cw_ownable::initialize_owner(deps.storage, deps.api, Some(&owner))?;
+
+    let instantiate_submsg =
+        instantiate::instantiate_cw721_ica_extension(env, msg.cw721_ica_extension_code_id)?;
+
+    let state = ContractState {
+        default_chan_init_options: msg.default_chan_init_options,
+        default_backend: msg.default_backend.unwrap_or_default(),
+        ica_controller_code_id: msg.ica_controller_code_id,
+        // TODO: remove this once injective supports instantiate2 (There is already a branch which supports it).
+        // Must be filled in by the reply from the cw721-ica-extension contract.
+        cw721_ica_extension_address: Addr::unchecked("".to_string()),
+        max_supply: msg.max_supply,
+        max_per_address: msg.max_per_address,
+        mint_gate_collection: None,
+        max_payload_size: msg.max_payload_size,
+        max_history_per_token: msg.max_history_per_token,
+        transfer_callbacks: msg.transfer_callbacks,
+        ibc_hooks: msg.ibc_hooks,
+        native_register_fee: msg.native_register_fee,
+    };
+
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_submessage(instantiate_submsg))
+}
+
+/// Execute the contract.
+#[entry_point]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::UpdateOwnership(action) => execute::update_ownership(deps, env, info, action),
+        ExecuteMsg::ReceiveIcaCallback(callback) => {
+            execute::receive_ica_callback(deps, env, info, callback)
+        }
+        ExecuteMsg::NftTransferHook(hook) => execute::nft_transfer_hook(deps, info, hook),
+        ExecuteMsg::MintIca {
+            salt,
+            proof,
+            gate_token_id,
+            recipient,
+            count,
+            chain,
+            chan_init_options,
+        } => execute::mint_ica(
+            deps,
+            env,
+            info,
+            salt,
+            proof,
+            gate_token_id,
+            recipient,
+            count,
+            chain,
+            chan_init_options,
+        ),
+        ExecuteMsg::SetMintPhases { phases } => execute::set_mint_phases(deps, info, phases),
+        ExecuteMsg::SetMintGate { collection } => execute::set_mint_gate(deps, info, collection),
+        ExecuteMsg::SetMaxPayloadSize { size } => execute::set_max_payload_size(deps, info, size),
+        ExecuteMsg::SetMaxHistoryPerToken { max } => {
+            execute::set_max_history_per_token(deps, info, max)
+        }
+        ExecuteMsg::SetTransferCallbacks { enabled } => {
+            execute::set_transfer_callbacks(deps, info, enabled)
+        }
+        ExecuteMsg::SetIbcHooks { enabled } => execute::set_ibc_hooks(deps, info, enabled),
+        ExecuteMsg::SetNativeRegisterFee { fee } => {
+            execute::set_native_register_fee(deps, info, fee)
+        }
+        ExecuteMsg::UpdateAllowedConnections { add, remove } => {
+            execute::update_allowed_connections(deps, info, add, remove)
+        }
+        ExecuteMsg::SetGuardian { guardian } => execute::set_guardian(deps, info, guardian),
+        ExecuteMsg::Pause { operations } => execute::pause(deps, info, operations),
+        ExecuteMsg::Unpause { operations } => execute::unpause(deps, info, operations),
+        ExecuteMsg::UpdateSubscribers { add, remove } => {
+            execute::update_subscribers(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdateMsgCategories { add, remove } => {
+            execute::update_msg_categories(deps, info, add, remove)
+        }
+        ExecuteMsg::RegisterChain { alias, config } => {
+            execute::register_chain(deps, info, alias, config)
+        }
+        ExecuteMsg::RemoveChain { alias } => execute::remove_chain(deps, info, alias),
+        ExecuteMsg::AddIcaToToken { token_id, chain } => {
+            execute::add_ica_to_token(deps, env, info, token_id, chain)
+        }
+        ExecuteMsg::ExecuteIcaMsg {
+            token_id,
+            chain,
+            msg,
+            packet_fee,
+        } => execute::ica_msg(deps, env, info, token_id, chain, msg, packet_fee),
+        ExecuteMsg::FundIca {
+            token_id,
+            chain,
+            channel_id,
+            timeout,
+        } => execute::fund_ica(deps, env, info, token_id, chain, channel_id, timeout),
+        ExecuteMsg::WithdrawFromIca {
+            token_id,
+            chain,
+            amount,
+            source_channel,
+            destination_channel,
+            receiver,
+            timeout,
+        } => execute::withdraw_from_ica(
+            deps,
+            env,
+            info,
+            token_id,
+            chain,
+            amount,
+            source_channel,
+            destination_channel,
+            receiver,
+            timeout,
+        ),
+        ExecuteMsg::CompleteWithdrawal { token_id, sequence } => {
+            execute::complete_withdrawal(deps, info, token_id, sequence)
+        }
+        ExecuteMsg::ExpireWithdrawal { token_id, sequence } => {
+            execute::expire_withdrawal(deps, env, token_id, sequence)
+        }
+        ExecuteMsg::IcaDelegate {
+            token_id,
+            chain,
+            validator,
+            amount,
+        } => execute::ica_delegate(deps, env, info, token_id, chain, validator, amount),
+        ExecuteMsg::IcaUndelegate {
+            token_id,
+            chain,
+            validator,
+            amount,
+        } => execute::ica_undelegate(deps, env, info, token_id, chain, validator, amount),
+        ExecuteMsg::IcaRedelegate {
+            token_id,
+            chain,
+            src_validator,
+            dst_validator,
+            amount,
+        } => execute::ica_redelegate(
+            deps,
+            env,
+            info,
+            token_id,
+            chain,
+            src_validator,
+            dst_validator,
+            amount,
+        ),
+        ExecuteMsg::IcaClaimRewards {
+            token_id,
+            chain,
+            validators,
+        } => execute::ica_claim_rewards(deps, env, info, token_id, chain, validators),
+        ExecuteMsg::IcaSetWithdrawAddress {
+            token_id,
+            chain,
+            address,
+        } => execute::ica_set_withdraw_address(deps, env, info, token_id, chain, address),
+        ExecuteMsg::IcaVote {
+            token_id,
+            chain,
+            proposal_id,
+            option,
+        } => execute::ica_vote(deps, env, info, token_id, chain, proposal_id, option),
+        ExecuteMsg::IcaVoteWeighted {
+            token_id,
+            chain,
+            proposal_id,
+            votes,
+        } => execute::ica_vote_weighted(deps, env, info, token_id, chain, proposal_id, votes),
+        ExecuteMsg::SetAutoCompound {
+            token_id,
+            chain,
+            config,
+        } => execute::set_auto_compound(deps, info, token_id, chain, config),
+        ExecuteMsg::Compound { token_id } => execute::compound(deps, env, info, token_id),
+        ExecuteMsg::PruneHistory { token_id, before } => {
+            execute::prune_history(deps, info, token_id, before)
+        }
+        ExecuteMsg::SetCallbackReceiver { token_id, addr } => {
+            execute::set_callback_receiver(deps, info, token_id, addr)
+        }
+        ExecuteMsg::SetTokenPacketFee { token_id, fee } => {
+            execute::set_token_packet_fee(deps, info, token_id, fee)
+        }
+        ExecuteMsg::SetSponsoredPacketFee { chain, fee } => {
+            execute::set_sponsored_packet_fee(deps, info, chain, fee)
+        }
+        ExecuteMsg::FundFeePool {} => execute::fund_fee_pool(deps, info),
+        ExecuteMsg::WithdrawFeePool { amount } => execute::withdraw_fee_pool(deps, info, amount),
+    }
+}
+
+/// Query the contract.
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Ownership {} => to_json_binary(&cw_ownable::get_ownership(deps.storage)?),
+        QueryMsg::GetContractState {} => to_json_binary(&query::state(deps)?),
+        QueryMsg::NftIcaControllerBimap { key } => {
+            to_json_binary(&query::nft_ica_controller_bimap(deps, key)?)
+        }
+        QueryMsg::GetIcaAddress { token_id, chain } => {
+            to_json_binary(&query::get_ica_address(deps, token_id, chain)?)
+        }
+        QueryMsg::GetIcaAddresses { token_ids } => {
+            to_json_binary(&query::get_ica_addresses(deps, token_ids)?)
+        }
+        QueryMsg::GetMintQueue {} => to_json_binary(&query::get_mint_queue(deps)?),
+        QueryMsg::GetTransactionHistory {
+            token_id,
+            start_after,
+            limit,
+            order,
+            filter,
+        } => to_json_binary(&query::get_transaction_history(
+            deps,
+            token_id,
+            start_after,
+            limit,
+            order.unwrap_or_default(),
+            filter.unwrap_or_default(),
+        )?),
+        QueryMsg::GetMsgCategories { start_after, limit } => {
+            to_json_binary(&query::get_msg_categories(deps, start_after, limit)?)
+        }
+        QueryMsg::GetActivityByAddress {
+            address,
+            start_after,
+            limit,
+            order,
+        } => to_json_binary(&query::get_activity_by_address(
+            deps,
+            address,
+            start_after,
+            limit,
+            order.unwrap_or_default(),
+        )?),
+        QueryMsg::GetActivityFeed {
+            start_after,
+            limit,
+            order,
+        } => to_json_binary(&query::get_activity_feed(
+            deps,
+            start_after,
+            limit,
+            order.unwrap_or_default(),
+        )?),
+        QueryMsg::GetTransaction { token_id, sequence } => {
+            to_json_binary(&query::get_transaction(deps, token_id, sequence)?)
+        }
+        QueryMsg::GetPendingWithdrawal { token_id, sequence } => {
+            to_json_binary(&query::get_pending_withdrawal(deps, token_id, sequence)?)
+        }
+        QueryMsg::SimulateIcaMsg {
+            sender,
+            token_id,
+            chain,
+            msg,
+        } => to_json_binary(&query::simulate_ica_msg(
+            deps, env, sender, token_id, chain, msg,
+        )?),
+        QueryMsg::GetChannelState { token_id, chain } => {
+            to_json_binary(&query::get_channel_state(deps, token_id, chain)?)
+        }
+        QueryMsg::GetTokenIcas { token_id } => {
+            to_json_binary(&query::get_token_icas(deps, token_id)?)
+        }
+        QueryMsg::GetRemainingSupply {} => to_json_binary(&query::get_remaining_supply(deps)?),
+        QueryMsg::GetPauseStatus {} => to_json_binary(&query::get_pause_status(deps)?),
+        QueryMsg::GetSubscribers {} => to_json_binary(&query::get_subscribers(deps)?),
+        QueryMsg::GetAllowedConnections {} => {
+            to_json_binary(&query::get_allowed_connections(deps)?)
+        }
+        QueryMsg::GetChainRegistry {} => to_json_binary(&query::get_chain_registry(deps)?),
+        QueryMsg::GetTokensByChain {
+            chain,
+            start_after,
+            limit,
+        } => to_json_binary(&query::get_tokens_by_chain(
+            deps,
+            chain,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetMintPhases {} => to_json_binary(&query::get_mint_phases(deps)?),
+        QueryMsg::GetActiveMintPhase {} => {
+            to_json_binary(&query::get_active_mint_phase(deps, env)?)
+        }
+        QueryMsg::GetPhaseMintCount { phase_id, address } => {
+            to_json_binary(&query::get_phase_mint_count(deps, phase_id, address)?)
+        }
+        QueryMsg::GetTokenPacketFee { token_id } => {
+            to_json_binary(&query::get_token_packet_fee(deps, token_id)?)
+        }
+        QueryMsg::GetSponsoredPacketFee { chain } => {
+            to_json_binary(&query::get_sponsored_packet_fee(deps, chain)?)
+        }
+        QueryMsg::GetFeePool {} => to_json_binary(&query::get_fee_pool(deps)?),
+        QueryMsg::GetEscrowedFees { token_id } => {
+            to_json_binary(&query::get_escrowed_fees(deps, token_id)?)
+        }
+        QueryMsg::GetAutoCompound { token_id } => {
+            to_json_binary(&query::get_auto_compound(deps, token_id)?)
+        }
+        QueryMsg::GetPendingCompound { token_id } => {
+            to_json_binary(&query::get_pending_compound(deps, token_id)?)
+        }
+        QueryMsg::GetCallbackReceiver { token_id } => {
+            to_json_binary(&query::get_callback_receiver(deps, token_id)?)
+        }
+    }
+}
+
+/// Handle the callbacks of the native ICA controller module and of the ICS-20 transfers.
+#[entry_point]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::OpenAck {
+            port_id,
+            channel_id,
+            counterparty_version,
+            ..
+        } => sudo::open_ack(deps, env, port_id, channel_id, counterparty_version),
+        SudoMsg::Response { request, data } => sudo::packet_result(
+            deps,
+            request,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(data)),
+        ),
+        SudoMsg::Error { request, details } => sudo::packet_result(
+            deps,
+            request,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error(details)),
+        ),
+        SudoMsg::Timeout { request } => {
+            sudo::packet_result(deps, request, TransactionStatus::Timeout, None)
+        }
+    }
+}
+
+/// Reply to a submessage.
+/// Migrate the contract.
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    migrate::migrate_single_chain_icas(deps.storage)?;
+
+    cw2::set_contract_version(deps.storage, keys::CONTRACT_NAME, keys::CONTRACT_VERSION)?;
+
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
+    match msg.id {
+        CW721_INSTANTIATE_REPLY_ID => reply::cw721_instantiate(deps, msg),
+        CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID => reply::cw_ica_controller_instantiate(deps, msg),
+        FUND_ICA_REPLY_ID => reply::fund_ica_transfer(deps, msg),
+        CALLBACK_RECEIVER_REPLY_ID => reply::callback_receiver(msg),
+        SUBSCRIBER_HOOK_REPLY_ID => reply::subscriber_hook(msg),
+        id => Err(StdError::generic_err(format!("Unknown reply id: {}", id))),
+    }
+}
+
+mod instantiate {
+    use crate::{types::keys::CW721_INSTANTIATE_REPLY_ID, utils};
+
+    use super::*;
+
+    use cosmwasm_std::{Addr, Api, CosmosMsg, QuerierWrapper, SubMsg, WasmMsg};
+
+    /// Instantiate the cw721-ica-extension contract using the instantiate2 pattern.
+    /// Returns the instantiate2 message and the contract address.
+    ///
+    /// This is ignored since injective doesn't seem to support instantiate2.
+    #[allow(dead_code)]
+    pub fn instantiate2_cw721_ica_extension(
+        api: &dyn Api,
+        querier: QuerierWrapper,
+        env: Env,
+        code_id: u64,
+        salt: Option<String>,
+    ) -> Result<(CosmosMsg, Addr), ContractError> {
+        let instantiate_msg = to_json_binary(&cw721_base::InstantiateMsg {
+            name: "NFT-ICA".to_string(),
+            symbol: "ICA".to_string(),
+            minter: env.contract.address.to_string(),
+        })?;
+
+        let label = format!("cw721-ica-{}", env.block.height);
+
+        utils::instantiate2_contract(api, querier, env, code_id, salt, label, instantiate_msg)
+    }
+
+    /// Instantiate the cw721-ica-extension contract using the submessage pattern.
+    /// Returns the instantiate submessage whose reply will contain the new contract address.
+    pub fn instantiate_cw721_ica_extension(
+        env: Env,
+        code_id: u64,
+    ) -> Result<SubMsg, ContractError> {
+        let instantiate_msg = WasmMsg::Instantiate {
+            admin: Some(env.contract.address.to_string()),
+            code_id,
+            msg: to_json_binary(&cw721_base::InstantiateMsg {
+                name: "NFT-ICA".to_string(),
+                symbol: "ICA".to_string(),
+                minter: env.contract.address.to_string(),
+            })?,
+            label: format!("cw721-ica-{}", env.block.height),
+            funds: vec![],
+        };
+
+        Ok(SubMsg::reply_on_success(
+            instantiate_msg,
+            CW721_INSTANTIATE_REPLY_ID,
+        ))
+    }
+}
+
+mod execute {
+    use super::*;
+
+    use std::marker::PhantomData;
+
+    use cosmwasm_std::{
+        to_json_string, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal, DistributionMsg, Empty,
+        Event, GovMsg, HexBinary, IbcMsg, Order, QuerierWrapper, StakingMsg, Storage, SubMsg,
+        Timestamp, Uint128, VoteOption, WasmMsg, WeightedVoteOption,
+    };
+    use cw721_base::helpers::Cw721Contract;
+    use cw721_ica_extension::{
+        helpers::new_cw721_ica_extension_helper, ChainIca, Extension, ExtensionMsg,
+        NftTransferHookMsg,
+    };
+    use cw_ica_controller::{
+        helpers::CwIcaControllerContract,
+        ibc::types::packet::acknowledgement::Data,
+        types::{
+            callbacks::IcaControllerCallbackMsg,
+            msg::{options::ChannelOpenInitOptions, ExecuteMsg as IcaControllerExecuteMsg},
+        },
+    };
+
+    use crate::{
+        types::{
+            events::{self, IcaEvent},
+            keys::{
+                CALLBACK_RECEIVER_GAS_LIMIT, CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
+                MAX_SUBSCRIBERS, MIN_COMPOUND_FREQUENCY_SECONDS, SUBSCRIBER_HOOK_GAS_LIMIT,
+            },
+            msg::{
+                callbacks::{
+                    NftIcaCallbackMsg, NftIcaHookMsg, ReceiverExecuteMsg, SubscriberExecuteMsg,
+                },
+                execute_responses::MintIcaResponse,
+            },
+            state::{
+                    chain::{AllowedConnection, ChainConfig},
+                channel::{ChannelState, ChannelStatus},
+                compound::{AutoCompound, AutoCompoundConfig, CompoundStep, PendingCompound},
+                fees::{self, EscrowedFee, FeePayer, PacketFee},
+                history::{
+                    self, MsgCategory, MsgResult, PendingTransfer, PendingWithdrawal,
+                    TransactionMsgType, TransactionRecord,
+                },
+                hooks::CallbackReceiver,
+                    MintPhase, QueueItem, ALLOWED_CONNECTIONS, AUTO_COMPOUND, CALLBACK_RECEIVERS,
+                CHAIN_REGISTRY, CHAIN_TOKENS, CHANNEL_STATE, CONSUMED_GATE_TOKENS, ESCROWED_FEES,
+                GUARDIAN, MINT_COUNT_PER_ADDRESS, MINT_PHASES, MSG_CATEGORIES,
+                NATIVE_ICA_CONNECTIONS, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
+                PAUSE_FLAGS, PENDING_COMPOUNDS, PENDING_MINTS, PENDING_WITHDRAWALS,
+                PHASE_MINT_COUNT, REGISTERED_ICA_ADDRS, SPONSORED_PACKET_FEES, SUBSCRIBERS,
+                TOKEN_COUNTER, TOKEN_PACKET_FEES, TOKEN_PRIMARY_CHAIN, TRANSFER_REPLY_STATE,
+                TX_HISTORY,
+            },
+        },
+        utils::{self, ica_ack, ics20, ics29, native_ica},
+    };
+
+    /// Update the ownership of the contract.
+    pub fn update_ownership(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        action: cw_ownable::Action,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::update_ownership(deps, &env.block, &info.sender, action)?;
+        Ok(Response::default())
+    }
+
+    /// Mint `count` new ICAs for the recipient, or the caller if no recipient is given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mint_ica(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        _salt: Option<String>,
+        proof: Option<Vec<HexBinary>>,
+        gate_token_id: Option<String>,
+        recipient: Option<String>,
+        count: Option<u64>,
+        chain: Option<String>,
+        chan_init_options: Option<ChannelOpenInitOptions>,
+    ) -> Result<Response, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::Minting)?;
+
+        let state = STATE.load(deps.storage)?;
+        let chain = chain.unwrap_or_else(|| keys::DEFAULT_CHAIN_ALIAS.to_string());
+        let (chain_options, backend) = chain_ica_options(deps.as_ref(), &state, &chain)?;
+        let chan_init_options = match chan_init_options {
+            Some(options) => {
+                verify_chan_init_options(deps.storage, &chain_options, &options)?;
+                options
+            }
+            None => chain_options,
+        };
+        let recipient = match recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => info.sender.clone(),
+        };
+
+        let count = count.unwrap_or(1);
+        if count == 0 {
+            return Err(ContractError::InvalidMintCount(
+                "count must be greater than zero".to_string(),
+            ));
+        }
+        if count > keys::MAX_MINT_BATCH {
+            return Err(ContractError::InvalidMintCount(format!(
+                "count must not exceed {}",
+                keys::MAX_MINT_BATCH
+            )));
+        }
+
+        // The token counter is incremented when a mint request is queued, so it also
+        // accounts for the items in the mint queue that are not minted yet.
+        let ica_count = TOKEN_COUNTER.may_load(deps.storage)?.unwrap_or_default();
+        let new_ica_count = checked_mint_count(ica_count, count)?;
+        if let Some(max_supply) = state.max_supply {
+            if new_ica_count > max_supply {
+                return Err(ContractError::MaxSupplyReached { max_supply });
+            }
+        }
+
+        let address_count = MINT_COUNT_PER_ADDRESS
+            .may_load(deps.storage, recipient.as_str())?
+            .unwrap_or_default();
+        let new_address_count = checked_mint_count(address_count, count)?;
+        if let Some(limit) = state.max_per_address {
+            if new_address_count > limit {
+                return Err(ContractError::MaxPerAddressReached {
+                    address: recipient.to_string(),
+                    limit,
+                });
+            }
+        }
+
+        if let Some(gate_collection) = &state.mint_gate_collection {
+            let gate_token_id = gate_token_id.ok_or(ContractError::GateTokenRequired)?;
+            if count != 1 {
+                return Err(ContractError::InvalidMintCount(
+                    "only one ICA can be minted per gate token".to_string(),
+                ));
+            }
+
+            // verify that the sender is the owner of the gate token
+            let partner_cw721 =
+                Cw721Contract::<Empty, Empty>(gate_collection.clone(), PhantomData, PhantomData);
+            let gate_token_owner = partner_cw721
+                .owner_of(&deps.querier, &gate_token_id, false)?
+                .owner;
+            if gate_token_owner != info.sender {
+                return Err(ContractError::Unauthorized);
+            }
+
+            if CONSUMED_GATE_TOKENS.has(deps.storage, (gate_collection, gate_token_id.as_str())) {
+                return Err(ContractError::GateTokenAlreadyUsed {
+                    token_id: gate_token_id,
+                });
+            }
+            CONSUMED_GATE_TOKENS.insert(deps.storage, (gate_collection, gate_token_id.as_str()))?;
+        }
+
+        let mut response = Response::new();
+
+        // Mint phases restrict the sender, who may be minting on behalf of the recipient.
+        let mint_phases = MINT_PHASES.may_load(deps.storage)?.unwrap_or_default();
+        let mut is_paid = false;
+        if !mint_phases.is_empty() {
+            let (phase_id, phase) = mint_phases
+                .iter()
+                .enumerate()
+                .find(|(_, phase)| phase.is_active(env.block.time))
+                .ok_or(ContractError::NoActiveMintPhase)?;
+            let phase_id = phase_id as u32;
+
+            if let Some(merkle_root) = &phase.merkle_root {
+                let proof = proof.unwrap_or_default();
+                if !utils::verify_merkle_proof(merkle_root, info.sender.as_bytes(), &proof) {
+                    return Err(ContractError::InvalidMerkleProof);
+                }
+            }
+
+            let phase_count = PHASE_MINT_COUNT
+                .may_load(deps.storage, (phase_id, info.sender.as_str()))?
+                .unwrap_or_default();
+            let new_phase_count = checked_mint_count(phase_count, count)?;
+            if let Some(limit) = phase.per_address_limit {
+                if new_phase_count > limit {
+                    return Err(ContractError::PhaseLimitReached {
+                        address: info.sender.to_string(),
+                        phase_id,
+                        limit,
+                    });
+                }
+            }
+            PHASE_MINT_COUNT.save(
+                deps.storage,
+                (phase_id, info.sender.as_str()),
+                &new_phase_count,
+            )?;
+
+            if let Some(price) = &phase.price {
+                let expected = Coin {
+                    denom: price.denom.clone(),
+                    amount: price
+                        .amount
+                        .checked_mul(Uint128::from(count))
+                        .map_err(StdError::from)?,
+                };
+                if info.funds != [expected.clone()] {
+                    return Err(ContractError::IncorrectPayment {
+                        expected: expected.to_string(),
+                    });
+                }
+
+                // The mint payment is forwarded to the owner of the contract. Once the ownership
+                // is renounced, it is deposited to the fee pool instead.
+                match cw_ownable::get_ownership(deps.storage)?.owner {
+                    Some(owner) => {
+                        response = response.add_message(BankMsg::Send {
+                            to_address: owner.to_string(),
+                            amount: info.funds.clone(),
+                        });
+                    }
+                    None => fees::deposit(deps.storage, &info.funds)?,
+                }
+                is_paid = true;
+            }
+        }
+
+        // Funds are only accepted as the payment of the active mint phase.
+        if !is_paid && !info.funds.is_empty() {
+            return Err(ContractError::IncorrectPayment {
+                expected: "no funds".to_string(),
+            });
+        }
+
+        let mut token_ids = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let queue_item = QueueItem {
+                token_id: format!("{}-{}", keys::TOKEN_PREFIX, ica_count + i),
+                owner: recipient.to_string(),
+                chain: chain.clone(),
+            };
+            TOKEN_PRIMARY_CHAIN.save(deps.storage, &queue_item.token_id, &chain)?;
+            token_ids.push(queue_item.token_id.clone());
+            response = response.add_event(
+                IcaEvent::new(events::NFT_ICA_MINT_REQUESTED, &queue_item.token_id)
+                    .chain(&chain)
+                    .owner(Some(&queue_item.owner))
+                    .into(),
+            );
+
+            let open_ica_submsg = open_ica(
+                deps.storage,
+                &env,
+                &state,
+                &backend,
+                queue_item,
+                chan_init_options.clone(),
+            )?;
+            response = response.add_submessage(open_ica_submsg);
+        }
+
+        TOKEN_COUNTER.save(deps.storage, &new_ica_count)?;
+        MINT_COUNT_PER_ADDRESS.save(deps.storage, recipient.as_str(), &new_address_count)?;
+
+        Ok(response.set_data(to_json_binary(&MintIcaResponse { token_ids })?))
+    }
+
+    /// Verify that the given channel open init options use the connection of the chain or an
+    /// allowed connection, paired with its counterparty connection and the ICA host port.
+    fn verify_chan_init_options(
+        storage: &dyn Storage,
+        chain_options: &ChannelOpenInitOptions,
+        options: &ChannelOpenInitOptions,
+    ) -> Result<(), ContractError> {
+        let counterparty_connection_id = if options.connection_id == chain_options.connection_id {
+            chain_options.counterparty_connection_id.clone()
+        } else {
+            ALLOWED_CONNECTIONS
+                .may_load(storage, &options.connection_id)?
+                .ok_or_else(|| ContractError::ConnectionNotAllowed {
+                    connection_id: options.connection_id.clone(),
+                })?
+        };
+        if options.counterparty_connection_id != counterparty_connection_id {
+            return Err(ContractError::CounterpartyConnectionNotAllowed {
+                connection_id: options.connection_id.clone(),
+                counterparty_connection_id: options.counterparty_connection_id.clone(),
+            });
+        }
+
+        let port_id = options
+            .counterparty_port_id
+            .as_deref()
+            .unwrap_or(keys::ICA_HOST_PORT_ID);
+        if port_id != keys::ICA_HOST_PORT_ID {
+            return Err(ContractError::CounterpartyPortNotAllowed {
+                port_id: port_id.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the given mint count increased by the number of ICAs to mint.
+    fn checked_mint_count(current: u64, count: u64) -> Result<u64, ContractError> {
+        current
+            .checked_add(count)
+            .ok_or_else(|| ContractError::InvalidMintCount("mint count overflow".to_string()))
+    }
+
+    /// Create an additional ICA on the given chain for an existing token.
+    /// Only the owner of the token can call this.
+    pub fn add_ica_to_token(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: String,
+    ) -> Result<Response, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::Minting)?;
+
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension =
+            new_cw721_ica_extension_helper(state.cw721_ica_extension_address.clone());
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        if NFT_ICA_CONTRACT_BI_MAP
+            .may_load_controller(deps.storage, &token_id, &chain)?
+            .is_some()
+        {
+            return Err(ContractError::IcaAlreadyExists { token_id, chain });
+        }
+
+        let (chan_init_options, backend) = chain_ica_options(deps.as_ref(), &state, &chain)?;
+
+        let queue_item = QueueItem {
+            token_id,
+            owner,
+            chain,
+        };
+        let open_ica_submsg = open_ica(
+            deps.storage,
+            &env,
+            &state,
+            &backend,
+            queue_item,
+            chan_init_options,
+        )?;
+
+        Ok(Response::new().add_submessage(open_ica_submsg))
+    }
+
+    /// Returns the channel open init options and the ICA backend of the given chain alias.
+    fn chain_ica_options(
+        deps: Deps,
+        state: &ContractState,
+        chain: &str,
+    ) -> Result<(ChannelOpenInitOptions, IcaBackend), ContractError> {
+        if chain == keys::DEFAULT_CHAIN_ALIAS {
+            return Ok((
+                state.default_chan_init_options.clone(),
+                state.default_backend.clone(),
+            ));
+        }
+
+        let config = CHAIN_REGISTRY
+            .may_load(deps.storage, chain)?
+            .ok_or_else(|| ContractError::ChainNotFound {
+                chain: chain.to_string(),
+            })?;
+
+        Ok((config.chan_init_options, config.backend))
+    }
+
+    /// Create the ICA of the given queue item using the given backend.
+    /// Returns the submessage that creates the ICA.
+    fn open_ica(
+        storage: &mut dyn Storage,
+        env: &Env,
+        state: &ContractState,
+        backend: &IcaBackend,
+        queue_item: QueueItem,
+        chan_init_options: ChannelOpenInitOptions,
+    ) -> Result<SubMsg, ContractError> {
+        match backend {
+            IcaBackend::CwIcaController => {
+                // The item is bound to its controller in the instantiate reply.
+                NFT_MINT_QUEUE.push_front(storage, &queue_item)?;
+
+                instantiate_cw_ica_controller(
+                    env.clone(),
+                    state.ica_controller_code_id,
+                    Some(chan_init_options),
+                )
+            }
+            IcaBackend::Native => {
+                let owner = native_ica::ica_owner(
+                    &env.contract.address,
+                    &queue_item.token_id,
+                    &queue_item.chain,
+                );
+                NATIVE_ICA_CONNECTIONS.save(storage, &owner, &chan_init_options.connection_id)?;
+                NFT_ICA_CONTRACT_BI_MAP.insert(
+                    storage,
+                    &owner,
+                    &queue_item.token_id,
+                    &queue_item.chain,
+                )?;
+
+                // Only the ICA on the primary chain of the token mints a new NFT.
+                if TOKEN_PRIMARY_CHAIN.load(storage, &queue_item.token_id)? == queue_item.chain {
+                    PENDING_MINTS.save(storage, &owner, &queue_item)?;
+                }
+
+                let register_fee = pay_register_fee(storage)?;
+                Ok(SubMsg::new(native_ica::register_interchain_account_msg(
+                    &owner,
+                    &chan_init_options.connection_id,
+                    &register_fee,
+                )?))
+            }
+        }
+    }
+
+    /// Withdraw the registration fee of the native backend from the fee pool.
+    /// Returns the fee.
+    fn pay_register_fee(storage: &mut dyn Storage) -> Result<Vec<Coin>, ContractError> {
+        let register_fee = STATE.load(storage)?.native_register_fee;
+        if !register_fee.is_empty() && !fees::withdraw(storage, &register_fee)? {
+            return Err(ContractError::InsufficientFeePool);
+        }
+
+        Ok(register_fee)
+    }
+
+    /// Replace the mint phases of the collection. Only the owner can call this.
+    pub fn set_mint_phases(
+        deps: DepsMut,
+        info: MessageInfo,
+        phases: Vec<MintPhase>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if let Some(phase) = phases.iter().find(|phase| phase.start >= phase.end) {
+            return Err(ContractError::InvalidMintPhases(format!(
+                "phase starting at {} must end after it starts",
+                phase.start
+            )));
+        }
+        if phases.windows(2).any(|pair| pair[1].start < pair[0].end) {
+            return Err(ContractError::InvalidMintPhases(
+                "phases must be ordered and must not overlap".to_string(),
+            ));
+        }
+
+        MINT_PHASES.save(deps.storage, &phases)?;
+
+        Ok(Response::default())
+    }
+
+    /// Set or remove the partner cw721 collection that gates minting. Only the owner can call this.
+    pub fn set_mint_gate(
+        deps: DepsMut,
+        info: MessageInfo,
+        collection: Option<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let collection = collection
+            .map(|addr| deps.api.addr_validate(&addr))
+            .transpose()?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.mint_gate_collection = collection;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set the maximum size of the payloads stored with the transaction history.
+    /// Only the owner can call this.
+    pub fn set_max_payload_size(
+        deps: DepsMut,
+        info: MessageInfo,
+        size: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.max_payload_size = size;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set the maximum number of transaction records stored for each token.
+    /// Only the owner can call this.
+    pub fn set_max_history_per_token(
+        deps: DepsMut,
+        info: MessageInfo,
+        max: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.max_history_per_token = max;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set whether the chain sends the callbacks of the ICS-20 transfers.
+    /// Only the owner can call this.
+    pub fn set_transfer_callbacks(
+        deps: DepsMut,
+        info: MessageInfo,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.transfer_callbacks = enabled;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set whether the chain runs the ibc-hooks middleware. Only the owner can call this.
+    pub fn set_ibc_hooks(
+        deps: DepsMut,
+        info: MessageInfo,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.ibc_hooks = enabled;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set the fee paid from the fee pool to register each ICA of the native backend.
+    /// Only the owner can call this.
+    pub fn set_native_register_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        fee: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.native_register_fee = fee;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Add and remove the connections that can be used to mint ICAs. Only the owner can call this.
+    pub fn update_allowed_connections(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<AllowedConnection>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        for connection in &add {
+            ALLOWED_CONNECTIONS.save(
+                deps.storage,
+                &connection.connection_id,
+                &connection.counterparty_connection_id,
+            )?;
+        }
+        for connection_id in &remove {
+            ALLOWED_CONNECTIONS.remove(deps.storage, connection_id);
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Add and remove the contracts receiving the hooks of all the tokens.
+    /// Only the owner can call this.
+    pub fn update_subscribers(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        for addr in &add {
+            let addr = deps.api.addr_validate(addr)?;
+            SUBSCRIBERS.insert(deps.storage, &addr)?;
+        }
+        for addr in &remove {
+            SUBSCRIBERS.remove(deps.storage, &Addr::unchecked(addr));
+        }
+
+        let count = SUBSCRIBERS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .count() as u64;
+        if count > MAX_SUBSCRIBERS {
+            return Err(ContractError::TooManySubscribers {
+                max: MAX_SUBSCRIBERS,
+            });
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Set or remove the guardian, who can pause operations. Only the owner can call this.
+    pub fn set_guardian(
+        deps: DepsMut,
+        info: MessageInfo,
+        guardian: Option<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let response = Response::new().add_attribute("action", "set_guardian");
+        let Some(guardian) = guardian else {
+            GUARDIAN.remove(deps.storage);
+            return Ok(response.add_attribute("guardian", ""));
+        };
+
+        let guardian = deps.api.addr_validate(&guardian)?;
+        GUARDIAN.save(deps.storage, &guardian)?;
+
+        Ok(response.add_attribute("guardian", guardian))
+    }
+
+    /// Pause the given operations. Only the owner or the guardian can call this.
+    pub fn pause(
+        deps: DepsMut,
+        info: MessageInfo,
+        operations: Vec<PausableOperation>,
+    ) -> Result<Response, ContractError> {
+        if GUARDIAN.may_load(deps.storage)?.as_ref() != Some(&info.sender) {
+            cw_ownable::assert_owner(deps.storage, &info.sender)?;
+        }
+
+        set_paused(deps.storage, &operations, true)?;
+
+        Ok(Response::new().add_event(pause_event(events::PAUSED, &info.sender, &operations)))
+    }
+
+    /// Unpause the given operations. Only the owner can call this.
+    pub fn unpause(
+        deps: DepsMut,
+        info: MessageInfo,
+        operations: Vec<PausableOperation>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        set_paused(deps.storage, &operations, false)?;
+
+        Ok(Response::new().add_event(pause_event(events::UNPAUSED, &info.sender, &operations)))
+    }
+
+    /// Set the pause flag of each given operation.
+    fn set_paused(
+        storage: &mut dyn Storage,
+        operations: &[PausableOperation],
+        paused: bool,
+    ) -> StdResult<()> {
+        let mut flags = PAUSE_FLAGS.may_load(storage)?.unwrap_or_default();
+        for operation in operations {
+            flags.set(*operation, paused);
+        }
+        PAUSE_FLAGS.save(storage, &flags)
+    }
+
+    /// Returns the event emitted when the given operations are paused or unpaused.
+    fn pause_event(ty: &str, sender: &Addr, operations: &[PausableOperation]) -> Event {
+        let operations = operations
+            .iter()
+            .map(|operation| operation.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Event::new(ty)
+            .add_attribute("sender", sender)
+            .add_attribute("operations", operations)
+    }
+
+    /// Returns an error if the given operation is paused.
+    pub fn assert_not_paused(
+        storage: &dyn Storage,
+        operation: PausableOperation,
+    ) -> Result<(), ContractError> {
+        let flags = PAUSE_FLAGS.may_load(storage)?.unwrap_or_default();
+        if flags.is_paused(operation) {
+            return Err(ContractError::Paused { operation });
+        }
+
+        Ok(())
+    }
+
+    /// Build the hooks sent to every subscriber. The hooks are gas limited and their errors
+    /// are ignored by the reply.
+    pub fn subscriber_hooks(storage: &dyn Storage, hook: NftIcaHookMsg) -> StdResult<Vec<SubMsg>> {
+        let msg = to_json_binary(&SubscriberExecuteMsg::NftIcaHook(hook))?;
+        SUBSCRIBERS
+            .keys(storage, None, None, Order::Ascending)
+            .map(|addr| {
+                let msg = WasmMsg::Execute {
+                    contract_addr: addr?.to_string(),
+                    msg: msg.clone(),
+                    funds: vec![],
+                };
+                Ok(SubMsg::reply_on_error(msg, SUBSCRIBER_HOOK_REPLY_ID)
+                    .with_gas_limit(SUBSCRIBER_HOOK_GAS_LIMIT))
+            })
+            .collect()
+    }
+
+    /// Handle the transfer of a token notified by the cw721-ica-extension contract.
+    pub fn nft_transfer_hook(
+        deps: DepsMut,
+        info: MessageInfo,
+        hook: NftTransferHookMsg,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.cw721_ica_extension_address {
+            return Err(ContractError::Unauthorized);
+        }
+        // The transfer is reverted by the error.
+        assert_not_paused(deps.storage, PausableOperation::Transfers)?;
+
+        let NftTransferHookMsg { token_id, from, to } = hook;
+        let chain = TOKEN_PRIMARY_CHAIN.may_load(deps.storage, &token_id)?;
+        // The auto-compound configuration and its bounty belong to the previous owner.
+        let refunds =
+            remove_auto_compound(deps.storage, &token_id, &deps.api.addr_validate(&from)?)?;
+        let event = IcaEvent::new(events::NFT_ICA_TRANSFERRED, &token_id)
+            .owner(Some(&to))
+            .attribute("from", &from);
+        let event = match chain {
+            Some(chain) => event.chain(chain),
+            None => event,
+        };
+
+        let hooks = subscriber_hooks(
+            deps.storage,
+            NftIcaHookMsg::Transferred { token_id, from, to },
+        )?;
+
+        Ok(Response::new()
+            .add_messages(refunds)
+            .add_event(event.into())
+            .add_submessages(hooks))
+    }
+
+    /// Set and remove the categories of message type URLs. Only the owner can call this.
+    pub fn update_msg_categories(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<MsgCategory>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        for MsgCategory { type_url, category } in &add {
+            MSG_CATEGORIES.save(deps.storage, type_url, category)?;
+        }
+        for type_url in &remove {
+            MSG_CATEGORIES.remove(deps.storage, type_url);
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Add or update a host chain in the chain registry. Only the owner can call this.
+    pub fn register_chain(
+        deps: DepsMut,
+        info: MessageInfo,
+        alias: String,
+        config: ChainConfig,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if alias == keys::DEFAULT_CHAIN_ALIAS {
+            return Err(ContractError::ReservedChainAlias(alias));
+        }
+
+        CHAIN_REGISTRY.save(deps.storage, &alias, &config)?;
+
+        Ok(Response::default())
+    }
+
+    /// Remove a host chain from the chain registry. Only the owner can call this.
+    pub fn remove_chain(
+        deps: DepsMut,
+        info: MessageInfo,
+        alias: String,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if !CHAIN_REGISTRY.has(deps.storage, &alias) {
+            return Err(ContractError::ChainNotFound { chain: alias });
+        }
+        CHAIN_REGISTRY.remove(deps.storage, &alias);
+
+        Ok(Response::default())
+    }
+
+    /// Handle the callbacks of the cw-ica-controller contracts.
+    pub fn receive_ica_callback(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        callback: IcaControllerCallbackMsg,
+    ) -> Result<Response, ContractError> {
+        if !REGISTERED_ICA_ADDRS.has(deps.storage, &info.sender) {
+            return Err(ContractError::Unauthorized);
+        };
+
+        match callback {
+            IcaControllerCallbackMsg::OnChannelOpenAckCallback {
+                ica_address,
+                channel,
+                ..
+            } => channel_open_ack(
+                deps,
+                &env,
+                info.sender.as_str(),
+                IcaBackend::CwIcaController,
+                ica_address,
+                channel.endpoint.channel_id,
+            ),
+            IcaControllerCallbackMsg::OnAcknowledgementPacketCallback {
+                original_packet,
+                ica_acknowledgement,
+                ..
+            } => {
+                let maybe_controller = original_packet
+                    .src
+                    .port_id
+                    .strip_prefix(keys::WASM_IBC_PORT_PREFIX);
+                if let Some(controller_addr) = maybe_controller {
+                    let (status, ack) = match ica_acknowledgement {
+                        Data::Result(data) => {
+                            (TransactionStatus::Completed, AckPayload::Result(data))
+                        }
+                        Data::Error(error) => (TransactionStatus::Failed, AckPayload::Error(error)),
+                    };
+                    return packet_result(deps, controller_addr, status, Some(ack));
+                }
+
+                Ok(Response::default())
+            }
+            IcaControllerCallbackMsg::OnTimeoutPacketCallback {
+                original_packet, ..
+            } => {
+                let maybe_controller = original_packet
+                    .src
+                    .port_id
+                    .strip_prefix(keys::WASM_IBC_PORT_PREFIX);
+                if let Some(controller_addr) = maybe_controller {
+                    return packet_result(deps, controller_addr, TransactionStatus::Timeout, None);
+                }
+
+                Ok(Response::default())
+            }
+        }
+    }
+
+    /// Record the ICA of the given controller once its channel is opened.
+    /// The token is minted if this is the ICA on its primary chain, otherwise the ICA is added
+    /// to the existing token.
+    pub fn channel_open_ack(
+        deps: DepsMut,
+        env: &Env,
+        controller: &str,
+        backend: IcaBackend,
+        ica_address: String,
+        channel_id: String,
+    ) -> Result<Response, ContractError> {
+        let (token_id, chain) = NFT_ICA_CONTRACT_BI_MAP.load_nft(deps.storage, controller)?;
+        let channel_state = ChannelState {
+            status: ChannelStatus::Open,
+            channel_id: Some(channel_id.clone()),
+        };
+        let channel_opened = IcaEvent::new(events::CHANNEL_OPENED, &token_id)
+            .chain(&chain)
+            .controller(controller)
+            .ica_address(Some(&ica_address))
+            .channel_id(Some(&channel_id));
+        let channel_hooks = subscriber_hooks(
+            deps.storage,
+            NftIcaHookMsg::ChannelStatusChanged {
+                token_id: token_id.clone(),
+                chain: chain.clone(),
+                status: ChannelStatus::Open,
+                channel_id: Some(channel_id.clone()),
+            },
+        )?;
+
+        // The channel is reopened if the ICA was already registered.
+        if NFT_ICA_MAP.has(deps.storage, (&token_id, &chain)) {
+            let current_state = CHANNEL_STATE.load(deps.storage, (&token_id, &chain))?;
+            if current_state.status == ChannelStatus::Open {
+                return Err(ContractError::ChannelAlreadyOpen);
+            };
+
+            CHANNEL_STATE.save(deps.storage, (&token_id, &chain), &channel_state)?;
+
+            let owner = token_owner(deps.as_ref(), &token_id);
+            return Ok(Response::new()
+                .add_event(channel_opened.owner(owner).into())
+                .add_submessages(channel_hooks));
+        }
+
+        NFT_ICA_MAP.save(deps.storage, (&token_id, &chain), &ica_address)?;
+        CHAIN_TOKENS.save(deps.storage, (&chain, &token_id), &Empty {})?;
+        CHANNEL_STATE.save(deps.storage, (&token_id, &chain), &channel_state)?;
+
+        // The native ICAs are controlled by this contract.
+        let ica_controller_address = match backend {
+            IcaBackend::CwIcaController => Addr::unchecked(controller),
+            IcaBackend::Native => env.contract.address.clone(),
+        };
+        let chain_ica = ChainIca {
+            chain: chain.clone(),
+            ica_controller_address: ica_controller_address.clone(),
+            ica_address: ica_address.clone(),
+        };
+
+        let mut response = Response::new();
+        let mut mint_hooks = vec![];
+        let msg = if TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)? == chain {
+            let queue_item = PENDING_MINTS
+                .may_load(deps.storage, controller)?
+                .ok_or(ContractError::QueueEmpty)?;
+            PENDING_MINTS.remove(deps.storage, controller);
+
+            response = response
+                .add_event(channel_opened.owner(Some(&queue_item.owner)).into())
+                .add_event(
+                    IcaEvent::new(events::NFT_ICA_MINTED, &queue_item.token_id)
+                        .chain(&chain)
+                        .controller(controller)
+                        .ica_address(Some(&ica_address))
+                        .channel_id(Some(&channel_id))
+                        .owner(Some(&queue_item.owner))
+                        .into(),
+                );
+            mint_hooks = subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::Minted {
+                    token_id: queue_item.token_id.clone(),
+                    owner: queue_item.owner.clone(),
+                    chain: chain.clone(),
+                    ica_address: ica_address.clone(),
+                },
+            )?;
+
+            cw721_ica_extension::ExecuteMsg::Mint {
+                token_id: queue_item.token_id,
+                owner: queue_item.owner,
+                token_uri: None,
+                extension: Extension {
+                    ica_controller_address,
+                    ica_address,
+                    chain: Some(chain),
+                    icas: vec![chain_ica],
+                },
+            }
+        } else {
+            let owner = token_owner(deps.as_ref(), &token_id);
+            response = response.add_event(channel_opened.owner(owner).into());
+
+            cw721_ica_extension::ExecuteMsg::Extension {
+                msg: ExtensionMsg::AddIca {
+                    token_id,
+                    ica: chain_ica,
+                },
+            }
+        };
+
+        let cw721_ica_extension_address = STATE.load(deps.storage)?.cw721_ica_extension_address;
+        let cosmos_msg: CosmosMsg = WasmMsg::Execute {
+            contract_addr: cw721_ica_extension_address.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        }
+        .into();
+
+        // The hooks are sent after the token is minted or updated.
+        Ok(response
+            .add_message(cosmos_msg)
+            .add_submessages(mint_hooks)
+            .add_submessages(channel_hooks))
+    }
+
+    /// Returns the owner of the given token, or `None` if it cannot be queried, e.g. because
+    /// the token is not minted yet. Used for the events of the callbacks, which must not fail.
+    fn token_owner(deps: Deps, token_id: &str) -> Option<String> {
+        let state = STATE.load(deps.storage).ok()?;
+        new_cw721_ica_extension_helper(state.cw721_ica_extension_address)
+            .owner_of(&deps.querier, token_id, false)
+            .ok()
+            .map(|response| response.owner)
+    }
+
+    /// Set the status of the oldest pending transaction of the given controller and store its
+    /// acknowledgement, if any.
+    /// The channel is closed if the packet timed out, since ICA channels are ordered.
+    pub fn packet_result(
+        deps: DepsMut,
+        controller: &str,
+        status: TransactionStatus,
+        ack: Option<AckPayload>,
+    ) -> Result<Response, ContractError> {
+        let (token_id, chain) = NFT_ICA_CONTRACT_BI_MAP.load_nft(deps.storage, controller)?;
+        let is_timeout = status == TransactionStatus::Timeout;
+
+        let (sequence, record) =
+            history::resolve_pending(deps.storage, controller, &token_id, status)?
+                .ok_or(ContractError::QueueEmpty)?;
+        let mut results = vec![];
+        if let Some(ack) = &ack {
+            // Results that cannot be decoded are not recorded, the acknowledgement must still
+            // be processed.
+            if let AckPayload::Result(data) = ack {
+                if let Ok(decoded) = ica_ack::decode_msg_results(data) {
+                    history::set_results(deps.storage, &token_id, sequence, decoded.clone())?;
+                    results = decoded;
+                }
+            }
+            history::save_ack_payload(deps.storage, &token_id, sequence, ack.clone())?;
+        }
+        // A withdrawal executed by the ICA is in transit until its transfer is received or
+        // expires. It is not pending anymore if the transfer was already received.
+        if let Some(mut withdrawal) =
+            PENDING_WITHDRAWALS.may_load(deps.storage, (&token_id, sequence))?
+        {
+            if record.status == TransactionStatus::Completed {
+                withdrawal.host_sequence = results.iter().find_map(|result| match result {
+                    MsgResult::Transfer { sequence } => Some(*sequence),
+                    _ => None,
+                });
+                PENDING_WITHDRAWALS.save(deps.storage, (&token_id, sequence), &withdrawal)?;
+                history::update_status(
+                    deps.storage,
+                    &token_id,
+                    sequence,
+                    TransactionStatus::InTransit,
+                )?;
+            } else {
+                PENDING_WITHDRAWALS.remove(deps.storage, (&token_id, sequence));
+            }
+        }
+        // A compound moves to its next step, or returns its reserved bounty.
+        let compound_msgs =
+            compound_result(deps.storage, &token_id, sequence, &record.status, &results)?;
+        // The results decoded from the acknowledgement are stored after the record is resolved.
+        let record = TX_HISTORY
+            .may_load(deps.storage, (&token_id, sequence))?
+            .unwrap_or(record);
+
+        let ica_address = NFT_ICA_MAP.may_load(deps.storage, (&token_id, &chain))?;
+        let channel_id = CHANNEL_STATE
+            .may_load(deps.storage, (&token_id, &chain))?
+            .and_then(|cs| cs.channel_id);
+        let event = |ty| {
+            IcaEvent::new(ty, &token_id)
+                .chain(&chain)
+                .controller(controller)
+                .ica_address(ica_address.as_ref())
+                .channel_id(channel_id.as_ref())
+                .owner(Some(&record.owner))
+        };
+
+        let mut response = if is_timeout {
+            Response::new()
+                .add_event(event(events::ICA_TX_TIMEOUT).sequence(sequence).into())
+                .add_event(event(events::CHANNEL_CLOSED).into())
+        } else {
+            Response::new().add_event(
+                event(events::ICA_TX_ACKED)
+                    .sequence(sequence)
+                    .attribute("status", record.status.to_string())
+                    .into(),
+            )
+        };
+        response = response.add_messages(compound_msgs);
+
+        // The fee middleware refunds the unused part of the escrowed fee to this contract
+        // before the callback is received.
+        if let Some(escrowed) = ESCROWED_FEES.may_load(deps.storage, (&token_id, sequence))? {
+            ESCROWED_FEES.remove(deps.storage, (&token_id, sequence));
+
+            let refund = escrowed.fee.refund(is_timeout);
+            if !refund.is_empty() {
+                match escrowed.payer {
+                    FeePayer::Sender(addr) => {
+                        response = response.add_message(BankMsg::Send {
+                            to_address: addr.to_string(),
+                            amount: refund,
+                        });
+                    }
+                    FeePayer::FeePool => fees::deposit(deps.storage, &refund)?,
+                }
+            }
+        }
+
+        if is_timeout {
+            CHANNEL_STATE.update(deps.storage, (&token_id, &chain), |maybe_cs| {
+                if let Some(mut cs) = maybe_cs {
+                    cs.status = ChannelStatus::Closed;
+                    Ok(cs)
+                } else {
+                    Err(ContractError::ChannelStateNotFound)
+                }
+            })?;
+        }
+
+        if let Some(msg) = callback_receiver_msg(deps.storage, &token_id, &record, ack)? {
+            response = response.add_submessage(msg);
+        }
+        response = response.add_submessages(subscriber_hooks(
+            deps.storage,
+            NftIcaHookMsg::TransactionCompleted {
+                token_id: token_id.clone(),
+                record,
+            },
+        )?);
+        if is_timeout {
+            response = response.add_submessages(subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::ChannelStatusChanged {
+                    token_id,
+                    chain,
+                    status: ChannelStatus::Closed,
+                    channel_id,
+                },
+            )?);
+        }
+
+        Ok(response)
+    }
+
+    /// Build the message forwarding the callback of a transaction to the callback receiver of
+    /// the token, if the receiver was registered by the owner who sent the transaction.
+    /// Errors of the receiver, including running out of gas, are ignored by the reply.
+    fn callback_receiver_msg(
+        storage: &dyn Storage,
+        token_id: &str,
+        record: &TransactionRecord,
+        ack: Option<AckPayload>,
+    ) -> StdResult<Option<SubMsg>> {
+        let Some(receiver) = CALLBACK_RECEIVERS.may_load(storage, token_id)? else {
+            return Ok(None);
+        };
+        if record.owner != receiver.owner {
+            return Ok(None);
+        }
+
+        let msg = WasmMsg::Execute {
+            contract_addr: receiver.addr.to_string(),
+            msg: to_json_binary(&ReceiverExecuteMsg::NftIcaCallback(NftIcaCallbackMsg {
+                token_id: token_id.to_string(),
+                record: record.clone(),
+                ack,
+            }))?,
+            funds: vec![],
+        };
+
+        Ok(Some(
+            SubMsg::reply_on_error(msg, CALLBACK_RECEIVER_REPLY_ID)
+                .with_gas_limit(CALLBACK_RECEIVER_GAS_LIMIT),
+        ))
+    }
+
+    /// Set or remove the contract receiving the callbacks of the ICA transactions of the token.
+    /// Only the owner of the token can call this.
+    pub fn set_callback_receiver(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        addr: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        let response = Response::new()
+            .add_attribute("action", "set_callback_receiver")
+            .add_attribute("token_id", &token_id);
+
+        let Some(addr) = addr else {
+            CALLBACK_RECEIVERS.remove(deps.storage, &token_id);
+            return Ok(response.add_attribute("receiver", ""));
+        };
+
+        let addr = deps.api.addr_validate(&addr)?;
+        CALLBACK_RECEIVERS.save(
+            deps.storage,
+            &token_id,
+            &CallbackReceiver {
+                owner: info.sender,
+                addr: addr.clone(),
+            },
+        )?;
+
+        Ok(response.add_attribute("receiver", addr))
+    }
+
+    /// Execute a message on the ICA contract if the sender is the owner of the ica token.
+    /// If no chain is given, the ICA on the primary chain of the token is used.
+    pub fn ica_msg(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: Option<PacketFee>,
+    ) -> Result<Response, ContractError> {
+        send_ica_msg(deps, env, info, token_id, chain, msg, packet_fee, None)
+    }
+
+    /// Withdraw funds from the ICA of a token to this chain. The transfer is received by this
+    /// contract through ibc-hooks, which calls [`complete_withdrawal`] to forward the funds to
+    /// the receiver. Only the owner of the token can call this.
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw_from_ica(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        amount: Coin,
+        source_channel: String,
+        destination_channel: String,
+        receiver: Option<String>,
+        timeout: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        // The transfer could not be received without ibc-hooks.
+        if !STATE.load(deps.storage)?.ibc_hooks {
+            return Err(ContractError::IbcHooksDisabled);
+        }
+
+        // The owner of the token is verified when the message is sent.
+        let receiver = match receiver {
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => info.sender.clone(),
+        };
+        let chain = ica_target(deps.storage, &token_id, chain)?.chain;
+        let ica_address = NFT_ICA_MAP
+            .may_load(deps.storage, (&token_id, &chain))?
+            .ok_or_else(|| ContractError::IcaAddressNotFound {
+                token_id: token_id.clone(),
+                chain: chain.clone(),
+            })?;
+
+        // The transfer must not time out before the ICA packet is relayed to the host chain.
+        let timeout =
+            transfer_timeout(&env, timeout)?.plus_seconds(keys::DEFAULT_ICA_TIMEOUT_SECONDS);
+
+        // The withdrawal is recorded as the next record of the token.
+        let sequence = history::next_sequence(deps.storage, &token_id)?;
+        let memo = ics20::wasm_hook_memo(
+            &env.contract.address,
+            &ExecuteMsg::CompleteWithdrawal {
+                token_id: token_id.clone(),
+                sequence,
+            },
+        )?;
+        let transfer = ics20::transfer_any(
+            &ica_address,
+            source_channel,
+            amount,
+            env.contract.address.to_string(),
+            timeout.into(),
+            Some(memo),
+        );
+        let msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages: vec![CosmosMsg::Stargate {
+                type_url: transfer.type_url,
+                value: transfer.value.into(),
+            }],
+            packet_memo: None,
+            timeout_seconds: Some(keys::DEFAULT_ICA_TIMEOUT_SECONDS),
+        };
+
+        PENDING_WITHDRAWALS.save(
+            deps.storage,
+            (&token_id, sequence),
+            &PendingWithdrawal {
+                receiver,
+                ica_address,
+                destination_channel,
+                host_sequence: None,
+                timeout,
+            },
+        )?;
+
+        send_ica_msg(
+            deps,
+            env,
+            info,
+            token_id,
+            Some(chain),
+            msg,
+            None,
+            Some(TransactionMsgType::Withdraw),
+        )
+    }
+
+    /// Complete a withdrawal whose transfer was received by this contract, forwarding the
+    /// received funds to its receiver. Only the ibc-hooks sender derived from the ICA and the
+    /// destination channel of the withdrawal can call this.
+    pub fn complete_withdrawal(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        sequence: u64,
+    ) -> Result<Response, ContractError> {
+        let withdrawal = PENDING_WITHDRAWALS
+            .may_load(deps.storage, (&token_id, sequence))?
+            .ok_or_else(|| ContractError::WithdrawalNotFound {
+                token_id: token_id.clone(),
+                sequence,
+            })?;
+        let hooks_sender = ics20::ibc_hooks_sender(
+            deps.api,
+            &withdrawal.destination_channel,
+            &withdrawal.ica_address,
+        )?;
+        if info.sender != hooks_sender {
+            return Err(ContractError::Unauthorized);
+        }
+        PENDING_WITHDRAWALS.remove(deps.storage, (&token_id, sequence));
+
+        let mut response = Response::new();
+        if !info.funds.is_empty() {
+            response = response.add_message(BankMsg::Send {
+                to_address: withdrawal.receiver.to_string(),
+                amount: info.funds,
+            });
+        }
+
+        // The transfer may be received before the ICA transaction is acknowledged, in which
+        // case the acknowledgement leaves the record completed.
+        resolve_withdrawal(
+            deps.storage,
+            response,
+            token_id,
+            sequence,
+            TransactionStatus::Completed,
+        )
+    }
+
+    /// Set the record of a withdrawal whose transfer timed out without being received to
+    /// timeout. The host chain refunds the funds to the ICA. Anyone can call this.
+    pub fn expire_withdrawal(
+        deps: DepsMut,
+        env: Env,
+        token_id: String,
+        sequence: u64,
+    ) -> Result<Response, ContractError> {
+        let withdrawal = PENDING_WITHDRAWALS
+            .may_load(deps.storage, (&token_id, sequence))?
+            .ok_or_else(|| ContractError::WithdrawalNotFound {
+                token_id: token_id.clone(),
+                sequence,
+            })?;
+        // The transfer is only sent once the ICA transaction is acknowledged, and it cannot be
+        // received on this chain after its timeout.
+        let status = TX_HISTORY.load(deps.storage, (&token_id, sequence))?.status;
+        if status != TransactionStatus::InTransit || env.block.time < withdrawal.timeout {
+            return Err(ContractError::WithdrawalNotExpired {
+                timeout: withdrawal.timeout,
+            });
+        }
+        PENDING_WITHDRAWALS.remove(deps.storage, (&token_id, sequence));
+
+        resolve_withdrawal(
+            deps.storage,
+            Response::new(),
+            token_id,
+            sequence,
+            TransactionStatus::Timeout,
+        )
+    }
+
+    /// Set the final status of the record of a withdrawal, and notify the callback receiver and
+    /// the subscribers.
+    fn resolve_withdrawal(
+        storage: &mut dyn Storage,
+        mut response: Response,
+        token_id: String,
+        sequence: u64,
+        status: TransactionStatus,
+    ) -> Result<Response, ContractError> {
+        let record = history::update_status(storage, &token_id, sequence, status)?;
+
+        response = response.add_event(
+            IcaEvent::new(events::ICA_WITHDRAWAL_RESOLVED, &token_id)
+                .chain(&record.chain)
+                .sequence(sequence)
+                .owner(Some(&record.owner))
+                .attribute("status", record.status.to_string())
+                .into(),
+        );
+        if let Some(msg) = callback_receiver_msg(storage, &token_id, &record, None)? {
+            response = response.add_submessage(msg);
+        }
+        response = response.add_submessages(subscriber_hooks(
+            storage,
+            NftIcaHookMsg::TransactionCompleted { token_id, record },
+        )?);
+
+        Ok(response)
+    }
+
+    /// Delegate tokens of the ICA of a token. Only the owner of the token can call this.
+    pub fn ica_delegate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![StakingMsg::Delegate { validator, amount }.into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Undelegate tokens of the ICA of a token. Only the owner of the token can call this.
+    pub fn ica_undelegate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![StakingMsg::Undelegate { validator, amount }.into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Redelegate tokens of the ICA of a token. Only the owner of the token can call this.
+    #[allow(clippy::too_many_arguments)]
+    pub fn ica_redelegate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        src_validator: String,
+        dst_validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![StakingMsg::Redelegate {
+            src_validator,
+            dst_validator,
+            amount,
+        }
+        .into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Withdraw the staking rewards of the ICA of a token from the given validators.
+    /// Only the owner of the token can call this.
+    pub fn ica_claim_rewards(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        validators: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        if validators.is_empty() {
+            return Err(ContractError::NoValidators);
+        }
+
+        let messages = validators
+            .into_iter()
+            .map(|validator| DistributionMsg::WithdrawDelegatorReward { validator }.into())
+            .collect();
+
+        send_ica_cosmos_msgs(
+            deps,
+            env,
+            info,
+            token_id,
+            chain,
+            messages,
+            Some(TransactionMsgType::Distribution),
+        )
+    }
+
+    /// Set the staking rewards withdraw address of the ICA of a token.
+    /// Only the owner of the token can call this.
+    pub fn ica_set_withdraw_address(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![DistributionMsg::SetWithdrawAddress { address }.into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Vote on a governance proposal of the host chain with the ICA of a token.
+    /// Only the owner of the token can call this.
+    pub fn ica_vote(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        proposal_id: u64,
+        option: VoteOption,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![GovMsg::Vote {
+            proposal_id,
+            vote: option,
+        }
+        .into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Cast a weighted vote on a governance proposal of the host chain with the ICA of a token.
+    /// Only the owner of the token can call this.
+    pub fn ica_vote_weighted(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        proposal_id: u64,
+        votes: Vec<WeightedVoteOption>,
+    ) -> Result<Response, ContractError> {
+        let total = votes
+            .iter()
+            .try_fold(Decimal::zero(), |total, vote| {
+                total.checked_add(vote.weight)
+            })
+            .map_err(StdError::from)?;
+        if votes.is_empty() || total != Decimal::one() {
+            return Err(ContractError::InvalidVoteWeights);
+        }
+
+        let messages = vec![GovMsg::VoteWeighted {
+            proposal_id,
+            options: votes,
+        }
+        .into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Send the given messages to the ICA of a token with the default packet options.
+    fn send_ica_cosmos_msgs(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        messages: Vec<CosmosMsg>,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<Response, ContractError> {
+        let msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages,
+            packet_memo: None,
+            timeout_seconds: None,
+        };
+
+        send_ica_msg(deps, env, info, token_id, chain, msg, None, msg_type)
+    }
+
+    /// Enable, update or disable auto-compounding for a token.
+    /// Only the owner of the token can call this.
+    pub fn set_auto_compound(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        config: Option<AutoCompoundConfig>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        let existing = AUTO_COMPOUND.may_load(deps.storage, &token_id)?;
+        let mut response = Response::new()
+            .add_attribute("action", "set_auto_compound")
+            .add_attribute("token_id", &token_id);
+
+        let Some(config) = config else {
+            if !info.funds.is_empty() {
+                return Err(ContractError::IncorrectPayment {
+                    expected: "no funds".to_string(),
+                });
+            }
+
+            let refunds = remove_auto_compound(deps.storage, &token_id, &info.sender)?;
+
+            return Ok(response
+                .add_messages(refunds)
+                .add_attribute("enabled", "false"));
+        };
+
+        if config.frequency < MIN_COMPOUND_FREQUENCY_SECONDS {
+            return Err(ContractError::InvalidCompoundFrequency {
+                min: MIN_COMPOUND_FREQUENCY_SECONDS,
+            });
+        }
+
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+        // the token must have an ICA on the chain
+        NFT_ICA_CONTRACT_BI_MAP.load_controller(deps.storage, &token_id, &chain)?;
+
+        // The bounty balance is kept if the bounty denom and the depositor do not change,
+        // otherwise it is refunded to its depositor.
+        let bounty_denom = config.bounty.as_ref().map(|bounty| bounty.denom.clone());
+        let (last_compound, mut bounty_balance) = match existing {
+            Some(existing)
+                if existing.config.bounty.as_ref().map(|bounty| &bounty.denom)
+                    == bounty_denom.as_ref()
+                    && existing
+                        .depositor
+                        .as_ref()
+                        .map_or(true, |depositor| depositor == info.sender) =>
+            {
+                (existing.last_compound, existing.bounty_balance)
+            }
+            Some(existing) => {
+                let last_compound = existing.last_compound;
+                let depositor = existing
+                    .depositor
+                    .clone()
+                    .unwrap_or_else(|| info.sender.clone());
+                if let Some(refund) = bounty_refund(existing) {
+                    response = response.add_message(BankMsg::Send {
+                        to_address: depositor.to_string(),
+                        amount: vec![refund],
+                    });
+                }
+                (last_compound, Uint128::zero())
+            }
+            None => (None, Uint128::zero()),
+        };
+
+        match (info.funds.as_slice(), &bounty_denom) {
+            ([], _) => {}
+            ([deposit], Some(denom)) if &deposit.denom == denom => {
+                bounty_balance += deposit.amount;
+            }
+            _ => {
+                return Err(ContractError::IncorrectPayment {
+                    expected: bounty_denom.unwrap_or_else(|| "no funds".to_string()),
+                })
+            }
+        }
+
+        AUTO_COMPOUND.save(
+            deps.storage,
+            &token_id,
+            &AutoCompound {
+                chain: chain.clone(),
+                config,
+                last_compound,
+                bounty_balance,
+                depositor: Some(info.sender),
+            },
+        )?;
+
+        Ok(response
+            .add_attribute("enabled", "true")
+            .add_attribute("chain", chain)
+            .add_attribute("bounty_balance", bounty_balance.to_string()))
+    }
+
+    /// Returns the remaining bounty balance of the given auto-compound state, if any.
+    fn bounty_refund(auto_compound: AutoCompound) -> Option<Coin> {
+        auto_compound
+            .config
+            .bounty
+            .filter(|_| !auto_compound.bounty_balance.is_zero())
+            .map(|bounty| Coin {
+                denom: bounty.denom,
+                amount: auto_compound.bounty_balance,
+            })
+    }
+
+    /// Remove the auto-compound state of a token and its compound in progress, if any.
+    /// Returns the messages refunding the bounty balance and the reserved bounty to their
+    /// depositor, which defaults to the given owner of the token.
+    fn remove_auto_compound(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        owner: &Addr,
+    ) -> StdResult<Vec<BankMsg>> {
+        let mut refunds = vec![];
+        if let Some(auto_compound) = AUTO_COMPOUND.may_load(storage, token_id)? {
+            AUTO_COMPOUND.remove(storage, token_id);
+            let depositor = auto_compound
+                .depositor
+                .clone()
+                .unwrap_or_else(|| owner.clone());
+            if let Some(refund) = bounty_refund(auto_compound) {
+                refunds.push(BankMsg::Send {
+                    to_address: depositor.to_string(),
+                    amount: vec![refund],
+                });
+            }
+        }
+        if let Some(pending) = PENDING_COMPOUNDS.may_load(storage, token_id)? {
+            PENDING_COMPOUNDS.remove(storage, token_id);
+            if let Some(bounty) = pending.bounty {
+                refunds.push(BankMsg::Send {
+                    to_address: pending.depositor.to_string(),
+                    amount: vec![bounty],
+                });
+            }
+        }
+
+        Ok(refunds)
+    }
+
+    /// Withdraw the staking rewards of the ICA of a token, or delegate the rewards withdrawn by
+    /// the previous compound. Anyone can call this once the configured frequency has elapsed
+    /// since the last compound. The configured bounty is reserved from the bounty balance, and
+    /// paid to the sender of the delegation once it is acknowledged.
+    pub fn compound(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+    ) -> Result<Response, ContractError> {
+        let mut auto_compound = AUTO_COMPOUND
+            .may_load(deps.storage, &token_id)?
+            .ok_or(ContractError::AutoCompoundNotEnabled)?;
+
+        let state = STATE.load(deps.storage)?;
+        let owner = new_cw721_ica_extension_helper(state.cw721_ica_extension_address)
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+
+        // The step is recorded as the next record of the token.
+        let sequence = history::next_sequence(deps.storage, &token_id)?;
+        let validator = auto_compound.config.validator.clone();
+        let (message, pending) = match PENDING_COMPOUNDS.may_load(deps.storage, &token_id)? {
+            Some(PendingCompound {
+                step: CompoundStep::Withdrawn { amount },
+                depositor,
+                bounty,
+                ..
+            }) => (
+                StakingMsg::Delegate { validator, amount }.into(),
+                PendingCompound {
+                    keeper: info.sender.clone(),
+                    depositor,
+                    bounty,
+                    sequence,
+                    step: CompoundStep::Delegating,
+                },
+            ),
+            Some(_) => return Err(ContractError::CompoundInProgress),
+            None => {
+                if let Some(next_compound) = auto_compound.next_compound() {
+                    if env.block.time < next_compound {
+                        return Err(ContractError::CompoundTooSoon { next_compound });
+                    }
+                }
+                auto_compound.last_compound = Some(env.block.time);
+
+                let bounty = auto_compound.config.bounty.clone().filter(|bounty| {
+                    !bounty.amount.is_zero() && auto_compound.bounty_balance >= bounty.amount
+                });
+                if let Some(bounty) = &bounty {
+                    auto_compound.bounty_balance -= bounty.amount;
+                }
+                AUTO_COMPOUND.save(deps.storage, &token_id, &auto_compound)?;
+
+                (
+                    DistributionMsg::WithdrawDelegatorReward { validator }.into(),
+                    PendingCompound {
+                        keeper: info.sender.clone(),
+                        depositor: auto_compound
+                            .depositor
+                            .clone()
+                            .unwrap_or_else(|| Addr::unchecked(&owner)),
+                        bounty,
+                        sequence,
+                        step: CompoundStep::Withdrawing,
+                    },
+                )
+            }
+        };
+        PENDING_COMPOUNDS.save(deps.storage, &token_id, &pending)?;
+
+        let msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages: vec![message],
+            packet_memo: None,
+            timeout_seconds: None,
+        };
+
+        dispatch_ica_msg(
+            deps,
+            env,
+            info,
+            owner,
+            token_id,
+            Some(auto_compound.chain),
+            msg,
+            None,
+            Some(TransactionMsgType::Compound),
+        )
+    }
+
+    /// Move the compound in progress of a token to its next step if the transaction with the
+    /// given sequence is its current step. Returns the messages paying the reserved bounty to the
+    /// keeper once the rewards are delegated, or refunding it if it cannot be returned to the
+    /// bounty balance.
+    fn compound_result(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+        status: &TransactionStatus,
+        results: &[MsgResult],
+    ) -> StdResult<Vec<BankMsg>> {
+        let Some(mut pending) = PENDING_COMPOUNDS.may_load(storage, token_id)? else {
+            return Ok(vec![]);
+        };
+        if pending.sequence != sequence {
+            return Ok(vec![]);
+        }
+
+        if *status == TransactionStatus::Completed {
+            match pending.step {
+                CompoundStep::Withdrawing => {
+                    let min_amount = AUTO_COMPOUND
+                        .may_load(storage, token_id)?
+                        .map(|auto_compound| auto_compound.config.min_amount);
+                    let rewards = min_amount.and_then(|min_amount| {
+                        results
+                            .iter()
+                            .find_map(|result| match result {
+                                MsgResult::WithdrawRewards { amount } => {
+                                    amount.iter().find(|coin| coin.denom == min_amount.denom)
+                                }
+                                _ => None,
+                            })
+                            .filter(|rewards| rewards.amount >= min_amount.amount)
+                            .cloned()
+                    });
+                    // Smaller rewards are left in the ICA.
+                    if let Some(amount) = rewards {
+                        pending.step = CompoundStep::Withdrawn { amount };
+                        PENDING_COMPOUNDS.save(storage, token_id, &pending)?;
+                        return Ok(vec![]);
+                    }
+                }
+                CompoundStep::Delegating => {
+                    PENDING_COMPOUNDS.remove(storage, token_id);
+                    return Ok(pending
+                        .bounty
+                        .map(|bounty| BankMsg::Send {
+                            to_address: pending.keeper.to_string(),
+                            amount: vec![bounty],
+                        })
+                        .into_iter()
+                        .collect());
+                }
+                CompoundStep::Withdrawn { .. } => return Ok(vec![]),
+            }
+        }
+
+        // The compound ends without delegating, so the reserved bounty is released.
+        PENDING_COMPOUNDS.remove(storage, token_id);
+        let Some(bounty) = pending.bounty else {
+            return Ok(vec![]);
+        };
+        if let Some(mut auto_compound) = AUTO_COMPOUND.may_load(storage, token_id)? {
+            let same_denom = auto_compound
+                .config
+                .bounty
+                .as_ref()
+                .map_or(false, |configured| configured.denom == bounty.denom);
+            let same_depositor = auto_compound
+                .depositor
+                .as_ref()
+                .map_or(true, |depositor| *depositor == pending.depositor);
+            if same_denom && same_depositor {
+                auto_compound.bounty_balance += bounty.amount;
+                AUTO_COMPOUND.save(storage, token_id, &auto_compound)?;
+                return Ok(vec![]);
+            }
+        }
+
+        Ok(vec![BankMsg::Send {
+            to_address: pending.depositor.to_string(),
+            amount: vec![bounty],
+        }])
+    }
+
+    /// Send a message to the ICA of a token if the sender is the owner of the token.
+    /// The message type of its transaction record is overridden by the given type, if any.
+    #[allow(clippy::too_many_arguments)]
+    fn send_ica_msg(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: Option<PacketFee>,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<Response, ContractError> {
+        let owner = verify_token_owner(deps.as_ref(), &token_id, info.sender.as_str())?;
+
+        dispatch_ica_msg(
+            deps, env, info, owner, token_id, chain, msg, packet_fee, msg_type,
+        )
+    }
+
+    /// Returns the owner of the token if it is the given sender.
+    pub fn verify_token_owner(
+        deps: Deps,
+        token_id: &str,
+        sender: &str,
+    ) -> Result<String, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, token_id, false)?
+            .owner;
+
+        if owner != sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        Ok(owner)
+    }
+
+    /// IcaTarget is the ICA of a token that a message is sent to.
+    pub struct IcaTarget {
+        /// The alias of the chain of the ICA.
+        pub chain: String,
+        /// The cw-ica-controller address, or the owner string of the native ICA.
+        pub controller: String,
+        /// The connection ID of the native ICA, or `None` for the cw-ica-controller backend.
+        pub native_connection: Option<String>,
+    }
+
+    /// Returns the ICA of the token on the given chain, or on its primary chain if no chain
+    /// is given. Fails if the controller of the ICA is not registered.
+    pub fn ica_target(
+        storage: &dyn Storage,
+        token_id: &str,
+        chain: Option<String>,
+    ) -> Result<IcaTarget, ContractError> {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(storage, token_id)?,
+        };
+        let controller = NFT_ICA_CONTRACT_BI_MAP.load_controller(storage, token_id, &chain)?;
+        // additional hardening check
+        let native_connection = NATIVE_ICA_CONNECTIONS.may_load(storage, &controller)?;
+        if native_connection.is_none()
+            && !REGISTERED_ICA_ADDRS.has(storage, &Addr::unchecked(&controller))
+        {
+            return Err(ContractError::Unauthorized);
+        };
+
+        Ok(IcaTarget {
+            chain,
+            controller,
+            native_connection,
+        })
+    }
+
+    /// Build the message sent to the controller of the ICA. The packet fee is only given for the
+    /// native backend, which escrows it with the message.
+    pub fn controller_msg(
+        deps: Deps,
+        target: &IcaTarget,
+        token_id: &str,
+        msg: IcaControllerExecuteMsg,
+        fee: Option<&PacketFee>,
+    ) -> Result<CosmosMsg, ContractError> {
+        match &target.native_connection {
+            Some(connection_id) => native_ica_msg(
+                deps,
+                &target.controller,
+                connection_id,
+                token_id,
+                &target.chain,
+                msg,
+                fee,
+            ),
+            None => {
+                Ok(CwIcaControllerContract::new(Addr::unchecked(&target.controller)).call(msg)?)
+            }
+        }
+    }
+
+    /// Send a message to the ICA of a token owned by the given owner, without verifying the
+    /// sender. Any packet fee is paid with the funds of the sender.
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch_ica_msg(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        owner: String,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: Option<PacketFee>,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<Response, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::IcaExecution)?;
+
+        let IcaTarget {
+            chain,
+            controller,
+            native_connection,
+        } = ica_target(deps.storage, &token_id, chain)?;
+
+        let mut response = Response::new();
+
+        // Set channel status to pending if the message is a create channel message.
+        if matches!(msg, IcaControllerExecuteMsg::CreateChannel { .. })
+            && matches!(
+                CHANNEL_STATE
+                    .load(deps.storage, (&token_id, &chain))?
+                    .status,
+                ChannelStatus::Closed
+            )
+        {
+            CHANNEL_STATE.save(
+                deps.storage,
+                (&token_id, &chain),
+                &ChannelState {
+                    status: ChannelStatus::Pending,
+                    channel_id: None,
+                },
+            )?;
+            response = response.add_submessages(subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::ChannelStatusChanged {
+                    token_id: token_id.clone(),
+                    chain: chain.clone(),
+                    status: ChannelStatus::Pending,
+                    channel_id: None,
+                },
+            )?);
+        }
+
+        let sends_packet = matches!(
+            msg,
+            IcaControllerExecuteMsg::SendCosmosMsgs { .. }
+                | IcaControllerExecuteMsg::SendCustomIcaMessages { .. }
+        );
+        let escrowed_fee = if sends_packet {
+            resolve_packet_fee(deps.storage, &info, &token_id, &chain, packet_fee)?
+        } else if !info.funds.is_empty() {
+            return Err(ContractError::IncorrectPayment {
+                expected: "no funds".to_string(),
+            });
+        } else {
+            None
+        };
+
+        let sequence = match TransactionRecord::from_ica_msg(
+            &msg,
+            &token_id,
+            &chain,
+            &owner,
+            env.block.height,
+            env.block.time.nanos(),
+        ) {
+            Some(mut tx_record) => {
+                if let Some(msg_type) = msg_type {
+                    tx_record.msg_type = msg_type;
+                }
+                tx_record.executor = Some(info.sender.to_string());
+                tx_record.categories = history::categories(deps.storage, &tx_record.type_urls)?;
+                let sequence = history::push_pending(deps.storage, &controller, &tx_record)?;
+                history::save_msg_payload(deps.storage, &token_id, sequence, &msg)?;
+                let evicted = history::evict(deps.storage, &token_id)?;
+                response = response.add_events(pruned_events(&evicted)?);
+
+                let channel_id = CHANNEL_STATE
+                    .may_load(deps.storage, (&token_id, &chain))?
+                    .and_then(|cs| cs.channel_id);
+                response = response.add_event(
+                    IcaEvent::new(events::ICA_TX_SENT, &token_id)
+                        .chain(&chain)
+                        .controller(&controller)
+                        .ica_address(NFT_ICA_MAP.may_load(deps.storage, (&token_id, &chain))?)
+                        .channel_id(channel_id)
+                        .sequence(sequence)
+                        .owner(Some(&owner))
+                        .attribute("executor", info.sender.as_str())
+                        .into(),
+                );
+                Some(sequence)
+            }
+            None => None,
+        };
+
+        // The native backend registers the ICA again to reopen its channel.
+        if native_connection.is_some()
+            && matches!(msg, IcaControllerExecuteMsg::CreateChannel { .. })
+        {
+            pay_register_fee(deps.storage)?;
+        }
+
+        // The fee is escrowed for the next packet sent on the channel, so it must be paid
+        // right before the packet is sent. The native backend escrows it with the message.
+        let mut native_fee = None;
+        if let (Some((fee, payer)), Some(sequence)) = (escrowed_fee, sequence) {
+            if native_connection.is_some() {
+                native_fee = Some(fee.clone());
+            } else {
+                let channel_id = CHANNEL_STATE
+                    .load(deps.storage, (&token_id, &chain))?
+                    .channel_id
+                    .ok_or(ContractError::ChannelStateNotFound)?;
+                let port_id = format!("{}{}", keys::WASM_IBC_PORT_PREFIX, controller);
+
+                response = response.add_message(ics29::pay_packet_fee_msg(
+                    env.contract.address.as_str(),
+                    &port_id,
+                    &channel_id,
+                    &fee,
+                ));
+            }
+            ESCROWED_FEES.save(
+                deps.storage,
+                (&token_id, sequence),
+                &EscrowedFee {
+                    chain: chain.clone(),
+                    fee,
+                    payer,
+                },
+            )?;
+        }
+
+        let target = IcaTarget {
+            chain,
+            controller,
+            native_connection,
+        };
+        let cosmos_msg =
+            controller_msg(deps.as_ref(), &target, &token_id, msg, native_fee.as_ref())?;
+
+        Ok(response.add_message(cosmos_msg))
+    }
+
+    /// Returns the ICS-29 fee of the next packet sent by the given token and its payer.
+    /// The fee given with the message, or else the default fee of the token, is paid with the
+    /// sent funds. Otherwise, the sponsored fee of the chain is paid from the fee pool if the
+    /// pool can cover it.
+    fn resolve_packet_fee(
+        storage: &mut dyn Storage,
+        info: &MessageInfo,
+        token_id: &str,
+        chain: &str,
+        packet_fee: Option<PacketFee>,
+    ) -> Result<Option<(PacketFee, FeePayer)>, ContractError> {
+        let packet_fee = match packet_fee {
+            Some(fee) => Some(fee),
+            None => TOKEN_PACKET_FEES.may_load(storage, token_id)?,
+        };
+
+        if let Some(fee) = packet_fee.filter(|fee| !fee.is_empty()) {
+            let expected = fee.total();
+            if info.funds != expected {
+                return Err(ContractError::IncorrectPayment {
+                    expected: expected
+                        .iter()
+                        .map(Coin::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                });
+            }
+
+            return Ok(Some((fee, FeePayer::Sender(info.sender.clone()))));
+        }
+
+        if !info.funds.is_empty() {
+            return Err(ContractError::IncorrectPayment {
+                expected: "no funds".to_string(),
+            });
+        }
+
+        match SPONSORED_PACKET_FEES.may_load(storage, chain)? {
+            Some(fee) if fees::withdraw(storage, &fee.total())? => {
+                Ok(Some((fee, FeePayer::FeePool)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Send the attached funds to the ICA of a token with an ICS-20 transfer.
+    /// Only the owner of the token can call this.
+    pub fn fund_ica(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        channel_id: String,
+        timeout: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::IcaExecution)?;
+
+        let state = STATE.load(deps.storage)?;
+        // The refund of a failed transfer relies on its callbacks.
+        if !state.transfer_callbacks {
+            return Err(ContractError::TransferCallbacksDisabled);
+        }
+        let timeout = transfer_timeout(&env, timeout)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+        let ica_address = NFT_ICA_MAP.load(deps.storage, (&token_id, &chain))?;
+
+        let amount = match info.funds.as_slice() {
+            [coin] => coin.clone(),
+            _ => {
+                return Err(ContractError::IncorrectPayment {
+                    expected: "a single coin".to_string(),
+                })
+            }
+        };
+
+        // The deposit is resolved by the acknowledgement of the transfer, not of the ICA channel.
+        let sequence = history::push(
+            deps.storage,
+            &TransactionRecord {
+                sequence: 0,
+                activity_id: None,
+                status: TransactionStatus::Pending,
+                token_id: token_id.clone(),
+                chain,
+                owner,
+                executor: Some(info.sender.to_string()),
+                msg_type: TransactionMsgType::Deposit,
+                block_height: env.block.height,
+                timestamp: env.block.time.nanos(),
+                vote: None,
+                results: vec![],
+                type_urls: vec![],
+                categories: vec![],
+            },
+        )?;
+        let evicted = history::evict(deps.storage, &token_id)?;
+        TRANSFER_REPLY_STATE.save(
+            deps.storage,
+            &(
+                channel_id.clone(),
+                PendingTransfer {
+                    token_id,
+                    sequence,
+                    sender: info.sender,
+                    amount: amount.clone(),
+                },
+            ),
+        )?;
+
+        let transfer_msg = IbcMsg::Transfer {
+            channel_id,
+            to_address: ica_address,
+            amount,
+            timeout: timeout.into(),
+        };
+
+        Ok(Response::new()
+            .add_submessage(SubMsg::reply_on_success(
+                transfer_msg,
+                keys::FUND_ICA_REPLY_ID,
+            ))
+            .add_events(pruned_events(&evicted)?))
+    }
+
+    /// Returns the timeout of an ICS-20 transfer with the given timeout in seconds, or the
+    /// default timeout if none is given.
+    fn transfer_timeout(env: &Env, timeout: Option<u64>) -> Result<Timestamp, ContractError> {
+        let timeout = timeout.unwrap_or(keys::DEFAULT_TRANSFER_TIMEOUT_SECONDS);
+        if timeout == 0 || timeout > keys::MAX_TRANSFER_TIMEOUT_SECONDS {
+            return Err(ContractError::InvalidTimeout {
+                max: keys::MAX_TRANSFER_TIMEOUT_SECONDS,
+            });
+        }
+
+        Ok(env.block.time.plus_seconds(timeout))
+    }
+
+    /// Remove the records of a token that are not pending and were sent before the given
+    /// sequence. Only the owner of the contract can call this, so that the holder of the token
+    /// cannot erase its history.
+    pub fn prune_history(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        before: u64,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let pruned = history::prune(deps.storage, &token_id, before)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "prune_history")
+            .add_attribute("token_id", token_id)
+            .add_attribute("pruned", pruned.len().to_string())
+            .add_events(pruned_events(&pruned)?))
+    }
+
+    /// Returns the events archiving the given pruned records.
+    fn pruned_events(records: &[TransactionRecord]) -> StdResult<Vec<Event>> {
+        records
+            .iter()
+            .map(|record| {
+                Ok(IcaEvent::new(events::ICA_TX_PRUNED, &record.token_id)
+                    .chain(&record.chain)
+                    .sequence(record.sequence)
+                    .owner(Some(&record.owner))
+                    .attribute("record", to_json_string(record)?)
+                    .into())
+            })
+            .collect()
+    }
+
+    /// Set or remove the default packet fee of a token. Only the owner of the token can call this.
+    pub fn set_token_packet_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        fee: Option<PacketFee>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        match fee {
+            Some(fee) => TOKEN_PACKET_FEES.save(deps.storage, &token_id, &fee)?,
+            None => TOKEN_PACKET_FEES.remove(deps.storage, &token_id),
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Set or remove the packet fee paid from the fee pool for a chain. Only the owner can call this.
+    pub fn set_sponsored_packet_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        chain: String,
+        fee: Option<PacketFee>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if chain != keys::DEFAULT_CHAIN_ALIAS && !CHAIN_REGISTRY.has(deps.storage, &chain) {
+            return Err(ContractError::ChainNotFound { chain });
+        }
+
+        match fee {
+            Some(fee) => SPONSORED_PACKET_FEES.save(deps.storage, &chain, &fee)?,
+            None => SPONSORED_PACKET_FEES.remove(deps.storage, &chain),
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Add the sent funds to the fee pool. Only the owner can call this.
+    pub fn fund_fee_pool(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        fees::deposit(deps.storage, &info.funds)?;
+
+        Ok(Response::default())
+    }
+
+    /// Send the given amount from the fee pool to the owner. Only the owner can call this.
+    pub fn withdraw_fee_pool(
+        deps: DepsMut,
+        info: MessageInfo,
+        amount: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if !fees::withdraw(deps.storage, &amount)? {
+            return Err(ContractError::InsufficientFeePool);
+        }
+
+        Ok(Response::new().add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount,
+        }))
+    }
+
+    /// Convert the given cw-ica-controller message to a message of the interchaintxs module
+    /// for the ICA of the given owner string, escrowing the given packet fee.
+    /// The ICA is always reopened on the connection it was registered on.
+    fn native_ica_msg(
+        deps: Deps,
+        owner: &str,
+        connection_id: &str,
+        token_id: &str,
+        chain: &str,
+        msg: IcaControllerExecuteMsg,
+        fee: Option<&PacketFee>,
+    ) -> Result<CosmosMsg, ContractError> {
+        match msg {
+            IcaControllerExecuteMsg::CreateChannel { .. } => {
+                let register_fee = STATE.load(deps.storage)?.native_register_fee;
+                native_ica::register_interchain_account_msg(owner, connection_id, &register_fee)
+            }
+            IcaControllerExecuteMsg::SendCosmosMsgs {
+                messages,
+                packet_memo,
+                timeout_seconds,
+            } => {
+                let ica_address = NFT_ICA_MAP.load(deps.storage, (token_id, chain))?;
+                let msgs = native_ica::encode_msgs(messages, &ica_address)?;
+
+                native_ica::submit_tx_msg(
+                    owner,
+                    connection_id,
+                    msgs,
+                    packet_memo,
+                    timeout_seconds,
+                    fee,
+                )
+            }
+            IcaControllerExecuteMsg::SendCustomIcaMessages {
+                messages,
+                packet_memo,
+                timeout_seconds,
+            } => native_ica::submit_tx_msg(
+                owner,
+                connection_id,
+                native_ica::decode_cosmos_tx(messages.as_slice())?,
+                packet_memo,
+                timeout_seconds,
+                fee,
+            ),
+            _ => Err(ContractError::UnsupportedIcaMsg(
+                "only channel and transaction messages can be sent".to_string(),
+            )),
+        }
+    }
+
+    /// Instantiate the cw721-ica extension contract using the instantiate2 pattern.
+    /// Returns the instantiate2 message and the contract address.
+    ///
+    /// This is ignored since injective doesn't seem to support instantiate2.
+    #[allow(dead_code)]
+    fn instantiate2_cw_ica_controller(
+        api: &dyn Api,
+        querier: QuerierWrapper,
+        env: Env,
+        code_id: u64,
+        salt: Option<String>,
+        channel_open_init_options: Option<ChannelOpenInitOptions>,
+    ) -> Result<(CosmosMsg, Addr), ContractError> {
+        let instantiate_msg = to_json_binary(&cw_ica_controller::types::msg::InstantiateMsg {
+            owner: Some(env.contract.address.to_string()),
+            channel_open_init_options,
+            send_callbacks_to: Some(env.contract.address.to_string()),
+        })?;
+
+        let label = format!("cw-ica-controller-{}", env.block.height);
+
+        utils::instantiate2_contract(api, querier, env, code_id, salt, label, instantiate_msg)
+    }
+
+    /// Instantiate the cw721-ica-extension contract using the submessage pattern.
+    /// Returns the instantiate submessage whose reply will contain the new contract address.
+    pub fn instantiate_cw_ica_controller(
+        env: Env,
+        code_id: u64,
+        channel_open_init_options: Option<ChannelOpenInitOptions>,
+    ) -> Result<SubMsg, ContractError> {
+        let instantiate_msg = WasmMsg::Instantiate {
+            admin: Some(env.contract.address.to_string()),
+            code_id,
+            msg: to_json_binary(&cw_ica_controller::types::msg::InstantiateMsg {
+                owner: Some(env.contract.address.to_string()),
+                channel_open_init_options,
+                send_callbacks_to: Some(env.contract.address.to_string()),
+            })?,
+            label: format!("cw-ica-controller-{}", env.block.height),
+            funds: vec![],
+        };
+
+        Ok(SubMsg::reply_on_success(
+            instantiate_msg,
+            CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
+        ))
+    }
+}
+
+mod query {
+    use super::*;
+
+    use crate::types::{
+        msg::query_responses::{
+            ActiveMintPhaseResponse, ActivityFeedResponse, ChainRegistryEntry, EscrowedFeeEntry,
+            GetIcaAddressesResponse, GetRemainingSupplyResponse, GetTransactionHistoryResponse,
+            GetTransactionResponse, NftIcaPair, PauseStatusResponse, SimulateIcaMsgResponse,
+            TokenIca,
+        },
+        state::{
+            backend::IcaBackend,
+            chain::AllowedConnection,
+            channel::ChannelState,
+            compound::{AutoCompound, PendingCompound},
+            fees::{self, PacketFee},
+            history::{
+                HistoryCursor, HistoryFilter, HistoryOrder, MsgCategory, PendingWithdrawal,
+                TransactionRecord,
+            },
+            hooks::CallbackReceiver,
+            pause::PausableOperation,
+            MintPhase, QueueItem, ACTIVITY_FEED, ALLOWED_CONNECTIONS, AUTO_COMPOUND,
+            CALLBACK_RECEIVERS, CHAIN_REGISTRY, CHAIN_TOKENS, CHANNEL_STATE, ESCROWED_FEES,
+            EXECUTOR_ACTIVITY, GUARDIAN, MINT_PHASES, MSG_CATEGORIES, NATIVE_ICA_CONNECTIONS,
+            NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, PAUSE_FLAGS, PENDING_COMPOUNDS, PENDING_MINTS,
+            PENDING_WITHDRAWALS, PHASE_MINT_COUNT, SPONSORED_PACKET_FEES, SUBSCRIBERS,
+            TOKEN_COUNTER, TOKEN_PACKET_FEES, TOKEN_PRIMARY_CHAIN, TX_HISTORY, TX_HISTORY_SEQUENCE,
+            TX_PAYLOADS,
+        },
+    };
+
+    use cosmwasm_std::{Coin, Order, StdResult};
+    use cw_ica_controller::types::msg::ExecuteMsg as IcaControllerExecuteMsg;
+    use cw_storage_plus::Bound;
+
+    /// Query the contract state.
+    pub fn state(deps: Deps) -> StdResult<ContractState> {
+        STATE.load(deps.storage)
+    }
+
+    /// Query the ICA NFT ID to ICA ID mapping.
+    /// Token IDs are mapped to the ICA controller of their primary chain.
+    pub fn nft_ica_controller_bimap(deps: Deps, key: String) -> StdResult<String> {
+        if let Some((token_id, _)) = NFT_ICA_CONTRACT_BI_MAP.may_load_nft(deps.storage, &key)? {
+            return Ok(token_id);
+        }
+
+        let chain = TOKEN_PRIMARY_CHAIN.load(deps.storage, &key)?;
+        NFT_ICA_CONTRACT_BI_MAP.load_controller(deps.storage, &key, &chain)
+    }
+
+    /// Query the ICA address for a given ICA NFT ID and chain.
+    /// If no chain is given, the primary chain of the token is used.
+    pub fn get_ica_address(
+        deps: Deps,
+        token_id: String,
+        chain: Option<String>,
+    ) -> StdResult<String> {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+
+        NFT_ICA_MAP.load(deps.storage, (&token_id, &chain))
+    }
+
+    /// Query all the ICAs of a given ICA NFT ID.
+    pub fn get_token_icas(deps: Deps, token_id: String) -> StdResult<Vec<TokenIca>> {
+        NFT_ICA_CONTRACT_BI_MAP
+            .controllers(deps.storage, &token_id)?
+            .into_iter()
+            .map(|(chain, controller)| {
+                let backend = if NATIVE_ICA_CONNECTIONS.has(deps.storage, &controller) {
+                    IcaBackend::Native
+                } else {
+                    IcaBackend::CwIcaController
+                };
+
+                Ok(TokenIca {
+                    ica_address: NFT_ICA_MAP.may_load(deps.storage, (&token_id, &chain))?,
+                    ica_controller_address: controller,
+                    backend,
+                    chain,
+                })
+            })
+            .collect()
+    }
+
+    /// Query the ICA controller addresses for a given list of ICA NFT IDs.
+    pub fn get_ica_addresses(
+        deps: Deps,
+        token_ids: Vec<String>,
+    ) -> StdResult<GetIcaAddressesResponse> {
+        let nft_ica_pairs =
+            token_ids
+                .iter()
+                .try_fold(Vec::new(), |mut acc, token_id| -> StdResult<_> {
+                    let chain = TOKEN_PRIMARY_CHAIN.load(deps.storage, token_id)?;
+                    let ica_address = NFT_ICA_MAP.load(deps.storage, (token_id, &chain))?;
+                    acc.push(NftIcaPair {
+                        nft_id: token_id.to_string(),
+                        ica_address,
+                    });
+                    Ok(acc)
+                })?;
+
+        Ok(GetIcaAddressesResponse {
+            pairs: nft_ica_pairs,
+        })
+    }
+
+    /// Query the mint queue.
+    pub fn get_mint_queue(deps: Deps) -> StdResult<Vec<QueueItem>> {
+        PENDING_MINTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, queue_item)| queue_item))
+            .collect()
+    }
+
+    /// Query all the registered host chains.
+    pub fn get_chain_registry(deps: Deps) -> StdResult<Vec<ChainRegistryEntry>> {
+        CHAIN_REGISTRY
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(alias, config)| ChainRegistryEntry { alias, config }))
+            .collect()
+    }
+
+    /// Query the token IDs of the ICA NFTs on the given chain.
+    pub fn get_tokens_by_chain(
+        deps: Deps,
+        chain: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<String>> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+        let min = start_after.as_deref().map(Bound::exclusive);
+
+        CHAIN_TOKENS
+            .prefix(&chain)
+            .keys(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .collect()
+    }
+
+    /// Query the categories of the message type URLs.
+    pub fn get_msg_categories(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<MsgCategory>> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+        let min = start_after.as_deref().map(Bound::exclusive);
+
+        MSG_CATEGORIES
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(type_url, category)| MsgCategory { type_url, category }))
+            .collect()
+    }
+
+    /// Query the paused operations and the guardian.
+    pub fn get_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+        Ok(PauseStatusResponse {
+            flags: PAUSE_FLAGS.may_load(deps.storage)?.unwrap_or_default(),
+            guardian: GUARDIAN.may_load(deps.storage)?,
+        })
+    }
+
+    /// Query the contracts receiving the hooks of all the tokens.
+    pub fn get_subscribers(deps: Deps) -> StdResult<Vec<String>> {
+        SUBSCRIBERS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|addr| addr.map(String::from))
+            .collect()
+    }
+
+    /// Query the connections that can be used to mint ICAs besides the default one.
+    pub fn get_allowed_connections(deps: Deps) -> StdResult<Vec<AllowedConnection>> {
+        ALLOWED_CONNECTIONS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(
+                    |(connection_id, counterparty_connection_id)| AllowedConnection {
+                        connection_id,
+                        counterparty_connection_id,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Query the transaction history for a given NFT ID.
+    /// Only the records matching the given filter are returned.
+    pub fn get_transaction_history(
+        deps: Deps,
+        token_id: String,
+        start_after: Option<HistoryCursor>,
+        limit: Option<u32>,
+        order: HistoryOrder,
+        filter: HistoryFilter,
+    ) -> StdResult<GetTransactionHistoryResponse> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+
+        // Records are stored by sequence, and their timestamps increase with their sequence.
+        let (sequence_cursor, timestamp_cursor) = match start_after {
+            Some(HistoryCursor::Sequence(sequence)) => (Some(Bound::exclusive(sequence)), None),
+            Some(HistoryCursor::Timestamp(timestamp)) => (None, Some(timestamp)),
+            None => (None, None),
+        };
+        let (min, max, order) = match order {
+            HistoryOrder::Ascending => (sequence_cursor, None, Order::Ascending),
+            HistoryOrder::Descending => (None, sequence_cursor, Order::Descending),
+        };
+        let is_before_cursor = |record: &TransactionRecord| match (timestamp_cursor, order) {
+            (Some(timestamp), Order::Ascending) => record.timestamp <= timestamp,
+            (Some(timestamp), Order::Descending) => record.timestamp >= timestamp,
+            (None, _) => false,
+        };
+
+        let mut records = TX_HISTORY
+            .prefix(&token_id)
+            .range(deps.storage, min, max, order)
+            .map(|item| item.map(|(sequence, record)| TransactionRecord { sequence, ..record }))
+            .skip_while(|item| item.as_ref().map_or(false, &is_before_cursor))
+            .filter(|item| item.as_ref().map_or(true, |record| filter.matches(record)))
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let next_cursor = if records.len() > limit {
+            records.truncate(limit);
+            records
+                .last()
+                .map(|record| HistoryCursor::Sequence(record.sequence))
+        } else {
+            None
+        };
+
+        let total = TX_HISTORY_SEQUENCE
+            .may_load(deps.storage, &token_id)?
+            .unwrap_or_default();
+
+        Ok(GetTransactionHistoryResponse {
+            records,
+            total: total as u32,
+            next_cursor,
+        })
+    }
+
+    /// Query the transaction records sent by the given address across all tokens.
+    pub fn get_activity_by_address(
+        deps: Deps,
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order: HistoryOrder,
+    ) -> StdResult<ActivityFeedResponse> {
+        let (min, max, order) = activity_bounds(start_after, order);
+        let keys = EXECUTOR_ACTIVITY
+            .prefix(&address)
+            .range(deps.storage, min, max, order);
+
+        activity_page(deps, keys, limit)
+    }
+
+    /// Query the transaction records of all tokens.
+    pub fn get_activity_feed(
+        deps: Deps,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order: HistoryOrder,
+    ) -> StdResult<ActivityFeedResponse> {
+        let (min, max, order) = activity_bounds(start_after, order);
+        let keys = ACTIVITY_FEED.range(deps.storage, min, max, order);
+
+        activity_page(deps, keys, limit)
+    }
+
+    /// Returns the range bounds and order of an activity feed page.
+    fn activity_bounds(
+        start_after: Option<u64>,
+        order: HistoryOrder,
+    ) -> (
+        Option<Bound<'static, u64>>,
+        Option<Bound<'static, u64>>,
+        Order,
+    ) {
+        let cursor = start_after.map(Bound::exclusive);
+        match order {
+            HistoryOrder::Ascending => (cursor, None, Order::Ascending),
+            HistoryOrder::Descending => (None, cursor, Order::Descending),
+        }
+    }
+
+    /// Loads the records of the given (activity id, (token id, sequence)) entries, up to the limit.
+    fn activity_page(
+        deps: Deps,
+        keys: impl Iterator<Item = StdResult<(u64, (String, u64))>>,
+        limit: Option<u32>,
+    ) -> StdResult<ActivityFeedResponse> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+
+        let mut entries = keys.take(limit + 1).collect::<StdResult<Vec<_>>>()?;
+        let next_cursor = if entries.len() > limit {
+            entries.truncate(limit);
+            entries.last().map(|(activity_id, _)| *activity_id)
+        } else {
+            None
+        };
+
+        // The records indexed before they stored their activity ID are not unindexed when they
+        // are removed, so the missing records are skipped.
+        let records = entries
+            .into_iter()
+            .filter_map(|(_, (token_id, sequence))| {
+                TX_HISTORY
+                    .may_load(deps.storage, (&token_id, sequence))
+                    .transpose()
+            })
+            .collect::<StdResult<_>>()?;
+
+        Ok(ActivityFeedResponse {
+            records,
+            next_cursor,
+        })
+    }
+
+    /// Query a transaction record of a given NFT ID with its stored payloads.
+    pub fn get_transaction(
+        deps: Deps,
+        token_id: String,
+        sequence: u64,
+    ) -> StdResult<GetTransactionResponse> {
+        Ok(GetTransactionResponse {
+            record: TX_HISTORY.load(deps.storage, (&token_id, sequence))?,
+            payload: TX_PAYLOADS.may_load(deps.storage, (&token_id, sequence))?,
+        })
+    }
+
+    /// Query the pending withdrawal of a transaction record of a token.
+    pub fn get_pending_withdrawal(
+        deps: Deps,
+        token_id: String,
+        sequence: u64,
+    ) -> StdResult<Option<PendingWithdrawal>> {
+        PENDING_WITHDRAWALS.may_load(deps.storage, (&token_id, sequence))
+    }
+
+    /// Simulate an [`ExecuteMsg::ExecuteIcaMsg`] sent by the given sender, using the same checks.
+    /// The packet fee and the sent funds are not verified.
+    pub fn simulate_ica_msg(
+        deps: Deps,
+        env: Env,
+        sender: String,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+    ) -> StdResult<SimulateIcaMsgResponse> {
+        let mut violations = vec![];
+
+        if let Err(err) = execute::assert_not_paused(deps.storage, PausableOperation::IcaExecution)
+        {
+            violations.push(err.to_string());
+        }
+
+        let authorized = match execute::verify_token_owner(deps, &token_id, &sender) {
+            Ok(_) => true,
+            Err(err) => {
+                violations.push(err.to_string());
+                false
+            }
+        };
+
+        let target = match execute::ica_target(deps.storage, &token_id, chain) {
+            Ok(target) => target,
+            Err(err) => {
+                violations.push(err.to_string());
+                return Ok(SimulateIcaMsgResponse {
+                    authorized,
+                    chain: None,
+                    channel: None,
+                    msg_type: None,
+                    controller_msg: None,
+                    violations,
+                });
+            }
+        };
+
+        let channel = CHANNEL_STATE.may_load(deps.storage, (&token_id, &target.chain))?;
+        let msg_type = TransactionRecord::from_ica_msg(
+            &msg,
+            &token_id,
+            &target.chain,
+            &sender,
+            env.block.height,
+            env.block.time.nanos(),
+        )
+        .map(|record| record.msg_type);
+        let controller_msg = match execute::controller_msg(deps, &target, &token_id, msg, None) {
+            Ok(controller_msg) => Some(controller_msg),
+            Err(err) => {
+                violations.push(err.to_string());
+                None
+            }
+        };
+
+        Ok(SimulateIcaMsgResponse {
+            authorized,
+            chain: Some(target.chain),
+            channel,
+            msg_type,
+            controller_msg,
+            violations,
+        })
+    }
+
+    /// Query the channel state for a given ICA NFT ID and chain.
+    /// If no chain is given, the primary chain of the token is used.
+    pub fn get_channel_state(
+        deps: Deps,
+        token_id: String,
+        chain: Option<String>,
+    ) -> StdResult<ChannelState> {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+
+        CHANNEL_STATE.load(deps.storage, (&token_id, &chain))
+    }
+
+    /// Query the remaining supply of the collection.
+    pub fn get_remaining_supply(deps: Deps) -> StdResult<GetRemainingSupplyResponse> {
+        let max_supply = STATE.load(deps.storage)?.max_supply;
+        let total_supply = TOKEN_COUNTER.may_load(deps.storage)?.unwrap_or_default();
+
+        Ok(GetRemainingSupplyResponse {
+            max_supply,
+            total_supply,
+            remaining: max_supply.map(|max| max.saturating_sub(total_supply)),
+        })
+    }
+
+    /// Query all the configured mint phases.
+    pub fn get_mint_phases(deps: Deps) -> StdResult<Vec<MintPhase>> {
+        Ok(MINT_PHASES.may_load(deps.storage)?.unwrap_or_default())
+    }
+
+    /// Query the currently active mint phase.
+    pub fn get_active_mint_phase(
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<Option<ActiveMintPhaseResponse>> {
+        let active_phase = MINT_PHASES
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .find(|(_, phase)| phase.is_active(env.block.time))
+            .map(|(phase_id, phase)| ActiveMintPhaseResponse {
+                phase_id: phase_id as u32,
+                phase,
+            });
+
+        Ok(active_phase)
+    }
+
+    /// Query the default packet fee of the given token.
+    pub fn get_token_packet_fee(deps: Deps, token_id: String) -> StdResult<Option<PacketFee>> {
+        TOKEN_PACKET_FEES.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the packet fee paid from the fee pool for the given chain.
+    pub fn get_sponsored_packet_fee(deps: Deps, chain: String) -> StdResult<Option<PacketFee>> {
+        SPONSORED_PACKET_FEES.may_load(deps.storage, &chain)
+    }
+
+    /// Query the balance of the fee pool.
+    pub fn get_fee_pool(deps: Deps) -> StdResult<Vec<Coin>> {
+        fees::balance(deps.storage)
+    }
+
+    /// Query the outstanding packet fees escrowed for the given token.
+    pub fn get_escrowed_fees(deps: Deps, token_id: String) -> StdResult<Vec<EscrowedFeeEntry>> {
+        ESCROWED_FEES
+            .prefix(&token_id)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(sequence, escrowed)| EscrowedFeeEntry { sequence, escrowed }))
+            .collect()
+    }
+
+    /// Query the auto-compound state of the given token.
+    pub fn get_auto_compound(deps: Deps, token_id: String) -> StdResult<Option<AutoCompound>> {
+        AUTO_COMPOUND.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the compound in progress of the given token id.
+    pub fn get_pending_compound(
+        deps: Deps,
+        token_id: String,
+    ) -> StdResult<Option<PendingCompound>> {
+        PENDING_COMPOUNDS.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the callback receiver of the given token id.
+    pub fn get_callback_receiver(
+        deps: Deps,
+        token_id: String,
+    ) -> StdResult<Option<CallbackReceiver>> {
+        CALLBACK_RECEIVERS.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the number of NFTs minted by the given address in the given mint phase.
+    pub fn get_phase_mint_count(deps: Deps, phase_id: u32, address: String) -> StdResult<u64> {
+        Ok(PHASE_MINT_COUNT
+            .may_load(deps.storage, (phase_id, &address))?
+            .unwrap_or_default())
+    }
+}
+
+mod sudo {
+    use super::*;
+
+    use cosmwasm_std::BankMsg;
+
+    use crate::{
+        types::{
+            msg::{callbacks::NftIcaHookMsg, RequestPacket},
+            state::{
+                backend::IcaBackend, history, NATIVE_ICA_CONNECTIONS, PENDING_TRANSFERS, TX_HISTORY,
+            },
+        },
+        utils::native_ica,
+    };
+
+    /// Handle the channel open ack of a native ICA.
+    pub fn open_ack(
+        deps: DepsMut,
+        env: Env,
+        port_id: String,
+        channel_id: String,
+        counterparty_version: String,
+    ) -> Result<Response, ContractError> {
+        let owner = native_owner(deps.as_ref(), &port_id)?;
+        let ica_address = native_ica::ica_address_from_version(&counterparty_version)?;
+
+        execute::channel_open_ack(
+            deps,
+            &env,
+            &owner,
+            IcaBackend::Native,
+            ica_address,
+            channel_id,
+        )
+    }
+
+    /// Handle the acknowledgement or timeout of a packet sent to a native ICA or of an
+    /// ICS-20 transfer sent to an ICA.
+    pub fn packet_result(
+        deps: DepsMut,
+        request: RequestPacket,
+        status: TransactionStatus,
+        ack: Option<AckPayload>,
+    ) -> Result<Response, ContractError> {
+        let port_id = request
+            .source_port
+            .ok_or_else(|| StdError::generic_err("source port not found"))?;
+        if port_id == keys::ICS20_PORT_ID {
+            return transfer_result(deps, request.source_channel, request.sequence, status, ack);
+        }
+
+        let owner = native_owner(deps.as_ref(), &port_id)?;
+
+        execute::packet_result(deps, &owner, status, ack)
+    }
+
+    /// Set the status of the deposit record of an ICS-20 transfer sent to an ICA.
+    /// The funds of a failed transfer are refunded to this contract, so they are sent back
+    /// to the sender.
+    fn transfer_result(
+        deps: DepsMut,
+        channel_id: Option<String>,
+        sequence: Option<u64>,
+        status: TransactionStatus,
+        ack: Option<AckPayload>,
+    ) -> Result<Response, ContractError> {
+        let (Some(channel_id), Some(sequence)) = (channel_id, sequence) else {
+            return Err(StdError::generic_err("source channel or sequence not found").into());
+        };
+        // Transfers that were not sent to an ICA are ignored.
+        let Some(transfer) = PENDING_TRANSFERS.may_load(deps.storage, (&channel_id, sequence))?
+        else {
+            return Ok(Response::default());
+        };
+        PENDING_TRANSFERS.remove(deps.storage, (&channel_id, sequence));
+
+        let mut response = Response::default();
+        if status != TransactionStatus::Completed {
+            response = response.add_message(BankMsg::Send {
+                to_address: transfer.sender.to_string(),
+                amount: vec![transfer.amount],
+            });
+        }
+
+        history::update_status(deps.storage, &transfer.token_id, transfer.sequence, status)?;
+        if let Some(ack) = ack {
+            history::save_ack_payload(deps.storage, &transfer.token_id, transfer.sequence, ack)?;
+        }
+
+        // The record may have been pruned while the transfer was pending.
+        if let Some(record) =
+            TX_HISTORY.may_load(deps.storage, (&transfer.token_id, transfer.sequence))?
+        {
+            response = response.add_submessages(execute::subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::TransactionCompleted {
+                    token_id: transfer.token_id,
+                    record,
+                },
+            )?);
+        }
+
+        Ok(response)
+    }
+
+    /// Returns the owner string of the native ICA of the given port.
+    fn native_owner(deps: Deps, port_id: &str) -> Result<String, ContractError> {
+        let owner = native_ica::owner_from_port(port_id).ok_or(ContractError::Unauthorized)?;
+        if !NATIVE_ICA_CONNECTIONS.has(deps.storage, owner) {
+            return Err(ContractError::Unauthorized);
+        }
+
+        Ok(owner.to_string())
+    }
+}
+
+mod reply {
+    use cosmwasm_std::{SubMsgResult, WasmMsg};
+    use cw721_ica_extension::ExtensionMsg;
+
+    use crate::types::state::{
+        NFT_ICA_CONTRACT_BI_MAP, NFT_MINT_QUEUE, PENDING_MINTS, PENDING_TRANSFERS,
+        REGISTERED_ICA_ADDRS, TOKEN_PRIMARY_CHAIN, TRANSFER_REPLY_STATE,
+    };
+    use crate::utils::ics20;
+
+    use super::*;
+
+    pub fn cw721_instantiate(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(reply) => {
+                let event = reply
+                    .events
+                    .iter()
+                    .find(|e| {
+                        e.ty == "instantiate"
+                            || e.ty == "cosmwasm.wasm.v1.EventContractInstantiated"
+                    })
+                    .ok_or_else(|| StdError::generic_err("instantiate event not found"))?;
+                let maybe_address = &event
+                    .attributes
+                    .iter()
+                    .find(|a| a.key == "_contract_address" || a.key == "contract_address")
+                    .ok_or_else(|| StdError::generic_err("contract_address attribute not found"))?
+                    .value;
+
+                // added this to remove the quotes from the address in injective
+                let addr = deps.api.addr_validate(
+                    maybe_address
+                        .chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .collect::<String>()
+                        .as_str(),
+                )?;
+
+                STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+                    cs.cw721_ica_extension_address = addr.clone();
+                    Ok(cs)
+                })?;
+
+                // This contract accepts the transfer hook, which is disabled by default.
+                let enable_transfer_hook = WasmMsg::Execute {
+                    contract_addr: addr.to_string(),
+                    msg: to_json_binary(&cw721_ica_extension::ExecuteMsg::Extension {
+                        msg: ExtensionMsg::SetTransferHook { enabled: true },
+                    })?,
+                    funds: vec![],
+                };
+
+                Ok(Response::new().add_message(enable_transfer_hook))
+            }
+            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        }
+    }
+
+    pub fn cw_ica_controller_instantiate(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(reply) => {
+                let event = reply
+                    .events
+                    .iter()
+                    .find(|e| {
+                        e.ty == "instantiate"
+                            || e.ty == "cosmwasm.wasm.v1.EventContractInstantiated"
+                    })
+                    .ok_or_else(|| StdError::generic_err("instantiate event not found"))?;
+                let maybe_address = &event
+                    .attributes
+                    .iter()
+                    .find(|a| a.key == "_contract_address" || a.key == "contract_address")
+                    .ok_or_else(|| StdError::generic_err("contract_address attribute not found"))?
+                    .value;
+
+                // added this to remove the quotes from the address in injective
+                let addr = deps.api.addr_validate(
+                    maybe_address
+                        .chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .collect::<String>()
+                        .as_str(),
+                )?;
+
+                REGISTERED_ICA_ADDRS.insert(deps.storage, &addr)?;
+
+                // The replies are processed in the same order as the requests were queued.
+                let queue_item = NFT_MINT_QUEUE
+                    .pop_back(deps.storage)?
+                    .ok_or_else(|| StdError::generic_err("mint queue empty"))?;
+                NFT_ICA_CONTRACT_BI_MAP.insert(
+                    deps.storage,
+                    addr.as_str(),
+                    &queue_item.token_id,
+                    &queue_item.chain,
+                )?;
+
+                // Only the ICA on the primary chain of the token mints a new NFT.
+                if TOKEN_PRIMARY_CHAIN.load(deps.storage, &queue_item.token_id)? == queue_item.chain
+                {
+                    PENDING_MINTS.save(deps.storage, addr.as_str(), &queue_item)?;
+                }
+
+                Ok(Response::new())
+            }
+            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        }
+    }
+
+    pub fn fund_ica_transfer(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(reply) => {
+                let data = reply
+                    .data
+                    .ok_or_else(|| StdError::generic_err("transfer response data not found"))?;
+                let packet_sequence = ics20::transfer_sequence(&data)?;
+
+                // The transfer is tracked until its acknowledgement or timeout is received.
+                let (channel_id, transfer) = TRANSFER_REPLY_STATE.load(deps.storage)?;
+                TRANSFER_REPLY_STATE.remove(deps.storage);
+                PENDING_TRANSFERS.save(deps.storage, (&channel_id, packet_sequence), &transfer)?;
+
+                Ok(Response::new())
+            }
+            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        }
+    }
+
+    /// The callback receiver is only replied to on error, which is ignored so that the
+    /// callback is still processed.
+    pub fn callback_receiver(msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(_) => Ok(Response::new()),
+            SubMsgResult::Err(err) => Ok(Response::new()
+                .add_attribute("action", "callback_receiver_failed")
+                .add_attribute("error", err)),
+        }
+    }
+
+    /// The subscribers are only replied to on error, including running out of gas, which is
+    /// ignored so that the message sending the hook is still processed.
+    pub fn subscriber_hook(msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(_) => Ok(Response::new()),
+            SubMsgResult::Err(err) => Ok(Response::new()
+                .add_attribute("action", "subscriber_hook_failed")
+                .add_attribute("error", err)),
+        }
+    }
+}
+
+mod migrate {
+    use super::*;
+
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Empty, Order, Storage};
+    use cw_storage_plus::{Deque, Map};
+
+    use crate::types::state::{
+        channel::ChannelState,
+        history::{self, TransactionMsgType, TransactionRecord},
+        CHAIN_TOKENS, CHANNEL_STATE, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
+        TOKEN_PRIMARY_CHAIN,
+    };
+
+    /// The map used to map nft token ids to ICA addresses before tokens had several chains.
+    const LEGACY_NFT_ICA_MAP: Map<&str, String> = Map::new("nft_ica_map");
+    /// The map used to store both directions between cw-ica-controller addresses and NFT IDs
+    /// before tokens had several chains.
+    const LEGACY_NFT_ICA_CONTRACT_BI_MAP: Map<&str, String> = Map::new("nft_ica_contract_bi_map");
+    /// The map used to store the channel status of each token id before tokens had several chains.
+    const LEGACY_CHANNEL_STATE: Map<&str, ChannelState> = Map::new("channel_status");
+    /// The prefix of the queue used to store the transaction history of each token id, newest
+    /// first, before the history was indexed by sequence.
+    const LEGACY_TX_HISTORY_PREFIX: &str = "tx_history_";
+
+    /// The transaction record stored before the history was indexed by sequence.
+    #[cw_serde]
+    struct LegacyTransactionRecord {
+        status: TransactionStatus,
+        token_id: String,
+        owner: String,
+        msg_type: TransactionMsgType,
+        block_height: u64,
+        timestamp: u64,
+    }
+
+    /// Move the ICAs stored before tokens had several chains to the default chain, which
+    /// becomes the primary chain of their token.
+    pub fn migrate_single_chain_icas(storage: &mut dyn Storage) -> Result<(), ContractError> {
+        // The queued mints used to be bound to their controller when their channel opened,
+        // so they cannot be matched with their controller anymore.
+        let count = NFT_MINT_QUEUE.len(storage)?;
+        if count != 0 {
+            return Err(ContractError::MintQueueNotEmpty { count });
+        }
+
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        let icas = LEGACY_NFT_ICA_MAP
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (token_id, ica_address) in icas {
+            NFT_ICA_MAP.save(storage, (&token_id, chain), &ica_address)?;
+            TOKEN_PRIMARY_CHAIN.save(storage, &token_id, &chain.to_string())?;
+            CHAIN_TOKENS.save(storage, (chain, &token_id), &Empty {})?;
+            LEGACY_NFT_ICA_MAP.remove(storage, &token_id);
+
+            let controller = LEGACY_NFT_ICA_CONTRACT_BI_MAP.may_load(storage, &token_id)?;
+            if let Some(controller) = &controller {
+                NFT_ICA_CONTRACT_BI_MAP.insert(storage, controller, &token_id, chain)?;
+                LEGACY_NFT_ICA_CONTRACT_BI_MAP.remove(storage, &token_id);
+                LEGACY_NFT_ICA_CONTRACT_BI_MAP.remove(storage, controller);
+            }
+
+            if let Some(channel_state) = LEGACY_CHANNEL_STATE.may_load(storage, &token_id)? {
+                CHANNEL_STATE.save(storage, (&token_id, chain), &channel_state)?;
+                LEGACY_CHANNEL_STATE.remove(storage, &token_id);
+            }
+
+            migrate_tx_history(storage, &token_id, controller.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the transaction history of the given token id from its legacy queue to the history
+    /// indexed by sequence, in the order the transactions were sent. The pending transactions
+    /// are resolved by the acknowledgements from the given ICA controller.
+    fn migrate_tx_history(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        controller: Option<&str>,
+    ) -> StdResult<()> {
+        let prefix = format!("{}{}", LEGACY_TX_HISTORY_PREFIX, token_id);
+        let legacy_store: Deque<LegacyTransactionRecord> = Deque::new(&prefix);
+
+        while let Some(legacy) = legacy_store.pop_back(storage)? {
+            let record = TransactionRecord {
+                sequence: 0,
+                activity_id: None,
+                status: legacy.status,
+                token_id: legacy.token_id,
+                chain: keys::DEFAULT_CHAIN_ALIAS.to_string(),
+                owner: legacy.owner,
+                executor: None,
+                msg_type: legacy.msg_type,
+                block_height: legacy.block_height,
+                timestamp: legacy.timestamp,
+                vote: None,
+                results: vec![],
+                type_urls: vec![],
+                categories: vec![],
+            };
+
+            match controller {
+                Some(controller) if record.status == TransactionStatus::Pending => {
+                    history::push_pending(storage, controller, &record)?
+                }
+                _ => history::push(storage, &record)?,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{
+        coin, from_json,
+        testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+        Addr, BankMsg, Coin, ContractResult, CosmosMsg, OwnedDeps, ReplyOn, SubMsgResult,
+        SystemResult, Uint128,
+    };
+    use cw721_ica_extension::NftTransferHookMsg;
+    use cw_ica_controller::types::msg::ExecuteMsg as IcaControllerExecuteMsg;
+    use cw_storage_plus::{Deque, Map};
+
+    use crate::types::{
+        keys::CALLBACK_RECEIVER_GAS_LIMIT,
+        state::{
+            backend::IcaBackend,
+            channel::{ChannelState, ChannelStatus},
+            compound::{AutoCompound, AutoCompoundConfig, CompoundStep, PendingCompound},
+            fees::{self, FeePayer, PacketFee},
+            history::{self, PendingWithdrawal, TransactionMsgType, TransactionRecord},
+            hooks::CallbackReceiver,
+            pause::PausableOperation,
+            QueueItem, AUTO_COMPOUND, CALLBACK_RECEIVERS, CHAIN_TOKENS, CHANNEL_STATE,
+            ESCROWED_FEES, FEE_POOL, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
+            PENDING_COMPOUNDS, PENDING_WITHDRAWALS, REGISTERED_ICA_ADDRS, SPONSORED_PACKET_FEES,
+            TOKEN_PRIMARY_CHAIN, TX_HISTORY,
+        },
+    };
+
+    use super::*;
+
+    const EXTENSION: &str = "cw721-ica-extension";
+    const CONTROLLER: &str = "cw-ica-controller";
+    const HOLDER: &str = "holder";
+    const TOKEN_ID: &str = "ica-token-0";
+
+    fn setup(deps: DepsMut) {
+        STATE
+            .save(
+                deps.storage,
+                &ContractState {
+                    default_chan_init_options: from_json(
+                        br#"{"connection_id":"connection-0","counterparty_connection_id":"connection-0"}"#,
+                    )
+                    .unwrap(),
+                    default_backend: Default::default(),
+                    ica_controller_code_id: 1,
+                    cw721_ica_extension_address: Addr::unchecked(EXTENSION),
+                    max_supply: None,
+                    max_per_address: None,
+                    mint_gate_collection: None,
+                    max_payload_size: None,
+                    max_history_per_token: None,
+                    transfer_callbacks: false,
+                    ibc_hooks: false,
+                    native_register_fee: vec![],
+                },
+            )
+            .unwrap();
+        NFT_ICA_CONTRACT_BI_MAP
+            .insert(
+                deps.storage,
+                CONTROLLER,
+                TOKEN_ID,
+                keys::DEFAULT_CHAIN_ALIAS,
+            )
+            .unwrap();
+    }
+
+    /// Registers the ICA of the token with an open channel, and mocks the holder as the owner
+    /// of the token.
+    fn setup_ica(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) {
+        setup(deps.as_mut());
+        let storage = deps.as_mut().storage;
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        REGISTERED_ICA_ADDRS
+            .insert(storage, &Addr::unchecked(CONTROLLER))
+            .unwrap();
+        TOKEN_PRIMARY_CHAIN
+            .save(storage, TOKEN_ID, &chain.to_string())
+            .unwrap();
+        NFT_ICA_MAP
+            .save(storage, (TOKEN_ID, chain), &"cosmos1ica".to_string())
+            .unwrap();
+        CHANNEL_STATE
+            .save(
+                storage,
+                (TOKEN_ID, chain),
+                &ChannelState {
+                    status: ChannelStatus::Open,
+                    channel_id: Some("channel-0".to_string()),
+                },
+            )
+            .unwrap();
+        deps.querier.update_wasm(|_| {
+            SystemResult::Ok(ContractResult::Ok(Binary::from(
+                br#"{"owner":"holder","approvals":[]}"#.as_slice(),
+            )))
+        });
+    }
+
+    fn packet_fee() -> PacketFee {
+        PacketFee {
+            recv_fee: vec![coin(1, "untrn")],
+            ack_fee: vec![coin(2, "untrn")],
+            timeout_fee: vec![coin(3, "untrn")],
+        }
+    }
+
+    fn execute_ica_msg(packet_fee: Option<PacketFee>) -> ExecuteMsg {
+        ExecuteMsg::ExecuteIcaMsg {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            msg: IcaControllerExecuteMsg::SendCosmosMsgs {
+                messages: vec![],
+                packet_memo: None,
+                timeout_seconds: None,
+            },
+            packet_fee,
+        }
+    }
+
+    /// Enables auto-compounding for the token with the given bounty balance, and starts a
+    /// compound at the given step reserving a bounty of 10 ujuno.
+    fn setup_compound(deps: DepsMut, sequence: u64, step: CompoundStep) {
+        AUTO_COMPOUND
+            .save(
+                deps.storage,
+                TOKEN_ID,
+                &AutoCompound {
+                    chain: keys::DEFAULT_CHAIN_ALIAS.to_string(),
+                    config: AutoCompoundConfig {
+                        validator: "validator".to_string(),
+                        frequency: 86400,
+                        min_amount: coin(100, "uatom"),
+                        bounty: Some(coin(10, "ujuno")),
+                    },
+                    last_compound: None,
+                    bounty_balance: Uint128::new(20),
+                    depositor: Some(Addr::unchecked(HOLDER)),
+                },
+            )
+            .unwrap();
+        PENDING_COMPOUNDS
+            .save(
+                deps.storage,
+                TOKEN_ID,
+                &PendingCompound {
+                    keeper: Addr::unchecked("keeper"),
+                    depositor: Addr::unchecked(HOLDER),
+                    bounty: Some(coin(10, "ujuno")),
+                    sequence,
+                    step,
+                },
+            )
+            .unwrap();
+    }
+
+    fn bank_sends(res: &Response) -> Vec<(String, Vec<Coin>)> {
+        res.messages
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    Some((to_address.clone(), amount.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Records a pending transaction of the holder and returns its sequence.
+    fn push_pending_tx(deps: DepsMut) -> u64 {
+        let record = TransactionRecord::from_ica_msg(
+            &from_json(br#"{"send_cosmos_msgs":{"messages":[]}}"#).unwrap(),
+            TOKEN_ID,
+            keys::DEFAULT_CHAIN_ALIAS,
+            HOLDER,
+            1,
+            1,
+        )
+        .unwrap();
+        history::push_pending(deps.storage, CONTROLLER, &record).unwrap()
+    }
+
+    #[test]
+    fn test_failing_callback_receiver() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        CALLBACK_RECEIVERS
+            .save(
+                deps.as_mut().storage,
+                TOKEN_ID,
+                &CallbackReceiver {
+                    owner: Addr::unchecked(HOLDER),
+                    addr: Addr::unchecked("receiver"),
+                },
+            )
+            .unwrap();
+
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        let callback = res
+            .messages
+            .iter()
+            .find(|msg| msg.id == CALLBACK_RECEIVER_REPLY_ID)
+            .unwrap();
+        assert_eq!(callback.reply_on, ReplyOn::Error);
+        assert_eq!(callback.gas_limit, Some(CALLBACK_RECEIVER_GAS_LIMIT));
+
+        // the error of the receiver is ignored, so the record stays resolved
+        reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: CALLBACK_RECEIVER_REPLY_ID,
+                result: SubMsgResult::Err("out of gas".to_string()),
+            },
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Completed);
+    }
+
+    #[test]
+    fn test_withdrawal_in_transit() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        let mut env = mock_env();
+        let timeout = env.block.time.plus_seconds(600);
+        PENDING_WITHDRAWALS
+            .save(
+                deps.as_mut().storage,
+                (TOKEN_ID, sequence),
+                &PendingWithdrawal {
+                    receiver: Addr::unchecked(HOLDER),
+                    ica_address: "ica".to_string(),
+                    destination_channel: "channel-0".to_string(),
+                    host_sequence: None,
+                    timeout,
+                },
+            )
+            .unwrap();
+
+        // the ICA executed the transfer, which is not received yet
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::InTransit);
+
+        // only the ibc-hooks sender of the transfer can complete the withdrawal
+        execute::complete_withdrawal(
+            deps.as_mut(),
+            mock_info(HOLDER, &[]),
+            TOKEN_ID.to_string(),
+            sequence,
+        )
+        .unwrap_err();
+
+        let err =
+            execute::expire_withdrawal(deps.as_mut(), env.clone(), TOKEN_ID.to_string(), sequence)
+                .unwrap_err();
+        assert!(matches!(err, ContractError::WithdrawalNotExpired { .. }));
+
+        env.block.time = timeout;
+        execute::expire_withdrawal(deps.as_mut(), env, TOKEN_ID.to_string(), sequence).unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Timeout);
+        assert!(!PENDING_WITHDRAWALS.has(deps.as_ref().storage, (TOKEN_ID, sequence)));
+    }
+
+    #[test]
+    fn test_failed_withdrawal() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        PENDING_WITHDRAWALS
+            .save(
+                deps.as_mut().storage,
+                (TOKEN_ID, sequence),
+                &PendingWithdrawal {
+                    receiver: Addr::unchecked(HOLDER),
+                    ica_address: "ica".to_string(),
+                    destination_channel: "channel-0".to_string(),
+                    host_sequence: None,
+                    timeout: mock_env().block.time,
+                },
+            )
+            .unwrap();
+
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error("failed".to_string())),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Failed);
+        assert!(!PENDING_WITHDRAWALS.has(deps.as_ref().storage, (TOKEN_ID, sequence)));
+    }
+
+    #[test]
+    fn test_failed_compound_releases_bounty() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Withdrawing);
+
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error("failed".to_string())),
+        )
+        .unwrap();
+        assert!(bank_sends(&res).is_empty());
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+        let auto_compound = AUTO_COMPOUND.load(deps.as_ref().storage, TOKEN_ID).unwrap();
+        assert_eq!(auto_compound.bounty_balance, Uint128::new(30));
+    }
+
+    #[test]
+    fn test_compound_without_rewards() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Withdrawing);
+
+        // no rewards were withdrawn, so nothing is delegated
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+        let auto_compound = AUTO_COMPOUND.load(deps.as_ref().storage, TOKEN_ID).unwrap();
+        assert_eq!(auto_compound.bounty_balance, Uint128::new(30));
+    }
+
+    #[test]
+    fn test_compound_pays_bounty_once_delegated() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Delegating);
+
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![("keeper".to_string(), vec![coin(10, "ujuno")])]
+        );
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+        let auto_compound = AUTO_COMPOUND.load(deps.as_ref().storage, TOKEN_ID).unwrap();
+        assert_eq!(auto_compound.bounty_balance, Uint128::new(20));
+    }
+
+    #[test]
+    fn test_transfer_clears_auto_compound() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Withdrawing);
+
+        let res = execute::nft_transfer_hook(
+            deps.as_mut(),
+            mock_info(EXTENSION, &[]),
+            NftTransferHookMsg {
+                token_id: TOKEN_ID.to_string(),
+                from: HOLDER.to_string(),
+                to: "buyer".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![
+                (HOLDER.to_string(), vec![coin(20, "ujuno")]),
+                (HOLDER.to_string(), vec![coin(10, "ujuno")]),
+            ]
+        );
+        assert!(!AUTO_COMPOUND.has(deps.as_ref().storage, TOKEN_ID));
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+    }
+
+    #[cw_serde]
+    struct LegacyRecord {
+        status: TransactionStatus,
+        token_id: String,
+        owner: String,
+        msg_type: TransactionMsgType,
+        block_height: u64,
+        timestamp: u64,
+    }
+
+    #[test]
+    fn test_migrate_single_chain_icas() {
+        let mut deps = mock_dependencies();
+        let legacy_icas: Map<&str, String> = Map::new("nft_ica_map");
+        let legacy_bi_map: Map<&str, String> = Map::new("nft_ica_contract_bi_map");
+        let legacy_channels: Map<&str, ChannelState> = Map::new("channel_status");
+        let channel_state = ChannelState {
+            status: ChannelStatus::Open,
+            channel_id: Some("channel-0".to_string()),
+        };
+        let storage = deps.as_mut().storage;
+        legacy_icas
+            .save(storage, TOKEN_ID, &"cosmos1ica".to_string())
+            .unwrap();
+        legacy_bi_map
+            .save(storage, TOKEN_ID, &CONTROLLER.to_string())
+            .unwrap();
+        legacy_bi_map
+            .save(storage, CONTROLLER, &TOKEN_ID.to_string())
+            .unwrap();
+        legacy_channels
+            .save(storage, TOKEN_ID, &channel_state)
+            .unwrap();
+        // The legacy history stores the newest record first.
+        let legacy_history: Deque<LegacyRecord> = Deque::new("tx_history_ica-token-0");
+        for (status, block_height) in [
+            (TransactionStatus::Completed, 1),
+            (TransactionStatus::Pending, 2),
+        ] {
+            legacy_history
+                .push_front(
+                    storage,
+                    &LegacyRecord {
+                        status,
+                        token_id: TOKEN_ID.to_string(),
+                        owner: HOLDER.to_string(),
+                        msg_type: TransactionMsgType::Delegate,
+                        block_height,
+                        timestamp: 0,
+                    },
+                )
+                .unwrap();
+        }
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let storage = deps.as_ref().storage;
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        assert_eq!(
+            NFT_ICA_MAP.load(storage, (TOKEN_ID, chain)).unwrap(),
+            "cosmos1ica"
+        );
+        assert_eq!(TOKEN_PRIMARY_CHAIN.load(storage, TOKEN_ID).unwrap(), chain);
+        assert!(CHAIN_TOKENS.has(storage, (chain, TOKEN_ID)));
+        assert_eq!(
+            NFT_ICA_CONTRACT_BI_MAP
+                .load_nft(storage, CONTROLLER)
+                .unwrap(),
+            (TOKEN_ID.to_string(), chain.to_string())
+        );
+        assert_eq!(
+            CHANNEL_STATE.load(storage, (TOKEN_ID, chain)).unwrap(),
+            channel_state
+        );
+        assert!(!legacy_icas.has(storage, TOKEN_ID));
+        assert!(!legacy_bi_map.has(storage, TOKEN_ID));
+        assert!(!legacy_bi_map.has(storage, CONTROLLER));
+        assert!(!legacy_channels.has(storage, TOKEN_ID));
+        assert_eq!(legacy_history.len(storage).unwrap(), 0);
+
+        let record = TX_HISTORY.load(storage, (TOKEN_ID, 0)).unwrap();
+        assert_eq!(record.status, TransactionStatus::Completed);
+        assert_eq!(record.block_height, 1);
+        assert_eq!(record.chain, chain);
+        let record = TX_HISTORY.load(storage, (TOKEN_ID, 1)).unwrap();
+        assert_eq!(record.status, TransactionStatus::Pending);
+        assert_eq!(record.block_height, 2);
+        assert_eq!(
+            cw2::get_contract_version(storage).unwrap().version,
+            keys::CONTRACT_VERSION
+        );
+
+        // The pending record is resolved by the next acknowledgement of the controller.
+        let (sequence, _) = history::resolve_pending(
+            deps.as_mut().storage,
+            CONTROLLER,
+            TOKEN_ID,
+            TransactionStatus::Completed,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(sequence, 1);
+    }
+
+    #[test]
+    fn test_migrate_with_queued_mints() {
+        let mut deps = mock_dependencies();
+        NFT_MINT_QUEUE
+            .push_front(
+                deps.as_mut().storage,
+                &QueueItem {
+                    token_id: TOKEN_ID.to_string(),
+                    owner: HOLDER.to_string(),
+                    chain: keys::DEFAULT_CHAIN_ALIAS.to_string(),
+                },
+            )
+            .unwrap();
+
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::MintQueueNotEmpty { count: 1 }));
+    }
+
+    #[test]
+    fn test_packet_fee_paid_by_sender() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+
+        // the fee must be paid with the sent funds
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(Some(packet_fee())),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IncorrectPayment { .. }));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[coin(6, "untrn")]),
+            execute_ica_msg(Some(packet_fee())),
+        )
+        .unwrap();
+        assert!(res.messages.iter().any(|msg| matches!(
+            &msg.msg,
+            CosmosMsg::Stargate { type_url, .. } if type_url == "/ibc.applications.fee.v1.MsgPayPacketFee"
+        )));
+        let escrowed = ESCROWED_FEES
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(escrowed.fee, packet_fee());
+        assert_eq!(escrowed.payer, FeePayer::Sender(Addr::unchecked(HOLDER)));
+
+        // the unused timeout fee is refunded to the sender once the packet is acknowledged
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![(HOLDER.to_string(), vec![coin(3, "untrn")])]
+        );
+        assert!(!ESCROWED_FEES.has(deps.as_ref().storage, (TOKEN_ID, 0)));
+    }
+
+    #[test]
+    fn test_packet_fee_paid_by_fee_pool() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        SPONSORED_PACKET_FEES
+            .save(
+                deps.as_mut().storage,
+                keys::DEFAULT_CHAIN_ALIAS,
+                &packet_fee(),
+            )
+            .unwrap();
+        fees::deposit(deps.as_mut().storage, &[coin(10, "untrn")]).unwrap();
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(None),
+        )
+        .unwrap();
+        assert_eq!(
+            FEE_POOL.load(deps.as_ref().storage, "untrn").unwrap(),
+            Uint128::new(4)
+        );
+        let escrowed = ESCROWED_FEES
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(escrowed.payer, FeePayer::FeePool);
+
+        // the unused receive and acknowledgement fees of a timed out packet return to the pool
+        let res =
+            execute::packet_result(deps.as_mut(), CONTROLLER, TransactionStatus::Timeout, None)
+                .unwrap();
+        assert!(bank_sends(&res).is_empty());
+        assert_eq!(
+            FEE_POOL.load(deps.as_ref().storage, "untrn").unwrap(),
+            Uint128::new(7)
+        );
+        assert!(!ESCROWED_FEES.has(deps.as_ref().storage, (TOKEN_ID, 0)));
+    }
+
+}
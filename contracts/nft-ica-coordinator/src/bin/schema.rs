@@ -1,11 +1,13 @@
 use cosmwasm_schema::write_api;
 
-use nft_ica_coordinator::types::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use nft_ica_coordinator::types::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 
 fn main() {
     write_api! {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
+        sudo: SudoMsg,
+        migrate: MigrateMsg,
     }
 }
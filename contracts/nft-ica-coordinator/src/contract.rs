@@ -4,10 +4,14 @@ use cosmwasm_std::{entry_point, Addr, Reply, StdError};
 use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
 
 use crate::types::keys::{
-    self, CW721_INSTANTIATE_REPLY_ID, CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
+    self, CALLBACK_RECEIVER_REPLY_ID, CW721_INSTANTIATE_REPLY_ID,
+    CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID, FUND_ICA_REPLY_ID, SUBSCRIBER_HOOK_REPLY_ID,
+};
+use crate::types::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
+use crate::types::state::{
+    history::{AckPayload, TransactionStatus},
+    ContractState, STATE,
 };
-use crate::types::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::types::state::{ContractState, STATE};
 use crate::types::ContractError;
 
 /// Instantiate the contract.
@@ -28,10 +32,19 @@ pub fn instantiate(
 
     let state = ContractState {
         default_chan_init_options: msg.default_chan_init_options,
+        default_backend: msg.default_backend.unwrap_or_default(),
         ica_controller_code_id: msg.ica_controller_code_id,
         // TODO: remove this once injective supports instantiate2 (There is already a branch which supports it).
         // Must be filled in by the reply from the cw721-ica-extension contract.
         cw721_ica_extension_address: Addr::unchecked("".to_string()),
+        max_supply: msg.max_supply,
+        max_per_address: msg.max_per_address,
+        mint_gate_collection: None,
+        max_payload_size: msg.max_payload_size,
+        max_history_per_token: msg.max_history_per_token,
+        transfer_callbacks: msg.transfer_callbacks,
+        ibc_hooks: msg.ibc_hooks,
+        native_register_fee: msg.native_register_fee,
     };
 
     STATE.save(deps.storage, &state)?;
@@ -50,26 +63,183 @@ pub fn execute(
     match msg {
         ExecuteMsg::UpdateOwnership(action) => execute::update_ownership(deps, env, info, action),
         ExecuteMsg::ReceiveIcaCallback(callback) => {
-            execute::receive_ica_callback(deps, info, callback)
+            execute::receive_ica_callback(deps, env, info, callback)
+        }
+        ExecuteMsg::NftTransferHook(hook) => execute::nft_transfer_hook(deps, info, hook),
+        ExecuteMsg::MintIca {
+            salt,
+            proof,
+            gate_token_id,
+            recipient,
+            count,
+            chain,
+            chan_init_options,
+        } => execute::mint_ica(
+            deps,
+            env,
+            info,
+            salt,
+            proof,
+            gate_token_id,
+            recipient,
+            count,
+            chain,
+            chan_init_options,
+        ),
+        ExecuteMsg::SetMintPhases { phases } => execute::set_mint_phases(deps, info, phases),
+        ExecuteMsg::SetMintGate { collection } => execute::set_mint_gate(deps, info, collection),
+        ExecuteMsg::SetMaxPayloadSize { size } => execute::set_max_payload_size(deps, info, size),
+        ExecuteMsg::SetMaxHistoryPerToken { max } => {
+            execute::set_max_history_per_token(deps, info, max)
+        }
+        ExecuteMsg::SetTransferCallbacks { enabled } => {
+            execute::set_transfer_callbacks(deps, info, enabled)
+        }
+        ExecuteMsg::SetIbcHooks { enabled } => execute::set_ibc_hooks(deps, info, enabled),
+        ExecuteMsg::SetNativeRegisterFee { fee } => {
+            execute::set_native_register_fee(deps, info, fee)
+        }
+        ExecuteMsg::UpdateAllowedConnections { add, remove } => {
+            execute::update_allowed_connections(deps, info, add, remove)
+        }
+        ExecuteMsg::SetGuardian { guardian } => execute::set_guardian(deps, info, guardian),
+        ExecuteMsg::Pause { operations } => execute::pause(deps, info, operations),
+        ExecuteMsg::Unpause { operations } => execute::unpause(deps, info, operations),
+        ExecuteMsg::UpdateSubscribers { add, remove } => {
+            execute::update_subscribers(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdateMsgCategories { add, remove } => {
+            execute::update_msg_categories(deps, info, add, remove)
+        }
+        ExecuteMsg::RegisterChain { alias, config } => {
+            execute::register_chain(deps, info, alias, config)
+        }
+        ExecuteMsg::RemoveChain { alias } => execute::remove_chain(deps, info, alias),
+        ExecuteMsg::AddIcaToToken { token_id, chain } => {
+            execute::add_ica_to_token(deps, env, info, token_id, chain)
+        }
+        ExecuteMsg::ExecuteIcaMsg {
+            token_id,
+            chain,
+            msg,
+            packet_fee,
+        } => execute::ica_msg(deps, env, info, token_id, chain, msg, packet_fee),
+        ExecuteMsg::FundIca {
+            token_id,
+            chain,
+            channel_id,
+            timeout,
+        } => execute::fund_ica(deps, env, info, token_id, chain, channel_id, timeout),
+        ExecuteMsg::WithdrawFromIca {
+            token_id,
+            chain,
+            amount,
+            source_channel,
+            destination_channel,
+            receiver,
+            timeout,
+        } => execute::withdraw_from_ica(
+            deps,
+            env,
+            info,
+            token_id,
+            chain,
+            amount,
+            source_channel,
+            destination_channel,
+            receiver,
+            timeout,
+        ),
+        ExecuteMsg::CompleteWithdrawal { token_id, sequence } => {
+            execute::complete_withdrawal(deps, info, token_id, sequence)
         }
-        ExecuteMsg::MintIca { salt } => execute::mint_ica(deps, env, info, salt),
-        ExecuteMsg::ExecuteIcaMsg { token_id, msg } => {
-            execute::ica_msg(deps, env, info, token_id, msg)
+        ExecuteMsg::ExpireWithdrawal { token_id, sequence } => {
+            execute::expire_withdrawal(deps, env, token_id, sequence)
         }
+        ExecuteMsg::IcaDelegate {
+            token_id,
+            chain,
+            validator,
+            amount,
+        } => execute::ica_delegate(deps, env, info, token_id, chain, validator, amount),
+        ExecuteMsg::IcaUndelegate {
+            token_id,
+            chain,
+            validator,
+            amount,
+        } => execute::ica_undelegate(deps, env, info, token_id, chain, validator, amount),
+        ExecuteMsg::IcaRedelegate {
+            token_id,
+            chain,
+            src_validator,
+            dst_validator,
+            amount,
+        } => execute::ica_redelegate(
+            deps,
+            env,
+            info,
+            token_id,
+            chain,
+            src_validator,
+            dst_validator,
+            amount,
+        ),
+        ExecuteMsg::IcaClaimRewards {
+            token_id,
+            chain,
+            validators,
+        } => execute::ica_claim_rewards(deps, env, info, token_id, chain, validators),
+        ExecuteMsg::IcaSetWithdrawAddress {
+            token_id,
+            chain,
+            address,
+        } => execute::ica_set_withdraw_address(deps, env, info, token_id, chain, address),
+        ExecuteMsg::IcaVote {
+            token_id,
+            chain,
+            proposal_id,
+            option,
+        } => execute::ica_vote(deps, env, info, token_id, chain, proposal_id, option),
+        ExecuteMsg::IcaVoteWeighted {
+            token_id,
+            chain,
+            proposal_id,
+            votes,
+        } => execute::ica_vote_weighted(deps, env, info, token_id, chain, proposal_id, votes),
+        ExecuteMsg::SetAutoCompound {
+            token_id,
+            chain,
+            config,
+        } => execute::set_auto_compound(deps, info, token_id, chain, config),
+        ExecuteMsg::Compound { token_id } => execute::compound(deps, env, info, token_id),
+        ExecuteMsg::PruneHistory { token_id, before } => {
+            execute::prune_history(deps, info, token_id, before)
+        }
+        ExecuteMsg::SetCallbackReceiver { token_id, addr } => {
+            execute::set_callback_receiver(deps, info, token_id, addr)
+        }
+        ExecuteMsg::SetTokenPacketFee { token_id, fee } => {
+            execute::set_token_packet_fee(deps, info, token_id, fee)
+        }
+        ExecuteMsg::SetSponsoredPacketFee { chain, fee } => {
+            execute::set_sponsored_packet_fee(deps, info, chain, fee)
+        }
+        ExecuteMsg::FundFeePool {} => execute::fund_fee_pool(deps, info),
+        ExecuteMsg::WithdrawFeePool { amount } => execute::withdraw_fee_pool(deps, info, amount),
     }
 }
 
 /// Query the contract.
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Ownership {} => to_json_binary(&cw_ownable::get_ownership(deps.storage)?),
         QueryMsg::GetContractState {} => to_json_binary(&query::state(deps)?),
         QueryMsg::NftIcaControllerBimap { key } => {
             to_json_binary(&query::nft_ica_controller_bimap(deps, key)?)
         }
-        QueryMsg::GetIcaAddress { token_id } => {
-            to_json_binary(&query::get_ica_address(deps, token_id)?)
+        QueryMsg::GetIcaAddress { token_id, chain } => {
+            to_json_binary(&query::get_ica_address(deps, token_id, chain)?)
         }
         QueryMsg::GetIcaAddresses { token_ids } => {
             to_json_binary(&query::get_ica_addresses(deps, token_ids)?)
@@ -77,15 +247,160 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetMintQueue {} => to_json_binary(&query::get_mint_queue(deps)?),
         QueryMsg::GetTransactionHistory {
             token_id,
-            page,
-            page_size,
+            start_after,
+            limit,
+            order,
+            filter,
         } => to_json_binary(&query::get_transaction_history(
-            deps, token_id, page, page_size,
+            deps,
+            token_id,
+            start_after,
+            limit,
+            order.unwrap_or_default(),
+            filter.unwrap_or_default(),
+        )?),
+        QueryMsg::GetMsgCategories { start_after, limit } => {
+            to_json_binary(&query::get_msg_categories(deps, start_after, limit)?)
+        }
+        QueryMsg::GetActivityByAddress {
+            address,
+            start_after,
+            limit,
+            order,
+        } => to_json_binary(&query::get_activity_by_address(
+            deps,
+            address,
+            start_after,
+            limit,
+            order.unwrap_or_default(),
+        )?),
+        QueryMsg::GetActivityFeed {
+            start_after,
+            limit,
+            order,
+        } => to_json_binary(&query::get_activity_feed(
+            deps,
+            start_after,
+            limit,
+            order.unwrap_or_default(),
+        )?),
+        QueryMsg::GetTransaction { token_id, sequence } => {
+            to_json_binary(&query::get_transaction(deps, token_id, sequence)?)
+        }
+        QueryMsg::GetPendingWithdrawal { token_id, sequence } => {
+            to_json_binary(&query::get_pending_withdrawal(deps, token_id, sequence)?)
+        }
+        QueryMsg::SimulateIcaMsg {
+            sender,
+            funds,
+            token_id,
+            chain,
+            msg,
+            packet_fee,
+        } => to_json_binary(&query::simulate_ica_msg(
+            deps, env, sender, funds, token_id, chain, msg, packet_fee,
+        )?),
+        QueryMsg::GetChannelState { token_id, chain } => {
+            to_json_binary(&query::get_channel_state(deps, token_id, chain)?)
+        }
+        QueryMsg::GetTokenIcas { token_id } => {
+            to_json_binary(&query::get_token_icas(deps, token_id)?)
+        }
+        QueryMsg::GetRemainingSupply {} => to_json_binary(&query::get_remaining_supply(deps)?),
+        QueryMsg::GetPauseStatus {} => to_json_binary(&query::get_pause_status(deps)?),
+        QueryMsg::GetSubscribers {} => to_json_binary(&query::get_subscribers(deps)?),
+        QueryMsg::GetAllowedConnections {} => {
+            to_json_binary(&query::get_allowed_connections(deps)?)
+        }
+        QueryMsg::GetChainRegistry {} => to_json_binary(&query::get_chain_registry(deps)?),
+        QueryMsg::GetTokensByChain {
+            chain,
+            start_after,
+            limit,
+        } => to_json_binary(&query::get_tokens_by_chain(
+            deps,
+            chain,
+            start_after,
+            limit,
         )?),
-        QueryMsg::GetChannelState { token_id } => {
-            to_json_binary(&query::get_channel_state(deps, token_id)?)
+        QueryMsg::GetMintPhases {} => to_json_binary(&query::get_mint_phases(deps)?),
+        QueryMsg::GetActiveMintPhase {} => {
+            to_json_binary(&query::get_active_mint_phase(deps, env)?)
+        }
+        QueryMsg::GetPhaseMintCount { phase_id, address } => {
+            to_json_binary(&query::get_phase_mint_count(deps, phase_id, address)?)
+        }
+        QueryMsg::GetTokenPacketFee { token_id } => {
+            to_json_binary(&query::get_token_packet_fee(deps, token_id)?)
+        }
+        QueryMsg::GetSponsoredPacketFee { chain } => {
+            to_json_binary(&query::get_sponsored_packet_fee(deps, chain)?)
+        }
+        QueryMsg::GetFeePool {} => to_json_binary(&query::get_fee_pool(deps)?),
+        QueryMsg::GetEscrowedFees { token_id } => {
+            to_json_binary(&query::get_escrowed_fees(deps, token_id)?)
+        }
+        QueryMsg::GetAutoCompound { token_id } => {
+            to_json_binary(&query::get_auto_compound(deps, token_id)?)
+        }
+        QueryMsg::GetPendingCompound { token_id } => {
+            to_json_binary(&query::get_pending_compound(deps, token_id)?)
         }
+        QueryMsg::GetCallbackReceiver { token_id } => {
+            to_json_binary(&query::get_callback_receiver(deps, token_id)?)
+        }
+    }
+}
+
+/// Handle the callbacks of the native ICA controller module and of the ICS-20 transfers.
+#[entry_point]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::OpenAck {
+            port_id,
+            channel_id,
+            counterparty_version,
+            ..
+        } => sudo::open_ack(deps, env, port_id, channel_id, counterparty_version),
+        SudoMsg::Response { request, data } => sudo::packet_result(
+            deps,
+            request,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(data)),
+        ),
+        SudoMsg::Error { request, details } => sudo::packet_result(
+            deps,
+            request,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error(details)),
+        ),
+        SudoMsg::Timeout { request } => {
+            sudo::packet_result(deps, request, TransactionStatus::Timeout, None)
+        }
+    }
+}
+
+/// Migrate the contract.
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let previous_version =
+        cw2::ensure_from_older_version(deps.storage, keys::CONTRACT_NAME, keys::CONTRACT_VERSION)?;
+
+    let mut response = Response::default();
+    // The ICAs were stored for a single chain before version 0.2.0, and the collection did not
+    // send the transfer hook.
+    if previous_version.major == 0 && previous_version.minor < 2 {
+        migrate::migrate_single_chain_icas(deps.storage)?;
+
+        let code_id = msg.cw721_ica_extension_code_id.ok_or_else(|| {
+            ContractError::MissingCw721IcaExtensionCodeId {
+                version: previous_version.to_string(),
+            }
+        })?;
+        response = response.add_messages(migrate::cw721_ica_extension_msgs(deps.storage, code_id)?);
     }
+
+    Ok(response)
 }
 
 /// Reply to a submessage.
@@ -94,6 +409,9 @@ pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
     match msg.id {
         CW721_INSTANTIATE_REPLY_ID => reply::cw721_instantiate(deps, msg),
         CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID => reply::cw_ica_controller_instantiate(deps, msg),
+        FUND_ICA_REPLY_ID => reply::fund_ica_transfer(deps, msg),
+        CALLBACK_RECEIVER_REPLY_ID => reply::callback_receiver(msg),
+        SUBSCRIBER_HOOK_REPLY_ID => reply::subscriber_hook(msg),
         id => Err(StdError::generic_err(format!("Unknown reply id: {}", id))),
     }
 }
@@ -156,8 +474,18 @@ mod instantiate {
 mod execute {
     use super::*;
 
-    use cosmwasm_std::{Addr, Api, CosmosMsg, QuerierWrapper, SubMsg, WasmMsg};
-    use cw721_ica_extension::{helpers::new_cw721_ica_extension_helper, Extension};
+    use std::marker::PhantomData;
+
+    use cosmwasm_std::{
+        to_json_string, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal, DistributionMsg, Empty,
+        Event, GovMsg, HexBinary, IbcMsg, Order, QuerierWrapper, StakingMsg, Storage, SubMsg,
+        Timestamp, Uint128, VoteOption, WasmMsg, WeightedVoteOption,
+    };
+    use cw721_base::helpers::Cw721Contract;
+    use cw721_ica_extension::{
+        helpers::new_cw721_ica_extension_helper, ChainIca, Extension, ExtensionMsg,
+        NftTransferHookMsg,
+    };
     use cw_ica_controller::{
         helpers::CwIcaControllerContract,
         ibc::types::packet::acknowledgement::Data,
@@ -166,20 +494,43 @@ mod execute {
             msg::{options::ChannelOpenInitOptions, ExecuteMsg as IcaControllerExecuteMsg},
         },
     };
-    use cw_storage_plus::Deque;
 
     use crate::{
         types::{
-            keys::CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
+            events::{self, IcaEvent},
+            keys::{
+                CALLBACK_RECEIVER_GAS_LIMIT, CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
+                MAX_SUBSCRIBERS, MIN_COMPOUND_FREQUENCY_SECONDS, SUBSCRIBER_HOOK_GAS_LIMIT,
+            },
+            msg::{
+                callbacks::{
+                    NftIcaCallbackMsg, NftIcaHookMsg, ReceiverExecuteMsg, SubscriberExecuteMsg,
+                },
+                execute_responses::MintIcaResponse,
+            },
             state::{
+                backend::IcaBackend,
+                chain::{AllowedConnection, ChainConfig},
                 channel::{ChannelState, ChannelStatus},
-                get_tx_history_prefix,
-                history::{TransactionRecord, TransactionStatus},
-                QueueItem, CHANNEL_STATE, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
-                REGISTERED_ICA_ADDRS, TOKEN_COUNTER,
+                compound::{AutoCompound, AutoCompoundConfig, CompoundStep, PendingCompound},
+                fees::{self, EscrowedFee, FeePayer, PacketFee},
+                history::{
+                    self, MsgCategory, MsgResult, PendingTransfer, PendingWithdrawal,
+                    TransactionMsgType, TransactionRecord,
+                },
+                hooks::CallbackReceiver,
+                pause::PausableOperation,
+                MintPhase, QueueItem, ALLOWED_CONNECTIONS, AUTO_COMPOUND, CALLBACK_RECEIVERS,
+                CHAIN_REGISTRY, CHAIN_TOKENS, CHANNEL_STATE, CONSUMED_GATE_TOKENS, ESCROWED_FEES,
+                GUARDIAN, MINT_COUNT_PER_ADDRESS, MINT_PHASES, MSG_CATEGORIES,
+                NATIVE_ICA_CONNECTIONS, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
+                PAUSE_FLAGS, PENDING_COMPOUNDS, PENDING_MINTS, PENDING_WITHDRAWALS,
+                PHASE_MINT_COUNT, REGISTERED_ICA_ADDRS, SPONSORED_PACKET_FEES, SUBSCRIBERS,
+                TOKEN_COUNTER, TOKEN_PACKET_FEES, TOKEN_PRIMARY_CHAIN, TRANSFER_REPLY_STATE,
+                TX_HISTORY, UNBOUND_MINTS,
             },
         },
-        utils,
+        utils::{self, ica_ack, ics20, ics29, native_ica},
     };
 
     /// Update the ownership of the contract.
@@ -193,455 +544,6024 @@ mod execute {
         Ok(Response::default())
     }
 
-    /// Mint a new ICA for the caller.
+    /// Mint `count` new ICAs for the recipient, or the caller if no recipient is given.
+    #[allow(clippy::too_many_arguments)]
     pub fn mint_ica(
         deps: DepsMut,
         env: Env,
         info: MessageInfo,
         _salt: Option<String>,
+        proof: Option<Vec<HexBinary>>,
+        gate_token_id: Option<String>,
+        recipient: Option<String>,
+        count: Option<u64>,
+        chain: Option<String>,
+        chan_init_options: Option<ChannelOpenInitOptions>,
     ) -> Result<Response, ContractError> {
-        let state = STATE.load(deps.storage)?;
-        let ica_count = TOKEN_COUNTER.may_load(deps.storage)?.unwrap_or_default();
+        assert_not_paused(deps.storage, PausableOperation::Minting)?;
 
-        let queue_item = QueueItem {
-            token_id: format!("{}-{}", keys::TOKEN_PREFIX, ica_count),
-            owner: info.sender.to_string(),
+        let state = STATE.load(deps.storage)?;
+        let chain = chain.unwrap_or_else(|| keys::DEFAULT_CHAIN_ALIAS.to_string());
+        let (chain_options, backend) = chain_ica_options(deps.as_ref(), &state, &chain)?;
+        let chan_init_options = match chan_init_options {
+            Some(options) => {
+                verify_chan_init_options(deps.storage, &chain_options, &options)?;
+                options
+            }
+            None => chain_options,
+        };
+        let recipient = match recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => info.sender.clone(),
         };
 
-        NFT_MINT_QUEUE.push_front(deps.storage, &queue_item)?;
-        TOKEN_COUNTER.save(deps.storage, &(ica_count + 1))?;
+        let count = count.unwrap_or(1);
+        if count == 0 {
+            return Err(ContractError::InvalidMintCount(
+                "count must be greater than zero".to_string(),
+            ));
+        }
+        if count > keys::MAX_MINT_BATCH {
+            return Err(ContractError::InvalidMintCount(format!(
+                "count must not exceed {}",
+                keys::MAX_MINT_BATCH
+            )));
+        }
 
-        let instantiate_submsg = instantiate_cw_ica_controller(
-            env,
-            state.ica_controller_code_id,
-            Some(state.default_chan_init_options),
-        )?;
+        // The token counter is incremented when a mint request is queued, so it also
+        // accounts for the items in the mint queue that are not minted yet.
+        let ica_count = TOKEN_COUNTER.may_load(deps.storage)?.unwrap_or_default();
+        let new_ica_count = checked_mint_count(ica_count, count)?;
+        if let Some(max_supply) = state.max_supply {
+            if new_ica_count > max_supply {
+                return Err(ContractError::MaxSupplyReached { max_supply });
+            }
+        }
 
-        Ok(Response::new().add_submessage(instantiate_submsg))
-    }
+        let address_count = MINT_COUNT_PER_ADDRESS
+            .may_load(deps.storage, recipient.as_str())?
+            .unwrap_or_default();
+        let new_address_count = checked_mint_count(address_count, count)?;
+        if let Some(limit) = state.max_per_address {
+            if new_address_count > limit {
+                return Err(ContractError::MaxPerAddressReached {
+                    address: recipient.to_string(),
+                    limit,
+                });
+            }
+        }
 
-    pub fn receive_ica_callback(
-        deps: DepsMut,
-        info: MessageInfo,
-        callback: IcaControllerCallbackMsg,
-    ) -> Result<Response, ContractError> {
-        if !REGISTERED_ICA_ADDRS.has(deps.storage, &info.sender) {
-            return Err(ContractError::Unauthorized);
-        };
+        if let Some(gate_collection) = &state.mint_gate_collection {
+            let gate_token_id = gate_token_id.ok_or(ContractError::GateTokenRequired)?;
+            if count != 1 {
+                return Err(ContractError::InvalidMintCount(
+                    "only one ICA can be minted per gate token".to_string(),
+                ));
+            }
 
-        match callback {
-            IcaControllerCallbackMsg::OnChannelOpenAckCallback {
-                ica_address,
-                channel,
-                ..
-            } => match NFT_ICA_CONTRACT_BI_MAP.may_load(deps.storage, info.sender.as_str())? {
-                Some(token_id) => {
-                    let channel_state = CHANNEL_STATE.load(deps.storage, &token_id)?;
-                    if channel_state.status == ChannelStatus::Open {
-                        return Err(ContractError::ChannelAlreadyOpen);
-                    };
+            // verify that the sender is the owner of the gate token
+            let partner_cw721 =
+                Cw721Contract::<Empty, Empty>(gate_collection.clone(), PhantomData, PhantomData);
+            let gate_token_owner = partner_cw721
+                .owner_of(&deps.querier, &gate_token_id, false)?
+                .owner;
+            if gate_token_owner != info.sender {
+                return Err(ContractError::Unauthorized);
+            }
 
-                    CHANNEL_STATE.save(
-                        deps.storage,
-                        &token_id,
-                        &ChannelState {
-                            status: ChannelStatus::Open,
-                            channel_id: Some(channel.endpoint.channel_id),
-                        },
-                    )?;
+            if CONSUMED_GATE_TOKENS.has(deps.storage, (gate_collection, gate_token_id.as_str())) {
+                return Err(ContractError::GateTokenAlreadyUsed {
+                    token_id: gate_token_id,
+                });
+            }
+            CONSUMED_GATE_TOKENS.insert(deps.storage, (gate_collection, gate_token_id.as_str()))?;
+        }
 
-                    Ok(Response::default())
-                }
-                None => {
-                    let queue_item = NFT_MINT_QUEUE
-                        .pop_back(deps.storage)?
-                        .ok_or(ContractError::QueueEmpty)?;
-
-                    let cw721_ica_extension_address =
-                        STATE.load(deps.storage)?.cw721_ica_extension_address;
-
-                    NFT_ICA_CONTRACT_BI_MAP.insert(
-                        deps.storage,
-                        info.sender.as_str(),
-                        &queue_item.token_id,
-                    )?;
-
-                    NFT_ICA_MAP.save(deps.storage, &queue_item.token_id, &ica_address)?;
-                    CHANNEL_STATE.save(
-                        deps.storage,
-                        &queue_item.token_id,
-                        &ChannelState {
-                            status: ChannelStatus::Open,
-                            channel_id: Some(channel.endpoint.channel_id),
-                        },
-                    )?;
-
-                    let msg = cw721_ica_extension::ExecuteMsg::Mint {
-                        token_id: queue_item.token_id,
-                        owner: queue_item.owner,
-                        token_uri: None,
-                        extension: Extension {
-                            ica_controller_address: info.sender,
-                            ica_address,
-                        },
-                    };
+        let mut response = Response::new();
 
-                    let cosmos_msg: CosmosMsg = WasmMsg::Execute {
-                        contract_addr: cw721_ica_extension_address.to_string(),
-                        msg: to_json_binary(&msg)?,
-                        funds: vec![],
-                    }
-                    .into();
+        // Mint phases restrict the sender, who may be minting on behalf of the recipient.
+        let mint_phases = MINT_PHASES.may_load(deps.storage)?.unwrap_or_default();
+        let mut is_paid = false;
+        if !mint_phases.is_empty() {
+            let (phase_id, phase) = mint_phases
+                .iter()
+                .enumerate()
+                .find(|(_, phase)| phase.is_active(env.block.time))
+                .ok_or(ContractError::NoActiveMintPhase)?;
+            let phase_id = phase_id as u32;
 
-                    Ok(Response::new().add_message(cosmos_msg))
-                }
-            },
-            IcaControllerCallbackMsg::OnAcknowledgementPacketCallback {
-                original_packet,
-                ica_acknowledgement,
-                ..
-            } => {
-                let maybe_controller = original_packet
-                    .src
-                    .port_id
-                    .strip_prefix(keys::WASM_IBC_PORT_PREFIX);
-                if let Some(controller_addr) = maybe_controller {
-                    let token_id = NFT_ICA_CONTRACT_BI_MAP.load(deps.storage, controller_addr)?;
-                    let prefix = get_tx_history_prefix(&token_id);
-                    let records_store: Deque<TransactionRecord> = Deque::new(&prefix);
-                    let mut record = records_store
-                        .pop_front(deps.storage)?
-                        .ok_or(ContractError::QueueEmpty)?;
-                    record.status = match ica_acknowledgement {
-                        Data::Result(_) => TransactionStatus::Completed,
-                        Data::Error(_) => TransactionStatus::Failed,
-                    };
-                    records_store.push_front(deps.storage, &record)?;
+            if let Some(merkle_root) = &phase.merkle_root {
+                let proof = proof.unwrap_or_default();
+                if !utils::verify_merkle_proof(merkle_root, info.sender.as_bytes(), &proof) {
+                    return Err(ContractError::InvalidMerkleProof);
                 }
-
-                Ok(Response::default())
             }
-            IcaControllerCallbackMsg::OnTimeoutPacketCallback {
-                original_packet, ..
-            } => {
-                let maybe_controller = original_packet
-                    .src
-                    .port_id
-                    .strip_prefix(keys::WASM_IBC_PORT_PREFIX);
 
-                if let Some(controller_addr) = maybe_controller {
-                    let token_id = NFT_ICA_CONTRACT_BI_MAP.load(deps.storage, controller_addr)?;
-
-                    let prefix = get_tx_history_prefix(&token_id);
-                    let records_store: Deque<TransactionRecord> = Deque::new(&prefix);
-                    let mut record = records_store
-                        .pop_front(deps.storage)?
-                        .ok_or(ContractError::QueueEmpty)?;
-                    record.status = TransactionStatus::Timeout;
-                    records_store.push_front(deps.storage, &record)?;
-
-                    CHANNEL_STATE.update(deps.storage, &token_id, |maybe_cs| {
-                        if let Some(mut cs) = maybe_cs {
-                            cs.status = ChannelStatus::Closed;
-                            Ok(cs)
-                        } else {
-                            Err(ContractError::ChannelStateNotFound)
-                        }
-                    })?;
+            let phase_count = PHASE_MINT_COUNT
+                .may_load(deps.storage, (phase_id, info.sender.as_str()))?
+                .unwrap_or_default();
+            let new_phase_count = checked_mint_count(phase_count, count)?;
+            if let Some(limit) = phase.per_address_limit {
+                if new_phase_count > limit {
+                    return Err(ContractError::PhaseLimitReached {
+                        address: info.sender.to_string(),
+                        phase_id,
+                        limit,
+                    });
+                }
+            }
+            if let Some(price) = &phase.price {
+                let expected = Coin {
+                    denom: price.denom.clone(),
+                    amount: price
+                        .amount
+                        .checked_mul(Uint128::from(count))
+                        .map_err(StdError::from)?,
+                };
+                if info.funds != [expected.clone()] {
+                    return Err(ContractError::IncorrectPayment {
+                        expected: expected.to_string(),
+                    });
                 }
 
-                Ok(Response::default())
+                // The mint payment is forwarded to the owner of the contract. Once the ownership
+                // is renounced, it is deposited to the fee pool instead.
+                match cw_ownable::get_ownership(deps.storage)?.owner {
+                    Some(owner) => {
+                        response = response.add_message(BankMsg::Send {
+                            to_address: owner.to_string(),
+                            amount: info.funds.clone(),
+                        });
+                    }
+                    None => fees::deposit(deps.storage, &info.funds)?,
+                }
+                is_paid = true;
             }
+
+            PHASE_MINT_COUNT.save(
+                deps.storage,
+                (phase_id, info.sender.as_str()),
+                &new_phase_count,
+            )?;
         }
+
+        // Funds are only accepted as the payment of the active mint phase.
+        if !is_paid && !info.funds.is_empty() {
+            return Err(ContractError::IncorrectPayment {
+                expected: "no funds".to_string(),
+            });
+        }
+
+        let mut token_ids = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let queue_item = QueueItem {
+                token_id: format!("{}-{}", keys::TOKEN_PREFIX, ica_count + i),
+                owner: recipient.to_string(),
+                chain: chain.clone(),
+            };
+            TOKEN_PRIMARY_CHAIN.save(deps.storage, &queue_item.token_id, &chain)?;
+            token_ids.push(queue_item.token_id.clone());
+            response = response.add_event(
+                IcaEvent::new(events::NFT_ICA_MINT_REQUESTED, &queue_item.token_id)
+                    .chain(&chain)
+                    .owner(Some(&queue_item.owner))
+                    .into(),
+            );
+
+            let open_ica_submsg = open_ica(
+                deps.storage,
+                &env,
+                &state,
+                &backend,
+                queue_item,
+                chan_init_options.clone(),
+            )?;
+            response = response.add_submessage(open_ica_submsg);
+        }
+
+        TOKEN_COUNTER.save(deps.storage, &new_ica_count)?;
+        MINT_COUNT_PER_ADDRESS.save(deps.storage, recipient.as_str(), &new_address_count)?;
+
+        Ok(response.set_data(to_json_binary(&MintIcaResponse { token_ids })?))
     }
 
-    /// Execute a message on the ICA contract if the sender is the owner of the ica token.
-    pub fn ica_msg(
+    /// Verify that the given channel open init options use the connection of the chain or an
+    /// allowed connection, paired with its counterparty connection and the ICA host port.
+    fn verify_chan_init_options(
+        storage: &dyn Storage,
+        chain_options: &ChannelOpenInitOptions,
+        options: &ChannelOpenInitOptions,
+    ) -> Result<(), ContractError> {
+        let counterparty_connection_id = if options.connection_id == chain_options.connection_id {
+            chain_options.counterparty_connection_id.clone()
+        } else {
+            ALLOWED_CONNECTIONS
+                .may_load(storage, &options.connection_id)?
+                .ok_or_else(|| ContractError::ConnectionNotAllowed {
+                    connection_id: options.connection_id.clone(),
+                })?
+        };
+        if options.counterparty_connection_id != counterparty_connection_id {
+            return Err(ContractError::CounterpartyConnectionNotAllowed {
+                connection_id: options.connection_id.clone(),
+                counterparty_connection_id: options.counterparty_connection_id.clone(),
+            });
+        }
+
+        let port_id = options
+            .counterparty_port_id
+            .as_deref()
+            .unwrap_or(keys::ICA_HOST_PORT_ID);
+        if port_id != keys::ICA_HOST_PORT_ID {
+            return Err(ContractError::CounterpartyPortNotAllowed {
+                port_id: port_id.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the given mint count increased by the number of ICAs to mint.
+    fn checked_mint_count(current: u64, count: u64) -> Result<u64, ContractError> {
+        current
+            .checked_add(count)
+            .ok_or_else(|| ContractError::InvalidMintCount("mint count overflow".to_string()))
+    }
+
+    /// Create an additional ICA on the given chain for an existing token.
+    /// Only the owner of the token can call this.
+    pub fn add_ica_to_token(
         deps: DepsMut,
         env: Env,
         info: MessageInfo,
         token_id: String,
-        msg: IcaControllerExecuteMsg,
+        chain: String,
     ) -> Result<Response, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::Minting)?;
+
         let state = STATE.load(deps.storage)?;
 
         // verify that the sender is the owner of the token
-        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let cw721_ica_extension =
+            new_cw721_ica_extension_helper(state.cw721_ica_extension_address.clone());
         let owner = cw721_ica_extension
             .owner_of(&deps.querier, &token_id, false)?
             .owner;
-
         if owner != info.sender {
             return Err(ContractError::Unauthorized);
         };
 
-        let ica_address = Addr::unchecked(NFT_ICA_CONTRACT_BI_MAP.load(deps.storage, &token_id)?);
-        // additional hardening check
-        if !REGISTERED_ICA_ADDRS.has(deps.storage, &ica_address) {
-            return Err(ContractError::Unauthorized);
-        };
-
-        // Set channel status to pending if the message is a create channel message.
-        if matches!(msg, IcaControllerExecuteMsg::CreateChannel { .. })
-            && matches!(
-                CHANNEL_STATE.load(deps.storage, &token_id)?.status,
-                ChannelStatus::Closed
-            )
+        if NFT_ICA_CONTRACT_BI_MAP
+            .may_load_controller(deps.storage, &token_id, &chain)?
+            .is_some()
         {
-            CHANNEL_STATE.save(
-                deps.storage,
-                &token_id,
-                &ChannelState {
-                    status: ChannelStatus::Pending,
-                    channel_id: None,
-                },
-            )?;
+            return Err(ContractError::IcaAlreadyExists { token_id, chain });
         }
 
-        if let Some(tx_record) = TransactionRecord::from_ica_msg(
-            &msg,
-            &token_id,
-            owner,
-            env.block.height,
-            env.block.time.nanos(),
-        ) {
-            let prefix = get_tx_history_prefix(&token_id);
-            let records_store: Deque<TransactionRecord> = Deque::new(&prefix);
-            records_store.push_front(deps.storage, &tx_record)?;
-        }
+        let (chan_init_options, backend) = chain_ica_options(deps.as_ref(), &state, &chain)?;
 
-        let cw_ica_controller = CwIcaControllerContract::new(ica_address);
-        let cosmos_msg = cw_ica_controller.call(msg)?;
+        let queue_item = QueueItem {
+            token_id,
+            owner,
+            chain,
+        };
+        let open_ica_submsg = open_ica(
+            deps.storage,
+            &env,
+            &state,
+            &backend,
+            queue_item,
+            chan_init_options,
+        )?;
 
-        Ok(Response::new().add_message(cosmos_msg))
+        Ok(Response::new().add_submessage(open_ica_submsg))
     }
 
-    /// Instantiate the cw721-ica extension contract using the instantiate2 pattern.
-    /// Returns the instantiate2 message and the contract address.
-    ///
-    /// This is ignored since injective doesn't seem to support instantiate2.
-    #[allow(dead_code)]
-    fn instantiate2_cw_ica_controller(
-        api: &dyn Api,
-        querier: QuerierWrapper,
-        env: Env,
-        code_id: u64,
-        salt: Option<String>,
-        channel_open_init_options: Option<ChannelOpenInitOptions>,
-    ) -> Result<(CosmosMsg, Addr), ContractError> {
-        let instantiate_msg = to_json_binary(&cw_ica_controller::types::msg::InstantiateMsg {
-            owner: Some(env.contract.address.to_string()),
-            channel_open_init_options,
-            send_callbacks_to: Some(env.contract.address.to_string()),
-        })?;
+    /// Returns the channel open init options and the ICA backend of the given chain alias.
+    fn chain_ica_options(
+        deps: Deps,
+        state: &ContractState,
+        chain: &str,
+    ) -> Result<(ChannelOpenInitOptions, IcaBackend), ContractError> {
+        if chain == keys::DEFAULT_CHAIN_ALIAS {
+            return Ok((
+                state.default_chan_init_options.clone(),
+                state.default_backend.clone(),
+            ));
+        }
 
-        let label = format!("cw-ica-controller-{}", env.block.height);
+        let config = CHAIN_REGISTRY
+            .may_load(deps.storage, chain)?
+            .ok_or_else(|| ContractError::ChainNotFound {
+                chain: chain.to_string(),
+            })?;
 
-        utils::instantiate2_contract(api, querier, env, code_id, salt, label, instantiate_msg)
+        Ok((config.chan_init_options, config.backend))
     }
 
-    /// Instantiate the cw721-ica-extension contract using the submessage pattern.
-    /// Returns the instantiate submessage whose reply will contain the new contract address.
-    pub fn instantiate_cw_ica_controller(
-        env: Env,
-        code_id: u64,
-        channel_open_init_options: Option<ChannelOpenInitOptions>,
+    /// Create the ICA of the given queue item using the given backend.
+    /// Returns the submessage that creates the ICA.
+    fn open_ica(
+        storage: &mut dyn Storage,
+        env: &Env,
+        state: &ContractState,
+        backend: &IcaBackend,
+        queue_item: QueueItem,
+        chan_init_options: ChannelOpenInitOptions,
     ) -> Result<SubMsg, ContractError> {
-        let instantiate_msg = WasmMsg::Instantiate {
-            admin: Some(env.contract.address.to_string()),
-            code_id,
-            msg: to_json_binary(&cw_ica_controller::types::msg::InstantiateMsg {
-                owner: Some(env.contract.address.to_string()),
-                channel_open_init_options,
-                send_callbacks_to: Some(env.contract.address.to_string()),
-            })?,
-            label: format!("cw-ica-controller-{}", env.block.height),
-            funds: vec![],
-        };
+        match backend {
+            IcaBackend::CwIcaController => {
+                // The item is bound to its controller in the instantiate reply.
+                NFT_MINT_QUEUE.push_front(storage, &queue_item)?;
 
-        Ok(SubMsg::reply_on_success(
-            instantiate_msg,
-            CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
-        ))
-    }
-}
+                instantiate_cw_ica_controller(
+                    env.clone(),
+                    state.ica_controller_code_id,
+                    Some(chan_init_options),
+                )
+            }
+            IcaBackend::Native => {
+                let owner = native_ica::ica_owner(
+                    &env.contract.address,
+                    &queue_item.token_id,
+                    &queue_item.chain,
+                );
+                NATIVE_ICA_CONNECTIONS.save(storage, &owner, &chan_init_options.connection_id)?;
+                NFT_ICA_CONTRACT_BI_MAP.insert(
+                    storage,
+                    &owner,
+                    &queue_item.token_id,
+                    &queue_item.chain,
+                )?;
 
-mod query {
-    use super::*;
+                // Only the ICA on the primary chain of the token mints a new NFT.
+                if TOKEN_PRIMARY_CHAIN.load(storage, &queue_item.token_id)? == queue_item.chain {
+                    PENDING_MINTS.save(storage, &owner, &queue_item)?;
+                }
 
-    use crate::types::{
-        msg::query_responses::{
-            GetIcaAddressesResponse, GetTransactionHistoryResponse, NftIcaPair,
-        },
-        state::{
-            channel::ChannelState, get_tx_history_prefix, history::TransactionRecord, QueueItem,
-            CHANNEL_STATE, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
-        },
-    };
+                let register_fee = pay_register_fee(storage)?;
+                Ok(SubMsg::new(native_ica::register_interchain_account_msg(
+                    &owner,
+                    &chan_init_options.connection_id,
+                    &register_fee,
+                )?))
+            }
+        }
+    }
 
-    use cosmwasm_std::StdResult;
-    use cw_storage_plus::Deque;
+    /// Withdraw the registration fee of the native backend from the fee pool.
+    /// Returns the fee.
+    fn pay_register_fee(storage: &mut dyn Storage) -> Result<Vec<Coin>, ContractError> {
+        let register_fee = STATE.load(storage)?.native_register_fee;
+        withdraw_fee_pool(storage, &register_fee)?;
+
+        Ok(register_fee)
+    }
+
+    /// Replace the mint phases of the collection. Only the owner can call this.
+    pub fn set_mint_phases(
+        deps: DepsMut,
+        info: MessageInfo,
+        phases: Vec<MintPhase>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if let Some(phase) = phases.iter().find(|phase| phase.start >= phase.end) {
+            return Err(ContractError::InvalidMintPhases(format!(
+                "phase starting at {} must end after it starts",
+                phase.start
+            )));
+        }
+        if let Some(phase) = phases.iter().find(|phase| {
+            phase
+                .price
+                .as_ref()
+                .map_or(false, |price| price.amount.is_zero())
+        }) {
+            return Err(ContractError::InvalidMintPhases(format!(
+                "the price of the phase starting at {} must not be zero",
+                phase.start
+            )));
+        }
+        if phases.windows(2).any(|pair| pair[1].start < pair[0].end) {
+            return Err(ContractError::InvalidMintPhases(
+                "phases must be ordered and must not overlap".to_string(),
+            ));
+        }
+
+        MINT_PHASES.save(deps.storage, &phases)?;
+
+        Ok(Response::default())
+    }
+
+    /// Set or remove the partner cw721 collection that gates minting. Only the owner can call this.
+    pub fn set_mint_gate(
+        deps: DepsMut,
+        info: MessageInfo,
+        collection: Option<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let collection = collection
+            .map(|addr| deps.api.addr_validate(&addr))
+            .transpose()?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.mint_gate_collection = collection;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set the maximum size of the payloads stored with the transaction history.
+    /// Only the owner can call this.
+    pub fn set_max_payload_size(
+        deps: DepsMut,
+        info: MessageInfo,
+        size: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.max_payload_size = size;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set the maximum number of transaction records stored for each token.
+    /// Only the owner can call this.
+    pub fn set_max_history_per_token(
+        deps: DepsMut,
+        info: MessageInfo,
+        max: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.max_history_per_token = max;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set whether the chain sends the callbacks of the ICS-20 transfers.
+    /// Only the owner can call this.
+    pub fn set_transfer_callbacks(
+        deps: DepsMut,
+        info: MessageInfo,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.transfer_callbacks = enabled;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set whether the chain runs the ibc-hooks middleware. Only the owner can call this.
+    pub fn set_ibc_hooks(
+        deps: DepsMut,
+        info: MessageInfo,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.ibc_hooks = enabled;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Set the fee paid from the fee pool to register each ICA of the native backend.
+    /// Only the owner can call this.
+    pub fn set_native_register_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        fee: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+            cs.native_register_fee = fee;
+            Ok(cs)
+        })?;
+
+        Ok(Response::default())
+    }
+
+    /// Add and remove the connections that can be used to mint ICAs. Only the owner can call this.
+    pub fn update_allowed_connections(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<AllowedConnection>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        for connection in &add {
+            ALLOWED_CONNECTIONS.save(
+                deps.storage,
+                &connection.connection_id,
+                &connection.counterparty_connection_id,
+            )?;
+        }
+        for connection_id in &remove {
+            ALLOWED_CONNECTIONS.remove(deps.storage, connection_id);
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Add and remove the contracts receiving the hooks of all the tokens.
+    /// Only the owner can call this.
+    pub fn update_subscribers(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        for addr in &add {
+            let addr = deps.api.addr_validate(addr)?;
+            SUBSCRIBERS.insert(deps.storage, &addr)?;
+        }
+        for addr in &remove {
+            SUBSCRIBERS.remove(deps.storage, &Addr::unchecked(addr));
+        }
+
+        let count = SUBSCRIBERS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .count() as u64;
+        if count > MAX_SUBSCRIBERS {
+            return Err(ContractError::TooManySubscribers {
+                max: MAX_SUBSCRIBERS,
+            });
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Set or remove the guardian, who can pause operations. Only the owner can call this.
+    pub fn set_guardian(
+        deps: DepsMut,
+        info: MessageInfo,
+        guardian: Option<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let response = Response::new().add_attribute("action", "set_guardian");
+        let Some(guardian) = guardian else {
+            GUARDIAN.remove(deps.storage);
+            return Ok(response.add_attribute("guardian", ""));
+        };
+
+        let guardian = deps.api.addr_validate(&guardian)?;
+        GUARDIAN.save(deps.storage, &guardian)?;
+
+        Ok(response.add_attribute("guardian", guardian))
+    }
+
+    /// Pause the given operations. Only the owner or the guardian can call this.
+    pub fn pause(
+        deps: DepsMut,
+        info: MessageInfo,
+        operations: Vec<PausableOperation>,
+    ) -> Result<Response, ContractError> {
+        if GUARDIAN.may_load(deps.storage)?.as_ref() != Some(&info.sender) {
+            cw_ownable::assert_owner(deps.storage, &info.sender)?;
+        }
+
+        set_paused(deps.storage, &operations, true)?;
+
+        Ok(Response::new().add_event(pause_event(events::PAUSED, &info.sender, &operations)))
+    }
+
+    /// Unpause the given operations. Only the owner can call this.
+    pub fn unpause(
+        deps: DepsMut,
+        info: MessageInfo,
+        operations: Vec<PausableOperation>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        set_paused(deps.storage, &operations, false)?;
+
+        Ok(Response::new().add_event(pause_event(events::UNPAUSED, &info.sender, &operations)))
+    }
+
+    /// Set the pause flag of each given operation.
+    fn set_paused(
+        storage: &mut dyn Storage,
+        operations: &[PausableOperation],
+        paused: bool,
+    ) -> StdResult<()> {
+        let mut flags = PAUSE_FLAGS.may_load(storage)?.unwrap_or_default();
+        for operation in operations {
+            flags.set(*operation, paused);
+        }
+        PAUSE_FLAGS.save(storage, &flags)
+    }
+
+    /// Returns the event emitted when the given operations are paused or unpaused.
+    fn pause_event(ty: &str, sender: &Addr, operations: &[PausableOperation]) -> Event {
+        let operations = operations
+            .iter()
+            .map(|operation| operation.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Event::new(ty)
+            .add_attribute("sender", sender)
+            .add_attribute("operations", operations)
+    }
+
+    /// Returns an error if the given operation is paused.
+    pub fn assert_not_paused(
+        storage: &dyn Storage,
+        operation: PausableOperation,
+    ) -> Result<(), ContractError> {
+        let flags = PAUSE_FLAGS.may_load(storage)?.unwrap_or_default();
+        if flags.is_paused(operation) {
+            return Err(ContractError::Paused { operation });
+        }
+
+        Ok(())
+    }
+
+    /// Build the hooks sent to every subscriber. The hooks are gas limited and their errors
+    /// are ignored by the reply.
+    pub fn subscriber_hooks(storage: &dyn Storage, hook: NftIcaHookMsg) -> StdResult<Vec<SubMsg>> {
+        let msg = to_json_binary(&SubscriberExecuteMsg::NftIcaHook(hook))?;
+        SUBSCRIBERS
+            .keys(storage, None, None, Order::Ascending)
+            .map(|addr| {
+                let msg = WasmMsg::Execute {
+                    contract_addr: addr?.to_string(),
+                    msg: msg.clone(),
+                    funds: vec![],
+                };
+                Ok(SubMsg::reply_on_error(msg, SUBSCRIBER_HOOK_REPLY_ID)
+                    .with_gas_limit(SUBSCRIBER_HOOK_GAS_LIMIT))
+            })
+            .collect()
+    }
+
+    /// Handle the transfer of a token notified by the cw721-ica-extension contract.
+    pub fn nft_transfer_hook(
+        deps: DepsMut,
+        info: MessageInfo,
+        hook: NftTransferHookMsg,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.cw721_ica_extension_address {
+            return Err(ContractError::Unauthorized);
+        }
+        // The transfer is reverted by the error.
+        assert_not_paused(deps.storage, PausableOperation::Transfers)?;
+
+        let NftTransferHookMsg { token_id, from, to } = hook;
+        let chain = TOKEN_PRIMARY_CHAIN.may_load(deps.storage, &token_id)?;
+        // The auto-compound configuration and its bounty belong to the previous owner.
+        let refunds = remove_auto_compound(deps.storage, &token_id)?;
+        let event = IcaEvent::new(events::NFT_ICA_TRANSFERRED, &token_id)
+            .owner(Some(&to))
+            .attribute("from", &from);
+        let event = match chain {
+            Some(chain) => event.chain(chain),
+            None => event,
+        };
+
+        let hooks = subscriber_hooks(
+            deps.storage,
+            NftIcaHookMsg::Transferred { token_id, from, to },
+        )?;
+
+        Ok(Response::new()
+            .add_messages(refunds)
+            .add_event(event.into())
+            .add_submessages(hooks))
+    }
+
+    /// Set and remove the categories of message type URLs. Only the owner can call this.
+    pub fn update_msg_categories(
+        deps: DepsMut,
+        info: MessageInfo,
+        add: Vec<MsgCategory>,
+        remove: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        for MsgCategory { type_url, category } in &add {
+            MSG_CATEGORIES.save(deps.storage, type_url, category)?;
+        }
+        for type_url in &remove {
+            MSG_CATEGORIES.remove(deps.storage, type_url);
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Add or update a host chain in the chain registry. Only the owner can call this.
+    pub fn register_chain(
+        deps: DepsMut,
+        info: MessageInfo,
+        alias: String,
+        config: ChainConfig,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if alias == keys::DEFAULT_CHAIN_ALIAS {
+            return Err(ContractError::ReservedChainAlias(alias));
+        }
+
+        CHAIN_REGISTRY.save(deps.storage, &alias, &config)?;
+
+        Ok(Response::default())
+    }
+
+    /// Remove a host chain from the chain registry. Only the owner can call this.
+    pub fn remove_chain(
+        deps: DepsMut,
+        info: MessageInfo,
+        alias: String,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if !CHAIN_REGISTRY.has(deps.storage, &alias) {
+            return Err(ContractError::ChainNotFound { chain: alias });
+        }
+        CHAIN_REGISTRY.remove(deps.storage, &alias);
+
+        Ok(Response::default())
+    }
+
+    /// Handle the callbacks of the cw-ica-controller contracts.
+    pub fn receive_ica_callback(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        callback: IcaControllerCallbackMsg,
+    ) -> Result<Response, ContractError> {
+        if !REGISTERED_ICA_ADDRS.has(deps.storage, &info.sender) {
+            return Err(ContractError::Unauthorized);
+        };
+
+        match callback {
+            IcaControllerCallbackMsg::OnChannelOpenAckCallback {
+                ica_address,
+                channel,
+                ..
+            } => {
+                bind_unbound_mint(deps.storage, info.sender.as_str())?;
+                channel_open_ack(
+                    deps,
+                    &env,
+                    info.sender.as_str(),
+                    IcaBackend::CwIcaController,
+                    ica_address,
+                    channel.endpoint.channel_id,
+                )
+            }
+            IcaControllerCallbackMsg::OnAcknowledgementPacketCallback {
+                original_packet,
+                ica_acknowledgement,
+                ..
+            } => {
+                let maybe_controller = original_packet
+                    .src
+                    .port_id
+                    .strip_prefix(keys::WASM_IBC_PORT_PREFIX);
+                if let Some(controller_addr) = maybe_controller {
+                    let (status, ack) = match ica_acknowledgement {
+                        Data::Result(data) => {
+                            (TransactionStatus::Completed, AckPayload::Result(data))
+                        }
+                        Data::Error(error) => (TransactionStatus::Failed, AckPayload::Error(error)),
+                    };
+                    return packet_result(deps, controller_addr, status, Some(ack));
+                }
+
+                Ok(Response::default())
+            }
+            IcaControllerCallbackMsg::OnTimeoutPacketCallback {
+                original_packet, ..
+            } => {
+                let maybe_controller = original_packet
+                    .src
+                    .port_id
+                    .strip_prefix(keys::WASM_IBC_PORT_PREFIX);
+                if let Some(controller_addr) = maybe_controller {
+                    return packet_result(deps, controller_addr, TransactionStatus::Timeout, None);
+                }
+
+                Ok(Response::default())
+            }
+        }
+    }
+
+    /// Record the ICA of the given controller once its channel is opened.
+    /// The token is minted if this is the ICA on its primary chain, otherwise the ICA is added
+    /// to the existing token.
+    pub fn channel_open_ack(
+        deps: DepsMut,
+        env: &Env,
+        controller: &str,
+        backend: IcaBackend,
+        ica_address: String,
+        channel_id: String,
+    ) -> Result<Response, ContractError> {
+        let (token_id, chain) = NFT_ICA_CONTRACT_BI_MAP.load_nft(deps.storage, controller)?;
+        let channel_state = ChannelState {
+            status: ChannelStatus::Open,
+            channel_id: Some(channel_id.clone()),
+        };
+        let channel_opened = IcaEvent::new(events::CHANNEL_OPENED, &token_id)
+            .chain(&chain)
+            .controller(controller)
+            .ica_address(Some(&ica_address))
+            .channel_id(Some(&channel_id));
+        let channel_hooks = subscriber_hooks(
+            deps.storage,
+            NftIcaHookMsg::ChannelStatusChanged {
+                token_id: token_id.clone(),
+                chain: chain.clone(),
+                status: ChannelStatus::Open,
+                channel_id: Some(channel_id.clone()),
+            },
+        )?;
+
+        // The channel is reopened if the ICA was already registered.
+        if NFT_ICA_MAP.has(deps.storage, (&token_id, &chain)) {
+            let current_state = CHANNEL_STATE.load(deps.storage, (&token_id, &chain))?;
+            if current_state.status == ChannelStatus::Open {
+                return Err(ContractError::ChannelAlreadyOpen);
+            };
+
+            CHANNEL_STATE.save(deps.storage, (&token_id, &chain), &channel_state)?;
+
+            let owner = token_owner(deps.as_ref(), &token_id);
+            return Ok(Response::new()
+                .add_event(channel_opened.owner(owner).into())
+                .add_submessages(channel_hooks));
+        }
+
+        NFT_ICA_MAP.save(deps.storage, (&token_id, &chain), &ica_address)?;
+        CHAIN_TOKENS.save(deps.storage, (&chain, &token_id), &Empty {})?;
+        CHANNEL_STATE.save(deps.storage, (&token_id, &chain), &channel_state)?;
+
+        // The native ICAs are controlled by this contract.
+        let ica_controller_address = match backend {
+            IcaBackend::CwIcaController => Addr::unchecked(controller),
+            IcaBackend::Native => env.contract.address.clone(),
+        };
+        let chain_ica = ChainIca {
+            chain: chain.clone(),
+            ica_controller_address: ica_controller_address.clone(),
+            ica_address: ica_address.clone(),
+        };
+
+        let mut response = Response::new();
+        let mut mint_hooks = vec![];
+        let msg = if TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)? == chain {
+            let queue_item = PENDING_MINTS
+                .may_load(deps.storage, controller)?
+                .ok_or(ContractError::QueueEmpty)?;
+            PENDING_MINTS.remove(deps.storage, controller);
+
+            response = response
+                .add_event(channel_opened.owner(Some(&queue_item.owner)).into())
+                .add_event(
+                    IcaEvent::new(events::NFT_ICA_MINTED, &queue_item.token_id)
+                        .chain(&chain)
+                        .controller(controller)
+                        .ica_address(Some(&ica_address))
+                        .channel_id(Some(&channel_id))
+                        .owner(Some(&queue_item.owner))
+                        .into(),
+                );
+            mint_hooks = subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::Minted {
+                    token_id: queue_item.token_id.clone(),
+                    owner: queue_item.owner.clone(),
+                    chain: chain.clone(),
+                    ica_address: ica_address.clone(),
+                },
+            )?;
+
+            cw721_ica_extension::ExecuteMsg::Mint {
+                token_id: queue_item.token_id,
+                owner: queue_item.owner,
+                token_uri: None,
+                extension: Extension {
+                    ica_controller_address,
+                    ica_address,
+                    chain: Some(chain),
+                    icas: vec![chain_ica],
+                },
+            }
+        } else {
+            let owner = token_owner(deps.as_ref(), &token_id);
+            response = response.add_event(channel_opened.owner(owner).into());
+
+            cw721_ica_extension::ExecuteMsg::Extension {
+                msg: ExtensionMsg::AddIca {
+                    token_id,
+                    ica: chain_ica,
+                },
+            }
+        };
+
+        let cw721_ica_extension_address = STATE.load(deps.storage)?.cw721_ica_extension_address;
+        let cosmos_msg: CosmosMsg = WasmMsg::Execute {
+            contract_addr: cw721_ica_extension_address.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        }
+        .into();
+
+        // The hooks are sent after the token is minted or updated.
+        Ok(response
+            .add_message(cosmos_msg)
+            .add_submessages(mint_hooks)
+            .add_submessages(channel_hooks))
+    }
+
+    /// Bind the oldest mint request migrated from a previous version to the given controller,
+    /// if the controller is not bound yet.
+    pub fn bind_unbound_mint(storage: &mut dyn Storage, controller: &str) -> StdResult<()> {
+        if NFT_ICA_CONTRACT_BI_MAP
+            .may_load_nft(storage, controller)?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        if let Some(queue_item) = UNBOUND_MINTS.pop_back(storage)? {
+            NFT_ICA_CONTRACT_BI_MAP.insert(
+                storage,
+                controller,
+                &queue_item.token_id,
+                &queue_item.chain,
+            )?;
+            PENDING_MINTS.save(storage, controller, &queue_item)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the owner of the given token, or `None` if it cannot be queried, e.g. because
+    /// the token is not minted yet. Used for the events of the callbacks, which must not fail.
+    fn token_owner(deps: Deps, token_id: &str) -> Option<String> {
+        let state = STATE.load(deps.storage).ok()?;
+        new_cw721_ica_extension_helper(state.cw721_ica_extension_address)
+            .owner_of(&deps.querier, token_id, false)
+            .ok()
+            .map(|response| response.owner)
+    }
+
+    /// Set the status of the oldest pending transaction of the given controller and store its
+    /// acknowledgement, if any.
+    /// The channel is closed if the packet timed out, since ICA channels are ordered.
+    pub fn packet_result(
+        deps: DepsMut,
+        controller: &str,
+        status: TransactionStatus,
+        ack: Option<AckPayload>,
+    ) -> Result<Response, ContractError> {
+        let (token_id, chain) = NFT_ICA_CONTRACT_BI_MAP.load_nft(deps.storage, controller)?;
+        let is_timeout = status == TransactionStatus::Timeout;
+
+        let (sequence, record) =
+            history::resolve_pending(deps.storage, controller, &token_id, status)?
+                .ok_or(ContractError::QueueEmpty)?;
+        let mut results = vec![];
+        if let Some(ack) = &ack {
+            // Results that cannot be decoded are not recorded, the acknowledgement must still
+            // be processed.
+            if let AckPayload::Result(data) = ack {
+                if let Ok(decoded) = ica_ack::decode_msg_results(data) {
+                    history::set_results(deps.storage, &token_id, sequence, decoded.clone())?;
+                    results = decoded;
+                }
+            }
+            history::save_ack_payload(deps.storage, &token_id, sequence, ack.clone())?;
+        }
+        // A withdrawal executed by the ICA is in transit until its transfer is received or
+        // expires. It is not pending anymore if the transfer was already received.
+        if let Some(mut withdrawal) =
+            PENDING_WITHDRAWALS.may_load(deps.storage, (&token_id, sequence))?
+        {
+            if record.status == TransactionStatus::Completed {
+                withdrawal.host_sequence = results.iter().find_map(|result| match result {
+                    MsgResult::Transfer { sequence } => Some(*sequence),
+                    _ => None,
+                });
+                PENDING_WITHDRAWALS.save(deps.storage, (&token_id, sequence), &withdrawal)?;
+                history::update_status(
+                    deps.storage,
+                    &token_id,
+                    sequence,
+                    TransactionStatus::InTransit,
+                )?;
+            } else {
+                PENDING_WITHDRAWALS.remove(deps.storage, (&token_id, sequence));
+            }
+        }
+        // A compound moves to its next step, or returns its reserved bounty.
+        let compound_msgs =
+            compound_result(deps.storage, &token_id, sequence, &record.status, &results)?;
+        // The results decoded from the acknowledgement are stored after the record is resolved.
+        let record = TX_HISTORY
+            .may_load(deps.storage, (&token_id, sequence))?
+            .unwrap_or(record);
+
+        let ica_address = NFT_ICA_MAP.may_load(deps.storage, (&token_id, &chain))?;
+        let channel_id = CHANNEL_STATE
+            .may_load(deps.storage, (&token_id, &chain))?
+            .and_then(|cs| cs.channel_id);
+        let event = |ty| {
+            IcaEvent::new(ty, &token_id)
+                .chain(&chain)
+                .controller(controller)
+                .ica_address(ica_address.as_ref())
+                .channel_id(channel_id.as_ref())
+                .owner(Some(&record.owner))
+        };
+
+        let mut response = if is_timeout {
+            Response::new()
+                .add_event(event(events::ICA_TX_TIMEOUT).sequence(sequence).into())
+                .add_event(event(events::CHANNEL_CLOSED).into())
+        } else {
+            Response::new().add_event(
+                event(events::ICA_TX_ACKED)
+                    .sequence(sequence)
+                    .attribute("status", record.status.to_string())
+                    .into(),
+            )
+        };
+        response = response.add_messages(compound_msgs);
+
+        // The fee middleware refunds the unused part of the escrowed fee to this contract
+        // before the callback is received.
+        if let Some(escrowed) = ESCROWED_FEES.may_load(deps.storage, (&token_id, sequence))? {
+            ESCROWED_FEES.remove(deps.storage, (&token_id, sequence));
+
+            let refund = escrowed.fee.refund(is_timeout);
+            if !refund.is_empty() {
+                match escrowed.payer {
+                    FeePayer::Sender(addr) => {
+                        response = response.add_message(BankMsg::Send {
+                            to_address: addr.to_string(),
+                            amount: refund,
+                        });
+                    }
+                    FeePayer::FeePool => fees::deposit(deps.storage, &refund)?,
+                }
+            }
+        }
+
+        if is_timeout {
+            CHANNEL_STATE.update(deps.storage, (&token_id, &chain), |maybe_cs| {
+                if let Some(mut cs) = maybe_cs {
+                    cs.status = ChannelStatus::Closed;
+                    Ok(cs)
+                } else {
+                    Err(ContractError::ChannelStateNotFound)
+                }
+            })?;
+        }
+
+        if let Some(msg) = callback_receiver_msg(deps.storage, &token_id, &record, ack)? {
+            response = response.add_submessage(msg);
+        }
+        response = response.add_submessages(subscriber_hooks(
+            deps.storage,
+            NftIcaHookMsg::TransactionCompleted {
+                token_id: token_id.clone(),
+                record,
+            },
+        )?);
+        if is_timeout {
+            response = response.add_submessages(subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::ChannelStatusChanged {
+                    token_id,
+                    chain,
+                    status: ChannelStatus::Closed,
+                    channel_id,
+                },
+            )?);
+        }
+
+        Ok(response)
+    }
+
+    /// Build the message forwarding the callback of a transaction to the callback receiver of
+    /// the token, if the receiver was registered by the owner who sent the transaction.
+    /// Errors of the receiver, including running out of gas, are ignored by the reply.
+    fn callback_receiver_msg(
+        storage: &dyn Storage,
+        token_id: &str,
+        record: &TransactionRecord,
+        ack: Option<AckPayload>,
+    ) -> StdResult<Option<SubMsg>> {
+        let Some(receiver) = CALLBACK_RECEIVERS.may_load(storage, token_id)? else {
+            return Ok(None);
+        };
+        if record.owner != receiver.owner {
+            return Ok(None);
+        }
+
+        let msg = WasmMsg::Execute {
+            contract_addr: receiver.addr.to_string(),
+            msg: to_json_binary(&ReceiverExecuteMsg::NftIcaCallback(NftIcaCallbackMsg {
+                token_id: token_id.to_string(),
+                record: record.clone(),
+                ack,
+            }))?,
+            funds: vec![],
+        };
+
+        Ok(Some(
+            SubMsg::reply_on_error(msg, CALLBACK_RECEIVER_REPLY_ID)
+                .with_gas_limit(CALLBACK_RECEIVER_GAS_LIMIT),
+        ))
+    }
+
+    /// Set or remove the contract receiving the callbacks of the ICA transactions of the token.
+    /// Only the owner of the token can call this.
+    pub fn set_callback_receiver(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        addr: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        let response = Response::new()
+            .add_attribute("action", "set_callback_receiver")
+            .add_attribute("token_id", &token_id);
+
+        let Some(addr) = addr else {
+            CALLBACK_RECEIVERS.remove(deps.storage, &token_id);
+            return Ok(response.add_attribute("receiver", ""));
+        };
+
+        let addr = deps.api.addr_validate(&addr)?;
+        CALLBACK_RECEIVERS.save(
+            deps.storage,
+            &token_id,
+            &CallbackReceiver {
+                owner: info.sender,
+                addr: addr.clone(),
+            },
+        )?;
+
+        Ok(response.add_attribute("receiver", addr))
+    }
+
+    /// Execute a message on the ICA contract if the sender is the owner of the ica token.
+    /// If no chain is given, the ICA on the primary chain of the token is used.
+    pub fn ica_msg(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: Option<PacketFee>,
+    ) -> Result<Response, ContractError> {
+        send_ica_msg(deps, env, info, token_id, chain, msg, packet_fee, None)
+    }
+
+    /// Withdraw funds from the ICA of a token to this chain. The transfer is received by this
+    /// contract through ibc-hooks, which calls [`complete_withdrawal`] to forward the funds to
+    /// the receiver. Only the owner of the token can call this.
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw_from_ica(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        amount: Coin,
+        source_channel: String,
+        destination_channel: String,
+        receiver: Option<String>,
+        timeout: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        // The transfer could not be received without ibc-hooks.
+        if !STATE.load(deps.storage)?.ibc_hooks {
+            return Err(ContractError::IbcHooksDisabled);
+        }
+
+        // The owner of the token is verified when the message is sent.
+        let receiver = match receiver {
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => info.sender.clone(),
+        };
+        let chain = ica_target(deps.storage, &token_id, chain)?.chain;
+        let ica_address = NFT_ICA_MAP
+            .may_load(deps.storage, (&token_id, &chain))?
+            .ok_or_else(|| ContractError::IcaAddressNotFound {
+                token_id: token_id.clone(),
+                chain: chain.clone(),
+            })?;
+
+        // The transfer must not time out before the ICA packet is relayed to the host chain.
+        let timeout =
+            transfer_timeout(&env, timeout)?.plus_seconds(keys::DEFAULT_ICA_TIMEOUT_SECONDS);
+
+        // The withdrawal is recorded as the next record of the token.
+        let sequence = history::next_sequence(deps.storage, &token_id)?;
+        let memo = ics20::wasm_hook_memo(
+            &env.contract.address,
+            &ExecuteMsg::CompleteWithdrawal {
+                token_id: token_id.clone(),
+                sequence,
+            },
+        )?;
+        let transfer = ics20::transfer_any(
+            &ica_address,
+            source_channel,
+            amount,
+            env.contract.address.to_string(),
+            timeout.into(),
+            Some(memo),
+        );
+        let msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages: vec![CosmosMsg::Stargate {
+                type_url: transfer.type_url,
+                value: transfer.value.into(),
+            }],
+            packet_memo: None,
+            timeout_seconds: Some(keys::DEFAULT_ICA_TIMEOUT_SECONDS),
+        };
+
+        PENDING_WITHDRAWALS.save(
+            deps.storage,
+            (&token_id, sequence),
+            &PendingWithdrawal {
+                receiver,
+                ica_address,
+                destination_channel,
+                host_sequence: None,
+                timeout,
+            },
+        )?;
+
+        send_ica_msg(
+            deps,
+            env,
+            info,
+            token_id,
+            Some(chain),
+            msg,
+            None,
+            Some(TransactionMsgType::Withdraw),
+        )
+    }
+
+    /// Complete a withdrawal whose transfer was received by this contract, forwarding the
+    /// received funds to its receiver. Only the ibc-hooks sender derived from the ICA and the
+    /// destination channel of the withdrawal can call this.
+    pub fn complete_withdrawal(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        sequence: u64,
+    ) -> Result<Response, ContractError> {
+        let withdrawal = PENDING_WITHDRAWALS
+            .may_load(deps.storage, (&token_id, sequence))?
+            .ok_or_else(|| ContractError::WithdrawalNotFound {
+                token_id: token_id.clone(),
+                sequence,
+            })?;
+        let hooks_sender = ics20::ibc_hooks_sender(
+            deps.api,
+            &withdrawal.destination_channel,
+            &withdrawal.ica_address,
+        )?;
+        if info.sender != hooks_sender {
+            return Err(ContractError::Unauthorized);
+        }
+        PENDING_WITHDRAWALS.remove(deps.storage, (&token_id, sequence));
+
+        let mut response = Response::new();
+        if !info.funds.is_empty() {
+            response = response.add_message(BankMsg::Send {
+                to_address: withdrawal.receiver.to_string(),
+                amount: info.funds,
+            });
+        }
+
+        // The transfer may be received before the ICA transaction is acknowledged, in which
+        // case the acknowledgement leaves the record completed.
+        resolve_withdrawal(
+            deps.storage,
+            response,
+            token_id,
+            sequence,
+            TransactionStatus::Completed,
+        )
+    }
+
+    /// Set the record of a withdrawal whose transfer timed out without being received to
+    /// timeout. The host chain refunds the funds to the ICA. Anyone can call this.
+    pub fn expire_withdrawal(
+        deps: DepsMut,
+        env: Env,
+        token_id: String,
+        sequence: u64,
+    ) -> Result<Response, ContractError> {
+        let withdrawal = PENDING_WITHDRAWALS
+            .may_load(deps.storage, (&token_id, sequence))?
+            .ok_or_else(|| ContractError::WithdrawalNotFound {
+                token_id: token_id.clone(),
+                sequence,
+            })?;
+        // The transfer is only sent once the ICA transaction is acknowledged, and it cannot be
+        // received on this chain after its timeout.
+        let status = TX_HISTORY.load(deps.storage, (&token_id, sequence))?.status;
+        if status != TransactionStatus::InTransit || env.block.time < withdrawal.timeout {
+            return Err(ContractError::WithdrawalNotExpired {
+                timeout: withdrawal.timeout,
+            });
+        }
+        PENDING_WITHDRAWALS.remove(deps.storage, (&token_id, sequence));
+
+        resolve_withdrawal(
+            deps.storage,
+            Response::new(),
+            token_id,
+            sequence,
+            TransactionStatus::Timeout,
+        )
+    }
+
+    /// Set the final status of the record of a withdrawal, and notify the callback receiver and
+    /// the subscribers.
+    fn resolve_withdrawal(
+        storage: &mut dyn Storage,
+        mut response: Response,
+        token_id: String,
+        sequence: u64,
+        status: TransactionStatus,
+    ) -> Result<Response, ContractError> {
+        let record = history::update_status(storage, &token_id, sequence, status)?;
+
+        response = response.add_event(
+            IcaEvent::new(events::ICA_WITHDRAWAL_RESOLVED, &token_id)
+                .chain(&record.chain)
+                .sequence(sequence)
+                .owner(Some(&record.owner))
+                .attribute("status", record.status.to_string())
+                .into(),
+        );
+        if let Some(msg) = callback_receiver_msg(storage, &token_id, &record, None)? {
+            response = response.add_submessage(msg);
+        }
+        response = response.add_submessages(subscriber_hooks(
+            storage,
+            NftIcaHookMsg::TransactionCompleted { token_id, record },
+        )?);
+
+        Ok(response)
+    }
+
+    /// Delegate tokens of the ICA of a token. Only the owner of the token can call this.
+    pub fn ica_delegate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![StakingMsg::Delegate { validator, amount }.into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Undelegate tokens of the ICA of a token. Only the owner of the token can call this.
+    pub fn ica_undelegate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![StakingMsg::Undelegate { validator, amount }.into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Redelegate tokens of the ICA of a token. Only the owner of the token can call this.
+    #[allow(clippy::too_many_arguments)]
+    pub fn ica_redelegate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        src_validator: String,
+        dst_validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![StakingMsg::Redelegate {
+            src_validator,
+            dst_validator,
+            amount,
+        }
+        .into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Withdraw the staking rewards of the ICA of a token from the given validators.
+    /// Only the owner of the token can call this.
+    pub fn ica_claim_rewards(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        validators: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        if validators.is_empty() {
+            return Err(ContractError::NoValidators);
+        }
+
+        let messages = validators
+            .into_iter()
+            .map(|validator| DistributionMsg::WithdrawDelegatorReward { validator }.into())
+            .collect();
+
+        send_ica_cosmos_msgs(
+            deps,
+            env,
+            info,
+            token_id,
+            chain,
+            messages,
+            Some(TransactionMsgType::Distribution),
+        )
+    }
+
+    /// Set the staking rewards withdraw address of the ICA of a token.
+    /// Only the owner of the token can call this.
+    pub fn ica_set_withdraw_address(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![DistributionMsg::SetWithdrawAddress { address }.into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Vote on a governance proposal of the host chain with the ICA of a token.
+    /// Only the owner of the token can call this.
+    pub fn ica_vote(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        proposal_id: u64,
+        option: VoteOption,
+    ) -> Result<Response, ContractError> {
+        let messages = vec![GovMsg::Vote {
+            proposal_id,
+            vote: option,
+        }
+        .into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Cast a weighted vote on a governance proposal of the host chain with the ICA of a token.
+    /// Only the owner of the token can call this.
+    pub fn ica_vote_weighted(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        proposal_id: u64,
+        votes: Vec<WeightedVoteOption>,
+    ) -> Result<Response, ContractError> {
+        let total = votes
+            .iter()
+            .try_fold(Decimal::zero(), |total, vote| {
+                total.checked_add(vote.weight)
+            })
+            .map_err(StdError::from)?;
+        if votes.is_empty() || total != Decimal::one() {
+            return Err(ContractError::InvalidVoteWeights);
+        }
+
+        let messages = vec![GovMsg::VoteWeighted {
+            proposal_id,
+            options: votes,
+        }
+        .into()];
+
+        send_ica_cosmos_msgs(deps, env, info, token_id, chain, messages, None)
+    }
+
+    /// Send the given messages to the ICA of a token with the default packet options.
+    fn send_ica_cosmos_msgs(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        messages: Vec<CosmosMsg>,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<Response, ContractError> {
+        let msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages,
+            packet_memo: None,
+            timeout_seconds: None,
+        };
+
+        send_ica_msg(deps, env, info, token_id, chain, msg, None, msg_type)
+    }
+
+    /// Enable, update or disable auto-compounding for a token.
+    /// Only the owner of the token can call this.
+    pub fn set_auto_compound(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        config: Option<AutoCompoundConfig>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        let existing = AUTO_COMPOUND.may_load(deps.storage, &token_id)?;
+        let mut response = Response::new()
+            .add_attribute("action", "set_auto_compound")
+            .add_attribute("token_id", &token_id);
+
+        let Some(config) = config else {
+            if !info.funds.is_empty() {
+                return Err(ContractError::IncorrectPayment {
+                    expected: "no funds".to_string(),
+                });
+            }
+
+            let refunds = remove_auto_compound(deps.storage, &token_id)?;
+
+            return Ok(response
+                .add_messages(refunds)
+                .add_attribute("enabled", "false"));
+        };
+
+        if config.frequency < MIN_COMPOUND_FREQUENCY_SECONDS {
+            return Err(ContractError::InvalidCompoundFrequency {
+                min: MIN_COMPOUND_FREQUENCY_SECONDS,
+            });
+        }
+
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+        // the token must have an ICA on the chain
+        NFT_ICA_CONTRACT_BI_MAP.load_controller(deps.storage, &token_id, &chain)?;
+
+        // The bounty balance is kept if the bounty denom and the depositor do not change,
+        // otherwise it is refunded to its depositor.
+        let bounty_denom = config.bounty.as_ref().map(|bounty| bounty.denom.clone());
+        let (last_compound, mut bounty_balance) = match existing {
+            Some(existing)
+                if existing.config.bounty.as_ref().map(|bounty| &bounty.denom)
+                    == bounty_denom.as_ref()
+                    && existing.depositor == info.sender =>
+            {
+                (existing.last_compound, existing.bounty_balance)
+            }
+            Some(existing) => {
+                let last_compound = existing.last_compound;
+                let depositor = existing.depositor.clone();
+                if let Some(refund) = bounty_refund(existing) {
+                    response = response.add_message(BankMsg::Send {
+                        to_address: depositor.to_string(),
+                        amount: vec![refund],
+                    });
+                }
+                (last_compound, Uint128::zero())
+            }
+            None => (None, Uint128::zero()),
+        };
+
+        match (info.funds.as_slice(), &bounty_denom) {
+            ([], _) => {}
+            ([deposit], Some(denom)) if &deposit.denom == denom => {
+                bounty_balance += deposit.amount;
+            }
+            _ => {
+                return Err(ContractError::IncorrectPayment {
+                    expected: bounty_denom.unwrap_or_else(|| "no funds".to_string()),
+                })
+            }
+        }
+
+        AUTO_COMPOUND.save(
+            deps.storage,
+            &token_id,
+            &AutoCompound {
+                chain: chain.clone(),
+                config,
+                last_compound,
+                bounty_balance,
+                depositor: info.sender,
+            },
+        )?;
+
+        Ok(response
+            .add_attribute("enabled", "true")
+            .add_attribute("chain", chain)
+            .add_attribute("bounty_balance", bounty_balance.to_string()))
+    }
+
+    /// Returns the remaining bounty balance of the given auto-compound state, if any.
+    fn bounty_refund(auto_compound: AutoCompound) -> Option<Coin> {
+        auto_compound
+            .config
+            .bounty
+            .filter(|_| !auto_compound.bounty_balance.is_zero())
+            .map(|bounty| Coin {
+                denom: bounty.denom,
+                amount: auto_compound.bounty_balance,
+            })
+    }
+
+    /// Remove the auto-compound state of a token and its compound in progress, if any.
+    /// Returns the messages refunding the bounty balance and the reserved bounty to their
+    /// depositor.
+    fn remove_auto_compound(storage: &mut dyn Storage, token_id: &str) -> StdResult<Vec<BankMsg>> {
+        let mut refunds = vec![];
+        if let Some(auto_compound) = AUTO_COMPOUND.may_load(storage, token_id)? {
+            AUTO_COMPOUND.remove(storage, token_id);
+            let depositor = auto_compound.depositor.clone();
+            if let Some(refund) = bounty_refund(auto_compound) {
+                refunds.push(BankMsg::Send {
+                    to_address: depositor.to_string(),
+                    amount: vec![refund],
+                });
+            }
+        }
+        if let Some(pending) = PENDING_COMPOUNDS.may_load(storage, token_id)? {
+            PENDING_COMPOUNDS.remove(storage, token_id);
+            if let Some(bounty) = pending.bounty {
+                refunds.push(BankMsg::Send {
+                    to_address: pending.depositor.to_string(),
+                    amount: vec![bounty],
+                });
+            }
+        }
+
+        Ok(refunds)
+    }
+
+    /// Withdraw the staking rewards of the ICA of a token, or delegate the rewards withdrawn by
+    /// the previous compound. Anyone can call this once the configured frequency has elapsed
+    /// since the last compound. The configured bounty is reserved from the bounty balance, and
+    /// paid to the sender of the delegation once it is acknowledged.
+    pub fn compound(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+    ) -> Result<Response, ContractError> {
+        let mut auto_compound = AUTO_COMPOUND
+            .may_load(deps.storage, &token_id)?
+            .ok_or(ContractError::AutoCompoundNotEnabled)?;
+
+        let state = STATE.load(deps.storage)?;
+        let owner = new_cw721_ica_extension_helper(state.cw721_ica_extension_address)
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+
+        // The step is recorded as the next record of the token.
+        let sequence = history::next_sequence(deps.storage, &token_id)?;
+        let validator = auto_compound.config.validator.clone();
+        let (message, pending) = match PENDING_COMPOUNDS.may_load(deps.storage, &token_id)? {
+            Some(PendingCompound {
+                step: CompoundStep::Withdrawn { amount },
+                depositor,
+                bounty,
+                ..
+            }) => (
+                StakingMsg::Delegate { validator, amount }.into(),
+                PendingCompound {
+                    keeper: info.sender.clone(),
+                    depositor,
+                    bounty,
+                    sequence,
+                    step: CompoundStep::Delegating,
+                },
+            ),
+            Some(_) => return Err(ContractError::CompoundInProgress),
+            None => {
+                if let Some(next_compound) = auto_compound.next_compound() {
+                    if env.block.time < next_compound {
+                        return Err(ContractError::CompoundTooSoon { next_compound });
+                    }
+                }
+                auto_compound.last_compound = Some(env.block.time);
+
+                let bounty = auto_compound.config.bounty.clone().filter(|bounty| {
+                    !bounty.amount.is_zero() && auto_compound.bounty_balance >= bounty.amount
+                });
+                if let Some(bounty) = &bounty {
+                    auto_compound.bounty_balance -= bounty.amount;
+                }
+                AUTO_COMPOUND.save(deps.storage, &token_id, &auto_compound)?;
+
+                (
+                    DistributionMsg::WithdrawDelegatorReward { validator }.into(),
+                    PendingCompound {
+                        keeper: info.sender.clone(),
+                        depositor: auto_compound.depositor.clone(),
+                        bounty,
+                        sequence,
+                        step: CompoundStep::Withdrawing,
+                    },
+                )
+            }
+        };
+        PENDING_COMPOUNDS.save(deps.storage, &token_id, &pending)?;
+
+        let msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages: vec![message],
+            packet_memo: None,
+            timeout_seconds: None,
+        };
+
+        dispatch_ica_msg(
+            deps,
+            env,
+            info,
+            owner,
+            token_id,
+            Some(auto_compound.chain),
+            msg,
+            // The keeper is not charged a packet fee, and neither is the fee pool.
+            PacketFeeMode::Unpaid,
+            Some(TransactionMsgType::Compound),
+        )
+    }
+
+    /// Move the compound in progress of a token to its next step if the transaction with the
+    /// given sequence is its current step. Returns the messages paying the reserved bounty to the
+    /// keeper once the rewards are delegated, or refunding it if it cannot be returned to the
+    /// bounty balance.
+    fn compound_result(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+        status: &TransactionStatus,
+        results: &[MsgResult],
+    ) -> StdResult<Vec<BankMsg>> {
+        let Some(mut pending) = PENDING_COMPOUNDS.may_load(storage, token_id)? else {
+            return Ok(vec![]);
+        };
+        if pending.sequence != sequence {
+            return Ok(vec![]);
+        }
+
+        if *status == TransactionStatus::Completed {
+            match pending.step {
+                CompoundStep::Withdrawing => {
+                    let min_amount = AUTO_COMPOUND
+                        .may_load(storage, token_id)?
+                        .map(|auto_compound| auto_compound.config.min_amount);
+                    let rewards = min_amount.and_then(|min_amount| {
+                        results
+                            .iter()
+                            .find_map(|result| match result {
+                                MsgResult::WithdrawRewards { amount } => {
+                                    amount.iter().find(|coin| coin.denom == min_amount.denom)
+                                }
+                                _ => None,
+                            })
+                            .filter(|rewards| rewards.amount >= min_amount.amount)
+                            .cloned()
+                    });
+                    // Smaller rewards are left in the ICA.
+                    if let Some(amount) = rewards {
+                        pending.step = CompoundStep::Withdrawn { amount };
+                        PENDING_COMPOUNDS.save(storage, token_id, &pending)?;
+                        return Ok(vec![]);
+                    }
+                }
+                CompoundStep::Delegating => {
+                    PENDING_COMPOUNDS.remove(storage, token_id);
+                    return Ok(pending
+                        .bounty
+                        .map(|bounty| BankMsg::Send {
+                            to_address: pending.keeper.to_string(),
+                            amount: vec![bounty],
+                        })
+                        .into_iter()
+                        .collect());
+                }
+                CompoundStep::Withdrawn { .. } => return Ok(vec![]),
+            }
+        }
+
+        // The compound ends without delegating, so the reserved bounty is released.
+        PENDING_COMPOUNDS.remove(storage, token_id);
+        let Some(bounty) = pending.bounty else {
+            return Ok(vec![]);
+        };
+        if let Some(mut auto_compound) = AUTO_COMPOUND.may_load(storage, token_id)? {
+            let same_denom = auto_compound
+                .config
+                .bounty
+                .as_ref()
+                .map_or(false, |configured| configured.denom == bounty.denom);
+            let same_depositor = auto_compound.depositor == pending.depositor;
+            if same_denom && same_depositor {
+                auto_compound.bounty_balance += bounty.amount;
+                AUTO_COMPOUND.save(storage, token_id, &auto_compound)?;
+                return Ok(vec![]);
+            }
+        }
+
+        Ok(vec![BankMsg::Send {
+            to_address: pending.depositor.to_string(),
+            amount: vec![bounty],
+        }])
+    }
+
+    /// Send a message to the ICA of a token if the sender is the owner of the token.
+    /// The message type of its transaction record is overridden by the given type, if any.
+    #[allow(clippy::too_many_arguments)]
+    fn send_ica_msg(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: Option<PacketFee>,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<Response, ContractError> {
+        let owner = verify_token_owner(deps.as_ref(), &token_id, info.sender.as_str())?;
+
+        dispatch_ica_msg(
+            deps,
+            env,
+            info,
+            owner,
+            token_id,
+            chain,
+            msg,
+            PacketFeeMode::Resolve(packet_fee),
+            msg_type,
+        )
+    }
+
+    /// Returns the owner of the token if it is the given sender.
+    pub fn verify_token_owner(
+        deps: Deps,
+        token_id: &str,
+        sender: &str,
+    ) -> Result<String, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, token_id, false)?
+            .owner;
+
+        if owner != sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        Ok(owner)
+    }
+
+    /// IcaTarget is the ICA of a token that a message is sent to.
+    pub struct IcaTarget {
+        /// The alias of the chain of the ICA.
+        pub chain: String,
+        /// The cw-ica-controller address, or the owner string of the native ICA.
+        pub controller: String,
+        /// The connection ID of the native ICA, or `None` for the cw-ica-controller backend.
+        pub native_connection: Option<String>,
+    }
+
+    /// Returns the ICA of the token on the given chain, or on its primary chain if no chain
+    /// is given. Fails if the controller of the ICA is not registered.
+    pub fn ica_target(
+        storage: &dyn Storage,
+        token_id: &str,
+        chain: Option<String>,
+    ) -> Result<IcaTarget, ContractError> {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(storage, token_id)?,
+        };
+        let controller = NFT_ICA_CONTRACT_BI_MAP.load_controller(storage, token_id, &chain)?;
+        // additional hardening check
+        let native_connection = NATIVE_ICA_CONNECTIONS.may_load(storage, &controller)?;
+        if native_connection.is_none()
+            && !REGISTERED_ICA_ADDRS.has(storage, &Addr::unchecked(&controller))
+        {
+            return Err(ContractError::Unauthorized);
+        };
+
+        Ok(IcaTarget {
+            chain,
+            controller,
+            native_connection,
+        })
+    }
+
+    /// Build the message sent to the controller of the ICA. The packet fee is only given for the
+    /// native backend, which escrows it with the message.
+    fn controller_msg(
+        deps: Deps,
+        target: &IcaTarget,
+        token_id: &str,
+        msg: IcaControllerExecuteMsg,
+        fee: Option<&PacketFee>,
+    ) -> Result<CosmosMsg, ContractError> {
+        match &target.native_connection {
+            Some(connection_id) => native_ica_msg(
+                deps,
+                &target.controller,
+                connection_id,
+                token_id,
+                &target.chain,
+                msg,
+                fee,
+            ),
+            None => {
+                Ok(CwIcaControllerContract::new(Addr::unchecked(&target.controller)).call(msg)?)
+            }
+        }
+    }
+
+    /// IcaMsgPlan is what sending a message to the ICA of a token does, validated without
+    /// modifying the state.
+    pub struct IcaMsgPlan {
+        /// The ICA the message is sent to.
+        pub target: IcaTarget,
+        /// Whether the message reopens the closed channel of the ICA.
+        pub reopens_channel: bool,
+        /// The transaction recorded for the packet sent by the message, if any.
+        pub record: Option<TransactionRecord>,
+        /// The packet fee escrowed for the packet, if any.
+        pub escrowed_fee: Option<EscrowedFee>,
+        /// The fee paid from the fee pool to register the native ICA again.
+        pub register_fee: Vec<Coin>,
+        /// The messages sent, ending with the message to the controller of the ICA.
+        pub msgs: Vec<CosmosMsg>,
+    }
+
+    /// PacketFeeMode is how the packet fee of a message sent to an ICA is paid.
+    pub enum PacketFeeMode {
+        /// The given fee, or else the default fee of the token, is paid with the sent funds.
+        /// Otherwise, the sponsored fee of the chain is paid from the fee pool if it can cover it.
+        Resolve(Option<PacketFee>),
+        /// No packet fee is paid, and no funds can be sent.
+        Unpaid,
+    }
+
+    /// Validate a message sent to the ICA of a token owned by the given owner, without
+    /// verifying the sender, and build the messages it sends.
+    #[allow(clippy::too_many_arguments)]
+    pub fn plan_ica_msg(
+        deps: Deps,
+        env: &Env,
+        info: &MessageInfo,
+        owner: &str,
+        token_id: &str,
+        chain: Option<String>,
+        msg: &IcaControllerExecuteMsg,
+        packet_fee: PacketFeeMode,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<IcaMsgPlan, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::IcaExecution)?;
+
+        let target = ica_target(deps.storage, token_id, chain)?;
+        let is_create_channel = matches!(msg, IcaControllerExecuteMsg::CreateChannel { .. });
+
+        let reopens_channel = is_create_channel
+            && matches!(
+                CHANNEL_STATE
+                    .load(deps.storage, (token_id, &target.chain))?
+                    .status,
+                ChannelStatus::Closed
+            );
+
+        let record = TransactionRecord::from_ica_msg(
+            msg,
+            token_id,
+            &target.chain,
+            owner,
+            env.block.height,
+            env.block.time.nanos(),
+        )
+        .map(|mut record| -> StdResult<_> {
+            if let Some(msg_type) = msg_type {
+                record.msg_type = msg_type;
+            }
+            record.executor = Some(info.sender.to_string());
+            record.categories = history::categories(deps.storage, &record.type_urls)?;
+            Ok(record)
+        })
+        .transpose()?;
+
+        // Only the messages sending a packet pay a packet fee.
+        let escrowed_fee = match (&record, packet_fee) {
+            (Some(_), PacketFeeMode::Resolve(packet_fee)) => {
+                resolve_packet_fee(deps.storage, info, token_id, &target.chain, packet_fee)?.map(
+                    |(fee, payer)| EscrowedFee {
+                        chain: target.chain.clone(),
+                        fee,
+                        payer,
+                    },
+                )
+            }
+            _ if !info.funds.is_empty() => {
+                return Err(ContractError::IncorrectPayment {
+                    expected: "no funds".to_string(),
+                })
+            }
+            _ => None,
+        };
+
+        // The native backend registers the ICA again to reopen its channel.
+        let register_fee = if target.native_connection.is_some() && is_create_channel {
+            let register_fee = STATE.load(deps.storage)?.native_register_fee;
+            if !fees::covers(deps.storage, &register_fee)? {
+                return Err(ContractError::InsufficientFeePool);
+            }
+            register_fee
+        } else {
+            vec![]
+        };
+
+        // The fee is escrowed for the next packet sent on the channel, so it must be paid
+        // right before the packet is sent. The native backend escrows it with the message.
+        let mut msgs = vec![];
+        let mut native_fee = None;
+        if let Some(escrowed_fee) = &escrowed_fee {
+            if target.native_connection.is_some() {
+                native_fee = Some(&escrowed_fee.fee);
+            } else {
+                let channel_id = CHANNEL_STATE
+                    .load(deps.storage, (token_id, &target.chain))?
+                    .channel_id
+                    .ok_or(ContractError::ChannelStateNotFound)?;
+                let port_id = format!("{}{}", keys::WASM_IBC_PORT_PREFIX, target.controller);
+
+                msgs.push(ics29::pay_packet_fee_msg(
+                    env.contract.address.as_str(),
+                    &port_id,
+                    &channel_id,
+                    &escrowed_fee.fee,
+                ));
+            }
+        }
+        msgs.push(controller_msg(
+            deps,
+            &target,
+            token_id,
+            msg.clone(),
+            native_fee,
+        )?);
+
+        Ok(IcaMsgPlan {
+            target,
+            reopens_channel,
+            record,
+            escrowed_fee,
+            register_fee,
+            msgs,
+        })
+    }
+
+    /// Send a message to the ICA of a token owned by the given owner, without verifying the
+    /// sender.
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch_ica_msg(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        owner: String,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: PacketFeeMode,
+        msg_type: Option<TransactionMsgType>,
+    ) -> Result<Response, ContractError> {
+        let IcaMsgPlan {
+            target,
+            reopens_channel,
+            record,
+            escrowed_fee,
+            register_fee,
+            msgs,
+        } = plan_ica_msg(
+            deps.as_ref(),
+            &env,
+            &info,
+            &owner,
+            &token_id,
+            chain,
+            &msg,
+            packet_fee,
+            msg_type,
+        )?;
+        let IcaTarget {
+            chain, controller, ..
+        } = target;
+
+        let mut response = Response::new();
+
+        // Set channel status to pending if the message reopens the channel.
+        if reopens_channel {
+            CHANNEL_STATE.save(
+                deps.storage,
+                (&token_id, &chain),
+                &ChannelState {
+                    status: ChannelStatus::Pending,
+                    channel_id: None,
+                },
+            )?;
+            response = response.add_submessages(subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::ChannelStatusChanged {
+                    token_id: token_id.clone(),
+                    chain: chain.clone(),
+                    status: ChannelStatus::Pending,
+                    channel_id: None,
+                },
+            )?);
+        }
+
+        if let Some(tx_record) = record {
+            let sequence = history::push_pending(deps.storage, &controller, &tx_record)?;
+            history::save_msg_payload(deps.storage, &token_id, sequence, &msg)?;
+            let evicted = history::evict(deps.storage, &token_id)?;
+            response = response.add_events(pruned_events(&evicted)?);
+
+            let channel_id = CHANNEL_STATE
+                .may_load(deps.storage, (&token_id, &chain))?
+                .and_then(|cs| cs.channel_id);
+            response = response.add_event(
+                IcaEvent::new(events::ICA_TX_SENT, &token_id)
+                    .chain(&chain)
+                    .controller(&controller)
+                    .ica_address(NFT_ICA_MAP.may_load(deps.storage, (&token_id, &chain))?)
+                    .channel_id(channel_id)
+                    .sequence(sequence)
+                    .owner(Some(&owner))
+                    .attribute("executor", info.sender.as_str())
+                    .into(),
+            );
+
+            if let Some(escrowed_fee) = escrowed_fee {
+                if escrowed_fee.payer == FeePayer::FeePool {
+                    withdraw_fee_pool(deps.storage, &escrowed_fee.fee.total())?;
+                }
+                ESCROWED_FEES.save(deps.storage, (&token_id, sequence), &escrowed_fee)?;
+            }
+        }
+
+        withdraw_fee_pool(deps.storage, &register_fee)?;
+
+        Ok(response.add_messages(msgs))
+    }
+
+    /// Withdraw the given coins from the fee pool, failing if it cannot cover them.
+    fn withdraw_fee_pool(storage: &mut dyn Storage, coins: &[Coin]) -> Result<(), ContractError> {
+        if !coins.is_empty() && !fees::withdraw(storage, coins)? {
+            return Err(ContractError::InsufficientFeePool);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the ICS-29 fee of the next packet sent by the given token and its payer.
+    /// The fee given with the message, or else the default fee of the token, is paid with the
+    /// sent funds. Otherwise, the sponsored fee of the chain is paid from the fee pool if the
+    /// pool can cover it.
+    fn resolve_packet_fee(
+        storage: &dyn Storage,
+        info: &MessageInfo,
+        token_id: &str,
+        chain: &str,
+        packet_fee: Option<PacketFee>,
+    ) -> Result<Option<(PacketFee, FeePayer)>, ContractError> {
+        let packet_fee = match packet_fee {
+            Some(fee) => Some(fee),
+            None => TOKEN_PACKET_FEES.may_load(storage, token_id)?,
+        };
+
+        if let Some(fee) = packet_fee.filter(|fee| !fee.is_empty()) {
+            let expected = fee.total();
+            if info.funds != expected {
+                return Err(ContractError::IncorrectPayment {
+                    expected: expected
+                        .iter()
+                        .map(Coin::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                });
+            }
+
+            return Ok(Some((fee, FeePayer::Sender(info.sender.clone()))));
+        }
+
+        if !info.funds.is_empty() {
+            return Err(ContractError::IncorrectPayment {
+                expected: "no funds".to_string(),
+            });
+        }
+
+        match SPONSORED_PACKET_FEES.may_load(storage, chain)? {
+            Some(fee) if fees::covers(storage, &fee.total())? => Ok(Some((fee, FeePayer::FeePool))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Send the attached funds to the ICA of a token with an ICS-20 transfer.
+    /// Only the owner of the token can call this.
+    pub fn fund_ica(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_id: String,
+        chain: Option<String>,
+        channel_id: String,
+        timeout: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        assert_not_paused(deps.storage, PausableOperation::IcaExecution)?;
+
+        let state = STATE.load(deps.storage)?;
+        // The refund of a failed transfer relies on its callbacks.
+        if !state.transfer_callbacks {
+            return Err(ContractError::TransferCallbacksDisabled);
+        }
+        let timeout = transfer_timeout(&env, timeout)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+        let ica_address = NFT_ICA_MAP.load(deps.storage, (&token_id, &chain))?;
+
+        let amount = match info.funds.as_slice() {
+            [coin] => coin.clone(),
+            _ => {
+                return Err(ContractError::IncorrectPayment {
+                    expected: "a single coin".to_string(),
+                })
+            }
+        };
+
+        // The deposit is resolved by the acknowledgement of the transfer, not of the ICA channel.
+        let sequence = history::push(
+            deps.storage,
+            &TransactionRecord {
+                sequence: 0,
+                activity_id: 0,
+                status: TransactionStatus::Pending,
+                token_id: token_id.clone(),
+                chain,
+                owner,
+                executor: Some(info.sender.to_string()),
+                msg_type: TransactionMsgType::Deposit,
+                block_height: env.block.height,
+                timestamp: env.block.time.nanos(),
+                vote: None,
+                results: vec![],
+                type_urls: vec![],
+                categories: vec![],
+            },
+        )?;
+        let evicted = history::evict(deps.storage, &token_id)?;
+        TRANSFER_REPLY_STATE.save(
+            deps.storage,
+            &(
+                channel_id.clone(),
+                PendingTransfer {
+                    token_id,
+                    sequence,
+                    sender: info.sender,
+                    amount: amount.clone(),
+                },
+            ),
+        )?;
+
+        let transfer_msg = IbcMsg::Transfer {
+            channel_id,
+            to_address: ica_address,
+            amount,
+            timeout: timeout.into(),
+        };
+
+        Ok(Response::new()
+            .add_submessage(SubMsg::reply_on_success(
+                transfer_msg,
+                keys::FUND_ICA_REPLY_ID,
+            ))
+            .add_events(pruned_events(&evicted)?))
+    }
+
+    /// Returns the timeout of an ICS-20 transfer with the given timeout in seconds, or the
+    /// default timeout if none is given.
+    fn transfer_timeout(env: &Env, timeout: Option<u64>) -> Result<Timestamp, ContractError> {
+        let timeout = timeout.unwrap_or(keys::DEFAULT_TRANSFER_TIMEOUT_SECONDS);
+        if timeout == 0 || timeout > keys::MAX_TRANSFER_TIMEOUT_SECONDS {
+            return Err(ContractError::InvalidTimeout {
+                max: keys::MAX_TRANSFER_TIMEOUT_SECONDS,
+            });
+        }
+
+        Ok(env.block.time.plus_seconds(timeout))
+    }
+
+    /// Remove the records of a token that are not pending and were sent before the given
+    /// sequence. Only the owner of the contract can call this, so that the holder of the token
+    /// cannot erase its history.
+    pub fn prune_history(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        before: u64,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        let pruned = history::prune(deps.storage, &token_id, before)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "prune_history")
+            .add_attribute("token_id", token_id)
+            .add_attribute("pruned", pruned.len().to_string())
+            .add_events(pruned_events(&pruned)?))
+    }
+
+    /// Returns the events archiving the given pruned records.
+    fn pruned_events(records: &[TransactionRecord]) -> StdResult<Vec<Event>> {
+        records
+            .iter()
+            .map(|record| {
+                Ok(IcaEvent::new(events::ICA_TX_PRUNED, &record.token_id)
+                    .chain(&record.chain)
+                    .sequence(record.sequence)
+                    .owner(Some(&record.owner))
+                    .attribute("record", to_json_string(record)?)
+                    .into())
+            })
+            .collect()
+    }
+
+    /// Set or remove the default packet fee of a token. Only the owner of the token can call this.
+    pub fn set_token_packet_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+        fee: Option<PacketFee>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // verify that the sender is the owner of the token
+        let cw721_ica_extension = new_cw721_ica_extension_helper(state.cw721_ica_extension_address);
+        let owner = cw721_ica_extension
+            .owner_of(&deps.querier, &token_id, false)?
+            .owner;
+        if owner != info.sender {
+            return Err(ContractError::Unauthorized);
+        };
+
+        match fee {
+            Some(fee) => TOKEN_PACKET_FEES.save(deps.storage, &token_id, &fee)?,
+            None => TOKEN_PACKET_FEES.remove(deps.storage, &token_id),
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Set or remove the packet fee paid from the fee pool for a chain. Only the owner can call this.
+    pub fn set_sponsored_packet_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        chain: String,
+        fee: Option<PacketFee>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if chain != keys::DEFAULT_CHAIN_ALIAS && !CHAIN_REGISTRY.has(deps.storage, &chain) {
+            return Err(ContractError::ChainNotFound { chain });
+        }
+
+        match fee {
+            Some(fee) => SPONSORED_PACKET_FEES.save(deps.storage, &chain, &fee)?,
+            None => SPONSORED_PACKET_FEES.remove(deps.storage, &chain),
+        }
+
+        Ok(Response::default())
+    }
+
+    /// Add the sent funds to the fee pool. Only the owner can call this.
+    pub fn fund_fee_pool(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        fees::deposit(deps.storage, &info.funds)?;
+
+        Ok(Response::default())
+    }
+
+    /// Send the given amount from the fee pool to the owner. Only the owner can call this.
+    pub fn withdraw_fee_pool(
+        deps: DepsMut,
+        info: MessageInfo,
+        amount: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if !fees::withdraw(deps.storage, &amount)? {
+            return Err(ContractError::InsufficientFeePool);
+        }
+
+        Ok(Response::new().add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount,
+        }))
+    }
+
+    /// Convert the given cw-ica-controller message to a message of the interchaintxs module
+    /// for the ICA of the given owner string, escrowing the given packet fee.
+    /// The ICA is always reopened on the connection it was registered on.
+    fn native_ica_msg(
+        deps: Deps,
+        owner: &str,
+        connection_id: &str,
+        token_id: &str,
+        chain: &str,
+        msg: IcaControllerExecuteMsg,
+        fee: Option<&PacketFee>,
+    ) -> Result<CosmosMsg, ContractError> {
+        match msg {
+            IcaControllerExecuteMsg::CreateChannel { .. } => {
+                let register_fee = STATE.load(deps.storage)?.native_register_fee;
+                native_ica::register_interchain_account_msg(owner, connection_id, &register_fee)
+            }
+            IcaControllerExecuteMsg::SendCosmosMsgs {
+                messages,
+                packet_memo,
+                timeout_seconds,
+            } => {
+                let ica_address = NFT_ICA_MAP.load(deps.storage, (token_id, chain))?;
+                let msgs = native_ica::encode_msgs(messages, &ica_address)?;
+
+                native_ica::submit_tx_msg(
+                    owner,
+                    connection_id,
+                    msgs,
+                    packet_memo,
+                    timeout_seconds,
+                    fee,
+                )
+            }
+            IcaControllerExecuteMsg::SendCustomIcaMessages {
+                messages,
+                packet_memo,
+                timeout_seconds,
+            } => native_ica::submit_tx_msg(
+                owner,
+                connection_id,
+                native_ica::decode_cosmos_tx(messages.as_slice())?,
+                packet_memo,
+                timeout_seconds,
+                fee,
+            ),
+            _ => Err(ContractError::UnsupportedIcaMsg(
+                "only channel and transaction messages can be sent".to_string(),
+            )),
+        }
+    }
+
+    /// Instantiate the cw721-ica extension contract using the instantiate2 pattern.
+    /// Returns the instantiate2 message and the contract address.
+    ///
+    /// This is ignored since injective doesn't seem to support instantiate2.
+    #[allow(dead_code)]
+    fn instantiate2_cw_ica_controller(
+        api: &dyn Api,
+        querier: QuerierWrapper,
+        env: Env,
+        code_id: u64,
+        salt: Option<String>,
+        channel_open_init_options: Option<ChannelOpenInitOptions>,
+    ) -> Result<(CosmosMsg, Addr), ContractError> {
+        let instantiate_msg = to_json_binary(&cw_ica_controller::types::msg::InstantiateMsg {
+            owner: Some(env.contract.address.to_string()),
+            channel_open_init_options,
+            send_callbacks_to: Some(env.contract.address.to_string()),
+        })?;
+
+        let label = format!("cw-ica-controller-{}", env.block.height);
+
+        utils::instantiate2_contract(api, querier, env, code_id, salt, label, instantiate_msg)
+    }
+
+    /// Instantiate the cw721-ica-extension contract using the submessage pattern.
+    /// Returns the instantiate submessage whose reply will contain the new contract address.
+    pub fn instantiate_cw_ica_controller(
+        env: Env,
+        code_id: u64,
+        channel_open_init_options: Option<ChannelOpenInitOptions>,
+    ) -> Result<SubMsg, ContractError> {
+        let instantiate_msg = WasmMsg::Instantiate {
+            admin: Some(env.contract.address.to_string()),
+            code_id,
+            msg: to_json_binary(&cw_ica_controller::types::msg::InstantiateMsg {
+                owner: Some(env.contract.address.to_string()),
+                channel_open_init_options,
+                send_callbacks_to: Some(env.contract.address.to_string()),
+            })?,
+            label: format!("cw-ica-controller-{}", env.block.height),
+            funds: vec![],
+        };
+
+        Ok(SubMsg::reply_on_success(
+            instantiate_msg,
+            CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID,
+        ))
+    }
+}
+
+mod query {
+    use super::*;
+
+    use crate::types::{
+        msg::query_responses::{
+            ActiveMintPhaseResponse, ActivityFeedResponse, ChainRegistryEntry, EscrowedFeeEntry,
+            GetIcaAddressesResponse, GetRemainingSupplyResponse, GetTransactionHistoryResponse,
+            GetTransactionResponse, NftIcaPair, PauseStatusResponse, SimulateIcaMsgResponse,
+            TokenIca,
+        },
+        state::{
+            backend::IcaBackend,
+            chain::AllowedConnection,
+            channel::ChannelState,
+            compound::{AutoCompound, PendingCompound},
+            fees::{self, PacketFee},
+            history::{
+                self, HistoryCursor, HistoryFilter, HistoryOrder, MsgCategory, PendingWithdrawal,
+                TransactionRecord,
+            },
+            hooks::CallbackReceiver,
+            MintPhase, QueueItem, ACTIVITY_FEED, ALLOWED_CONNECTIONS, AUTO_COMPOUND,
+            CALLBACK_RECEIVERS, CHAIN_REGISTRY, CHAIN_TOKENS, CHANNEL_STATE, ESCROWED_FEES,
+            EXECUTOR_ACTIVITY, GUARDIAN, MINT_PHASES, MSG_CATEGORIES, NATIVE_ICA_CONNECTIONS,
+            NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, PAUSE_FLAGS, PENDING_COMPOUNDS, PENDING_MINTS,
+            PENDING_WITHDRAWALS, PHASE_MINT_COUNT, SPONSORED_PACKET_FEES, SUBSCRIBERS,
+            TOKEN_COUNTER, TOKEN_PACKET_FEES, TOKEN_PRIMARY_CHAIN, TX_HISTORY, TX_PAYLOADS,
+            UNBOUND_MINTS,
+        },
+    };
+
+    use cosmwasm_std::{Coin, Order, StdResult};
+    use cw_ica_controller::types::msg::ExecuteMsg as IcaControllerExecuteMsg;
+    use cw_storage_plus::Bound;
 
     /// Query the contract state.
     pub fn state(deps: Deps) -> StdResult<ContractState> {
         STATE.load(deps.storage)
     }
 
-    /// Query the ICA NFT ID to ICA ID mapping.
-    pub fn nft_ica_controller_bimap(deps: Deps, key: String) -> StdResult<String> {
-        NFT_ICA_CONTRACT_BI_MAP.load(deps.storage, &key)
+    /// Query the ICA NFT ID to ICA ID mapping.
+    /// Token IDs are mapped to the ICA controller of their primary chain.
+    pub fn nft_ica_controller_bimap(deps: Deps, key: String) -> StdResult<String> {
+        if let Some((token_id, _)) = NFT_ICA_CONTRACT_BI_MAP.may_load_nft(deps.storage, &key)? {
+            return Ok(token_id);
+        }
+
+        let chain = TOKEN_PRIMARY_CHAIN.load(deps.storage, &key)?;
+        NFT_ICA_CONTRACT_BI_MAP.load_controller(deps.storage, &key, &chain)
+    }
+
+    /// Query the ICA address for a given ICA NFT ID and chain.
+    /// If no chain is given, the primary chain of the token is used.
+    pub fn get_ica_address(
+        deps: Deps,
+        token_id: String,
+        chain: Option<String>,
+    ) -> StdResult<String> {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+
+        NFT_ICA_MAP.load(deps.storage, (&token_id, &chain))
+    }
+
+    /// Query all the ICAs of a given ICA NFT ID.
+    pub fn get_token_icas(deps: Deps, token_id: String) -> StdResult<Vec<TokenIca>> {
+        NFT_ICA_CONTRACT_BI_MAP
+            .controllers(deps.storage, &token_id)?
+            .into_iter()
+            .map(|(chain, controller)| {
+                let backend = if NATIVE_ICA_CONNECTIONS.has(deps.storage, &controller) {
+                    IcaBackend::Native
+                } else {
+                    IcaBackend::CwIcaController
+                };
+
+                Ok(TokenIca {
+                    ica_address: NFT_ICA_MAP.may_load(deps.storage, (&token_id, &chain))?,
+                    ica_controller_address: controller,
+                    backend,
+                    chain,
+                })
+            })
+            .collect()
+    }
+
+    /// Query the ICA controller addresses for a given list of ICA NFT IDs.
+    pub fn get_ica_addresses(
+        deps: Deps,
+        token_ids: Vec<String>,
+    ) -> StdResult<GetIcaAddressesResponse> {
+        let nft_ica_pairs =
+            token_ids
+                .iter()
+                .try_fold(Vec::new(), |mut acc, token_id| -> StdResult<_> {
+                    let chain = TOKEN_PRIMARY_CHAIN.load(deps.storage, token_id)?;
+                    let ica_address = NFT_ICA_MAP.load(deps.storage, (token_id, &chain))?;
+                    acc.push(NftIcaPair {
+                        nft_id: token_id.to_string(),
+                        ica_address,
+                    });
+                    Ok(acc)
+                })?;
+
+        Ok(GetIcaAddressesResponse {
+            pairs: nft_ica_pairs,
+        })
+    }
+
+    /// Query the mint queue, including the mint requests migrated from a previous version that
+    /// are not bound to their controller yet.
+    pub fn get_mint_queue(deps: Deps) -> StdResult<Vec<QueueItem>> {
+        PENDING_MINTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, queue_item)| queue_item))
+            .chain(UNBOUND_MINTS.iter(deps.storage)?)
+            .collect()
+    }
+
+    /// Query all the registered host chains.
+    pub fn get_chain_registry(deps: Deps) -> StdResult<Vec<ChainRegistryEntry>> {
+        CHAIN_REGISTRY
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(alias, config)| ChainRegistryEntry { alias, config }))
+            .collect()
+    }
+
+    /// Query the token IDs of the ICA NFTs on the given chain.
+    pub fn get_tokens_by_chain(
+        deps: Deps,
+        chain: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<String>> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+        let min = start_after.as_deref().map(Bound::exclusive);
+
+        CHAIN_TOKENS
+            .prefix(&chain)
+            .keys(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .collect()
+    }
+
+    /// Query the categories of the message type URLs.
+    pub fn get_msg_categories(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<MsgCategory>> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+        let min = start_after.as_deref().map(Bound::exclusive);
+
+        MSG_CATEGORIES
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(type_url, category)| MsgCategory { type_url, category }))
+            .collect()
+    }
+
+    /// Query the paused operations and the guardian.
+    pub fn get_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+        Ok(PauseStatusResponse {
+            flags: PAUSE_FLAGS.may_load(deps.storage)?.unwrap_or_default(),
+            guardian: GUARDIAN.may_load(deps.storage)?,
+        })
+    }
+
+    /// Query the contracts receiving the hooks of all the tokens.
+    pub fn get_subscribers(deps: Deps) -> StdResult<Vec<String>> {
+        SUBSCRIBERS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|addr| addr.map(String::from))
+            .collect()
+    }
+
+    /// Query the connections that can be used to mint ICAs besides the default one.
+    pub fn get_allowed_connections(deps: Deps) -> StdResult<Vec<AllowedConnection>> {
+        ALLOWED_CONNECTIONS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(
+                    |(connection_id, counterparty_connection_id)| AllowedConnection {
+                        connection_id,
+                        counterparty_connection_id,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Query the transaction history for a given NFT ID.
+    /// Only the records matching the given filter are returned.
+    pub fn get_transaction_history(
+        deps: Deps,
+        token_id: String,
+        start_after: Option<HistoryCursor>,
+        limit: Option<u32>,
+        order: HistoryOrder,
+        filter: HistoryFilter,
+    ) -> StdResult<GetTransactionHistoryResponse> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+
+        // Records are stored by sequence, and their timestamps increase with their sequence.
+        let (sequence_cursor, timestamp_cursor) = match start_after {
+            Some(HistoryCursor::Sequence(sequence)) => (Some(Bound::exclusive(sequence)), None),
+            Some(HistoryCursor::Timestamp(timestamp)) => (None, Some(timestamp)),
+            None => (None, None),
+        };
+        let (min, max, order) = match order {
+            HistoryOrder::Ascending => (sequence_cursor, None, Order::Ascending),
+            HistoryOrder::Descending => (None, sequence_cursor, Order::Descending),
+        };
+        let is_before_cursor = |record: &TransactionRecord| match (timestamp_cursor, order) {
+            (Some(timestamp), Order::Ascending) => record.timestamp <= timestamp,
+            (Some(timestamp), Order::Descending) => record.timestamp >= timestamp,
+            (None, _) => false,
+        };
+
+        // At most MAX_HISTORY_SCAN records are scanned, so the next page starts after the last
+        // scanned record rather than the last matching one.
+        let mut records = vec![];
+        let mut last_scanned = None;
+        let mut exhausted = true;
+        let range = TX_HISTORY
+            .prefix(&token_id)
+            .range(deps.storage, min, max, order);
+        for (scanned, item) in range.enumerate() {
+            let (sequence, record) = item?;
+            if records.len() == limit || scanned == keys::MAX_HISTORY_SCAN {
+                exhausted = false;
+                break;
+            }
+            last_scanned = Some(sequence);
+            if !is_before_cursor(&record) && filter.matches(&record) {
+                records.push(record);
+            }
+        }
+        let next_cursor = if exhausted {
+            None
+        } else {
+            last_scanned.map(HistoryCursor::Sequence)
+        };
+
+        let total = u32::try_from(history::len(deps.storage, &token_id)?)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+        Ok(GetTransactionHistoryResponse {
+            records,
+            total,
+            next_cursor,
+        })
+    }
+
+    /// Query the transaction records sent by the given address across all tokens.
+    pub fn get_activity_by_address(
+        deps: Deps,
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order: HistoryOrder,
+    ) -> StdResult<ActivityFeedResponse> {
+        let (min, max, order) = activity_bounds(start_after, order);
+        let keys = EXECUTOR_ACTIVITY
+            .prefix(&address)
+            .range(deps.storage, min, max, order);
+
+        activity_page(deps, keys, limit)
+    }
+
+    /// Query the transaction records of all tokens.
+    pub fn get_activity_feed(
+        deps: Deps,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order: HistoryOrder,
+    ) -> StdResult<ActivityFeedResponse> {
+        let (min, max, order) = activity_bounds(start_after, order);
+        let keys = ACTIVITY_FEED.range(deps.storage, min, max, order);
+
+        activity_page(deps, keys, limit)
+    }
+
+    /// Returns the range bounds and order of an activity feed page.
+    fn activity_bounds(
+        start_after: Option<u64>,
+        order: HistoryOrder,
+    ) -> (
+        Option<Bound<'static, u64>>,
+        Option<Bound<'static, u64>>,
+        Order,
+    ) {
+        let cursor = start_after.map(Bound::exclusive);
+        match order {
+            HistoryOrder::Ascending => (cursor, None, Order::Ascending),
+            HistoryOrder::Descending => (None, cursor, Order::Descending),
+        }
+    }
+
+    /// Loads the records of the given (activity id, (token id, sequence)) entries, up to the limit.
+    fn activity_page(
+        deps: Deps,
+        keys: impl Iterator<Item = StdResult<(u64, (String, u64))>>,
+        limit: Option<u32>,
+    ) -> StdResult<ActivityFeedResponse> {
+        // using 30 as the default limit
+        let limit = limit.unwrap_or(30) as usize;
+
+        let mut entries = keys.take(limit + 1).collect::<StdResult<Vec<_>>>()?;
+        let next_cursor = if entries.len() > limit {
+            entries.truncate(limit);
+            entries.last().map(|(activity_id, _)| *activity_id)
+        } else {
+            None
+        };
+
+        let records = entries
+            .into_iter()
+            .map(|(_, (token_id, sequence))| TX_HISTORY.load(deps.storage, (&token_id, sequence)))
+            .collect::<StdResult<_>>()?;
+
+        Ok(ActivityFeedResponse {
+            records,
+            next_cursor,
+        })
+    }
+
+    /// Query a transaction record of a given NFT ID with its stored payloads.
+    pub fn get_transaction(
+        deps: Deps,
+        token_id: String,
+        sequence: u64,
+    ) -> StdResult<GetTransactionResponse> {
+        Ok(GetTransactionResponse {
+            record: TX_HISTORY.load(deps.storage, (&token_id, sequence))?,
+            payload: TX_PAYLOADS.may_load(deps.storage, (&token_id, sequence))?,
+        })
+    }
+
+    /// Query the pending withdrawal of a transaction record of a token.
+    pub fn get_pending_withdrawal(
+        deps: Deps,
+        token_id: String,
+        sequence: u64,
+    ) -> StdResult<Option<PendingWithdrawal>> {
+        PENDING_WITHDRAWALS.may_load(deps.storage, (&token_id, sequence))
+    }
+
+    /// Simulate an [`ExecuteMsg::ExecuteIcaMsg`] sent by the given sender with the given funds,
+    /// using the same checks and building the same messages.
+    #[allow(clippy::too_many_arguments)]
+    pub fn simulate_ica_msg(
+        deps: Deps,
+        env: Env,
+        sender: String,
+        funds: Vec<Coin>,
+        token_id: String,
+        chain: Option<String>,
+        msg: IcaControllerExecuteMsg,
+        packet_fee: Option<PacketFee>,
+    ) -> StdResult<SimulateIcaMsgResponse> {
+        let mut violations = vec![];
+
+        let authorized = match execute::verify_token_owner(deps, &token_id, &sender) {
+            Ok(_) => true,
+            Err(err) => {
+                violations.push(err.to_string());
+                false
+            }
+        };
+
+        let info = MessageInfo {
+            sender: deps.api.addr_validate(&sender)?,
+            funds,
+        };
+        match execute::plan_ica_msg(
+            deps,
+            &env,
+            &info,
+            &sender,
+            &token_id,
+            chain,
+            &msg,
+            execute::PacketFeeMode::Resolve(packet_fee),
+            None,
+        ) {
+            Ok(plan) => Ok(SimulateIcaMsgResponse {
+                authorized,
+                channel: CHANNEL_STATE.may_load(deps.storage, (&token_id, &plan.target.chain))?,
+                chain: Some(plan.target.chain),
+                msg_type: plan.record.map(|record| record.msg_type),
+                escrowed_fee: plan.escrowed_fee,
+                msgs: plan.msgs,
+                violations,
+            }),
+            Err(err) => {
+                violations.push(err.to_string());
+                Ok(SimulateIcaMsgResponse {
+                    authorized,
+                    chain: None,
+                    channel: None,
+                    msg_type: None,
+                    escrowed_fee: None,
+                    msgs: vec![],
+                    violations,
+                })
+            }
+        }
+    }
+
+    /// Query the channel state for a given ICA NFT ID and chain.
+    /// If no chain is given, the primary chain of the token is used.
+    pub fn get_channel_state(
+        deps: Deps,
+        token_id: String,
+        chain: Option<String>,
+    ) -> StdResult<ChannelState> {
+        let chain = match chain {
+            Some(chain) => chain,
+            None => TOKEN_PRIMARY_CHAIN.load(deps.storage, &token_id)?,
+        };
+
+        CHANNEL_STATE.load(deps.storage, (&token_id, &chain))
+    }
+
+    /// Query the remaining supply of the collection.
+    pub fn get_remaining_supply(deps: Deps) -> StdResult<GetRemainingSupplyResponse> {
+        let max_supply = STATE.load(deps.storage)?.max_supply;
+        let total_supply = TOKEN_COUNTER.may_load(deps.storage)?.unwrap_or_default();
+
+        Ok(GetRemainingSupplyResponse {
+            max_supply,
+            total_supply,
+            remaining: max_supply.map(|max| max.saturating_sub(total_supply)),
+        })
+    }
+
+    /// Query all the configured mint phases.
+    pub fn get_mint_phases(deps: Deps) -> StdResult<Vec<MintPhase>> {
+        Ok(MINT_PHASES.may_load(deps.storage)?.unwrap_or_default())
+    }
+
+    /// Query the currently active mint phase.
+    pub fn get_active_mint_phase(
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<Option<ActiveMintPhaseResponse>> {
+        let active_phase = MINT_PHASES
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .find(|(_, phase)| phase.is_active(env.block.time))
+            .map(|(phase_id, phase)| ActiveMintPhaseResponse {
+                phase_id: phase_id as u32,
+                phase,
+            });
+
+        Ok(active_phase)
+    }
+
+    /// Query the default packet fee of the given token.
+    pub fn get_token_packet_fee(deps: Deps, token_id: String) -> StdResult<Option<PacketFee>> {
+        TOKEN_PACKET_FEES.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the packet fee paid from the fee pool for the given chain.
+    pub fn get_sponsored_packet_fee(deps: Deps, chain: String) -> StdResult<Option<PacketFee>> {
+        SPONSORED_PACKET_FEES.may_load(deps.storage, &chain)
+    }
+
+    /// Query the balance of the fee pool.
+    pub fn get_fee_pool(deps: Deps) -> StdResult<Vec<Coin>> {
+        fees::balance(deps.storage)
+    }
+
+    /// Query the outstanding packet fees escrowed for the given token.
+    pub fn get_escrowed_fees(deps: Deps, token_id: String) -> StdResult<Vec<EscrowedFeeEntry>> {
+        ESCROWED_FEES
+            .prefix(&token_id)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(sequence, escrowed)| EscrowedFeeEntry { sequence, escrowed }))
+            .collect()
+    }
+
+    /// Query the auto-compound state of the given token.
+    pub fn get_auto_compound(deps: Deps, token_id: String) -> StdResult<Option<AutoCompound>> {
+        AUTO_COMPOUND.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the compound in progress of the given token id.
+    pub fn get_pending_compound(
+        deps: Deps,
+        token_id: String,
+    ) -> StdResult<Option<PendingCompound>> {
+        PENDING_COMPOUNDS.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the callback receiver of the given token id.
+    pub fn get_callback_receiver(
+        deps: Deps,
+        token_id: String,
+    ) -> StdResult<Option<CallbackReceiver>> {
+        CALLBACK_RECEIVERS.may_load(deps.storage, &token_id)
+    }
+
+    /// Query the number of NFTs minted by the given address in the given mint phase.
+    pub fn get_phase_mint_count(deps: Deps, phase_id: u32, address: String) -> StdResult<u64> {
+        Ok(PHASE_MINT_COUNT
+            .may_load(deps.storage, (phase_id, &address))?
+            .unwrap_or_default())
+    }
+}
+
+mod sudo {
+    use super::*;
+
+    use cosmwasm_std::BankMsg;
+
+    use crate::{
+        types::{
+            msg::{callbacks::NftIcaHookMsg, RequestPacket},
+            state::{
+                backend::IcaBackend, history, NATIVE_ICA_CONNECTIONS, PENDING_TRANSFERS, TX_HISTORY,
+            },
+        },
+        utils::native_ica,
+    };
+
+    /// Handle the channel open ack of a native ICA.
+    pub fn open_ack(
+        deps: DepsMut,
+        env: Env,
+        port_id: String,
+        channel_id: String,
+        counterparty_version: String,
+    ) -> Result<Response, ContractError> {
+        let owner = native_owner(deps.as_ref(), &port_id)?;
+        let ica_address = native_ica::ica_address_from_version(&counterparty_version)?;
+
+        execute::channel_open_ack(
+            deps,
+            &env,
+            &owner,
+            IcaBackend::Native,
+            ica_address,
+            channel_id,
+        )
+    }
+
+    /// Handle the acknowledgement or timeout of a packet sent to a native ICA or of an
+    /// ICS-20 transfer sent to an ICA.
+    pub fn packet_result(
+        deps: DepsMut,
+        request: RequestPacket,
+        status: TransactionStatus,
+        ack: Option<AckPayload>,
+    ) -> Result<Response, ContractError> {
+        let port_id = request
+            .source_port
+            .ok_or_else(|| StdError::generic_err("source port not found"))?;
+        if port_id == keys::ICS20_PORT_ID {
+            return transfer_result(deps, request.source_channel, request.sequence, status, ack);
+        }
+
+        let owner = native_owner(deps.as_ref(), &port_id)?;
+
+        execute::packet_result(deps, &owner, status, ack)
+    }
+
+    /// Set the status of the deposit record of an ICS-20 transfer sent to an ICA.
+    /// The funds of a failed transfer are refunded to this contract, so they are sent back
+    /// to the sender.
+    fn transfer_result(
+        deps: DepsMut,
+        channel_id: Option<String>,
+        sequence: Option<u64>,
+        status: TransactionStatus,
+        ack: Option<AckPayload>,
+    ) -> Result<Response, ContractError> {
+        let (Some(channel_id), Some(sequence)) = (channel_id, sequence) else {
+            return Err(StdError::generic_err("source channel or sequence not found").into());
+        };
+        // Transfers that were not sent to an ICA are ignored.
+        let Some(transfer) = PENDING_TRANSFERS.may_load(deps.storage, (&channel_id, sequence))?
+        else {
+            return Ok(Response::default());
+        };
+        PENDING_TRANSFERS.remove(deps.storage, (&channel_id, sequence));
+
+        let mut response = Response::default();
+        if status != TransactionStatus::Completed {
+            response = response.add_message(BankMsg::Send {
+                to_address: transfer.sender.to_string(),
+                amount: vec![transfer.amount],
+            });
+        }
+
+        history::update_status(deps.storage, &transfer.token_id, transfer.sequence, status)?;
+        if let Some(ack) = ack {
+            history::save_ack_payload(deps.storage, &transfer.token_id, transfer.sequence, ack)?;
+        }
+
+        // The record may have been pruned while the transfer was pending.
+        if let Some(record) =
+            TX_HISTORY.may_load(deps.storage, (&transfer.token_id, transfer.sequence))?
+        {
+            response = response.add_submessages(execute::subscriber_hooks(
+                deps.storage,
+                NftIcaHookMsg::TransactionCompleted {
+                    token_id: transfer.token_id,
+                    record,
+                },
+            )?);
+        }
+
+        Ok(response)
+    }
+
+    /// Returns the owner string of the native ICA of the given port.
+    fn native_owner(deps: Deps, port_id: &str) -> Result<String, ContractError> {
+        let owner = native_ica::owner_from_port(port_id).ok_or(ContractError::Unauthorized)?;
+        if !NATIVE_ICA_CONNECTIONS.has(deps.storage, owner) {
+            return Err(ContractError::Unauthorized);
+        }
+
+        Ok(owner.to_string())
+    }
+}
+
+mod reply {
+    use cosmwasm_std::{SubMsgResult, WasmMsg};
+    use cw721_ica_extension::ExtensionMsg;
+
+    use crate::types::state::{
+        NFT_ICA_CONTRACT_BI_MAP, NFT_MINT_QUEUE, PENDING_MINTS, PENDING_TRANSFERS,
+        REGISTERED_ICA_ADDRS, TOKEN_PRIMARY_CHAIN, TRANSFER_REPLY_STATE,
+    };
+    use crate::utils::ics20;
+
+    use super::*;
+
+    pub fn cw721_instantiate(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(reply) => {
+                let event = reply
+                    .events
+                    .iter()
+                    .find(|e| {
+                        e.ty == "instantiate"
+                            || e.ty == "cosmwasm.wasm.v1.EventContractInstantiated"
+                    })
+                    .ok_or_else(|| StdError::generic_err("instantiate event not found"))?;
+                let maybe_address = &event
+                    .attributes
+                    .iter()
+                    .find(|a| a.key == "_contract_address" || a.key == "contract_address")
+                    .ok_or_else(|| StdError::generic_err("contract_address attribute not found"))?
+                    .value;
+
+                // added this to remove the quotes from the address in injective
+                let addr = deps.api.addr_validate(
+                    maybe_address
+                        .chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .collect::<String>()
+                        .as_str(),
+                )?;
+
+                STATE.update(deps.storage, |mut cs| -> StdResult<_> {
+                    cs.cw721_ica_extension_address = addr.clone();
+                    Ok(cs)
+                })?;
+
+                // This contract accepts the transfer hook, which is disabled by default.
+                let enable_transfer_hook = WasmMsg::Execute {
+                    contract_addr: addr.to_string(),
+                    msg: to_json_binary(&cw721_ica_extension::ExecuteMsg::Extension {
+                        msg: ExtensionMsg::SetTransferHook { enabled: true },
+                    })?,
+                    funds: vec![],
+                };
+
+                Ok(Response::new().add_message(enable_transfer_hook))
+            }
+            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        }
+    }
+
+    pub fn cw_ica_controller_instantiate(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(reply) => {
+                let event = reply
+                    .events
+                    .iter()
+                    .find(|e| {
+                        e.ty == "instantiate"
+                            || e.ty == "cosmwasm.wasm.v1.EventContractInstantiated"
+                    })
+                    .ok_or_else(|| StdError::generic_err("instantiate event not found"))?;
+                let maybe_address = &event
+                    .attributes
+                    .iter()
+                    .find(|a| a.key == "_contract_address" || a.key == "contract_address")
+                    .ok_or_else(|| StdError::generic_err("contract_address attribute not found"))?
+                    .value;
+
+                // added this to remove the quotes from the address in injective
+                let addr = deps.api.addr_validate(
+                    maybe_address
+                        .chars()
+                        .filter(|c| c.is_alphanumeric())
+                        .collect::<String>()
+                        .as_str(),
+                )?;
+
+                REGISTERED_ICA_ADDRS.insert(deps.storage, &addr)?;
+
+                // The replies are processed in the same order as the requests were queued.
+                let queue_item = NFT_MINT_QUEUE
+                    .pop_back(deps.storage)?
+                    .ok_or_else(|| StdError::generic_err("mint queue empty"))?;
+                NFT_ICA_CONTRACT_BI_MAP.insert(
+                    deps.storage,
+                    addr.as_str(),
+                    &queue_item.token_id,
+                    &queue_item.chain,
+                )?;
+
+                // Only the ICA on the primary chain of the token mints a new NFT.
+                if TOKEN_PRIMARY_CHAIN.load(deps.storage, &queue_item.token_id)? == queue_item.chain
+                {
+                    PENDING_MINTS.save(deps.storage, addr.as_str(), &queue_item)?;
+                }
+
+                Ok(Response::new())
+            }
+            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        }
+    }
+
+    pub fn fund_ica_transfer(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(reply) => {
+                let data = reply
+                    .data
+                    .ok_or_else(|| StdError::generic_err("transfer response data not found"))?;
+                let packet_sequence = ics20::transfer_sequence(&data)?;
+
+                // The transfer is tracked until its acknowledgement or timeout is received.
+                let (channel_id, transfer) = TRANSFER_REPLY_STATE.load(deps.storage)?;
+                TRANSFER_REPLY_STATE.remove(deps.storage);
+                PENDING_TRANSFERS.save(deps.storage, (&channel_id, packet_sequence), &transfer)?;
+
+                Ok(Response::new())
+            }
+            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        }
+    }
+
+    /// The callback receiver is only replied to on error, which is ignored so that the
+    /// callback is still processed.
+    pub fn callback_receiver(msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(_) => Ok(Response::new()),
+            SubMsgResult::Err(err) => Ok(Response::new()
+                .add_attribute("action", "callback_receiver_failed")
+                .add_attribute("error", err)),
+        }
+    }
+
+    /// The subscribers are only replied to on error, including running out of gas, which is
+    /// ignored so that the message sending the hook is still processed.
+    pub fn subscriber_hook(msg: Reply) -> StdResult<Response> {
+        match msg.result {
+            SubMsgResult::Ok(_) => Ok(Response::new()),
+            SubMsgResult::Err(err) => Ok(Response::new()
+                .add_attribute("action", "subscriber_hook_failed")
+                .add_attribute("error", err)),
+        }
+    }
+}
+
+mod migrate {
+    use super::*;
+
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Empty, Order, Storage, WasmMsg};
+    use cw721_ica_extension::ExtensionMsg;
+    use cw_storage_plus::{Deque, Map};
+
+    use crate::types::state::{
+        channel::ChannelState,
+        history::{self, TransactionMsgType, TransactionRecord},
+        CHAIN_TOKENS, CHANNEL_STATE, NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE,
+        TOKEN_PRIMARY_CHAIN, UNBOUND_MINTS,
+    };
+
+    /// The map used to map nft token ids to ICA addresses before tokens had several chains.
+    const LEGACY_NFT_ICA_MAP: Map<&str, String> = Map::new("nft_ica_map");
+    /// The map used to store both directions between cw-ica-controller addresses and NFT IDs
+    /// before tokens had several chains.
+    const LEGACY_NFT_ICA_CONTRACT_BI_MAP: Map<&str, String> = Map::new("nft_ica_contract_bi_map");
+    /// The map used to store the channel status of each token id before tokens had several chains.
+    const LEGACY_CHANNEL_STATE: Map<&str, ChannelState> = Map::new("channel_status");
+    /// The prefix of the queue used to store the transaction history of each token id, newest
+    /// first, before the history was indexed by sequence.
+    const LEGACY_TX_HISTORY_PREFIX: &str = "tx_history_";
+
+    /// The transaction record stored before the history was indexed by sequence.
+    #[cw_serde]
+    struct LegacyTransactionRecord {
+        status: TransactionStatus,
+        token_id: String,
+        owner: String,
+        msg_type: TransactionMsgType,
+        block_height: u64,
+        timestamp: u64,
+    }
+
+    /// Move the ICAs stored before tokens had several chains to the default chain, which
+    /// becomes the primary chain of their token.
+    pub fn migrate_single_chain_icas(storage: &mut dyn Storage) -> Result<(), ContractError> {
+        // The queued mints used to be bound to their controller when its channel opened, so
+        // they wait for the next channels opened by unbound controllers, oldest first.
+        // Their chain is the default chain.
+        while let Some(queue_item) = NFT_MINT_QUEUE.pop_back(storage)? {
+            TOKEN_PRIMARY_CHAIN.save(storage, &queue_item.token_id, &queue_item.chain)?;
+            UNBOUND_MINTS.push_front(storage, &queue_item)?;
+        }
+
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        let icas = LEGACY_NFT_ICA_MAP
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (token_id, ica_address) in icas {
+            NFT_ICA_MAP.save(storage, (&token_id, chain), &ica_address)?;
+            TOKEN_PRIMARY_CHAIN.save(storage, &token_id, &chain.to_string())?;
+            CHAIN_TOKENS.save(storage, (chain, &token_id), &Empty {})?;
+            LEGACY_NFT_ICA_MAP.remove(storage, &token_id);
+
+            let controller = LEGACY_NFT_ICA_CONTRACT_BI_MAP.may_load(storage, &token_id)?;
+            if let Some(controller) = &controller {
+                NFT_ICA_CONTRACT_BI_MAP.insert(storage, controller, &token_id, chain)?;
+                LEGACY_NFT_ICA_CONTRACT_BI_MAP.remove(storage, &token_id);
+                LEGACY_NFT_ICA_CONTRACT_BI_MAP.remove(storage, controller);
+            }
+
+            if let Some(channel_state) = LEGACY_CHANNEL_STATE.may_load(storage, &token_id)? {
+                CHANNEL_STATE.save(storage, (&token_id, chain), &channel_state)?;
+                LEGACY_CHANNEL_STATE.remove(storage, &token_id);
+            }
+
+            migrate_tx_history(storage, &token_id, controller.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Build the messages migrating the cw721-ica-extension contract, whose admin is this
+    /// contract, to the given code ID, and enabling its transfer hook.
+    pub fn cw721_ica_extension_msgs(
+        storage: &dyn Storage,
+        code_id: u64,
+    ) -> StdResult<Vec<WasmMsg>> {
+        let contract_addr = STATE.load(storage)?.cw721_ica_extension_address.to_string();
+
+        let migrate_msg = WasmMsg::Migrate {
+            contract_addr: contract_addr.clone(),
+            new_code_id: code_id,
+            msg: to_json_binary(&cw721_ica_extension::MigrateMsg {})?,
+        };
+        let enable_transfer_hook = WasmMsg::Execute {
+            contract_addr,
+            msg: to_json_binary(&cw721_ica_extension::ExecuteMsg::Extension {
+                msg: ExtensionMsg::SetTransferHook { enabled: true },
+            })?,
+            funds: vec![],
+        };
+
+        Ok(vec![migrate_msg, enable_transfer_hook])
+    }
+
+    /// Move the transaction history of the given token id from its legacy queue to the history
+    /// indexed by sequence, in the order the transactions were sent. The pending transactions
+    /// are resolved by the acknowledgements from the given ICA controller.
+    fn migrate_tx_history(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        controller: Option<&str>,
+    ) -> StdResult<()> {
+        let prefix = format!("{}{}", LEGACY_TX_HISTORY_PREFIX, token_id);
+        let legacy_store: Deque<LegacyTransactionRecord> = Deque::new(&prefix);
+
+        while let Some(legacy) = legacy_store.pop_back(storage)? {
+            let record = TransactionRecord {
+                sequence: 0,
+                activity_id: 0,
+                status: legacy.status,
+                token_id: legacy.token_id,
+                chain: keys::DEFAULT_CHAIN_ALIAS.to_string(),
+                owner: legacy.owner,
+                executor: None,
+                msg_type: legacy.msg_type,
+                block_height: legacy.block_height,
+                timestamp: legacy.timestamp,
+                vote: None,
+                results: vec![],
+                type_urls: vec![],
+                categories: vec![],
+            };
+
+            match controller {
+                Some(controller) if record.status == TransactionStatus::Pending => {
+                    history::push_pending(storage, controller, &record)?
+                }
+                _ => history::push(storage, &record)?,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{
+        coin, from_json,
+        testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+        Addr, BankMsg, Coin, ContractResult, CosmosMsg, Decimal, HexBinary, IbcMsg, OwnedDeps,
+        ReplyOn, StakingMsg, SubMsgResponse, SubMsgResult, SystemResult, Uint128, VoteOption,
+        WasmMsg, WeightedVoteOption,
+    };
+    use cw721_ica_extension::{ExtensionMsg, NftTransferHookMsg};
+    use cw_ica_controller::types::msg::ExecuteMsg as IcaControllerExecuteMsg;
+    use cw_ownable::OwnershipError;
+    use cw_storage_plus::{Deque, Map};
+    use sha2::{Digest, Sha256};
+
+    use crate::types::{
+        events,
+        keys::{CALLBACK_RECEIVER_GAS_LIMIT, MAX_SUBSCRIBERS, SUBSCRIBER_HOOK_GAS_LIMIT},
+        msg::{
+            callbacks::{NftIcaHookMsg, SubscriberExecuteMsg},
+            execute_responses::MintIcaResponse,
+            query_responses::{
+                ActivityFeedResponse, GetTransactionHistoryResponse, GetTransactionResponse,
+            },
+            RequestPacket,
+        },
+        state::{
+            backend::IcaBackend,
+            chain::{AllowedConnection, ChainConfig},
+            channel::{ChannelState, ChannelStatus},
+            compound::{AutoCompound, AutoCompoundConfig, CompoundStep, PendingCompound},
+            fees::{self, FeePayer, PacketFee},
+            history::{
+                self, HistoryCursor, HistoryFilter, HistoryOrder, MsgCategory, PendingWithdrawal,
+                TransactionMsgType, TransactionRecord, VoteRecord,
+            },
+            hooks::CallbackReceiver,
+            pause::PausableOperation,
+            MintPhase, AUTO_COMPOUND, CALLBACK_RECEIVERS, CHAIN_TOKENS, CHANNEL_STATE,
+            CONSUMED_GATE_TOKENS, ESCROWED_FEES, FEE_POOL, NATIVE_ICA_CONNECTIONS,
+            NFT_ICA_CONTRACT_BI_MAP, NFT_ICA_MAP, NFT_MINT_QUEUE, PENDING_COMPOUNDS, PENDING_MINTS,
+            PENDING_WITHDRAWALS, REGISTERED_ICA_ADDRS, SPONSORED_PACKET_FEES, TOKEN_COUNTER,
+            TOKEN_PACKET_FEES, TOKEN_PRIMARY_CHAIN, TX_HISTORY, UNBOUND_MINTS,
+        },
+    };
+    use crate::utils::{native_ica, type_url};
+
+    use super::*;
+
+    const EXTENSION: &str = "cw721-ica-extension";
+    const CONTROLLER: &str = "cw-ica-controller";
+    const HOLDER: &str = "holder";
+    const TOKEN_ID: &str = "ica-token-0";
+
+    fn setup(deps: DepsMut) {
+        save_state(deps.storage);
+        NFT_ICA_CONTRACT_BI_MAP
+            .insert(
+                deps.storage,
+                CONTROLLER,
+                TOKEN_ID,
+                keys::DEFAULT_CHAIN_ALIAS,
+            )
+            .unwrap();
+    }
+
+    fn save_state(storage: &mut dyn cosmwasm_std::Storage) {
+        STATE
+            .save(
+                storage,
+                &ContractState {
+                    default_chan_init_options: from_json(
+                        br#"{"connection_id":"connection-0","counterparty_connection_id":"connection-0"}"#,
+                    )
+                    .unwrap(),
+                    default_backend: Default::default(),
+                    ica_controller_code_id: 1,
+                    cw721_ica_extension_address: Addr::unchecked(EXTENSION),
+                    max_supply: None,
+                    max_per_address: None,
+                    mint_gate_collection: None,
+                    max_payload_size: None,
+                    max_history_per_token: None,
+                    transfer_callbacks: false,
+                    ibc_hooks: false,
+                    native_register_fee: vec![],
+                },
+            )
+            .unwrap();
+    }
+
+    /// Registers the ICA of the token with an open channel, and mocks the holder as the owner
+    /// of the token.
+    fn setup_ica(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) {
+        setup(deps.as_mut());
+        let storage = deps.as_mut().storage;
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        REGISTERED_ICA_ADDRS
+            .insert(storage, &Addr::unchecked(CONTROLLER))
+            .unwrap();
+        TOKEN_PRIMARY_CHAIN
+            .save(storage, TOKEN_ID, &chain.to_string())
+            .unwrap();
+        NFT_ICA_MAP
+            .save(storage, (TOKEN_ID, chain), &"cosmos1ica".to_string())
+            .unwrap();
+        CHANNEL_STATE
+            .save(
+                storage,
+                (TOKEN_ID, chain),
+                &ChannelState {
+                    status: ChannelStatus::Open,
+                    channel_id: Some("channel-0".to_string()),
+                },
+            )
+            .unwrap();
+        deps.querier.update_wasm(|_| {
+            SystemResult::Ok(ContractResult::Ok(Binary::from(
+                br#"{"owner":"holder","approvals":[]}"#.as_slice(),
+            )))
+        });
+    }
+
+    fn set_owner(deps: DepsMut, owner: &str) {
+        cw_ownable::initialize_owner(deps.storage, deps.api, Some(owner)).unwrap();
+    }
+
+    fn packet_fee() -> PacketFee {
+        PacketFee {
+            recv_fee: vec![coin(1, "untrn")],
+            ack_fee: vec![coin(2, "untrn")],
+            timeout_fee: vec![coin(3, "untrn")],
+        }
+    }
+
+    fn execute_ica_msg(packet_fee: Option<PacketFee>) -> ExecuteMsg {
+        ExecuteMsg::ExecuteIcaMsg {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            msg: IcaControllerExecuteMsg::SendCosmosMsgs {
+                messages: vec![],
+                packet_memo: None,
+                timeout_seconds: None,
+            },
+            packet_fee,
+        }
+    }
+
+    fn mint_ica_msg(count: u64) -> ExecuteMsg {
+        ExecuteMsg::MintIca {
+            salt: None,
+            proof: None,
+            gate_token_id: None,
+            recipient: None,
+            count: Some(count),
+            chain: None,
+            chan_init_options: None,
+        }
+    }
+
+    /// Enables auto-compounding for the token with the given bounty balance, and starts a
+    /// compound at the given step reserving a bounty of 10 ujuno.
+    fn setup_compound(deps: DepsMut, sequence: u64, step: CompoundStep) {
+        AUTO_COMPOUND
+            .save(
+                deps.storage,
+                TOKEN_ID,
+                &AutoCompound {
+                    chain: keys::DEFAULT_CHAIN_ALIAS.to_string(),
+                    config: AutoCompoundConfig {
+                        validator: "validator".to_string(),
+                        frequency: 86400,
+                        min_amount: coin(100, "uatom"),
+                        bounty: Some(coin(10, "ujuno")),
+                    },
+                    last_compound: None,
+                    bounty_balance: Uint128::new(20),
+                    depositor: Addr::unchecked(HOLDER),
+                },
+            )
+            .unwrap();
+        PENDING_COMPOUNDS
+            .save(
+                deps.storage,
+                TOKEN_ID,
+                &PendingCompound {
+                    keeper: Addr::unchecked("keeper"),
+                    depositor: Addr::unchecked(HOLDER),
+                    bounty: Some(coin(10, "ujuno")),
+                    sequence,
+                    step,
+                },
+            )
+            .unwrap();
+    }
+
+    fn bank_sends(res: &Response) -> Vec<(String, Vec<Coin>)> {
+        res.messages
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    Some((to_address.clone(), amount.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Records a pending transaction of the holder and returns its sequence.
+    fn push_pending_tx(deps: DepsMut) -> u64 {
+        let record = TransactionRecord::from_ica_msg(
+            &from_json(br#"{"send_cosmos_msgs":{"messages":[]}}"#).unwrap(),
+            TOKEN_ID,
+            keys::DEFAULT_CHAIN_ALIAS,
+            HOLDER,
+            1,
+            1,
+        )
+        .unwrap();
+        history::push_pending(deps.storage, CONTROLLER, &record).unwrap()
+    }
+
+    #[test]
+    fn test_failing_callback_receiver() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        CALLBACK_RECEIVERS
+            .save(
+                deps.as_mut().storage,
+                TOKEN_ID,
+                &CallbackReceiver {
+                    owner: Addr::unchecked(HOLDER),
+                    addr: Addr::unchecked("receiver"),
+                },
+            )
+            .unwrap();
+
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        let callback = res
+            .messages
+            .iter()
+            .find(|msg| msg.id == CALLBACK_RECEIVER_REPLY_ID)
+            .unwrap();
+        assert_eq!(callback.reply_on, ReplyOn::Error);
+        assert_eq!(callback.gas_limit, Some(CALLBACK_RECEIVER_GAS_LIMIT));
+
+        // the error of the receiver is ignored, so the record stays resolved
+        reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: CALLBACK_RECEIVER_REPLY_ID,
+                result: SubMsgResult::Err("out of gas".to_string()),
+            },
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Completed);
+    }
+
+    #[test]
+    fn test_withdrawal_in_transit() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        let mut env = mock_env();
+        let timeout = env.block.time.plus_seconds(600);
+        PENDING_WITHDRAWALS
+            .save(
+                deps.as_mut().storage,
+                (TOKEN_ID, sequence),
+                &PendingWithdrawal {
+                    receiver: Addr::unchecked(HOLDER),
+                    ica_address: "ica".to_string(),
+                    destination_channel: "channel-0".to_string(),
+                    host_sequence: None,
+                    timeout,
+                },
+            )
+            .unwrap();
+
+        // the ICA executed the transfer, which is not received yet
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::InTransit);
+
+        // only the ibc-hooks sender of the transfer can complete the withdrawal
+        execute::complete_withdrawal(
+            deps.as_mut(),
+            mock_info(HOLDER, &[]),
+            TOKEN_ID.to_string(),
+            sequence,
+        )
+        .unwrap_err();
+
+        let err =
+            execute::expire_withdrawal(deps.as_mut(), env.clone(), TOKEN_ID.to_string(), sequence)
+                .unwrap_err();
+        assert!(matches!(err, ContractError::WithdrawalNotExpired { .. }));
+
+        env.block.time = timeout;
+        execute::expire_withdrawal(deps.as_mut(), env, TOKEN_ID.to_string(), sequence).unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Timeout);
+        assert!(!PENDING_WITHDRAWALS.has(deps.as_ref().storage, (TOKEN_ID, sequence)));
+    }
+
+    #[test]
+    fn test_failed_withdrawal() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        PENDING_WITHDRAWALS
+            .save(
+                deps.as_mut().storage,
+                (TOKEN_ID, sequence),
+                &PendingWithdrawal {
+                    receiver: Addr::unchecked(HOLDER),
+                    ica_address: "ica".to_string(),
+                    destination_channel: "channel-0".to_string(),
+                    host_sequence: None,
+                    timeout: mock_env().block.time,
+                },
+            )
+            .unwrap();
+
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error("failed".to_string())),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Failed);
+        assert!(!PENDING_WITHDRAWALS.has(deps.as_ref().storage, (TOKEN_ID, sequence)));
+    }
+
+    #[test]
+    fn test_failed_compound_releases_bounty() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Withdrawing);
+
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error("failed".to_string())),
+        )
+        .unwrap();
+        assert!(bank_sends(&res).is_empty());
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+        let auto_compound = AUTO_COMPOUND.load(deps.as_ref().storage, TOKEN_ID).unwrap();
+        assert_eq!(auto_compound.bounty_balance, Uint128::new(30));
+    }
+
+    #[test]
+    fn test_compound_without_rewards() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Withdrawing);
+
+        // no rewards were withdrawn, so nothing is delegated
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+        let auto_compound = AUTO_COMPOUND.load(deps.as_ref().storage, TOKEN_ID).unwrap();
+        assert_eq!(auto_compound.bounty_balance, Uint128::new(30));
+    }
+
+    #[test]
+    fn test_compound_pays_bounty_once_delegated() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Delegating);
+
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![("keeper".to_string(), vec![coin(10, "ujuno")])]
+        );
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+        let auto_compound = AUTO_COMPOUND.load(deps.as_ref().storage, TOKEN_ID).unwrap();
+        assert_eq!(auto_compound.bounty_balance, Uint128::new(20));
+    }
+
+    #[test]
+    fn test_compound_pays_no_packet_fee() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        setup_compound(
+            deps.as_mut(),
+            0,
+            CompoundStep::Withdrawn {
+                amount: coin(100, "uatom"),
+            },
+        );
+        TOKEN_PACKET_FEES
+            .save(deps.as_mut().storage, TOKEN_ID, &packet_fee())
+            .unwrap();
+        SPONSORED_PACKET_FEES
+            .save(
+                deps.as_mut().storage,
+                keys::DEFAULT_CHAIN_ALIAS,
+                &packet_fee(),
+            )
+            .unwrap();
+        fees::deposit(deps.as_mut().storage, &[coin(10, "untrn")]).unwrap();
+        let compound_msg = ExecuteMsg::Compound {
+            token_id: TOKEN_ID.to_string(),
+        };
+
+        // the keeper cannot pay the packet fee of the token
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &packet_fee().total()),
+            compound_msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IncorrectPayment { .. }));
+
+        // and the fee pool does not pay the sponsored fee
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            compound_msg,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let pending = PENDING_COMPOUNDS
+            .load(deps.as_ref().storage, TOKEN_ID)
+            .unwrap();
+        assert_eq!(pending.step, CompoundStep::Delegating);
+        assert!(!ESCROWED_FEES.has(deps.as_ref().storage, (TOKEN_ID, pending.sequence)));
+        assert_eq!(
+            FEE_POOL.load(deps.as_ref().storage, "untrn").unwrap(),
+            Uint128::new(10)
+        );
+    }
+
+    #[test]
+    fn test_transfer_clears_auto_compound() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let sequence = push_pending_tx(deps.as_mut());
+        setup_compound(deps.as_mut(), sequence, CompoundStep::Withdrawing);
+
+        let res = execute::nft_transfer_hook(
+            deps.as_mut(),
+            mock_info(EXTENSION, &[]),
+            NftTransferHookMsg {
+                token_id: TOKEN_ID.to_string(),
+                from: HOLDER.to_string(),
+                to: "buyer".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![
+                (HOLDER.to_string(), vec![coin(20, "ujuno")]),
+                (HOLDER.to_string(), vec![coin(10, "ujuno")]),
+            ]
+        );
+        assert!(!AUTO_COMPOUND.has(deps.as_ref().storage, TOKEN_ID));
+        assert!(!PENDING_COMPOUNDS.has(deps.as_ref().storage, TOKEN_ID));
+    }
+
+    #[cw_serde]
+    struct LegacyRecord {
+        status: TransactionStatus,
+        token_id: String,
+        owner: String,
+        msg_type: TransactionMsgType,
+        block_height: u64,
+        timestamp: u64,
+    }
+
+    fn migrate_msg() -> MigrateMsg {
+        MigrateMsg {
+            cw721_ica_extension_code_id: Some(2),
+        }
+    }
+
+    #[test]
+    fn test_migrate_single_chain_icas() {
+        let mut deps = mock_dependencies();
+        let legacy_icas: Map<&str, String> = Map::new("nft_ica_map");
+        let legacy_bi_map: Map<&str, String> = Map::new("nft_ica_contract_bi_map");
+        let legacy_channels: Map<&str, ChannelState> = Map::new("channel_status");
+        let channel_state = ChannelState {
+            status: ChannelStatus::Open,
+            channel_id: Some("channel-0".to_string()),
+        };
+        let storage = deps.as_mut().storage;
+        cw2::set_contract_version(storage, keys::CONTRACT_NAME, "0.1.0").unwrap();
+        save_state(storage);
+        legacy_icas
+            .save(storage, TOKEN_ID, &"cosmos1ica".to_string())
+            .unwrap();
+        legacy_bi_map
+            .save(storage, TOKEN_ID, &CONTROLLER.to_string())
+            .unwrap();
+        legacy_bi_map
+            .save(storage, CONTROLLER, &TOKEN_ID.to_string())
+            .unwrap();
+        legacy_channels
+            .save(storage, TOKEN_ID, &channel_state)
+            .unwrap();
+        // The legacy history stores the newest record first.
+        let legacy_history: Deque<LegacyRecord> = Deque::new("tx_history_ica-token-0");
+        for (status, block_height) in [
+            (TransactionStatus::Completed, 1),
+            (TransactionStatus::Pending, 2),
+        ] {
+            legacy_history
+                .push_front(
+                    storage,
+                    &LegacyRecord {
+                        status,
+                        token_id: TOKEN_ID.to_string(),
+                        owner: HOLDER.to_string(),
+                        msg_type: TransactionMsgType::Delegate,
+                        block_height,
+                        timestamp: 0,
+                    },
+                )
+                .unwrap();
+        }
+
+        let res = migrate(deps.as_mut(), mock_env(), migrate_msg()).unwrap();
+
+        // The collection is migrated to the new code ID, and its transfer hook is enabled.
+        assert_eq!(res.messages.len(), 2);
+        assert!(matches!(
+            &res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Migrate { contract_addr, new_code_id: 2, .. })
+                if contract_addr == EXTENSION
+        ));
+        let CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) = &res.messages[1].msg
+        else {
+            panic!("unexpected message");
+        };
+        assert_eq!(contract_addr, EXTENSION);
+        assert!(matches!(
+            from_json(msg).unwrap(),
+            cw721_ica_extension::ExecuteMsg::Extension {
+                msg: ExtensionMsg::SetTransferHook { enabled: true }
+            }
+        ));
+
+        let storage = deps.as_ref().storage;
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        assert_eq!(
+            NFT_ICA_MAP.load(storage, (TOKEN_ID, chain)).unwrap(),
+            "cosmos1ica"
+        );
+        assert_eq!(TOKEN_PRIMARY_CHAIN.load(storage, TOKEN_ID).unwrap(), chain);
+        assert!(CHAIN_TOKENS.has(storage, (chain, TOKEN_ID)));
+        assert_eq!(
+            NFT_ICA_CONTRACT_BI_MAP
+                .load_nft(storage, CONTROLLER)
+                .unwrap(),
+            (TOKEN_ID.to_string(), chain.to_string())
+        );
+        assert_eq!(
+            CHANNEL_STATE.load(storage, (TOKEN_ID, chain)).unwrap(),
+            channel_state
+        );
+        assert!(!legacy_icas.has(storage, TOKEN_ID));
+        assert!(!legacy_bi_map.has(storage, TOKEN_ID));
+        assert!(!legacy_bi_map.has(storage, CONTROLLER));
+        assert!(!legacy_channels.has(storage, TOKEN_ID));
+        assert_eq!(legacy_history.len(storage).unwrap(), 0);
+
+        let record = TX_HISTORY.load(storage, (TOKEN_ID, 0)).unwrap();
+        assert_eq!(record.status, TransactionStatus::Completed);
+        assert_eq!(record.block_height, 1);
+        assert_eq!(record.chain, chain);
+        let record = TX_HISTORY.load(storage, (TOKEN_ID, 1)).unwrap();
+        assert_eq!(record.status, TransactionStatus::Pending);
+        assert_eq!(record.block_height, 2);
+        assert_eq!(
+            cw2::get_contract_version(storage).unwrap().version,
+            keys::CONTRACT_VERSION
+        );
+
+        // The pending record is resolved by the next acknowledgement of the controller.
+        let (sequence, _) = history::resolve_pending(
+            deps.as_mut().storage,
+            CONTROLLER,
+            TOKEN_ID,
+            TransactionStatus::Completed,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(sequence, 1);
+
+        // The legacy migration only runs once.
+        legacy_icas
+            .save(
+                deps.as_mut().storage,
+                "ica-token-1",
+                &"cosmos1ica".to_string(),
+            )
+            .unwrap();
+        let res = migrate(
+            deps.as_mut(),
+            mock_env(),
+            MigrateMsg {
+                cw721_ica_extension_code_id: None,
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert!(legacy_icas.has(deps.as_ref().storage, "ica-token-1"));
+        assert!(!NFT_ICA_MAP.has(deps.as_ref().storage, ("ica-token-1", chain)));
+    }
+
+    #[test]
+    fn test_migrate_requires_cw721_ica_extension_code_id() {
+        let mut deps = mock_dependencies();
+        cw2::set_contract_version(deps.as_mut().storage, keys::CONTRACT_NAME, "0.1.0").unwrap();
+        save_state(deps.as_mut().storage);
+
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            MigrateMsg {
+                cw721_ica_extension_code_id: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::MissingCw721IcaExtensionCodeId { version } if version == "0.1.0"
+        ));
+    }
+
+    #[cw_serde]
+    struct LegacyQueueItem {
+        token_id: String,
+        owner: String,
+    }
+
+    #[test]
+    fn test_migrate_queued_mints() {
+        let mut deps = mock_dependencies();
+        let storage = deps.as_mut().storage;
+        cw2::set_contract_version(storage, keys::CONTRACT_NAME, "0.1.0").unwrap();
+        save_state(storage);
+        // The legacy queue stores the newest mint request first.
+        let legacy_queue: Deque<LegacyQueueItem> = Deque::new("nft_mint_queue");
+        for token_id in ["ica-token-0", "ica-token-1"] {
+            legacy_queue
+                .push_front(
+                    storage,
+                    &LegacyQueueItem {
+                        token_id: token_id.to_string(),
+                        owner: HOLDER.to_string(),
+                    },
+                )
+                .unwrap();
+        }
+
+        migrate(deps.as_mut(), mock_env(), migrate_msg()).unwrap();
+
+        let chain = keys::DEFAULT_CHAIN_ALIAS;
+        assert_eq!(NFT_MINT_QUEUE.len(deps.as_ref().storage).unwrap(), 0);
+        let queue = query::get_mint_queue(deps.as_ref()).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert!(queue.iter().all(|item| item.chain == chain));
+        assert_eq!(
+            TOKEN_PRIMARY_CHAIN
+                .load(deps.as_ref().storage, "ica-token-1")
+                .unwrap(),
+            chain
+        );
+
+        // The oldest mint request is bound to the first unbound controller opening a channel.
+        execute::bind_unbound_mint(deps.as_mut().storage, CONTROLLER).unwrap();
+        assert_eq!(
+            NFT_ICA_CONTRACT_BI_MAP
+                .load_nft(deps.as_ref().storage, CONTROLLER)
+                .unwrap(),
+            ("ica-token-0".to_string(), chain.to_string())
+        );
+        assert_eq!(
+            PENDING_MINTS
+                .load(deps.as_ref().storage, CONTROLLER)
+                .unwrap()
+                .token_id,
+            "ica-token-0"
+        );
+
+        // A bound controller does not take another mint request.
+        execute::bind_unbound_mint(deps.as_mut().storage, CONTROLLER).unwrap();
+        assert_eq!(UNBOUND_MINTS.len(deps.as_ref().storage).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_packet_fee_paid_by_sender() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+
+        // the fee must be paid with the sent funds
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(Some(packet_fee())),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IncorrectPayment { .. }));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[coin(6, "untrn")]),
+            execute_ica_msg(Some(packet_fee())),
+        )
+        .unwrap();
+        assert!(res.messages.iter().any(|msg| matches!(
+            &msg.msg,
+            CosmosMsg::Stargate { type_url, .. } if type_url == "/ibc.applications.fee.v1.MsgPayPacketFee"
+        )));
+        let escrowed = ESCROWED_FEES
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(escrowed.fee, packet_fee());
+        assert_eq!(escrowed.payer, FeePayer::Sender(Addr::unchecked(HOLDER)));
+
+        // the unused timeout fee is refunded to the sender once the packet is acknowledged
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![(HOLDER.to_string(), vec![coin(3, "untrn")])]
+        );
+        assert!(!ESCROWED_FEES.has(deps.as_ref().storage, (TOKEN_ID, 0)));
+    }
+
+    #[test]
+    fn test_simulate_matches_execute() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        SPONSORED_PACKET_FEES
+            .save(
+                deps.as_mut().storage,
+                keys::DEFAULT_CHAIN_ALIAS,
+                &packet_fee(),
+            )
+            .unwrap();
+        fees::deposit(deps.as_mut().storage, &[coin(10, "untrn")]).unwrap();
+
+        let ica_msg = IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages: vec![],
+            packet_memo: None,
+            timeout_seconds: None,
+        };
+        let simulate = |deps: Deps, funds: Vec<Coin>, packet_fee: Option<PacketFee>| {
+            query::simulate_ica_msg(
+                deps,
+                mock_env(),
+                HOLDER.to_string(),
+                funds,
+                TOKEN_ID.to_string(),
+                None,
+                ica_msg.clone(),
+                packet_fee,
+            )
+            .unwrap()
+        };
+
+        // the fee paid by the sender must match the sent funds
+        let sim = simulate(deps.as_ref(), vec![coin(1, "untrn")], Some(packet_fee()));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[coin(1, "untrn")]),
+            execute_ica_msg(Some(packet_fee())),
+        )
+        .unwrap_err();
+        assert_eq!(sim.violations, vec![err.to_string()]);
+        assert!(sim.msgs.is_empty());
+
+        // the fee paid by the sender, and then the sponsored fee paid from the fee pool
+        for (sender_fee, sequence) in [(Some(packet_fee()), 0), (None, 1)] {
+            let funds = sender_fee
+                .as_ref()
+                .map(PacketFee::total)
+                .unwrap_or_default();
+            let sim = simulate(deps.as_ref(), funds.clone(), sender_fee.clone());
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(HOLDER, &funds),
+                execute_ica_msg(sender_fee),
+            )
+            .unwrap();
+
+            assert!(sim.authorized);
+            assert!(sim.violations.is_empty());
+            assert_eq!(sim.msg_type, Some(TransactionMsgType::Empty));
+            assert_eq!(
+                sim.msgs,
+                res.messages
+                    .into_iter()
+                    .map(|msg| msg.msg)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(sim.msgs.len(), 2);
+            assert_eq!(
+                sim.escrowed_fee,
+                ESCROWED_FEES
+                    .may_load(deps.as_ref().storage, (TOKEN_ID, sequence))
+                    .unwrap()
+            );
+            assert!(sim.escrowed_fee.is_some());
+        }
+
+        // the fee pool cannot cover the sponsored fee anymore
+        let sim = simulate(deps.as_ref(), vec![], None);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(None),
+        )
+        .unwrap();
+        assert!(sim.escrowed_fee.is_none());
+        assert!(!ESCROWED_FEES.has(deps.as_ref().storage, (TOKEN_ID, 2)));
+        assert_eq!(
+            sim.msgs,
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_packet_fee_paid_by_fee_pool() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        SPONSORED_PACKET_FEES
+            .save(
+                deps.as_mut().storage,
+                keys::DEFAULT_CHAIN_ALIAS,
+                &packet_fee(),
+            )
+            .unwrap();
+        fees::deposit(deps.as_mut().storage, &[coin(10, "untrn")]).unwrap();
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(None),
+        )
+        .unwrap();
+        assert_eq!(
+            FEE_POOL.load(deps.as_ref().storage, "untrn").unwrap(),
+            Uint128::new(4)
+        );
+        let escrowed = ESCROWED_FEES
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(escrowed.payer, FeePayer::FeePool);
+
+        // the unused receive and acknowledgement fees of a timed out packet return to the pool
+        let res =
+            execute::packet_result(deps.as_mut(), CONTROLLER, TransactionStatus::Timeout, None)
+                .unwrap();
+        assert!(bank_sends(&res).is_empty());
+        assert_eq!(
+            FEE_POOL.load(deps.as_ref().storage, "untrn").unwrap(),
+            Uint128::new(7)
+        );
+        assert!(!ESCROWED_FEES.has(deps.as_ref().storage, (TOKEN_ID, 0)));
+    }
+
+    #[test]
+    fn test_batch_mint_limits() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.default_backend = IcaBackend::Native;
+                state.max_supply = Some(5);
+                state.max_per_address = Some(4);
+                Ok(state)
+            })
+            .unwrap();
+        let mut mint = |count| {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(HOLDER, &[]),
+                mint_ica_msg(count),
+            )
+        };
+
+        let err = mint(0).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMintCount(_)));
+        let err = mint(keys::MAX_MINT_BATCH + 1).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMintCount(_)));
+        let err = mint(5).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::MaxPerAddressReached { limit: 4, .. }
+        ));
+
+        let res = mint(3).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        let response: MintIcaResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            response.token_ids,
+            vec!["ica-token-0", "ica-token-1", "ica-token-2"]
+        );
+
+        let err = mint(3).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::MaxSupplyReached { max_supply: 5 }
+        ));
+
+        // the counters cannot overflow
+        TOKEN_COUNTER
+            .save(deps.as_mut().storage, &u64::MAX)
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            mint_ica_msg(1),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMintCount(_)));
+    }
+
+    #[test]
+    fn test_mint_caps() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.default_backend = IcaBackend::Native;
+                state.max_supply = Some(3);
+                state.max_per_address = Some(2);
+                Ok(state)
+            })
+            .unwrap();
+        let mint = |deps: DepsMut, sender: &str, recipient: Option<&str>| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::MintIca {
+                    salt: None,
+                    proof: None,
+                    gate_token_id: None,
+                    recipient: recipient.map(String::from),
+                    count: None,
+                    chain: None,
+                    chan_init_options: None,
+                },
+            )
+        };
+
+        // the mints are counted for the recipient, not the sender
+        mint(deps.as_mut(), HOLDER, None).unwrap();
+        mint(deps.as_mut(), "other", Some(HOLDER)).unwrap();
+        let err = mint(deps.as_mut(), HOLDER, None).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::MaxPerAddressReached { address, limit: 2 } if address == HOLDER
+        ));
+
+        // the supply counts every requested mint
+        mint(deps.as_mut(), "other", None).unwrap();
+        let err = mint(deps.as_mut(), "third", None).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::MaxSupplyReached { max_supply: 3 }
+        ));
+        let supply = query::get_remaining_supply(deps.as_ref()).unwrap();
+        assert_eq!(supply.total_supply, 3);
+        assert_eq!(supply.remaining, Some(0));
     }
 
-    /// Query the ICA controller address for a given ICA NFT ID.
-    pub fn get_ica_address(deps: Deps, token_id: String) -> StdResult<String> {
-        NFT_ICA_MAP.load(deps.storage, &token_id)
+    #[test]
+    fn test_mint_gate() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        set_owner(deps.as_mut(), "owner");
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.default_backend = IcaBackend::Native;
+                Ok(state)
+            })
+            .unwrap();
+        // the holder owns every token of the partner collection
+        deps.querier.update_wasm(|_| {
+            SystemResult::Ok(ContractResult::Ok(Binary::from(
+                br#"{"owner":"holder","approvals":[]}"#.as_slice(),
+            )))
+        });
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetMintGate {
+                collection: Some("partner".to_string()),
+            },
+        )
+        .unwrap();
+        let mint = |deps: DepsMut, sender: &str, gate_token_id: Option<&str>, count| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::MintIca {
+                    salt: None,
+                    proof: None,
+                    gate_token_id: gate_token_id.map(String::from),
+                    recipient: None,
+                    count: Some(count),
+                    chain: None,
+                    chan_init_options: None,
+                },
+            )
+        };
+
+        let err = mint(deps.as_mut(), HOLDER, None, 1).unwrap_err();
+        assert!(matches!(err, ContractError::GateTokenRequired));
+        let err = mint(deps.as_mut(), HOLDER, Some("1"), 2).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMintCount(_)));
+        let err = mint(deps.as_mut(), "other", Some("1"), 1).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // each gate token mints a single ICA
+        mint(deps.as_mut(), HOLDER, Some("1"), 1).unwrap();
+        let partner = Addr::unchecked("partner");
+        assert!(CONSUMED_GATE_TOKENS.has(deps.as_ref().storage, (&partner, "1")));
+        let err = mint(deps.as_mut(), HOLDER, Some("1"), 1).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::GateTokenAlreadyUsed { token_id } if token_id == "1"
+        ));
+        mint(deps.as_mut(), HOLDER, Some("2"), 1).unwrap();
     }
 
-    /// Query the ICA controller addresses for a given list of ICA NFT IDs.
-    pub fn get_ica_addresses(
-        deps: Deps,
-        token_ids: Vec<String>,
-    ) -> StdResult<GetIcaAddressesResponse> {
-        let nft_ica_pairs =
-            token_ids
-                .iter()
-                .try_fold(Vec::new(), |mut acc, token_id| -> StdResult<_> {
-                    let ica_address = NFT_ICA_MAP.load(deps.storage, token_id)?;
-                    acc.push(NftIcaPair {
-                        nft_id: token_id.to_string(),
-                        ica_address,
-                    });
-                    Ok(acc)
-                })?;
+    #[test]
+    fn test_allowed_connections() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        set_owner(deps.as_mut(), "owner");
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.default_backend = IcaBackend::Native;
+                Ok(state)
+            })
+            .unwrap();
+        let update_msg = |add, remove| ExecuteMsg::UpdateAllowedConnections { add, remove };
+        let allowed = AllowedConnection {
+            connection_id: "connection-1".to_string(),
+            counterparty_connection_id: "connection-5".to_string(),
+        };
 
-        Ok(GetIcaAddressesResponse {
-            pairs: nft_ica_pairs,
-        })
+        // only the owner can allow connections
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            update_msg(vec![allowed.clone()], vec![]),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::OwnershipError(OwnershipError::NotOwner)
+        ));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            update_msg(vec![allowed.clone()], vec![]),
+        )
+        .unwrap();
+        assert_eq!(
+            query::get_allowed_connections(deps.as_ref()).unwrap(),
+            vec![allowed]
+        );
+
+        let mint = |deps: DepsMut, options: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(HOLDER, &[]),
+                ExecuteMsg::MintIca {
+                    salt: None,
+                    proof: None,
+                    gate_token_id: None,
+                    recipient: None,
+                    count: None,
+                    chain: None,
+                    chan_init_options: Some(from_json(options).unwrap()),
+                },
+            )
+        };
+
+        let err = mint(
+            deps.as_mut(),
+            r#"{"connection_id":"connection-2","counterparty_connection_id":"connection-5"}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ConnectionNotAllowed { .. }));
+        // each connection is paired with its counterparty connection
+        let err = mint(
+            deps.as_mut(),
+            r#"{"connection_id":"connection-1","counterparty_connection_id":"connection-0"}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CounterpartyConnectionNotAllowed { .. }
+        ));
+        // and the ICA host port
+        let err = mint(
+            deps.as_mut(),
+            r#"{"connection_id":"connection-1","counterparty_connection_id":"connection-5","counterparty_port_id":"transfer"}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CounterpartyPortNotAllowed { port_id } if port_id == "transfer"
+        ));
+
+        mint(
+            deps.as_mut(),
+            r#"{"connection_id":"connection-1","counterparty_connection_id":"connection-5"}"#,
+        )
+        .unwrap();
+        let ica_owner = native_ica::ica_owner(
+            &mock_env().contract.address,
+            TOKEN_ID,
+            keys::DEFAULT_CHAIN_ALIAS,
+        );
+        assert_eq!(
+            NATIVE_ICA_CONNECTIONS
+                .load(deps.as_ref().storage, &ica_owner)
+                .unwrap(),
+            "connection-1"
+        );
+
+        // a removed connection cannot be used anymore
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            update_msg(vec![], vec!["connection-1".to_string()]),
+        )
+        .unwrap();
+        let err = mint(
+            deps.as_mut(),
+            r#"{"connection_id":"connection-1","counterparty_connection_id":"connection-5"}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ConnectionNotAllowed { .. }));
     }
 
-    /// Query the mint queue.
-    pub fn get_mint_queue(deps: Deps) -> StdResult<Vec<QueueItem>> {
-        NFT_MINT_QUEUE.iter(deps.storage)?.collect()
+    #[test]
+    fn test_register_chain() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        set_owner(deps.as_mut(), "owner");
+        deps.querier.update_wasm(|_| {
+            SystemResult::Ok(ContractResult::Ok(Binary::from(
+                br#"{"owner":"holder","approvals":[]}"#.as_slice(),
+            )))
+        });
+        let chain_config = |connection_id: &str| {
+            ChainConfig {
+            chan_init_options: from_json(format!(
+                r#"{{"connection_id":"{connection_id}","counterparty_connection_id":"connection-0"}}"#
+            ))
+            .unwrap(),
+            backend: IcaBackend::Native,
+            bech32_prefix: "osmo".to_string(),
+            native_denom: "uosmo".to_string(),
+        }
+        };
+        let register = |deps: DepsMut, sender: &str, alias: &str, connection_id: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::RegisterChain {
+                    alias: alias.to_string(),
+                    config: chain_config(connection_id),
+                },
+            )
+        };
+        let mint = |deps: DepsMut, chain: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(HOLDER, &[]),
+                ExecuteMsg::MintIca {
+                    salt: None,
+                    proof: None,
+                    gate_token_id: None,
+                    recipient: None,
+                    count: None,
+                    chain: Some(chain.to_string()),
+                    chan_init_options: None,
+                },
+            )
+        };
+
+        // only the owner can register chains, and the default alias is reserved
+        let err = register(deps.as_mut(), HOLDER, "osmosis", "connection-1").unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::OwnershipError(OwnershipError::NotOwner)
+        ));
+        let err = register(
+            deps.as_mut(),
+            "owner",
+            keys::DEFAULT_CHAIN_ALIAS,
+            "connection-1",
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ReservedChainAlias(_)));
+        let err = mint(deps.as_mut(), "osmosis").unwrap_err();
+        assert!(matches!(err, ContractError::ChainNotFound { .. }));
+
+        // the ICA is created with the connection and backend of the registered chain
+        register(deps.as_mut(), "owner", "osmosis", "connection-1").unwrap();
+        mint(deps.as_mut(), "osmosis").unwrap();
+        assert_eq!(
+            TOKEN_PRIMARY_CHAIN
+                .load(deps.as_ref().storage, TOKEN_ID)
+                .unwrap(),
+            "osmosis"
+        );
+        let env = mock_env();
+        let osmosis_owner = native_ica::ica_owner(&env.contract.address, TOKEN_ID, "osmosis");
+        assert_eq!(
+            NATIVE_ICA_CONNECTIONS
+                .load(deps.as_ref().storage, &osmosis_owner)
+                .unwrap(),
+            "connection-1"
+        );
+        assert!(PENDING_MINTS.has(deps.as_ref().storage, &osmosis_owner));
+
+        // the token can have one more ICA on each registered chain, which mints no NFT
+        register(deps.as_mut(), "owner", "juno", "connection-2").unwrap();
+        let add_ica_msg = ExecuteMsg::AddIcaToToken {
+            token_id: TOKEN_ID.to_string(),
+            chain: "juno".to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            add_ica_msg.clone(),
+        )
+        .unwrap();
+        let juno_owner = native_ica::ica_owner(&env.contract.address, TOKEN_ID, "juno");
+        assert_eq!(
+            NATIVE_ICA_CONNECTIONS
+                .load(deps.as_ref().storage, &juno_owner)
+                .unwrap(),
+            "connection-2"
+        );
+        assert!(!PENDING_MINTS.has(deps.as_ref().storage, &juno_owner));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            add_ica_msg,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IcaAlreadyExists { .. }));
+
+        // a removed chain cannot be used to mint anymore
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::RemoveChain {
+                alias: "osmosis".to_string(),
+            },
+        )
+        .unwrap();
+        let err = mint(deps.as_mut(), "osmosis").unwrap_err();
+        assert!(matches!(err, ContractError::ChainNotFound { .. }));
     }
 
-    /// Query the transaction history for a given NFT ID.
-    pub fn get_transaction_history(
-        deps: Deps,
-        token_id: String,
-        page: Option<u32>,
-        page_size: Option<u32>,
-    ) -> StdResult<GetTransactionHistoryResponse> {
-        let page = page.unwrap_or(0);
-        // using 30 as the default page size
-        let page_size = page_size.unwrap_or(30);
-
-        let start = (page * page_size) as usize;
-        let end = start + page_size as usize;
-
-        let prefix = get_tx_history_prefix(&token_id);
-        let records_store: Deque<TransactionRecord> = Deque::new(&prefix);
-
-        let tx_records = records_store
-            .iter(deps.storage)?
-            .skip(start)
-            .take(end)
-            .try_fold(Vec::new(), |mut acc, maybe_record| -> StdResult<_> {
-                acc.push(maybe_record?);
-                Ok(acc)
-            });
+    #[test]
+    fn test_fund_ica() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        set_owner(deps.as_mut(), "owner");
+        let fund_msg = |timeout| ExecuteMsg::FundIca {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            channel_id: "channel-1".to_string(),
+            timeout,
+        };
+        let transfer_result = |deps: DepsMut, sequence, status| {
+            let request = RequestPacket {
+                sequence: Some(sequence),
+                source_port: Some(keys::ICS20_PORT_ID.to_string()),
+                source_channel: Some("channel-1".to_string()),
+                destination_port: None,
+                destination_channel: None,
+                data: None,
+                timeout_height: None,
+                timeout_timestamp: None,
+            };
+            let msg = match status {
+                TransactionStatus::Completed => SudoMsg::Response {
+                    request,
+                    data: Binary::default(),
+                },
+                _ => SudoMsg::Error {
+                    request,
+                    details: "failed".to_string(),
+                },
+            };
+            sudo(deps, mock_env(), msg).unwrap()
+        };
+        let funds = [coin(100, "ujuno")];
 
-        Ok(GetTransactionHistoryResponse {
-            records: tx_records?,
-            total: records_store.len(deps.storage)?,
-        })
+        // the refund of a failed transfer requires the transfer callbacks
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &funds),
+            fund_msg(None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::TransferCallbacksDisabled));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetTransferCallbacks { enabled: true },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &funds),
+            fund_msg(Some(0)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTimeout { .. }));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other", &funds),
+            fund_msg(None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[coin(100, "ujuno"), coin(1, "untrn")]),
+            fund_msg(None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IncorrectPayment { .. }));
+
+        // the funds are sent to the ICA and recorded as a pending deposit
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &funds),
+            fund_msg(Some(60)),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, keys::FUND_ICA_REPLY_ID);
+        assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "channel-1".to_string(),
+                to_address: "cosmos1ica".to_string(),
+                amount: coin(100, "ujuno"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            })
+        );
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(record.msg_type, TransactionMsgType::Deposit);
+        assert_eq!(record.status, TransactionStatus::Pending);
+
+        // the transfer is tracked by the packet sequence from the reply
+        let transfer_reply = |sequence: u8| Reply {
+            id: keys::FUND_ICA_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                // the protobuf encoding of MsgTransferResponse { sequence }
+                data: Some(Binary::from(vec![8, sequence])),
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), transfer_reply(7)).unwrap();
+
+        // the funds of a failed transfer are refunded to the sender
+        let res = transfer_result(deps.as_mut(), 7, TransactionStatus::Failed);
+        assert_eq!(
+            bank_sends(&res),
+            vec![(HOLDER.to_string(), vec![coin(100, "ujuno")])]
+        );
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Failed);
+
+        // a received transfer completes the deposit
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &funds),
+            fund_msg(None),
+        )
+        .unwrap();
+        reply(deps.as_mut(), mock_env(), transfer_reply(8)).unwrap();
+        let res = transfer_result(deps.as_mut(), 8, TransactionStatus::Completed);
+        assert!(bank_sends(&res).is_empty());
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 1))
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::Completed);
     }
 
-    pub fn get_channel_state(deps: Deps, token_id: String) -> StdResult<ChannelState> {
-        CHANNEL_STATE.load(deps.storage, &token_id)
+    #[test]
+    fn test_ica_staking() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        let delegate_msg = ExecuteMsg::IcaDelegate {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            validator: "validator".to_string(),
+            amount: coin(100, "uatom"),
+        };
+        let claim_msg = |validators: &[&str]| ExecuteMsg::IcaClaimRewards {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            validators: validators.iter().map(|v| v.to_string()).collect(),
+        };
+
+        // only the holder can stake with the ICA
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other", &[]),
+            delegate_msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            delegate_msg,
+        )
+        .unwrap();
+        let sent = res
+            .messages
+            .iter()
+            .find_map(|msg| match &msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr, msg, ..
+                }) if contract_addr == CONTROLLER => Some(from_json(msg).unwrap()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            sent,
+            IcaControllerExecuteMsg::SendCosmosMsgs {
+                messages: vec![StakingMsg::Delegate {
+                    validator: "validator".to_string(),
+                    amount: coin(100, "uatom"),
+                }
+                .into()],
+                packet_memo: None,
+                timeout_seconds: None,
+            }
+        );
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(record.msg_type, TransactionMsgType::Delegate);
+
+        // the rewards are claimed from every validator in a single transaction
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            claim_msg(&[]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NoValidators));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            claim_msg(&["validator", "other-validator"]),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 1))
+            .unwrap();
+        assert_eq!(record.msg_type, TransactionMsgType::Distribution);
     }
-}
 
-mod reply {
-    use cosmwasm_std::SubMsgResult;
+    #[test]
+    fn test_ica_vote_weighted() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        let vote_msg = |weights: &[&str]| ExecuteMsg::IcaVoteWeighted {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            proposal_id: 7,
+            votes: weights
+                .iter()
+                .zip([VoteOption::Yes, VoteOption::No, VoteOption::Abstain])
+                .map(|(weight, option)| WeightedVoteOption {
+                    option,
+                    weight: weight.parse().unwrap(),
+                })
+                .collect(),
+        };
 
-    use crate::types::state::REGISTERED_ICA_ADDRS;
+        // the weights must add up to one
+        let invalid_weights: [&[&str]; 3] = [&[], &["0.5"], &["0.5", "0.6"]];
+        for weights in invalid_weights {
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(HOLDER, &[]),
+                vote_msg(weights),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidVoteWeights));
+        }
 
-    use super::*;
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            vote_msg(&["0.5", "0.3", "0.2"]),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(record.msg_type, TransactionMsgType::Vote);
+        assert_eq!(
+            record.vote,
+            Some(VoteRecord {
+                proposal_id: 7,
+                options: vec![
+                    WeightedVoteOption {
+                        option: VoteOption::Yes,
+                        weight: Decimal::percent(50),
+                    },
+                    WeightedVoteOption {
+                        option: VoteOption::No,
+                        weight: Decimal::percent(30),
+                    },
+                    WeightedVoteOption {
+                        option: VoteOption::Abstain,
+                        weight: Decimal::percent(20),
+                    },
+                ],
+            })
+        );
+    }
 
-    pub fn cw721_instantiate(deps: DepsMut, msg: Reply) -> StdResult<Response> {
-        match msg.result {
-            SubMsgResult::Ok(reply) => {
-                let event = reply
-                    .events
-                    .iter()
-                    .find(|e| {
-                        e.ty == "instantiate"
-                            || e.ty == "cosmwasm.wasm.v1.EventContractInstantiated"
-                    })
-                    .ok_or_else(|| StdError::generic_err("instantiate event not found"))?;
-                let maybe_address = &event
-                    .attributes
-                    .iter()
-                    .find(|a| a.key == "_contract_address" || a.key == "contract_address")
-                    .ok_or_else(|| StdError::generic_err("contract_address attribute not found"))?
-                    .value;
+    #[test]
+    fn test_mint_phases() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        set_owner(deps.as_mut(), "owner");
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.default_backend = IcaBackend::Native;
+                Ok(state)
+            })
+            .unwrap();
 
-                // added this to remove the quotes from the address in injective
-                let addr = deps.api.addr_validate(
-                    maybe_address
-                        .chars()
-                        .filter(|c| c.is_alphanumeric())
-                        .collect::<String>()
-                        .as_str(),
-                )?;
+        // the allowlist of the first phase contains the holder and another address
+        let holder_leaf = Sha256::digest(HOLDER.as_bytes()).to_vec();
+        let other_leaf = Sha256::digest(b"other").to_vec();
+        let (left, right) = if holder_leaf <= other_leaf {
+            (&holder_leaf, &other_leaf)
+        } else {
+            (&other_leaf, &holder_leaf)
+        };
+        let merkle_root = Sha256::new()
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .to_vec();
+        let now = mock_env().block.time;
+        let mut phases = vec![
+            MintPhase {
+                start: now.minus_seconds(100),
+                end: now.plus_seconds(100),
+                price: Some(coin(100, "ujuno")),
+                per_address_limit: Some(2),
+                merkle_root: Some(HexBinary::from(merkle_root)),
+            },
+            MintPhase {
+                start: now.plus_seconds(100),
+                end: now.plus_seconds(200),
+                price: None,
+                per_address_limit: None,
+                merkle_root: None,
+            },
+        ];
+        let set_phases = |deps: DepsMut, phases| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("owner", &[]),
+                ExecuteMsg::SetMintPhases { phases },
+            )
+        };
 
-                STATE.update(deps.storage, |mut cs| -> StdResult<_> {
-                    cs.cw721_ica_extension_address = addr;
-                    Ok(cs)
-                })?;
+        // the price of a phase cannot be zero
+        phases[0].price = Some(coin(0, "ujuno"));
+        let err = set_phases(deps.as_mut(), phases.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMintPhases(_)));
+        phases[0].price = Some(coin(100, "ujuno"));
+        set_phases(deps.as_mut(), phases).unwrap();
 
-                Ok(Response::new())
+        let mint = |deps: DepsMut, env: Env, funds: &[Coin], proof: Option<Vec<HexBinary>>| {
+            execute(
+                deps,
+                env,
+                mock_info(HOLDER, funds),
+                ExecuteMsg::MintIca {
+                    salt: None,
+                    proof,
+                    gate_token_id: None,
+                    recipient: None,
+                    count: Some(2),
+                    chain: None,
+                    chan_init_options: None,
+                },
+            )
+        };
+        let proof = Some(vec![HexBinary::from(other_leaf.clone())]);
+
+        // the sender must be in the allowlist of the phase
+        let err = mint(deps.as_mut(), mock_env(), &[coin(200, "ujuno")], None).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMerkleProof));
+        let wrong_proof = Some(vec![HexBinary::from(holder_leaf)]);
+        let err = mint(
+            deps.as_mut(),
+            mock_env(),
+            &[coin(200, "ujuno")],
+            wrong_proof,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMerkleProof));
+
+        // and pay the price of each minted NFT, which is forwarded to the owner
+        let err = mint(
+            deps.as_mut(),
+            mock_env(),
+            &[coin(100, "ujuno")],
+            proof.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IncorrectPayment { .. }));
+        let res = mint(
+            deps.as_mut(),
+            mock_env(),
+            &[coin(200, "ujuno")],
+            proof.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            bank_sends(&res),
+            vec![("owner".to_string(), vec![coin(200, "ujuno")])]
+        );
+
+        // the phase limits the mints of each address
+        let err = mint(deps.as_mut(), mock_env(), &[coin(200, "ujuno")], proof).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::PhaseLimitReached {
+                phase_id: 0,
+                limit: 2,
+                ..
             }
-            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
-        }
+        ));
+
+        // the next phase is free and open to anyone
+        let mut env = mock_env();
+        env.block.time = now.plus_seconds(150);
+        let err = mint(deps.as_mut(), env.clone(), &[coin(200, "ujuno")], None).unwrap_err();
+        assert!(matches!(err, ContractError::IncorrectPayment { .. }));
+        let res = mint(deps.as_mut(), env.clone(), &[], None).unwrap();
+        assert!(bank_sends(&res).is_empty());
+
+        // no phase is active after the last one
+        env.block.time = now.plus_seconds(250);
+        let err = mint(deps.as_mut(), env, &[], None).unwrap_err();
+        assert!(matches!(err, ContractError::NoActiveMintPhase));
     }
 
-    pub fn cw_ica_controller_instantiate(deps: DepsMut, msg: Reply) -> StdResult<Response> {
-        match msg.result {
-            SubMsgResult::Ok(reply) => {
-                let event = reply
-                    .events
-                    .iter()
-                    .find(|e| {
-                        e.ty == "instantiate"
-                            || e.ty == "cosmwasm.wasm.v1.EventContractInstantiated"
-                    })
-                    .ok_or_else(|| StdError::generic_err("instantiate event not found"))?;
-                let maybe_address = &event
-                    .attributes
-                    .iter()
-                    .find(|a| a.key == "_contract_address" || a.key == "contract_address")
-                    .ok_or_else(|| StdError::generic_err("contract_address attribute not found"))?
-                    .value;
+    #[test]
+    fn test_pause() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        set_owner(deps.as_mut(), "owner");
+        let pause_msg = |operations| ExecuteMsg::Pause { operations };
 
-                // added this to remove the quotes from the address in injective
-                let addr = deps.api.addr_validate(
-                    maybe_address
-                        .chars()
-                        .filter(|c| c.is_alphanumeric())
-                        .collect::<String>()
-                        .as_str(),
-                )?;
+        // only the owner or the guardian can pause
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            pause_msg(vec![PausableOperation::IcaExecution]),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::OwnershipError(OwnershipError::NotOwner)
+        ));
 
-                REGISTERED_ICA_ADDRS.insert(deps.storage, &addr)?;
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetGuardian {
+                guardian: Some("guardian".to_string()),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            pause_msg(vec![
+                PausableOperation::Minting,
+                PausableOperation::IcaExecution,
+            ]),
+        )
+        .unwrap();
 
-                // Save res.contract_address
-                Ok(Response::new())
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            mint_ica_msg(1),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Paused {
+                operation: PausableOperation::Minting
             }
-            SubMsgResult::Err(err) => Err(StdError::generic_err(err)),
+        ));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(None),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Paused {
+                operation: PausableOperation::IcaExecution
+            }
+        ));
+
+        // only the owner can unpause
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian", &[]),
+            ExecuteMsg::Unpause {
+                operations: vec![PausableOperation::IcaExecution],
+            },
+        )
+        .unwrap_err();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::Unpause {
+                operations: vec![PausableOperation::IcaExecution],
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            execute_ica_msg(None),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_transaction_history_paging() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        let count = keys::MAX_HISTORY_SCAN as u64 + 2;
+        for height in 0..count {
+            let mut record = TransactionRecord::from_ica_msg(
+                &from_json(br#"{"send_cosmos_msgs":{"messages":[]}}"#).unwrap(),
+                TOKEN_ID,
+                keys::DEFAULT_CHAIN_ALIAS,
+                HOLDER,
+                height,
+                height,
+            )
+            .unwrap();
+            record.status = if height % 2 == 0 {
+                TransactionStatus::Completed
+            } else {
+                TransactionStatus::Failed
+            };
+            history::push(deps.as_mut().storage, &record).unwrap();
         }
+        let history_page = |deps: Deps, start_after: Option<HistoryCursor>, status| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::GetTransactionHistory {
+                    token_id: TOKEN_ID.to_string(),
+                    start_after,
+                    limit: Some(2),
+                    order: Some(HistoryOrder::Ascending),
+                    filter: Some(HistoryFilter {
+                        status: Some(status),
+                        ..Default::default()
+                    }),
+                },
+            )
+            .unwrap();
+            from_json::<GetTransactionHistoryResponse>(&res).unwrap()
+        };
+        let sequences = |page: &GetTransactionHistoryResponse| {
+            page.records
+                .iter()
+                .map(|record| record.sequence)
+                .collect::<Vec<_>>()
+        };
+
+        // the pages only contain the matching records
+        let page = history_page(deps.as_ref(), None, TransactionStatus::Failed);
+        assert_eq!(sequences(&page), vec![1, 3]);
+        assert_eq!(page.next_cursor, Some(HistoryCursor::Sequence(3)));
+        assert_eq!(page.total, count as u32);
+        let page = history_page(deps.as_ref(), page.next_cursor, TransactionStatus::Failed);
+        assert_eq!(sequences(&page), vec![5, 7]);
+
+        // the scanned records are capped, and the next page starts after the last scanned one
+        let page = history_page(deps.as_ref(), None, TransactionStatus::Timeout);
+        assert!(page.records.is_empty());
+        let last_scanned = keys::MAX_HISTORY_SCAN as u64 - 1;
+        assert_eq!(
+            page.next_cursor,
+            Some(HistoryCursor::Sequence(last_scanned))
+        );
+        let page = history_page(deps.as_ref(), page.next_cursor, TransactionStatus::Timeout);
+        assert!(page.records.is_empty());
+        assert_eq!(page.next_cursor, None);
+
+        // the total is the number of stored records
+        history::prune(deps.as_mut().storage, TOKEN_ID, 10).unwrap();
+        let page = history_page(deps.as_ref(), None, TransactionStatus::Failed);
+        assert_eq!(sequences(&page), vec![11, 13]);
+        assert_eq!(page.total, count as u32 - 10);
     }
-}
 
-#[cfg(test)]
-mod tests {}
+    #[test]
+    fn test_transaction_payloads() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        set_owner(deps.as_mut(), "owner");
+        let msg_size = to_json_binary(&IcaControllerExecuteMsg::SendCosmosMsgs {
+            messages: vec![],
+            packet_memo: None,
+            timeout_seconds: None,
+        })
+        .unwrap()
+        .len() as u64;
+        let set_size = |deps: DepsMut, size| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("owner", &[]),
+                ExecuteMsg::SetMaxPayloadSize { size },
+            )
+            .unwrap();
+        };
+        let send = |deps: DepsMut| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(HOLDER, &[]),
+                execute_ica_msg(None),
+            )
+            .unwrap();
+        };
+        let get_transaction = |deps: Deps, sequence| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::GetTransaction {
+                    token_id: TOKEN_ID.to_string(),
+                    sequence,
+                },
+            )
+            .unwrap();
+            from_json::<GetTransactionResponse>(&res).unwrap()
+        };
+
+        // payloads are not stored by default
+        send(deps.as_mut());
+        assert_eq!(get_transaction(deps.as_ref(), 0).payload, None);
+
+        // the message is stored only if it fits the size limit
+        set_size(deps.as_mut(), Some(msg_size));
+        send(deps.as_mut());
+        let payload = get_transaction(deps.as_ref(), 1).payload.unwrap();
+        assert!(payload.msg.is_some());
+        assert!(!payload.truncated);
+        set_size(deps.as_mut(), Some(msg_size - 1));
+        send(deps.as_mut());
+        let payload = get_transaction(deps.as_ref(), 2).payload.unwrap();
+        assert_eq!(payload.msg, None);
+        assert!(payload.truncated);
+
+        // so are the acknowledgement results and errors
+        let long_error = "e".repeat(msg_size as usize);
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error(long_error)),
+        )
+        .unwrap();
+        let payload = get_transaction(deps.as_ref(), 0).payload.unwrap();
+        assert_eq!(payload.ack, None);
+        assert!(payload.truncated);
+        execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Failed,
+            Some(AckPayload::Error("out of gas".to_string())),
+        )
+        .unwrap();
+        let payload = get_transaction(deps.as_ref(), 1).payload.unwrap();
+        assert_eq!(
+            payload.ack,
+            Some(AckPayload::Error("out of gas".to_string()))
+        );
+        assert!(payload.msg.is_some());
+        assert!(!payload.truncated);
+    }
+
+    #[test]
+    fn test_msg_categories() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        set_owner(deps.as_mut(), "owner");
+        const SWAP: &str = "/osmosis.gamm.v1beta1.MsgSwapExactAmountIn";
+        let update_msg = ExecuteMsg::UpdateMsgCategories {
+            add: vec![
+                MsgCategory {
+                    type_url: SWAP.to_string(),
+                    category: "swap".to_string(),
+                },
+                MsgCategory {
+                    type_url: "/osmosis.poolmanager.v1beta1.MsgSwapExactAmountIn".to_string(),
+                    category: "swap".to_string(),
+                },
+            ],
+            remove: vec![],
+        };
+        let swap_msg = || CosmosMsg::Stargate {
+            type_url: SWAP.to_string(),
+            value: Binary::default(),
+        };
+        let send_msg = |messages| ExecuteMsg::ExecuteIcaMsg {
+            token_id: TOKEN_ID.to_string(),
+            chain: None,
+            msg: IcaControllerExecuteMsg::SendCosmosMsgs {
+                messages,
+                packet_memo: None,
+                timeout_seconds: None,
+            },
+            packet_fee: None,
+        };
+        let category_history = |deps: Deps, category: &str| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::GetTransactionHistory {
+                    token_id: TOKEN_ID.to_string(),
+                    start_after: None,
+                    limit: None,
+                    order: None,
+                    filter: Some(HistoryFilter {
+                        category: Some(category.to_string()),
+                        ..Default::default()
+                    }),
+                },
+            )
+            .unwrap();
+            from_json::<GetTransactionHistoryResponse>(&res)
+                .unwrap()
+                .records
+                .into_iter()
+                .map(|record| record.sequence)
+                .collect::<Vec<_>>()
+        };
+
+        // only the owner can set the categories
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            update_msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::OwnershipError(OwnershipError::NotOwner)
+        ));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            update_msg,
+        )
+        .unwrap();
+
+        // the records keep every type URL, and each category once
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            send_msg(vec![
+                swap_msg(),
+                BankMsg::Send {
+                    to_address: "cosmos1recipient".to_string(),
+                    amount: vec![coin(1, "uosmo")],
+                }
+                .into(),
+                swap_msg(),
+            ]),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 0))
+            .unwrap();
+        assert_eq!(
+            record.type_urls,
+            vec![SWAP, type_url::MSG_SEND_TYPE_URL, SWAP]
+        );
+        assert_eq!(record.categories, vec!["swap".to_string()]);
+
+        // the categories are set when the transaction is sent
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::UpdateMsgCategories {
+                add: vec![],
+                remove: vec![SWAP.to_string()],
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            send_msg(vec![swap_msg()]),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, 1))
+            .unwrap();
+        assert!(record.categories.is_empty());
+        assert_eq!(category_history(deps.as_ref(), "swap"), vec![0]);
+    }
+
+    #[test]
+    fn test_activity_feeds() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        const OTHER_TOKEN_ID: &str = "ica-token-1";
+        for (height, (token_id, executor)) in [
+            (TOKEN_ID, HOLDER),
+            (OTHER_TOKEN_ID, "other"),
+            (TOKEN_ID, "other"),
+            (OTHER_TOKEN_ID, HOLDER),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut record = TransactionRecord::from_ica_msg(
+                &from_json(br#"{"send_cosmos_msgs":{"messages":[]}}"#).unwrap(),
+                token_id,
+                keys::DEFAULT_CHAIN_ALIAS,
+                HOLDER,
+                height as u64,
+                height as u64,
+            )
+            .unwrap();
+            record.status = TransactionStatus::Completed;
+            record.executor = Some(executor.to_string());
+            history::push(deps.as_mut().storage, &record).unwrap();
+        }
+        let feed_page = |deps: Deps, address: Option<&str>, start_after, order| {
+            let limit = Some(3);
+            let msg = match address {
+                Some(address) => QueryMsg::GetActivityByAddress {
+                    address: address.to_string(),
+                    start_after,
+                    limit,
+                    order,
+                },
+                None => QueryMsg::GetActivityFeed {
+                    start_after,
+                    limit,
+                    order,
+                },
+            };
+            from_json::<ActivityFeedResponse>(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let keys = |page: &ActivityFeedResponse| {
+            page.records
+                .iter()
+                .map(|record| (record.token_id.clone(), record.sequence))
+                .collect::<Vec<_>>()
+        };
+
+        // the collection feed is paginated, most recent records first
+        let page = feed_page(deps.as_ref(), None, None, None);
+        assert_eq!(
+            keys(&page),
+            vec![
+                (OTHER_TOKEN_ID.to_string(), 1),
+                (TOKEN_ID.to_string(), 1),
+                (OTHER_TOKEN_ID.to_string(), 0),
+            ]
+        );
+        assert_eq!(page.next_cursor, Some(1));
+        let page = feed_page(deps.as_ref(), None, page.next_cursor, None);
+        assert_eq!(keys(&page), vec![(TOKEN_ID.to_string(), 0)]);
+        assert_eq!(page.next_cursor, None);
+
+        // the address feed only contains the records sent by the address, across tokens
+        let page = feed_page(
+            deps.as_ref(),
+            Some(HOLDER),
+            None,
+            Some(HistoryOrder::Ascending),
+        );
+        assert_eq!(
+            keys(&page),
+            vec![(TOKEN_ID.to_string(), 0), (OTHER_TOKEN_ID.to_string(), 1)]
+        );
+        assert_eq!(page.next_cursor, None);
+
+        // the pruned records are removed from the feeds
+        history::prune(deps.as_mut().storage, TOKEN_ID, 1).unwrap();
+        let page = feed_page(
+            deps.as_ref(),
+            Some(HOLDER),
+            None,
+            Some(HistoryOrder::Ascending),
+        );
+        assert_eq!(keys(&page), vec![(OTHER_TOKEN_ID.to_string(), 1)]);
+        let page = feed_page(deps.as_ref(), None, None, Some(HistoryOrder::Ascending));
+        assert_eq!(
+            keys(&page),
+            vec![
+                (OTHER_TOKEN_ID.to_string(), 0),
+                (TOKEN_ID.to_string(), 1),
+                (OTHER_TOKEN_ID.to_string(), 1),
+            ]
+        );
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn test_ica_tx_events() {
+        let mut deps = mock_dependencies();
+        setup_ica(&mut deps);
+        let send = |deps: DepsMut| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(HOLDER, &[]),
+                execute_ica_msg(None),
+            )
+            .unwrap()
+        };
+        let attributes = |res: &Response, ty: &str| {
+            res.events
+                .iter()
+                .find(|event| event.ty == ty)
+                .unwrap()
+                .attributes
+                .iter()
+                .map(|attr| (attr.key.clone(), attr.value.clone()))
+                .collect::<Vec<_>>()
+        };
+        let ica_attributes = |sequence: Option<&str>| {
+            [
+                ("token_id", Some(TOKEN_ID)),
+                ("chain", Some(keys::DEFAULT_CHAIN_ALIAS)),
+                ("controller", Some(CONTROLLER)),
+                ("ica_address", Some("cosmos1ica")),
+                ("channel_id", Some("channel-0")),
+                ("sequence", sequence),
+                ("owner", Some(HOLDER)),
+            ]
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key.to_string(), value.to_string())))
+            .collect::<Vec<_>>()
+        };
+        let with = |mut attributes: Vec<(String, String)>, key: &str, value: &str| {
+            attributes.push((key.to_string(), value.to_string()));
+            attributes
+        };
+
+        let res = send(deps.as_mut());
+        assert_eq!(
+            attributes(&res, events::ICA_TX_SENT),
+            with(ica_attributes(Some("0")), "executor", HOLDER)
+        );
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        assert_eq!(
+            attributes(&res, events::ICA_TX_ACKED),
+            with(ica_attributes(Some("0")), "status", "completed")
+        );
+
+        // a timeout also closes the channel
+        send(deps.as_mut());
+        let res =
+            execute::packet_result(deps.as_mut(), CONTROLLER, TransactionStatus::Timeout, None)
+                .unwrap();
+        assert_eq!(
+            attributes(&res, events::ICA_TX_TIMEOUT),
+            ica_attributes(Some("1"))
+        );
+        assert_eq!(
+            attributes(&res, events::CHANNEL_CLOSED),
+            ica_attributes(None)
+        );
+    }
+
+    #[test]
+    fn test_subscribers() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        set_owner(deps.as_mut(), "owner");
+        let sequence = push_pending_tx(deps.as_mut());
+        let subscribers = (0..MAX_SUBSCRIBERS)
+            .map(|i| format!("subscriber-{i}"))
+            .collect::<Vec<_>>();
+        let update_msg = |add: &[String], remove: &[String]| ExecuteMsg::UpdateSubscribers {
+            add: add.to_vec(),
+            remove: remove.to_vec(),
+        };
+
+        // only the owner can manage the subscribers
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            update_msg(&subscribers, &[]),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::OwnershipError(OwnershipError::NotOwner)
+        ));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            update_msg(&subscribers, &[]),
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetSubscribers {}).unwrap();
+        assert_eq!(from_json::<Vec<String>>(&res).unwrap(), subscribers);
+
+        // every subscriber receives a gas limited hook, replied to only on error
+        let res = execute::packet_result(
+            deps.as_mut(),
+            CONTROLLER,
+            TransactionStatus::Completed,
+            Some(AckPayload::Result(Binary::default())),
+        )
+        .unwrap();
+        let record = TX_HISTORY
+            .load(deps.as_ref().storage, (TOKEN_ID, sequence))
+            .unwrap();
+        let hook = to_json_binary(&SubscriberExecuteMsg::NftIcaHook(
+            NftIcaHookMsg::TransactionCompleted {
+                token_id: TOKEN_ID.to_string(),
+                record,
+            },
+        ))
+        .unwrap();
+        let hooks = res
+            .messages
+            .iter()
+            .filter(|msg| msg.id == SUBSCRIBER_HOOK_REPLY_ID)
+            .collect::<Vec<_>>();
+        assert_eq!(hooks.len(), subscribers.len());
+        for (msg, subscriber) in hooks.into_iter().zip(&subscribers) {
+            assert_eq!(msg.reply_on, ReplyOn::Error);
+            assert_eq!(msg.gas_limit, Some(SUBSCRIBER_HOOK_GAS_LIMIT));
+            assert_eq!(
+                msg.msg,
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: subscriber.clone(),
+                    msg: hook.clone(),
+                    funds: vec![],
+                })
+            );
+        }
+
+        // the error of a subscriber is ignored
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: SUBSCRIBER_HOOK_REPLY_ID,
+                result: SubMsgResult::Err("out of gas".to_string()),
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        // the number of subscribers is capped
+        let replacement = vec!["subscriber-new".to_string()];
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            update_msg(&replacement, &subscribers[..1]),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            update_msg(&subscribers[..1], &[]),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::TooManySubscribers {
+                max: MAX_SUBSCRIBERS
+            }
+        ));
+    }
+
+    #[test]
+    fn test_prune_history() {
+        let mut deps = mock_dependencies();
+        setup(deps.as_mut());
+        set_owner(deps.as_mut(), "owner");
+        let completed = push_pending_tx(deps.as_mut());
+        history::update_status(
+            deps.as_mut().storage,
+            TOKEN_ID,
+            completed,
+            TransactionStatus::Completed,
+        )
+        .unwrap();
+        let pending = push_pending_tx(deps.as_mut());
+        let prune_msg = ExecuteMsg::PruneHistory {
+            token_id: TOKEN_ID.to_string(),
+            before: pending + 1,
+        };
+
+        // the holder cannot erase the history of the token
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(HOLDER, &[]),
+            prune_msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::OwnershipError(OwnershipError::NotOwner)
+        ));
+
+        // the pending record is kept
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            prune_msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.events
+                .iter()
+                .filter(|event| event.ty == events::ICA_TX_PRUNED)
+                .count(),
+            1
+        );
+        assert!(!TX_HISTORY.has(deps.as_ref().storage, (TOKEN_ID, completed)));
+        assert!(TX_HISTORY.has(deps.as_ref().storage, (TOKEN_ID, pending)));
+    }
+}
@@ -1,7 +1,9 @@
-use cosmwasm_std::{Instantiate2AddressError, StdError};
+use cosmwasm_std::{Instantiate2AddressError, StdError, Timestamp};
 use cw_ownable::OwnershipError;
 use thiserror::Error;
 
+use super::state::pause::PausableOperation;
+
 /// ContractError is the error type returned by contract's functions.
 #[allow(missing_docs)]
 #[derive(Error, Debug)]
@@ -18,6 +20,9 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    #[error("The code ID of the cw721-ica-extension contract is required to migrate from version {version}")]
+    MissingCw721IcaExtensionCodeId { version: String },
+
     #[error("Queue empty")]
     QueueEmpty,
 
@@ -26,4 +31,107 @@ pub enum ContractError {
 
     #[error("Channel state not found")]
     ChannelStateNotFound,
+
+    #[error("Maximum supply of {max_supply} reached")]
+    MaxSupplyReached { max_supply: u64 },
+
+    #[error("Address {address} reached the maximum of {limit} mints per address")]
+    MaxPerAddressReached { address: String, limit: u64 },
+
+    #[error("Invalid mint phases: {0}")]
+    InvalidMintPhases(String),
+
+    #[error("No active mint phase")]
+    NoActiveMintPhase,
+
+    #[error("Invalid merkle proof")]
+    InvalidMerkleProof,
+
+    #[error("Address {address} reached the maximum of {limit} mints in phase {phase_id}")]
+    PhaseLimitReached {
+        address: String,
+        phase_id: u32,
+        limit: u64,
+    },
+
+    #[error("Incorrect payment, expected {expected}")]
+    IncorrectPayment { expected: String },
+
+    #[error("A gate token is required to mint")]
+    GateTokenRequired,
+
+    #[error("Gate token {token_id} was already used to mint")]
+    GateTokenAlreadyUsed { token_id: String },
+
+    #[error("Invalid mint count: {0}")]
+    InvalidMintCount(String),
+
+    #[error("Connection {connection_id} is not allowed")]
+    ConnectionNotAllowed { connection_id: String },
+
+    #[error("Connection {connection_id} is not allowed with counterparty connection {counterparty_connection_id}")]
+    CounterpartyConnectionNotAllowed {
+        connection_id: String,
+        counterparty_connection_id: String,
+    },
+
+    #[error("Counterparty port {port_id} is not allowed")]
+    CounterpartyPortNotAllowed { port_id: String },
+
+    #[error("Chain {chain} not found")]
+    ChainNotFound { chain: String },
+
+    #[error("Chain alias {0} is reserved")]
+    ReservedChainAlias(String),
+
+    #[error("Token {token_id} already has an ICA on chain {chain}")]
+    IcaAlreadyExists { token_id: String, chain: String },
+
+    #[error("Message not supported by the native ICA controller backend: {0}")]
+    UnsupportedIcaMsg(String),
+
+    #[error("Insufficient funds in the fee pool")]
+    InsufficientFeePool,
+
+    #[error("At least one validator is required")]
+    NoValidators,
+
+    #[error("Vote weights must add up to one")]
+    InvalidVoteWeights,
+
+    #[error("Compound frequency must be at least {min} seconds")]
+    InvalidCompoundFrequency { min: u64 },
+
+    #[error("Auto-compound is not enabled for this token")]
+    AutoCompoundNotEnabled,
+
+    #[error("Token cannot be compounded before {next_compound}")]
+    CompoundTooSoon { next_compound: Timestamp },
+
+    #[error("A compound of this token is in progress")]
+    CompoundInProgress,
+
+    #[error("Operation {operation} is paused")]
+    Paused { operation: PausableOperation },
+
+    #[error("The chain does not send the callbacks of the ICS-20 transfers")]
+    TransferCallbacksDisabled,
+
+    #[error("Timeout must be between 1 and {max} seconds")]
+    InvalidTimeout { max: u64 },
+
+    #[error("Cannot have more than {max} subscribers")]
+    TooManySubscribers { max: u64 },
+
+    #[error("The chain does not run ibc-hooks")]
+    IbcHooksDisabled,
+
+    #[error("ICA address of token {token_id} on chain {chain} not found")]
+    IcaAddressNotFound { token_id: String, chain: String },
+
+    #[error("Withdrawal {sequence} of token {token_id} not found")]
+    WithdrawalNotFound { token_id: String, sequence: u64 },
+
+    #[error("Withdrawal cannot be expired before it is in transit and {timeout}")]
+    WithdrawalNotExpired { timeout: Timestamp },
 }
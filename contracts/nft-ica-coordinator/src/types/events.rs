@@ -0,0 +1,127 @@
+//! This module defines the events emitted by the contract.
+//!
+//! The ICA events carry the same attributes, omitted when they are not known yet,
+//! so that indexers can follow the state of the ICAs without querying the contract.
+
+use cosmwasm_std::Event;
+
+/// The event emitted for each NFT whose mint is requested.
+pub const NFT_ICA_MINT_REQUESTED: &str = "nft_ica_mint_requested";
+/// The event emitted when an NFT is minted after its ICA channel is opened.
+pub const NFT_ICA_MINTED: &str = "nft_ica_minted";
+/// The event emitted when a transaction is sent to an ICA.
+pub const ICA_TX_SENT: &str = "ica_tx_sent";
+/// The event emitted when the packet of an ICA transaction is acknowledged.
+pub const ICA_TX_ACKED: &str = "ica_tx_acked";
+/// The event emitted when the packet of an ICA transaction times out.
+pub const ICA_TX_TIMEOUT: &str = "ica_tx_timeout";
+/// The event emitted when the transfer of a withdrawal from an ICA is received or expires.
+pub const ICA_WITHDRAWAL_RESOLVED: &str = "ica_withdrawal_resolved";
+/// The event emitted when the channel of an ICA is opened or reopened.
+pub const CHANNEL_OPENED: &str = "channel_opened";
+/// The event emitted when the channel of an ICA is closed.
+pub const CHANNEL_CLOSED: &str = "channel_closed";
+/// The event emitted when an NFT is transferred through the cw721-ica-extension contract.
+pub const NFT_ICA_TRANSFERRED: &str = "nft_ica_transferred";
+/// The event emitted for each transaction record removed from the history.
+pub const ICA_TX_PRUNED: &str = "ica_tx_pruned";
+
+/// The event emitted when operations are paused.
+pub const PAUSED: &str = "paused";
+/// The event emitted when operations are unpaused.
+pub const UNPAUSED: &str = "unpaused";
+
+/// IcaEvent builds an event with the common attributes of the ICA events.
+pub struct IcaEvent {
+    ty: &'static str,
+    token_id: String,
+    chain: Option<String>,
+    controller: Option<String>,
+    ica_address: Option<String>,
+    channel_id: Option<String>,
+    sequence: Option<u64>,
+    owner: Option<String>,
+    extra: Vec<(&'static str, String)>,
+}
+
+impl IcaEvent {
+    /// Creates a new event of the given type for the given token.
+    pub fn new(ty: &'static str, token_id: impl Into<String>) -> Self {
+        Self {
+            ty,
+            token_id: token_id.into(),
+            chain: None,
+            controller: None,
+            ica_address: None,
+            channel_id: None,
+            sequence: None,
+            owner: None,
+            extra: vec![],
+        }
+    }
+
+    /// Sets the alias of the chain of the ICA.
+    pub fn chain(mut self, chain: impl Into<String>) -> Self {
+        self.chain = Some(chain.into());
+        self
+    }
+
+    /// Sets the ICA controller, which is the cw-ica-controller address or the native owner string.
+    pub fn controller(mut self, controller: impl Into<String>) -> Self {
+        self.controller = Some(controller.into());
+        self
+    }
+
+    /// Sets the address of the ICA on the host chain.
+    pub fn ica_address(mut self, ica_address: Option<impl Into<String>>) -> Self {
+        self.ica_address = ica_address.map(Into::into);
+        self
+    }
+
+    /// Sets the channel ID of the ICA.
+    pub fn channel_id(mut self, channel_id: Option<impl Into<String>>) -> Self {
+        self.channel_id = channel_id.map(Into::into);
+        self
+    }
+
+    /// Sets the sequence of the transaction record.
+    pub fn sequence(mut self, sequence: u64) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Sets the owner of the NFT.
+    pub fn owner(mut self, owner: Option<impl Into<String>>) -> Self {
+        self.owner = owner.map(Into::into);
+        self
+    }
+
+    /// Adds an attribute specific to the type of the event.
+    pub fn attribute(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.extra.push((key, value.into()));
+        self
+    }
+}
+
+impl From<IcaEvent> for Event {
+    fn from(event: IcaEvent) -> Self {
+        let attributes = [
+            ("chain", event.chain),
+            ("controller", event.controller),
+            ("ica_address", event.ica_address),
+            ("channel_id", event.channel_id),
+            (
+                "sequence",
+                event.sequence.map(|sequence| sequence.to_string()),
+            ),
+            ("owner", event.owner),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key, value)));
+
+        Event::new(event.ty)
+            .add_attribute("token_id", event.token_id)
+            .add_attributes(attributes)
+            .add_attributes(event.extra)
+    }
+}
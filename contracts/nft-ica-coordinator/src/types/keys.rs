@@ -8,11 +8,67 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// The prefix used for the NFT-ICA token.
 pub const TOKEN_PREFIX: &str = "ica-token";
 
+/// The chain alias reserved for the host chain of the default channel open init options.
+pub const DEFAULT_CHAIN_ALIAS: &str = "default";
+
 /// The prefix used by `x/wasm` for IBC ports.
 pub const WASM_IBC_PORT_PREFIX: &str = "wasm.";
 
+/// The port ID of the ICS-20 transfer module.
+pub const ICS20_PORT_ID: &str = "transfer";
+
+/// The default timeout in seconds of the ICS-20 transfers sent to the ICAs.
+pub const DEFAULT_TRANSFER_TIMEOUT_SECONDS: u64 = 600;
+
+/// The maximum timeout in seconds of the ICS-20 transfers sent to and from the ICAs.
+pub const MAX_TRANSFER_TIMEOUT_SECONDS: u64 = 7 * 24 * 3600;
+
+/// The prefix hashed by ibc-hooks to derive the sender of the contract executions of the
+/// received transfers.
+pub const IBC_HOOKS_SENDER_PREFIX: &str = "ibc-wasm-hook-intermediary";
+
+/// The port ID of the ICA host module.
+pub const ICA_HOST_PORT_ID: &str = "icahost";
+
+/// The prefix used by the native ICA controller module for IBC ports.
+pub const ICA_CONTROLLER_PORT_PREFIX: &str = "icacontroller-";
+
+/// The default timeout in seconds of the packets sent by the native ICA controller backend.
+/// This is the same as the default timeout of the cw-ica-controller contract.
+pub const DEFAULT_ICA_TIMEOUT_SECONDS: u64 = 600;
+
+/// The maximum number of ICAs that can be minted by a single message.
+pub const MAX_MINT_BATCH: u64 = 20;
+
+/// The maximum number of transaction records scanned by a page of the transaction history,
+/// which bounds the gas used by filtered queries.
+pub const MAX_HISTORY_SCAN: usize = 300;
+
+/// The minimum number of seconds between two compounds of the same token.
+pub const MIN_COMPOUND_FREQUENCY_SECONDS: u64 = 3600;
+
 /// The reply ID used when instantiating the cw721-ica-extension contract.
 pub const CW721_INSTANTIATE_REPLY_ID: u64 = 1;
 
 /// The reply ID used when instantiating the cw-ica-controller contract.
 pub const CW_ICA_CONTROLLER_INSTANTIATE_REPLY_ID: u64 = 2;
+
+/// The reply ID used when sending an ICS-20 transfer to an ICA.
+pub const FUND_ICA_REPLY_ID: u64 = 3;
+
+/// The reply ID used when forwarding a callback to the callback receiver of a token.
+/// The reply is only received if the receiver fails.
+pub const CALLBACK_RECEIVER_REPLY_ID: u64 = 4;
+
+/// The gas limit of each callback forwarded to a callback receiver.
+pub const CALLBACK_RECEIVER_GAS_LIMIT: u64 = 500_000;
+
+/// The reply ID used when sending a hook to a subscriber.
+/// The reply is only received if the subscriber fails.
+pub const SUBSCRIBER_HOOK_REPLY_ID: u64 = 5;
+
+/// The gas limit of each hook sent to a subscriber.
+pub const SUBSCRIBER_HOOK_GAS_LIMIT: u64 = 500_000;
+
+/// The maximum number of subscribers, which bounds the gas used by the hooks of a message.
+pub const MAX_SUBSCRIBERS: u64 = 10;
@@ -1,6 +1,7 @@
 //! This module contains the types used by the contract's execution and state logic.
 
 mod error;
+pub mod events;
 pub mod keys;
 pub mod msg;
 pub mod state;
@@ -3,12 +3,24 @@
 //! This module defines the messages the ICA controller contract receives.
 
 use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Binary, Coin, HexBinary, VoteOption, WeightedVoteOption};
+use cw721_ica_extension::NftTransferHookMsg;
 use cw_ica_controller::types::{
     callbacks::IcaControllerCallbackMsg,
     msg::{options::ChannelOpenInitOptions, ExecuteMsg as IcaControllerExecuteMsg},
 };
 
-use super::state::channel::ChannelState;
+use super::state::{
+    backend::IcaBackend,
+    chain::{AllowedConnection, ChainConfig},
+    channel::ChannelState,
+    compound::{AutoCompound, AutoCompoundConfig, PendingCompound},
+    fees::PacketFee,
+    history::{HistoryCursor, HistoryFilter, HistoryOrder, MsgCategory, PendingWithdrawal},
+    hooks::CallbackReceiver,
+    pause::PausableOperation,
+    MintPhase,
+};
 
 /// This is the instantiation message for the contract.
 #[cw_serde]
@@ -23,34 +35,467 @@ pub struct InstantiateMsg {
     pub cw721_ica_extension_code_id: u64,
     /// The default channel open init options for interchain accounts.
     pub default_chan_init_options: ChannelOpenInitOptions,
+    /// The optional ICA backend used with the default channel open init options.
+    /// Defaults to [`IcaBackend::CwIcaController`].
+    #[serde(default)]
+    pub default_backend: Option<IcaBackend>,
     /// The optional salt used to generate the cw721 ICA extension
     /// contract address.
     #[serde(default)]
     pub salt: Option<String>,
+    /// The optional maximum number of NFTs that can be minted.
+    #[serde(default)]
+    pub max_supply: Option<u64>,
+    /// The optional maximum number of NFTs that can be minted for a single address.
+    #[serde(default)]
+    pub max_per_address: Option<u64>,
+    /// The optional maximum size in bytes of the message and acknowledgement payloads stored
+    /// with the transaction history. If not set, payloads are not stored.
+    #[serde(default)]
+    pub max_payload_size: Option<u64>,
+    /// The optional maximum number of transaction records stored for each ICA NFT.
+    /// If not set, the history is unbounded.
+    #[serde(default)]
+    pub max_history_per_token: Option<u64>,
+    /// Whether the chain sends the acknowledgements and timeouts of the ICS-20 transfers sent
+    /// by this contract to its sudo entry point. Required by [`ExecuteMsg::FundIca`].
+    #[serde(default)]
+    pub transfer_callbacks: bool,
+    /// Whether the chain runs the ibc-hooks middleware. Required by
+    /// [`ExecuteMsg::WithdrawFromIca`].
+    #[serde(default)]
+    pub ibc_hooks: bool,
+    /// The optional fee paid from the fee pool to register each ICA of the native backend.
+    #[serde(default)]
+    pub native_register_fee: Vec<Coin>,
+}
+
+/// This is the migration message for the contract.
+#[cw_serde]
+pub struct MigrateMsg {
+    /// The code ID of the cw721-ica-extension contract to migrate the collection to.
+    /// Required when migrating from a version before 0.2.0, whose collection does not send the
+    /// transfer hook to this contract.
+    #[serde(default)]
+    pub cw721_ica_extension_code_id: Option<u64>,
 }
 
 /// This is the execution message for the contract.
 #[cw_ownable::cw_ownable_execute]
 #[cw_serde]
 pub enum ExecuteMsg {
-    /// MintIca creates a new ICA for the caller.
-    /// The NFT is minted after a callback from the ICA controller contract.
+    /// MintIca creates new ICAs for the recipient, or the caller if no recipient is given.
+    /// Each NFT is minted after a callback from its ICA controller contract.
+    /// The reserved token IDs are returned as [`execute_responses::MintIcaResponse`] in the response data.
+    /// The sent funds must be exactly the price of the active mint phase, and no funds are
+    /// accepted if it has no price.
     MintIca {
         /// The optional salt used to generate the cw721 ICA extension
         /// contract address.
         #[serde(default)]
         salt: Option<String>,
+        /// The optional merkle proof of the sender's address. This is required if the
+        /// active mint phase has a merkle root.
+        #[serde(default)]
+        proof: Option<Vec<HexBinary>>,
+        /// The token ID of the partner collection NFT held by the sender. This is required
+        /// if the contract has a mint gate collection.
+        #[serde(default)]
+        gate_token_id: Option<String>,
+        /// The optional address that receives the minted NFTs. Defaults to the sender.
+        #[serde(default)]
+        recipient: Option<String>,
+        /// The optional number of ICAs to mint, at most 20. Defaults to one.
+        #[serde(default)]
+        count: Option<u64>,
+        /// The optional alias of the host chain to create the ICAs on.
+        /// Defaults to the chain of the default channel open init options.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The optional channel open init options used instead of the ones of the chain.
+        /// The connection must be the connection of the chain or one of the allowed connections,
+        /// paired with its counterparty connection, and the counterparty port must be the ICA
+        /// host port.
+        #[serde(default)]
+        chan_init_options: Option<ChannelOpenInitOptions>,
+    },
+    /// SetMintPhases replaces the mint phases of the collection. Only the owner can call this.
+    /// The phases must be ordered by their start time and must not overlap.
+    /// If no phases are set, minting is not restricted.
+    SetMintPhases {
+        /// The new mint phases.
+        phases: Vec<MintPhase>,
+    },
+    /// SetMintGate sets or removes the partner cw721 collection whose holders are allowed
+    /// to mint. Only the owner can call this.
+    SetMintGate {
+        /// The address of the partner cw721 collection. If not set, minting is not gated.
+        #[serde(default)]
+        collection: Option<String>,
+    },
+    /// SetMaxPayloadSize sets or removes the maximum size in bytes of the message and
+    /// acknowledgement payloads stored with the transaction history. If not set, payloads are
+    /// not stored. Only the owner can call this.
+    SetMaxPayloadSize {
+        /// The maximum payload size in bytes.
+        #[serde(default)]
+        size: Option<u64>,
+    },
+    /// SetMaxHistoryPerToken sets or removes the maximum number of transaction records stored
+    /// for each ICA NFT. The oldest records that are not pending are evicted when a new record
+    /// is added, and emitted as `ica_tx_pruned` events. Only the owner can call this.
+    SetMaxHistoryPerToken {
+        /// The maximum number of records per ICA NFT.
+        #[serde(default)]
+        max: Option<u64>,
+    },
+    /// SetTransferCallbacks sets whether the chain sends the acknowledgements and timeouts of
+    /// the ICS-20 transfers sent by this contract to its sudo entry point, which is required by
+    /// [`ExecuteMsg::FundIca`]. Only the owner can call this.
+    SetTransferCallbacks {
+        /// Whether the transfer callbacks are sent by the chain.
+        enabled: bool,
+    },
+    /// SetIbcHooks sets whether the chain runs the ibc-hooks middleware, which is required by
+    /// [`ExecuteMsg::WithdrawFromIca`]. Only the owner can call this.
+    SetIbcHooks {
+        /// Whether the chain runs ibc-hooks.
+        enabled: bool,
+    },
+    /// SetNativeRegisterFee sets the fee paid from the fee pool to register each ICA of the
+    /// native backend, as required by the interchaintxs module of Neutron. Only the owner can
+    /// call this.
+    SetNativeRegisterFee {
+        /// The registration fee.
+        fee: Vec<Coin>,
+    },
+    /// UpdateAllowedConnections adds and removes connection IDs that can be used to mint
+    /// ICAs besides the default connection. Each connection can only be used with its
+    /// counterparty connection. Only the owner can call this.
+    UpdateAllowedConnections {
+        /// The connections to allow.
+        #[serde(default)]
+        add: Vec<AllowedConnection>,
+        /// The connection IDs to disallow.
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// SetGuardian sets or removes the guardian, who can pause operations besides the owner.
+    /// Only the owner can call this.
+    SetGuardian {
+        /// The address of the guardian. If not set, the guardian is removed.
+        #[serde(default)]
+        guardian: Option<String>,
+    },
+    /// Pause pauses the given operations in an emergency. The ICA callbacks are still
+    /// processed while paused. Only the owner or the guardian can call this.
+    Pause {
+        /// The operations to pause.
+        operations: Vec<PausableOperation>,
+    },
+    /// Unpause resumes the given operations. Only the owner can call this.
+    Unpause {
+        /// The operations to resume.
+        operations: Vec<PausableOperation>,
+    },
+    /// UpdateSubscribers adds and removes the contracts receiving a
+    /// [`callbacks::SubscriberExecuteMsg`] on the mints, transfers, channel status changes and
+    /// completed transactions of all the ICA NFTs. The hooks are gas limited and their errors
+    /// are ignored, and the number of subscribers is capped. Only the owner can call this.
+    UpdateSubscribers {
+        /// The addresses of the contracts to subscribe.
+        #[serde(default)]
+        add: Vec<String>,
+        /// The addresses of the contracts to unsubscribe.
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// UpdateMsgCategories sets and removes the categories of message type URLs, which are used
+    /// to classify new transaction records. Only the owner can call this.
+    UpdateMsgCategories {
+        /// The categories to set.
+        #[serde(default)]
+        add: Vec<MsgCategory>,
+        /// The type URLs whose category is removed.
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// RegisterChain adds or updates a host chain in the chain registry. Only the owner can call this.
+    RegisterChain {
+        /// The alias of the chain, e.g. "cosmoshub".
+        alias: String,
+        /// The configuration of the chain.
+        config: ChainConfig,
+    },
+    /// RemoveChain removes a host chain from the chain registry. Only the owner can call this.
+    /// Existing ICAs on the chain are not affected.
+    RemoveChain {
+        /// The alias of the chain.
+        alias: String,
     },
     /// ReceiveIcaCallback is the message sent by the ICA controller contract
     /// on packet and channel lifecycle events.
     ReceiveIcaCallback(IcaControllerCallbackMsg),
+    /// NftTransferHook is the message sent by the cw721-ica-extension contract when an ICA NFT
+    /// is transferred.
+    NftTransferHook(NftTransferHookMsg),
+    /// AddIcaToToken creates an additional ICA on the given chain for an existing ICA NFT.
+    /// Only the owner of the NFT can call this, and each NFT can have one ICA per chain.
+    AddIcaToToken {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The alias of the chain to create the ICA on.
+        chain: String,
+    },
     /// ExecuteIcaMsg allows the owner of the ICA NFT to send a custom message.
     /// This is directly forwarded to the ICA controller contract after authorization.
+    ///
+    /// If the message sends a packet, an ICS-29 fee is escrowed for its relayers. The fee is
+    /// paid with the sent funds, which must match the fee exactly. If no fee is given or set
+    /// for the token, the sponsored fee of the chain is paid from the fee pool if it can cover it.
+    /// Channel handshakes cannot be incentivized by ICS-29, so no fee is paid for them.
     ExecuteIcaMsg {
         /// The token ID of the ICA NFT.
         token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
         /// The custom message to send to the ICA controller contract.
         msg: IcaControllerExecuteMsg,
+        /// The optional packet fee paid with the sent funds. Defaults to the packet fee of the token.
+        #[serde(default)]
+        packet_fee: Option<PacketFee>,
+    },
+    /// FundIca sends the attached funds to the ICA of the given ICA NFT with an ICS-20 transfer.
+    /// The transfer is recorded as a deposit in the transaction history of the NFT, and the
+    /// funds are refunded to the sender if it fails. Only available if the chain sends the
+    /// transfer callbacks, see [`ExecuteMsg::SetTransferCallbacks`]. Only the owner of the NFT
+    /// can call this.
+    FundIca {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The ICS-20 channel ID on this chain to the host chain of the ICA.
+        channel_id: String,
+        /// The optional timeout of the transfer in seconds, at most one week. Defaults to
+        /// 10 minutes.
+        #[serde(default)]
+        timeout: Option<u64>,
+    },
+    /// WithdrawFromIca sends funds from the ICA of the given ICA NFT back to this chain with an
+    /// ICS-20 transfer executed by the ICA. The transfer is received by this contract through
+    /// ibc-hooks and forwarded to the receiver. The transfer is recorded as a withdrawal in the
+    /// transaction history of the NFT, which is `in_transit` once the ICA executed it, until
+    /// [`ExecuteMsg::CompleteWithdrawal`] or [`ExecuteMsg::ExpireWithdrawal`]. Only available if
+    /// the chain runs ibc-hooks, see [`ExecuteMsg::SetIbcHooks`]. Only the owner of the NFT can
+    /// call this.
+    WithdrawFromIca {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The amount to withdraw, in the denom of the host chain.
+        amount: Coin,
+        /// The ICS-20 channel ID on the host chain to this chain.
+        source_channel: String,
+        /// The ICS-20 channel ID on this chain, the counterparty of the source channel.
+        destination_channel: String,
+        /// The optional address on this chain that receives the funds. Defaults to the sender.
+        #[serde(default)]
+        receiver: Option<String>,
+        /// The optional timeout of the transfer in seconds after the ICA transaction times out,
+        /// at most one week. Defaults to 10 minutes.
+        #[serde(default)]
+        timeout: Option<u64>,
+    },
+    /// CompleteWithdrawal is executed by ibc-hooks when the transfer of a withdrawal is received.
+    /// The received funds are forwarded to the receiver of the withdrawal, and its transaction
+    /// record is completed.
+    CompleteWithdrawal {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The sequence of the transaction record of the withdrawal.
+        sequence: u64,
+    },
+    /// ExpireWithdrawal sets the transaction record of a withdrawal whose transfer timed out
+    /// without being received to `timeout`. The funds are refunded to the ICA by the host chain.
+    /// Anyone can call this.
+    ExpireWithdrawal {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The sequence of the transaction record of the withdrawal.
+        sequence: u64,
+    },
+    /// IcaDelegate delegates tokens of the ICA of the given ICA NFT to a validator of the host chain.
+    /// Only the owner of the NFT can call this.
+    IcaDelegate {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The validator address on the host chain.
+        validator: String,
+        /// The amount to delegate.
+        amount: Coin,
+    },
+    /// IcaUndelegate undelegates tokens of the ICA of the given ICA NFT from a validator of the
+    /// host chain. Only the owner of the NFT can call this.
+    IcaUndelegate {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The validator address on the host chain.
+        validator: String,
+        /// The amount to undelegate.
+        amount: Coin,
+    },
+    /// IcaRedelegate moves a delegation of the ICA of the given ICA NFT to another validator of
+    /// the host chain. Only the owner of the NFT can call this.
+    IcaRedelegate {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The validator address to redelegate from.
+        src_validator: String,
+        /// The validator address to redelegate to.
+        dst_validator: String,
+        /// The amount to redelegate.
+        amount: Coin,
+    },
+    /// IcaClaimRewards withdraws the staking rewards of the ICA of the given ICA NFT from the
+    /// given validators in a single transaction. Only the owner of the NFT can call this.
+    IcaClaimRewards {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The validator addresses to withdraw the rewards from.
+        validators: Vec<String>,
+    },
+    /// IcaSetWithdrawAddress sets the address that receives the staking rewards of the ICA of
+    /// the given ICA NFT. Only the owner of the NFT can call this.
+    IcaSetWithdrawAddress {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The withdraw address on the host chain.
+        address: String,
+    },
+    /// IcaVote votes on a governance proposal of the host chain with the ICA of the given ICA NFT.
+    /// The vote is recorded in the transaction history of the NFT. Only the owner of the NFT can
+    /// call this.
+    IcaVote {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The ID of the proposal on the host chain.
+        proposal_id: u64,
+        /// The vote option.
+        option: VoteOption,
+    },
+    /// IcaVoteWeighted casts a weighted vote on a governance proposal of the host chain with the
+    /// ICA of the given ICA NFT. The weights must add up to one. The vote is recorded in the
+    /// transaction history of the NFT. Only the owner of the NFT can call this.
+    IcaVoteWeighted {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The ID of the proposal on the host chain.
+        proposal_id: u64,
+        /// The weighted vote options.
+        votes: Vec<WeightedVoteOption>,
+    },
+    /// SetAutoCompound enables, updates or disables (if `config` is `None`) auto-compounding of
+    /// the staking rewards of the ICA of the given ICA NFT. The attached funds are added to the
+    /// keeper bounty balance of the NFT, and the remaining balance is refunded to the sender when
+    /// auto-compounding is disabled or the NFT is transferred. Only the owner of the NFT can call
+    /// this.
+    SetAutoCompound {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The auto-compound configuration.
+        config: Option<AutoCompoundConfig>,
+    },
+    /// Compound withdraws the staking rewards of the ICA of the given ICA NFT, as configured with
+    /// [`ExecuteMsg::SetAutoCompound`]. Once the withdrawal is acknowledged, the next Compound
+    /// delegates the withdrawn rewards if they reach the minimum amount. Anyone can call this once
+    /// the configured frequency has elapsed, and the sender of the delegation receives the
+    /// configured bounty once it is acknowledged.
+    ///
+    /// Compounding takes two calls instead of a single transaction withdrawing and delegating the
+    /// rewards, since the withdrawn amount is only known from the acknowledgement of the
+    /// withdrawal. Neither step pays a packet fee: the packet fee of the NFT and the sponsored fee
+    /// of the chain are not used, and no funds can be sent.
+    Compound {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+    /// PruneHistory removes the transaction records of the given ICA NFT that are not pending and
+    /// whose sequence is lower than `before`. Each removed record is emitted as an
+    /// `ica_tx_pruned` event. Only the owner of the contract can call this.
+    PruneHistory {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The sequence before which the records are removed.
+        before: u64,
+    },
+    /// SetCallbackReceiver sets or removes the contract that receives a
+    /// [`callbacks::ReceiverExecuteMsg`] when an ICA transaction of the given ICA NFT is
+    /// acknowledged or times out. Errors of the receiver are ignored. The receiver is only
+    /// notified of the transactions sent while the sender owns the NFT. Only the owner of the
+    /// NFT can call this.
+    SetCallbackReceiver {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The address of the receiver contract. If not set, the receiver is removed.
+        #[serde(default)]
+        addr: Option<String>,
+    },
+    /// SetTokenPacketFee sets or removes the default packet fee of an ICA NFT.
+    /// Only the owner of the NFT can call this.
+    SetTokenPacketFee {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The default packet fee. If not set, the sponsored fee of the chain is used.
+        #[serde(default)]
+        fee: Option<PacketFee>,
+    },
+    /// SetSponsoredPacketFee sets or removes the packet fee paid from the fee pool for the ICAs
+    /// on the given chain. The channels of the chain must support ICS-29.
+    /// Only the owner can call this.
+    SetSponsoredPacketFee {
+        /// The alias of the chain.
+        chain: String,
+        /// The sponsored packet fee. If not set, the fee pool does not pay for this chain.
+        #[serde(default)]
+        fee: Option<PacketFee>,
+    },
+    /// FundFeePool adds the sent funds to the collection-sponsored fee pool.
+    /// Only the owner can call this.
+    FundFeePool {},
+    /// WithdrawFeePool sends the given amount from the fee pool to the owner.
+    /// Only the owner can call this.
+    WithdrawFeePool {
+        /// The amount to withdraw.
+        amount: Vec<Coin>,
     },
 }
 
@@ -68,19 +513,22 @@ pub enum QueryMsg {
         /// The token ID or ICA address to query.
         key: String,
     },
-    /// GetIcaAddress returns the ICA controller address for the given ICA NFT ID.
+    /// GetIcaAddress returns the ICA address for the given ICA NFT ID and chain.
     #[returns(String)]
     GetIcaAddress {
         /// The token ID of the ICA NFT.
         token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
     },
-    /// GetIcaAddresses returns the ICA controller addresses for the given ICA NFT IDs.
+    /// GetIcaAddresses returns the primary ICA addresses for the given ICA NFT IDs.
     #[returns(query_responses::GetIcaAddressesResponse)]
     GetIcaAddresses {
         /// The token IDs of the ICA NFTs.
         token_ids: Vec<String>,
     },
-    /// GetMintQueue returns the mint queue.
+    /// GetMintQueue returns the mint requests waiting for their ICA channel to open.
     #[returns(Vec<super::state::QueueItem>)]
     GetMintQueue {},
     /// GetTransactionHistory returns the transaction history for the given ICA NFT ID.
@@ -88,22 +536,365 @@ pub enum QueryMsg {
     GetTransactionHistory {
         /// The token ID of the ICA NFT.
         token_id: String,
-        /// The optional page number.
-        page: Option<u32>,
-        /// The optional page size.
-        page_size: Option<u32>,
+        /// The optional cursor to start after, usually the `next_cursor` of the previous page.
+        #[serde(default)]
+        start_after: Option<HistoryCursor>,
+        /// The optional maximum number of records returned.
+        #[serde(default)]
+        limit: Option<u32>,
+        /// The optional order of the records. Defaults to the most recent records first.
+        #[serde(default)]
+        order: Option<HistoryOrder>,
+        /// The optional filter of the records.
+        #[serde(default)]
+        filter: Option<HistoryFilter>,
     },
-    /// GetChannelState returns the channel state for the given ICA NFT ID.
+    /// GetActivityByAddress returns the transaction records sent by the given address across all
+    /// the ICA NFTs.
+    #[returns(query_responses::ActivityFeedResponse)]
+    GetActivityByAddress {
+        /// The address that sent the transactions.
+        address: String,
+        /// The optional activity ID to start after, usually the `next_cursor` of the previous page.
+        #[serde(default)]
+        start_after: Option<u64>,
+        /// The optional maximum number of records returned.
+        #[serde(default)]
+        limit: Option<u32>,
+        /// The optional order of the records. Defaults to the most recent records first.
+        #[serde(default)]
+        order: Option<HistoryOrder>,
+    },
+    /// GetActivityFeed returns the transaction records of all the ICA NFTs of the collection.
+    #[returns(query_responses::ActivityFeedResponse)]
+    GetActivityFeed {
+        /// The optional activity ID to start after, usually the `next_cursor` of the previous page.
+        #[serde(default)]
+        start_after: Option<u64>,
+        /// The optional maximum number of records returned.
+        #[serde(default)]
+        limit: Option<u32>,
+        /// The optional order of the records. Defaults to the most recent records first.
+        #[serde(default)]
+        order: Option<HistoryOrder>,
+    },
+    /// GetTransaction returns a transaction record of the given ICA NFT ID with its stored
+    /// payloads.
+    #[returns(query_responses::GetTransactionResponse)]
+    GetTransaction {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The sequence of the transaction record.
+        sequence: u64,
+    },
+    /// GetPendingWithdrawal returns the withdrawal of the given transaction record of the given
+    /// ICA NFT ID, if its transfer is not received or expired yet.
+    #[returns(Option<PendingWithdrawal>)]
+    GetPendingWithdrawal {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The sequence of the transaction record of the withdrawal.
+        sequence: u64,
+    },
+    /// SimulateIcaMsg returns whether an [`ExecuteMsg::ExecuteIcaMsg`] sent by the given sender
+    /// with the given funds would be accepted, the packet fee it would escrow, and the messages
+    /// it would send.
+    #[returns(query_responses::SimulateIcaMsgResponse)]
+    SimulateIcaMsg {
+        /// The sender of the message.
+        sender: String,
+        /// The funds sent with the message.
+        #[serde(default)]
+        funds: Vec<Coin>,
+        /// The token ID of the ICA NFT.
+        token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+        /// The custom message to send to the ICA controller contract.
+        msg: IcaControllerExecuteMsg,
+        /// The optional packet fee paid with the sent funds. Defaults to the packet fee of the token.
+        #[serde(default)]
+        packet_fee: Option<PacketFee>,
+    },
+    /// GetChannelState returns the channel state for the given ICA NFT ID and chain.
     #[returns(ChannelState)]
     GetChannelState {
         /// The token ID of the ICA NFT.
         token_id: String,
+        /// The optional alias of the chain of the ICA. Defaults to the primary chain of the NFT.
+        #[serde(default)]
+        chain: Option<String>,
+    },
+    /// GetTokenIcas returns all the ICAs of the given ICA NFT ID.
+    #[returns(Vec<query_responses::TokenIca>)]
+    GetTokenIcas {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+    /// GetRemainingSupply returns the number of NFTs that can still be minted.
+    #[returns(query_responses::GetRemainingSupplyResponse)]
+    GetRemainingSupply {},
+    /// GetAllowedConnections returns the connections that can be used to mint ICAs
+    /// besides the default connection.
+    #[returns(Vec<AllowedConnection>)]
+    GetAllowedConnections {},
+    /// GetPauseStatus returns the paused operations and the guardian.
+    #[returns(query_responses::PauseStatusResponse)]
+    GetPauseStatus {},
+    /// GetSubscribers returns the addresses of the contracts receiving the hooks of all the
+    /// ICA NFTs.
+    #[returns(Vec<String>)]
+    GetSubscribers {},
+    /// GetMsgCategories returns the categories of the message type URLs.
+    #[returns(Vec<MsgCategory>)]
+    GetMsgCategories {
+        /// The optional type URL to start after.
+        start_after: Option<String>,
+        /// The optional limit of the number of categories returned.
+        limit: Option<u32>,
+    },
+    /// GetChainRegistry returns all the registered host chains.
+    #[returns(Vec<query_responses::ChainRegistryEntry>)]
+    GetChainRegistry {},
+    /// GetTokensByChain returns the token IDs of the ICA NFTs on the given chain.
+    #[returns(Vec<String>)]
+    GetTokensByChain {
+        /// The alias of the chain.
+        chain: String,
+        /// The optional token ID to start after.
+        start_after: Option<String>,
+        /// The optional maximum number of token IDs to return.
+        limit: Option<u32>,
+    },
+    /// GetMintPhases returns all the configured mint phases.
+    #[returns(Vec<MintPhase>)]
+    GetMintPhases {},
+    /// GetActiveMintPhase returns the currently active mint phase, if any.
+    #[returns(Option<query_responses::ActiveMintPhaseResponse>)]
+    GetActiveMintPhase {},
+    /// GetPhaseMintCount returns the number of NFTs minted by the given address in the given phase.
+    #[returns(u64)]
+    GetPhaseMintCount {
+        /// The index of the mint phase.
+        phase_id: u32,
+        /// The address to query.
+        address: String,
+    },
+    /// GetTokenPacketFee returns the default packet fee of the given ICA NFT ID.
+    #[returns(Option<PacketFee>)]
+    GetTokenPacketFee {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+    /// GetSponsoredPacketFee returns the packet fee paid from the fee pool for the given chain.
+    #[returns(Option<PacketFee>)]
+    GetSponsoredPacketFee {
+        /// The alias of the chain.
+        chain: String,
+    },
+    /// GetFeePool returns the balance of the collection-sponsored fee pool.
+    #[returns(Vec<Coin>)]
+    GetFeePool {},
+    /// GetEscrowedFees returns the outstanding packet fees escrowed for the given ICA NFT ID.
+    #[returns(Vec<query_responses::EscrowedFeeEntry>)]
+    GetEscrowedFees {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+    /// GetCallbackReceiver returns the callback receiver of the given ICA NFT ID, if any.
+    #[returns(Option<CallbackReceiver>)]
+    GetCallbackReceiver {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+    /// GetAutoCompound returns the auto-compound state of the given ICA NFT ID, if enabled.
+    #[returns(Option<AutoCompound>)]
+    GetAutoCompound {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+    /// GetPendingCompound returns the compound in progress of the given ICA NFT ID, if any.
+    #[returns(Option<PendingCompound>)]
+    GetPendingCompound {
+        /// The token ID of the ICA NFT.
+        token_id: String,
+    },
+}
+
+/// This is the sudo message sent by the chain for the packets of the native ICA controller backend
+/// and for the ICS-20 transfers sent to the ICAs.
+#[cw_serde]
+pub enum SudoMsg {
+    /// OpenAck is sent when the channel of a native ICA is opened.
+    OpenAck {
+        /// The port ID of the ICA channel.
+        port_id: String,
+        /// The channel ID of the ICA channel.
+        channel_id: String,
+        /// The counterparty channel ID of the ICA channel.
+        counterparty_channel_id: String,
+        /// The counterparty version of the ICA channel, which contains the ICA address.
+        counterparty_version: String,
+    },
+    /// Response is sent when a packet is successfully acknowledged.
+    Response {
+        /// The packet that was sent.
+        request: RequestPacket,
+        /// The acknowledgement result.
+        data: Binary,
+    },
+    /// Error is sent when a packet is acknowledged with an error.
+    Error {
+        /// The packet that was sent.
+        request: RequestPacket,
+        /// The acknowledgement error.
+        details: String,
+    },
+    /// Timeout is sent when a packet times out.
+    Timeout {
+        /// The packet that was sent.
+        request: RequestPacket,
     },
 }
 
+/// RequestPacket is a packet sent by the contract.
+#[cw_serde]
+pub struct RequestPacket {
+    /// The sequence of the packet.
+    pub sequence: Option<u64>,
+    /// The source port of the packet.
+    pub source_port: Option<String>,
+    /// The source channel of the packet.
+    pub source_channel: Option<String>,
+    /// The destination port of the packet.
+    pub destination_port: Option<String>,
+    /// The destination channel of the packet.
+    pub destination_channel: Option<String>,
+    /// The data of the packet.
+    pub data: Option<Binary>,
+    /// The timeout height of the packet.
+    pub timeout_height: Option<RequestPacketTimeoutHeight>,
+    /// The timeout timestamp of the packet.
+    pub timeout_timestamp: Option<u64>,
+}
+
+/// RequestPacketTimeoutHeight is the timeout height of a [`RequestPacket`].
+#[cw_serde]
+pub struct RequestPacketTimeoutHeight {
+    /// The revision number of the timeout height.
+    pub revision_number: Option<u64>,
+    /// The revision height of the timeout height.
+    pub revision_height: Option<u64>,
+}
+
+/// This module contains the messages sent by the coordinator to other contracts.
+pub mod callbacks {
+    use super::cw_serde;
+
+    use crate::types::state::{
+        channel::ChannelStatus,
+        history::{AckPayload, TransactionRecord},
+    };
+
+    /// ReceiverExecuteMsg is the execute message sent to the callback receiver of an ICA NFT.
+    /// The receiver contract must accept this variant in its execute message.
+    #[cw_serde]
+    pub enum ReceiverExecuteMsg {
+        /// NftIcaCallback is sent when an ICA transaction of the NFT is acknowledged or
+        /// times out.
+        NftIcaCallback(NftIcaCallbackMsg),
+    }
+
+    /// NftIcaCallbackMsg is the simplified callback of an ICA transaction.
+    #[cw_serde]
+    pub struct NftIcaCallbackMsg {
+        /// The token ID of the ICA NFT.
+        pub token_id: String,
+        /// The transaction record, with its final status.
+        pub record: TransactionRecord,
+        /// The acknowledgement of the packet, or `None` if it timed out.
+        pub ack: Option<AckPayload>,
+    }
+
+    /// SubscriberExecuteMsg is the execute message sent to the subscribers of the collection.
+    /// The subscriber contracts must accept this variant in their execute message.
+    #[cw_serde]
+    pub enum SubscriberExecuteMsg {
+        /// NftIcaHook is sent on the lifecycle events of every ICA NFT.
+        NftIcaHook(NftIcaHookMsg),
+    }
+
+    /// NftIcaHookMsg is the lifecycle event of an ICA NFT sent to the subscribers.
+    #[cw_serde]
+    pub enum NftIcaHookMsg {
+        /// Minted is sent when an ICA NFT is minted.
+        Minted {
+            /// The token ID of the ICA NFT.
+            token_id: String,
+            /// The owner of the ICA NFT.
+            owner: String,
+            /// The alias of the chain of the ICA.
+            chain: String,
+            /// The address of the ICA on the host chain.
+            ica_address: String,
+        },
+        /// Transferred is sent when an ICA NFT is transferred.
+        Transferred {
+            /// The token ID of the ICA NFT.
+            token_id: String,
+            /// The previous owner of the ICA NFT.
+            from: String,
+            /// The new owner of the ICA NFT.
+            to: String,
+        },
+        /// ChannelStatusChanged is sent when the channel of an ICA is opened, closed or
+        /// reopening.
+        ChannelStatusChanged {
+            /// The token ID of the ICA NFT.
+            token_id: String,
+            /// The alias of the chain of the ICA.
+            chain: String,
+            /// The new status of the channel.
+            status: ChannelStatus,
+            /// The channel ID, if the channel is open.
+            channel_id: Option<String>,
+        },
+        /// TransactionCompleted is sent when an ICA transaction is acknowledged or times out.
+        TransactionCompleted {
+            /// The token ID of the ICA NFT.
+            token_id: String,
+            /// The transaction record, with its final status.
+            record: TransactionRecord,
+        },
+    }
+}
+
+/// This module contains the responses returned in the data field of some execute messages.
+pub mod execute_responses {
+    use super::cw_serde;
+
+    /// MintIcaResponse is the response data of the [`super::ExecuteMsg::MintIca`] message.
+    #[cw_serde]
+    pub struct MintIcaResponse {
+        /// The token IDs reserved for the NFTs to be minted.
+        pub token_ids: Vec<String>,
+    }
+}
+
 /// This module contains some of the query responses.
 pub mod query_responses {
-    use crate::types::state::history::TransactionRecord;
+    use cosmwasm_std::{Addr, CosmosMsg};
+
+    use crate::types::state::{
+        backend::IcaBackend,
+        chain::ChainConfig,
+        channel::ChannelState,
+        fees::EscrowedFee,
+        history::{HistoryCursor, TransactionMsgType, TransactionPayload, TransactionRecord},
+        pause::PauseFlags,
+        MintPhase,
+    };
 
     use super::cw_serde;
 
@@ -112,8 +903,87 @@ pub mod query_responses {
     pub struct GetTransactionHistoryResponse {
         /// The transaction history.
         pub records: Vec<TransactionRecord>,
-        /// The total number of transactions.
+        /// The number of transaction records stored for the token, regardless of the filter.
         pub total: u32,
+        /// The cursor of the next page, if there may be more matching records. The cursor is the
+        /// last scanned record, so a page can be empty if few records match the filter.
+        pub next_cursor: Option<HistoryCursor>,
+    }
+
+    /// ActivityFeedResponse is the response for the [`super::QueryMsg::GetActivityByAddress`] and
+    /// [`super::QueryMsg::GetActivityFeed`] queries.
+    #[cw_serde]
+    pub struct ActivityFeedResponse {
+        /// The transaction records.
+        pub records: Vec<TransactionRecord>,
+        /// The activity ID of the next page, if there may be more records.
+        pub next_cursor: Option<u64>,
+    }
+
+    /// GetTransactionResponse is the response for the [`super::QueryMsg::GetTransaction`] query.
+    #[cw_serde]
+    pub struct GetTransactionResponse {
+        /// The transaction record.
+        pub record: TransactionRecord,
+        /// The stored payloads of the transaction, if any.
+        pub payload: Option<TransactionPayload>,
+    }
+
+    /// SimulateIcaMsgResponse is the response for the [`super::QueryMsg::SimulateIcaMsg`] query.
+    #[cw_serde]
+    pub struct SimulateIcaMsgResponse {
+        /// Whether the sender is the owner of the ICA NFT.
+        pub authorized: bool,
+        /// The alias of the chain of the ICA, if the message would be accepted.
+        pub chain: Option<String>,
+        /// The channel state of the ICA, if the message would be accepted.
+        pub channel: Option<ChannelState>,
+        /// The type the transaction would be recorded as, or `None` if the message sends no packet.
+        pub msg_type: Option<TransactionMsgType>,
+        /// The packet fee that would be escrowed for the packet, if any.
+        pub escrowed_fee: Option<EscrowedFee>,
+        /// The messages that would be sent, ending with the message to the ICA controller.
+        pub msgs: Vec<CosmosMsg>,
+        /// The reasons the message would be rejected. The message would be accepted if empty.
+        pub violations: Vec<String>,
+    }
+
+    /// PauseStatusResponse is the response for the [`super::QueryMsg::GetPauseStatus`] query.
+    #[cw_serde]
+    pub struct PauseStatusResponse {
+        /// The paused operations.
+        pub flags: PauseFlags,
+        /// The guardian, if any.
+        pub guardian: Option<Addr>,
+    }
+
+    /// GetRemainingSupplyResponse is the response for the [`super::QueryMsg::GetRemainingSupply`] query.
+    #[cw_serde]
+    pub struct GetRemainingSupplyResponse {
+        /// The maximum supply of the collection. If not set, the supply is unlimited.
+        pub max_supply: Option<u64>,
+        /// The number of NFTs minted so far, including the ones waiting in the mint queue.
+        pub total_supply: u64,
+        /// The number of NFTs that can still be minted. If not set, the supply is unlimited.
+        pub remaining: Option<u64>,
+    }
+
+    /// ChainRegistryEntry is a registered host chain returned by the [`super::QueryMsg::GetChainRegistry`] query.
+    #[cw_serde]
+    pub struct ChainRegistryEntry {
+        /// The alias of the chain.
+        pub alias: String,
+        /// The configuration of the chain.
+        pub config: ChainConfig,
+    }
+
+    /// ActiveMintPhaseResponse is the response for the [`super::QueryMsg::GetActiveMintPhase`] query.
+    #[cw_serde]
+    pub struct ActiveMintPhaseResponse {
+        /// The index of the active mint phase.
+        pub phase_id: u32,
+        /// The active mint phase.
+        pub phase: MintPhase,
     }
 
     /// GetIcaAddressesResponse is the response for the [`super::QueryMsg::GetIcaAddresses`] query.
@@ -123,6 +993,29 @@ pub mod query_responses {
         pub pairs: Vec<NftIcaPair>,
     }
 
+    /// EscrowedFeeEntry is an escrowed packet fee returned by the [`super::QueryMsg::GetEscrowedFees`] query.
+    #[cw_serde]
+    pub struct EscrowedFeeEntry {
+        /// The sequence of the transaction record of the packet.
+        pub sequence: u64,
+        /// The escrowed fee.
+        pub escrowed: EscrowedFee,
+    }
+
+    /// TokenIca is an ICA of an NFT returned by the [`super::QueryMsg::GetTokenIcas`] query.
+    #[cw_serde]
+    pub struct TokenIca {
+        /// The alias of the chain of the ICA.
+        pub chain: String,
+        /// The backend controlling the ICA.
+        pub backend: IcaBackend,
+        /// The ICA controller address, or the owner string of the ICA if it uses the
+        /// native ICA controller backend.
+        pub ica_controller_address: String,
+        /// The ICA address. This is not set until the ICA channel is opened.
+        pub ica_address: Option<String>,
+    }
+
     /// NftIcaPair is a pair of NFT ID and ICA controller address.
     #[cw_serde]
     pub struct NftIcaPair {
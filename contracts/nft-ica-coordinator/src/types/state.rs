@@ -3,56 +3,178 @@
 use cosmwasm_schema::cw_serde;
 
 pub use contract::ContractState;
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Empty, Uint128};
 use cw_storage_plus::{Deque, Item, Map};
-pub use mint::QueueItem;
+pub use mint::{MintPhase, QueueItem};
 
 use crate::utils::storage::{KeySet, NftIcaBiMap};
 
 /// The item used to store the state of the IBC application.
 pub const STATE: Item<ContractState> = Item::new("state");
-/// The map used to map nft token ids to ICA addresses.
-pub const NFT_ICA_MAP: Map<&str, String> = Map::new("nft_ica_map");
+/// The map used to map (nft token id, chain alias) pairs to ICA addresses.
+pub const NFT_ICA_MAP: Map<(&str, &str), String> = Map::new("nft_chain_ica_map");
+/// The map used to store the alias of the chain of the ICA created when each token was minted.
+pub const TOKEN_PRIMARY_CHAIN: Map<&str, String> = Map::new("token_primary_chain");
 /// The keyset used to store the registered ICA addresses to accept callbacks from.
 pub const REGISTERED_ICA_ADDRS: KeySet<&Addr> = KeySet::new("registered_ica");
-/// The item used to store the bi-directional map between cw-ica-controller address and NFT IDs.
-pub const NFT_ICA_CONTRACT_BI_MAP: NftIcaBiMap = NftIcaBiMap::new("nft_ica_contract_bi_map");
-/// NFT_MINT_QUEUE is the queue of NFT mint requests, waiting for a callback from the ICA controller contract.
+/// The item used to store the bi-directional map between ICA controllers and (NFT ID, chain alias) pairs.
+/// The controller is the cw-ica-controller address, or the owner string of the ICA if it uses the
+/// native ICA controller backend.
+pub const NFT_ICA_CONTRACT_BI_MAP: NftIcaBiMap =
+    NftIcaBiMap::new("controller_nft_map", "nft_controller_map");
+/// The map used to store the connection ID of each ICA owner string of the native ICA controller backend.
+pub const NATIVE_ICA_CONNECTIONS: Map<&str, String> = Map::new("native_ica_connections");
+/// NFT_MINT_QUEUE is the queue of NFT mint (and add ICA) requests, waiting for their ICA controller contract to be instantiated.
 pub const NFT_MINT_QUEUE: Deque<mint::QueueItem> = Deque::new("nft_mint_queue");
+/// The map used to store the NFT mint requests waiting for a callback from their ICA controller.
+/// Requests to add an ICA to an existing token are not stored here.
+pub const PENDING_MINTS: Map<&str, mint::QueueItem> = Map::new("pending_mints");
+/// The queue of the mint requests migrated from a version that bound them to their ICA controller
+/// when its channel opened. They are bound to the next unbound controllers opening a channel.
+pub const UNBOUND_MINTS: Deque<mint::QueueItem> = Deque::new("unbound_mints");
 /// The item used to store the NFT-ICA counter.
 pub const TOKEN_COUNTER: Item<u64> = Item::new("ica_nft_counter");
-/// tha map used to store channel status for each token id
-pub const CHANNEL_STATE: Map<&str, channel::ChannelState> = Map::new("channel_status");
+/// The map used to store the number of NFTs minted (or queued for minting) for each owner.
+pub const MINT_COUNT_PER_ADDRESS: Map<&str, u64> = Map::new("mint_count_per_address");
+/// The item used to store the ordered mint phases configured by the owner.
+pub const MINT_PHASES: Item<Vec<mint::MintPhase>> = Item::new("mint_phases");
+/// The map used to store the number of NFTs minted by each address in each mint phase.
+pub const PHASE_MINT_COUNT: Map<(u32, &str), u64> = Map::new("phase_mint_count");
+/// The keyset used to store the gate tokens (partner collection, token id) that were already used to mint.
+pub const CONSUMED_GATE_TOKENS: KeySet<(&Addr, &str)> = KeySet::new("consumed_gate_tokens");
+/// The map used to store the registered host chains by their alias.
+pub const CHAIN_REGISTRY: Map<&str, chain::ChainConfig> = Map::new("chain_registry");
+/// The map used to index the minted token ids by their (chain alias, token id).
+pub const CHAIN_TOKENS: Map<(&str, &str), Empty> = Map::new("chain_tokens");
+/// The map used to store the counterparty connection IDs of the connection IDs that can be used
+/// to mint ICAs besides the default one.
+pub const ALLOWED_CONNECTIONS: Map<&str, String> = Map::new("allowed_connection_pairs");
+/// tha map used to store channel status for each (token id, chain alias)
+pub const CHANNEL_STATE: Map<(&str, &str), channel::ChannelState> =
+    Map::new("chain_channel_status");
+/// The map used to store the transaction history by (token id, sequence).
+pub const TX_HISTORY: Map<(&str, u64), history::TransactionRecord> = Map::new("tx_history");
+/// The map used to store the sequence of the next transaction record of each token id.
+pub const TX_HISTORY_SEQUENCE: Map<&str, u64> = Map::new("tx_history_sequence");
+/// The map used to store the number of stored transaction records of each token id.
+pub const TX_HISTORY_COUNT: Map<&str, u64> = Map::new("tx_history_count");
+/// The map used to index all the transaction records of the collection by their activity ID,
+/// which increases with every new record. Values are (token id, sequence) pairs.
+pub const ACTIVITY_FEED: Map<u64, (String, u64)> = Map::new("activity_feed");
+/// The map used to index the transaction records by (executor, activity id).
+/// Values are (token id, sequence) pairs.
+pub const EXECUTOR_ACTIVITY: Map<(&str, u64), (String, u64)> = Map::new("executor_activity");
+/// The item used to store the activity ID of the next transaction record.
+pub const ACTIVITY_SEQUENCE: Item<u64> = Item::new("activity_sequence");
+/// The map used to store the category of each message type URL, used to classify the transactions.
+pub const MSG_CATEGORIES: Map<&str, String> = Map::new("msg_categories");
+/// The map used to store the optional message and acknowledgement payloads of the transaction
+/// records by (token id, sequence).
+pub const TX_PAYLOADS: Map<(&str, u64), history::TransactionPayload> = Map::new("tx_payloads");
 
-/// The prefix used to store the transaction history.
-const TX_HISTORY_PREFIX: &str = "tx_history_";
+/// The item used to store the channel ID and the ICS-20 transfer sent to an ICA until its packet
+/// sequence is known from the reply.
+pub const TRANSFER_REPLY_STATE: Item<(String, history::PendingTransfer)> =
+    Item::new("transfer_reply_state");
+/// The map used to store the ICS-20 transfers sent to the ICAs by (channel id, packet sequence),
+/// waiting for their acknowledgement.
+pub const PENDING_TRANSFERS: Map<(&str, u64), history::PendingTransfer> =
+    Map::new("pending_transfers");
+/// The map used to store the withdrawals from the ICAs by (token id, history sequence),
+/// waiting for their ICS-20 transfer to be received or to time out.
+pub const PENDING_WITHDRAWALS: Map<(&str, u64), history::PendingWithdrawal> =
+    Map::new("pending_withdrawals");
+/// The map used to store the default ICS-29 packet fee of each token id, paid by its owner.
+pub const TOKEN_PACKET_FEES: Map<&str, fees::PacketFee> = Map::new("token_packet_fees");
+/// The map used to store the ICS-29 packet fee paid from the fee pool for each chain alias.
+pub const SPONSORED_PACKET_FEES: Map<&str, fees::PacketFee> = Map::new("sponsored_packet_fees");
+/// The map used to store the balance of the collection-sponsored fee pool by denom.
+pub const FEE_POOL: Map<&str, Uint128> = Map::new("fee_pool");
+/// The map used to store the outstanding escrowed packet fees by (token id, history sequence).
+pub const ESCROWED_FEES: Map<(&str, u64), fees::EscrowedFee> = Map::new("escrowed_fees");
+/// The map used to store the contract registered by the owner of each token id to receive
+/// the callbacks of its ICA transactions.
+pub const CALLBACK_RECEIVERS: Map<&str, hooks::CallbackReceiver> = Map::new("callback_receivers");
+/// The keyset used to store the contracts receiving the hooks of all the tokens of the collection.
+pub const SUBSCRIBERS: KeySet<&Addr> = KeySet::new("subscribers");
+/// The item used to store the operations paused by the owner or the guardian.
+pub const PAUSE_FLAGS: Item<pause::PauseFlags> = Item::new("pause_flags");
+/// The item used to store the guardian, who can pause operations besides the owner.
+pub const GUARDIAN: Item<Addr> = Item::new("guardian");
+/// The map used to store the auto-compound configuration of each token id that opted in.
+pub const AUTO_COMPOUND: Map<&str, compound::AutoCompound> = Map::new("auto_compound");
+/// The map used to store the compound in progress of each token id.
+pub const PENDING_COMPOUNDS: Map<&str, compound::PendingCompound> = Map::new("pending_compounds");
 
-/// Returns the key used to store the transaction history of the given token ID.
-pub fn get_tx_history_prefix(token_id: &str) -> String {
-    format!("{}{}", TX_HISTORY_PREFIX, token_id)
+/// The prefix used to store the queue of pending transactions of an ICA controller.
+const PENDING_TXS_PREFIX: &str = "pending_txs_";
+
+/// Returns the key used to store the sequences of the pending transactions of the given
+/// ICA controller, in the order they were sent.
+pub fn get_pending_txs_prefix(controller: &str) -> String {
+    format!("{}{}", PENDING_TXS_PREFIX, controller)
 }
 
 mod contract {
     use super::*;
 
-    use cosmwasm_std::Addr;
+    use cosmwasm_std::{Addr, Coin};
     use cw_ica_controller::types::msg::options::ChannelOpenInitOptions;
 
+    use super::backend::IcaBackend;
+
     /// The state of the contract.
     #[cw_serde]
     pub struct ContractState {
         /// The default options for new ICA channels.
         pub default_chan_init_options: ChannelOpenInitOptions,
+        /// The ICA backend used with the default channel options.
+        #[serde(default)]
+        pub default_backend: IcaBackend,
         /// The code ID of the cw-ica-controller contract.
         pub ica_controller_code_id: u64,
         /// The address of the cw721-ica-extension contract.
         pub cw721_ica_extension_address: Addr,
+        /// The optional maximum number of NFTs that can be minted.
+        #[serde(default)]
+        pub max_supply: Option<u64>,
+        /// The optional maximum number of NFTs that can be minted for a single address.
+        #[serde(default)]
+        pub max_per_address: Option<u64>,
+        /// The optional partner cw721 collection whose holders are allowed to mint.
+        /// Each token of the partner collection can be used to mint a single ICA NFT.
+        #[serde(default)]
+        pub mint_gate_collection: Option<Addr>,
+        /// The optional maximum size in bytes of the message and acknowledgement payloads stored
+        /// with the transaction records. If not set, payloads are not stored.
+        #[serde(default)]
+        pub max_payload_size: Option<u64>,
+        /// The optional maximum number of transaction records stored for each token. The oldest
+        /// records that are not pending are evicted. If not set, the history is unbounded.
+        #[serde(default)]
+        pub max_history_per_token: Option<u64>,
+        /// Whether the chain sends the acknowledgements and timeouts of the ICS-20 transfers
+        /// sent by this contract to its sudo entry point. Funding the ICAs is disabled otherwise,
+        /// since the funds of a failed transfer could not be refunded.
+        #[serde(default)]
+        pub transfer_callbacks: bool,
+        /// Whether the chain runs the ibc-hooks middleware, which executes this contract when
+        /// it receives an ICS-20 transfer with a wasm memo. Withdrawing from the ICAs is disabled
+        /// otherwise, since the outcome of the withdrawal could not be observed.
+        #[serde(default)]
+        pub ibc_hooks: bool,
+        /// The fee paid from the fee pool to register each ICA of the native backend, as
+        /// required by the interchaintxs module of Neutron.
+        #[serde(default)]
+        pub native_register_fee: Vec<Coin>,
     }
 }
 
 mod mint {
     use super::*;
 
+    use cosmwasm_std::{Coin, HexBinary, Timestamp};
+
     /// The item used to store the mint queue.
     #[cw_serde]
     pub struct QueueItem {
@@ -60,6 +182,229 @@ mod mint {
         pub token_id: String,
         /// The owner of the NFT.
         pub owner: String,
+        /// The alias of the host chain of the ICA.
+        /// Items queued before chain aliases were introduced use the default chain.
+        #[serde(default = "default_chain")]
+        pub chain: String,
+    }
+
+    fn default_chain() -> String {
+        crate::types::keys::DEFAULT_CHAIN_ALIAS.to_string()
+    }
+
+    /// A mint phase configured by the owner of the contract.
+    #[cw_serde]
+    pub struct MintPhase {
+        /// The time at which the phase starts (inclusive).
+        pub start: Timestamp,
+        /// The time at which the phase ends (exclusive).
+        pub end: Timestamp,
+        /// The optional price of minting a single NFT in this phase.
+        #[serde(default)]
+        pub price: Option<Coin>,
+        /// The optional maximum number of NFTs that a single address can mint in this phase.
+        #[serde(default)]
+        pub per_address_limit: Option<u64>,
+        /// The optional sha256 merkle root of the addresses allowed to mint in this phase.
+        /// If not set, anyone can mint in this phase.
+        #[serde(default)]
+        pub merkle_root: Option<HexBinary>,
+    }
+
+    impl MintPhase {
+        /// Returns true if the phase is active at the given time.
+        pub fn is_active(&self, time: Timestamp) -> bool {
+            self.start <= time && time < self.end
+        }
+    }
+}
+
+/// This module contains the types used to store the host chain registry.
+pub mod chain {
+    use cosmwasm_schema::cw_serde;
+    use cw_ica_controller::types::msg::options::ChannelOpenInitOptions;
+
+    use super::backend::IcaBackend;
+
+    /// ChainConfig is the configuration of a host chain that ICAs can be created on.
+    #[cw_serde]
+    pub struct ChainConfig {
+        /// The channel open init options used to create ICAs on this chain.
+        pub chan_init_options: ChannelOpenInitOptions,
+        /// The ICA backend used to create and control ICAs on this chain.
+        #[serde(default)]
+        pub backend: IcaBackend,
+        /// The bech32 address prefix of the chain.
+        pub bech32_prefix: String,
+        /// The native denom of the chain.
+        pub native_denom: String,
+    }
+
+    /// AllowedConnection is a connection that can be used to mint ICAs, with the connection
+    /// it must be paired with on the counterparty chain.
+    #[cw_serde]
+    pub struct AllowedConnection {
+        /// The connection ID on this chain.
+        pub connection_id: String,
+        /// The connection ID on the counterparty chain.
+        pub counterparty_connection_id: String,
+    }
+}
+
+/// This module contains the types used to select how ICAs are created and controlled.
+pub mod backend {
+    use cosmwasm_schema::cw_serde;
+
+    /// IcaBackend is the implementation used to create and control ICAs.
+    #[cw_serde]
+    #[derive(Default)]
+    pub enum IcaBackend {
+        /// Each ICA is controlled by its own cw-ica-controller contract instance.
+        #[default]
+        CwIcaController,
+        /// The ICAs are controlled by this contract through the interchaintxs module of Neutron,
+        /// using a different interchain account ID for each ICA. Acknowledgements are received
+        /// through the sudo entry point, the packet fees are escrowed by the feerefunder module,
+        /// and only proto3 encoding is supported.
+        Native,
+    }
+}
+
+/// This module contains the types used to store the ICS-29 relayer fees.
+pub mod fees {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use cosmwasm_std::{Coin, Order, StdResult, Storage};
+
+    /// PacketFee is the ICS-29 fee paid to the relayers of a single packet.
+    #[cw_serde]
+    #[derive(Default)]
+    pub struct PacketFee {
+        /// The fee paid to the relayer of the packet.
+        #[serde(default)]
+        pub recv_fee: Vec<Coin>,
+        /// The fee paid to the relayer of the acknowledgement.
+        #[serde(default)]
+        pub ack_fee: Vec<Coin>,
+        /// The fee paid to the relayer of the timeout.
+        #[serde(default)]
+        pub timeout_fee: Vec<Coin>,
+    }
+
+    /// FeePayer is the payer of an escrowed packet fee, which receives its refund.
+    #[cw_serde]
+    pub enum FeePayer {
+        /// The fee was paid with the funds sent by this address.
+        Sender(Addr),
+        /// The fee was paid from the collection-sponsored fee pool.
+        FeePool,
+    }
+
+    /// EscrowedFee is a packet fee escrowed by the fee middleware until the packet is
+    /// acknowledged or times out.
+    #[cw_serde]
+    pub struct EscrowedFee {
+        /// The alias of the chain of the ICA that sent the packet.
+        pub chain: String,
+        /// The escrowed fee.
+        pub fee: PacketFee,
+        /// The payer of the fee.
+        pub payer: FeePayer,
+    }
+
+    impl PacketFee {
+        /// Returns the total amount escrowed for this fee, sorted by denom.
+        pub fn total(&self) -> Vec<Coin> {
+            merge_coins(
+                self.recv_fee
+                    .iter()
+                    .chain(&self.ack_fee)
+                    .chain(&self.timeout_fee),
+            )
+        }
+
+        /// Returns true if this fee pays nothing to the relayers.
+        pub fn is_empty(&self) -> bool {
+            self.total().is_empty()
+        }
+
+        /// Returns the amount refunded to the payer once the packet is resolved.
+        /// The timeout fee is refunded if the packet is acknowledged, otherwise the receive
+        /// and acknowledgement fees are refunded.
+        pub fn refund(&self, timed_out: bool) -> Vec<Coin> {
+            if timed_out {
+                merge_coins(self.recv_fee.iter().chain(&self.ack_fee))
+            } else {
+                merge_coins(&self.timeout_fee)
+            }
+        }
+    }
+
+    fn merge_coins<'a>(coins: impl IntoIterator<Item = &'a Coin>) -> Vec<Coin> {
+        coins
+            .into_iter()
+            .filter(|coin| !coin.amount.is_zero())
+            .fold(BTreeMap::<&str, Uint128>::new(), |mut acc, coin| {
+                *acc.entry(&coin.denom).or_default() += coin.amount;
+                acc
+            })
+            .into_iter()
+            .map(|(denom, amount)| Coin::new(amount.u128(), denom))
+            .collect()
+    }
+
+    /// Adds the given coins to the fee pool.
+    pub fn deposit(storage: &mut dyn Storage, coins: &[Coin]) -> StdResult<()> {
+        for coin in coins {
+            FEE_POOL.update(storage, &coin.denom, |balance| -> StdResult<_> {
+                Ok(balance.unwrap_or_default().checked_add(coin.amount)?)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the fee pool has enough funds to withdraw the given coins.
+    pub fn covers(storage: &dyn Storage, coins: &[Coin]) -> StdResult<bool> {
+        for coin in merge_coins(coins) {
+            let balance = FEE_POOL.may_load(storage, &coin.denom)?.unwrap_or_default();
+            if balance < coin.amount {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Removes the given coins from the fee pool.
+    /// Returns false and leaves the pool untouched if it does not have enough funds.
+    pub fn withdraw(storage: &mut dyn Storage, coins: &[Coin]) -> StdResult<bool> {
+        let coins = merge_coins(coins);
+        let mut balances = Vec::with_capacity(coins.len());
+        for coin in &coins {
+            let balance = FEE_POOL.may_load(storage, &coin.denom)?.unwrap_or_default();
+            match balance.checked_sub(coin.amount) {
+                Ok(remaining) => balances.push((&coin.denom, remaining)),
+                Err(_) => return Ok(false),
+            }
+        }
+
+        for (denom, remaining) in balances {
+            FEE_POOL.save(storage, denom, &remaining)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the balance of the fee pool.
+    pub fn balance(storage: &dyn Storage) -> StdResult<Vec<Coin>> {
+        FEE_POOL
+            .range(storage, None, None, Order::Ascending)
+            .filter(|item| !matches!(item, Ok((_, amount)) if amount.is_zero()))
+            .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+            .collect()
     }
 }
 
@@ -91,8 +436,14 @@ pub mod channel {
 /// This module contains the types used to store the ICA transaction history.
 pub mod history {
     use super::*;
-    use cosmwasm_std::{CosmosMsg, StakingMsg};
+    use cosmwasm_std::{
+        to_json_binary, Binary, Coin, CosmosMsg, Decimal, GovMsg, Order, StakingMsg, StdResult,
+        Storage, Timestamp, WeightedVoteOption,
+    };
     use cw_ica_controller::types::msg::ExecuteMsg as IcaControllerExecuteMsg;
+    use cw_storage_plus::Bound;
+
+    use crate::utils::type_url;
 
     /// Represents the status of a transaction.
     #[cw_serde]
@@ -105,6 +456,28 @@ pub mod history {
         Failed,
         /// The transaction has timed out.
         Timeout,
+        /// The transaction has been executed by the ICA, and the ICS-20 transfer it sent back to
+        /// this chain is waiting to be received or to time out.
+        InTransit,
+    }
+
+    impl TransactionStatus {
+        /// Returns true if the final outcome of the transaction is not known yet.
+        pub fn is_pending(&self) -> bool {
+            matches!(self, Self::Pending | Self::InTransit)
+        }
+    }
+
+    impl std::fmt::Display for TransactionStatus {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Pending => write!(f, "pending"),
+                Self::Completed => write!(f, "completed"),
+                Self::Failed => write!(f, "failed"),
+                Self::Timeout => write!(f, "timeout"),
+                Self::InTransit => write!(f, "in_transit"),
+            }
+        }
     }
 
     /// Represents the type of a transaction message.
@@ -134,6 +507,12 @@ pub mod history {
         Distribution,
         /// The transaction has more than one message.
         MultiMsg,
+        /// The transaction is an ICS-20 transfer from the source chain to the ICA.
+        Deposit,
+        /// The transaction is an ICS-20 transfer from the ICA back to the source chain.
+        Withdraw,
+        /// The transaction withdraws the staking rewards of the ICA and re-delegates them.
+        Compound,
         /// The transaction type cannot be determined.
         Unknown,
     }
@@ -141,18 +520,239 @@ pub mod history {
     /// Represents a transaction record.
     #[cw_serde]
     pub struct TransactionRecord {
+        /// The sequence of the record in the history of its token.
+        pub sequence: u64,
+        /// The ID of the record in the activity feeds.
+        pub activity_id: u64,
         /// The status of the transaction.
         pub status: TransactionStatus,
         /// The token ID of the NFT.
         pub token_id: String,
+        /// The alias of the chain of the ICA.
+        pub chain: String,
         /// The owner of the NFT.
         pub owner: String,
+        /// The address that sent the transaction, which differs from the owner for transactions
+        /// triggered by keepers.
+        #[serde(default)]
+        pub executor: Option<String>,
         /// The type of the message sent to the ICA controller contract.
         pub msg_type: TransactionMsgType,
         /// The height of the block when the transaction was sent.
         pub block_height: u64,
         /// The timestamp of the block when the transaction was sent in nanoseconds.
         pub timestamp: u64,
+        /// The governance vote cast by the transaction, if it is a vote.
+        #[serde(default)]
+        pub vote: Option<VoteRecord>,
+        /// The results of the messages of the transaction, decoded from its acknowledgement.
+        #[serde(default)]
+        pub results: Vec<MsgResult>,
+        /// The type URLs of the host messages of the transaction.
+        #[serde(default)]
+        pub type_urls: Vec<String>,
+        /// The categories of the type URLs of the transaction, without duplicates.
+        #[serde(default)]
+        pub categories: Vec<String>,
+    }
+
+    /// MsgCategory assigns a category to a message type URL.
+    #[cw_serde]
+    pub struct MsgCategory {
+        /// The type URL of the message, e.g. `/osmosis.gamm.v1beta1.MsgSwapExactAmountIn`.
+        pub type_url: String,
+        /// The category of the message, e.g. `swap`.
+        pub category: String,
+    }
+
+    /// MsgResult is the decoded result of a message executed by an ICA.
+    #[cw_serde]
+    pub enum MsgResult {
+        /// The result of a [`StakingMsg::Delegate`] message.
+        Delegate,
+        /// The result of a [`StakingMsg::Undelegate`] message.
+        Undelegate {
+            /// The time when the unbonding completes.
+            completion_time: Option<Timestamp>,
+            /// The undelegated amount, only returned since Cosmos SDK v0.50.
+            amount: Option<Coin>,
+        },
+        /// The result of a [`StakingMsg::Redelegate`] message.
+        Redelegate {
+            /// The time when the redelegation completes.
+            completion_time: Option<Timestamp>,
+        },
+        /// The result of a withdraw delegator reward message.
+        WithdrawRewards {
+            /// The withdrawn rewards.
+            amount: Vec<Coin>,
+        },
+        /// The result of an ICS-20 transfer sent by the ICA.
+        Transfer {
+            /// The packet sequence of the transfer.
+            sequence: u64,
+        },
+        /// The result of a message that is not decoded.
+        Unknown {
+            /// The type URL of the message or of its response.
+            type_url: String,
+        },
+    }
+
+    /// VoteRecord is a governance vote cast by an ICA.
+    #[cw_serde]
+    pub struct VoteRecord {
+        /// The ID of the proposal on the host chain.
+        pub proposal_id: u64,
+        /// The options voted for. A plain vote has a single option with a weight of one.
+        pub options: Vec<WeightedVoteOption>,
+    }
+
+    /// TransactionPayload is the stored message and acknowledgement of a transaction record.
+    /// Payloads larger than the size limit of the contract are dropped.
+    #[cw_serde]
+    #[derive(Default)]
+    pub struct TransactionPayload {
+        /// The JSON encoded message sent to the ICA controller, if stored.
+        pub msg: Option<Binary>,
+        /// The acknowledgement of the packet, if received and stored.
+        pub ack: Option<AckPayload>,
+        /// Whether a payload was dropped because it exceeded the size limit.
+        pub truncated: bool,
+    }
+
+    /// AckPayload is the acknowledgement of a packet.
+    #[cw_serde]
+    pub enum AckPayload {
+        /// The result bytes of a successful acknowledgement.
+        Result(Binary),
+        /// The error string of a failed acknowledgement.
+        Error(String),
+    }
+
+    impl AckPayload {
+        /// Returns the size of the payload in bytes.
+        pub fn len(&self) -> usize {
+            match self {
+                Self::Result(data) => data.len(),
+                Self::Error(error) => error.len(),
+            }
+        }
+
+        /// Returns whether the payload is empty.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
+    /// HistoryCursor is the position after which a page of transaction records starts.
+    #[cw_serde]
+    pub enum HistoryCursor {
+        /// Start after the record with the given sequence.
+        Sequence(u64),
+        /// Start after the records sent at the given timestamp in nanoseconds.
+        Timestamp(u64),
+    }
+
+    /// HistoryOrder is the order in which transaction records are returned.
+    #[cw_serde]
+    #[derive(Default, Copy)]
+    pub enum HistoryOrder {
+        /// The oldest records are returned first.
+        Ascending,
+        /// The most recent records are returned first.
+        #[default]
+        Descending,
+    }
+
+    /// HistoryFilter selects the transaction records returned by the history queries.
+    /// Unset fields match all records.
+    #[cw_serde]
+    #[derive(Default)]
+    pub struct HistoryFilter {
+        /// Only return the records with this status.
+        #[serde(default)]
+        pub status: Option<TransactionStatus>,
+        /// Only return the records with this message type.
+        #[serde(default)]
+        pub msg_type: Option<TransactionMsgType>,
+        /// Only return the records sent by this address.
+        #[serde(default)]
+        pub executor: Option<String>,
+        /// Only return the records sent at or after this block height.
+        #[serde(default)]
+        pub min_block_height: Option<u64>,
+        /// Only return the records sent at or before this block height.
+        #[serde(default)]
+        pub max_block_height: Option<u64>,
+        /// Only return the records containing a message with this type URL.
+        #[serde(default)]
+        pub type_url: Option<String>,
+        /// Only return the records with this category.
+        #[serde(default)]
+        pub category: Option<String>,
+    }
+
+    impl HistoryFilter {
+        /// Returns whether the given record matches the filter.
+        pub fn matches(&self, record: &TransactionRecord) -> bool {
+            self.status
+                .as_ref()
+                .map_or(true, |status| &record.status == status)
+                && self
+                    .msg_type
+                    .as_ref()
+                    .map_or(true, |msg_type| &record.msg_type == msg_type)
+                && self
+                    .executor
+                    .as_ref()
+                    .map_or(true, |executor| record.executor.as_ref() == Some(executor))
+                && self
+                    .min_block_height
+                    .map_or(true, |min| record.block_height >= min)
+                && self
+                    .max_block_height
+                    .map_or(true, |max| record.block_height <= max)
+                && self
+                    .type_url
+                    .as_ref()
+                    .map_or(true, |type_url| record.type_urls.contains(type_url))
+                && self
+                    .category
+                    .as_ref()
+                    .map_or(true, |category| record.categories.contains(category))
+        }
+    }
+
+    /// PendingTransfer is an ICS-20 transfer sent to an ICA, waiting for its acknowledgement.
+    #[cw_serde]
+    pub struct PendingTransfer {
+        /// The token ID of the NFT.
+        pub token_id: String,
+        /// The sequence of the transaction record of the transfer.
+        pub sequence: u64,
+        /// The sender of the funds, which receives the refund if the transfer fails.
+        pub sender: Addr,
+        /// The transferred amount.
+        pub amount: Coin,
+    }
+
+    /// PendingWithdrawal is an ICS-20 transfer sent by an ICA back to this chain, waiting to be
+    /// received by this contract through ibc-hooks or to time out.
+    #[cw_serde]
+    pub struct PendingWithdrawal {
+        /// The address on this chain that receives the funds.
+        pub receiver: Addr,
+        /// The address of the ICA sending the transfer.
+        pub ica_address: String,
+        /// The ICS-20 channel ID on this chain that receives the transfer.
+        pub destination_channel: String,
+        /// The packet sequence of the transfer on the host chain, known once the ICA transaction
+        /// is acknowledged.
+        #[serde(default)]
+        pub host_sequence: Option<u64>,
+        /// The timeout of the transfer. The transfer cannot be received after it.
+        pub timeout: Timestamp,
     }
 
     impl TransactionMsgType {
@@ -174,37 +774,475 @@ pub mod history {
         }
     }
 
+    impl VoteRecord {
+        /// Returns the [`VoteRecord`] of the given [`CosmosMsg`], if it is a vote.
+        pub fn from_cosmos_msg(msg: &CosmosMsg) -> Option<Self> {
+            match msg {
+                CosmosMsg::Gov(GovMsg::Vote { proposal_id, vote }) => Some(Self {
+                    proposal_id: *proposal_id,
+                    options: vec![WeightedVoteOption {
+                        option: vote.clone(),
+                        weight: Decimal::one(),
+                    }],
+                }),
+                CosmosMsg::Gov(GovMsg::VoteWeighted {
+                    proposal_id,
+                    options,
+                }) => Some(Self {
+                    proposal_id: *proposal_id,
+                    options: options.clone(),
+                }),
+                _ => None,
+            }
+        }
+    }
+
     impl TransactionRecord {
         /// Creates a new [`TransactionRecord`] from the given [`IcaControllerExecuteMsg`].
         pub fn from_ica_msg(
             msg: &IcaControllerExecuteMsg,
             token_id: impl Into<String>,
+            chain: impl Into<String>,
             owner: impl Into<String>,
             block_height: u64,
             timestamp: u64,
         ) -> Option<Self> {
-            let msg_type = match msg {
+            let (msg_type, vote, type_urls) = match msg {
                 IcaControllerExecuteMsg::SendCosmosMsgs { messages, .. } => {
-                    if messages.is_empty() {
-                        TransactionMsgType::Empty
-                    } else if messages.len() == 1 {
-                        TransactionMsgType::from_cosmos_msg(&messages[0])
-                    } else {
-                        TransactionMsgType::MultiMsg
+                    let type_urls = messages
+                        .iter()
+                        .filter_map(type_url::msg_type_url)
+                        .map(String::from)
+                        .collect();
+                    match messages.as_slice() {
+                        [] => (TransactionMsgType::Empty, None, type_urls),
+                        [msg] => (
+                            TransactionMsgType::from_cosmos_msg(msg),
+                            VoteRecord::from_cosmos_msg(msg),
+                            type_urls,
+                        ),
+                        _ => (TransactionMsgType::MultiMsg, None, type_urls),
                     }
                 }
-                IcaControllerExecuteMsg::SendCustomIcaMessages { .. } => TransactionMsgType::Custom,
+                IcaControllerExecuteMsg::SendCustomIcaMessages { messages, .. } => (
+                    TransactionMsgType::Custom,
+                    None,
+                    type_url::custom_msg_type_urls(messages),
+                ),
                 _ => return None,
             };
 
             Some(Self {
+                sequence: 0,
+                activity_id: 0,
                 status: TransactionStatus::Pending,
                 token_id: token_id.into(),
+                chain: chain.into(),
                 owner: owner.into(),
+                executor: None,
                 msg_type,
                 block_height,
                 timestamp,
+                vote,
+                results: vec![],
+                type_urls,
+                categories: vec![],
+            })
+        }
+    }
+
+    /// Returns the sequence of the next record of the given token.
+    pub fn next_sequence(storage: &dyn Storage, token_id: &str) -> StdResult<u64> {
+        Ok(TX_HISTORY_SEQUENCE
+            .may_load(storage, token_id)?
+            .unwrap_or_default())
+    }
+
+    /// Saves a new record to the history of its token, setting its sequence and activity ID, and
+    /// indexes it in the activity feeds.
+    /// Returns the sequence of the record.
+    pub fn push(storage: &mut dyn Storage, record: &TransactionRecord) -> StdResult<u64> {
+        let sequence = next_sequence(storage, &record.token_id)?;
+        let activity_id = ACTIVITY_SEQUENCE.may_load(storage)?.unwrap_or_default();
+        let record = TransactionRecord {
+            sequence,
+            activity_id,
+            ..record.clone()
+        };
+        let count = len(storage, &record.token_id)?;
+        TX_HISTORY.save(storage, (record.token_id.as_str(), sequence), &record)?;
+        TX_HISTORY_SEQUENCE.save(storage, &record.token_id, &(sequence + 1))?;
+        TX_HISTORY_COUNT.save(storage, &record.token_id, &(count + 1))?;
+
+        // index the record in the activity feeds
+        let key = (record.token_id.clone(), sequence);
+        ACTIVITY_FEED.save(storage, activity_id, &key)?;
+        if let Some(executor) = &record.executor {
+            EXECUTOR_ACTIVITY.save(storage, (executor, activity_id), &key)?;
+        }
+        ACTIVITY_SEQUENCE.save(storage, &(activity_id + 1))?;
+
+        Ok(sequence)
+    }
+
+    /// Saves a new pending record to the history of its token and queues its sequence
+    /// to be resolved by the acknowledgement from the given ICA controller.
+    /// Returns the sequence of the record.
+    pub fn push_pending(
+        storage: &mut dyn Storage,
+        controller: &str,
+        record: &TransactionRecord,
+    ) -> StdResult<u64> {
+        let sequence = push(storage, record)?;
+
+        let prefix = get_pending_txs_prefix(controller);
+        let pending_store: Deque<u64> = Deque::new(&prefix);
+        pending_store.push_back(storage, &sequence)?;
+
+        Ok(sequence)
+    }
+
+    /// Sets the status of the oldest pending record sent by the given ICA controller.
+    /// Since ICA channels are ordered, packets are acknowledged in the order they were sent.
+    /// Returns the sequence and the updated record, or `None` if there are no pending records.
+    pub fn resolve_pending(
+        storage: &mut dyn Storage,
+        controller: &str,
+        token_id: &str,
+        status: TransactionStatus,
+    ) -> StdResult<Option<(u64, TransactionRecord)>> {
+        let prefix = get_pending_txs_prefix(controller);
+        let pending_store: Deque<u64> = Deque::new(&prefix);
+        let Some(sequence) = pending_store.pop_front(storage)? else {
+            return Ok(None);
+        };
+
+        let record = update_status(storage, token_id, sequence, status)?;
+
+        Ok(Some((sequence, record)))
+    }
+
+    /// Sets the status of the record with the given sequence.
+    /// Returns the updated record.
+    pub fn update_status(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+        status: TransactionStatus,
+    ) -> StdResult<TransactionRecord> {
+        let mut record = TX_HISTORY.load(storage, (token_id, sequence))?;
+        record.status = status;
+        TX_HISTORY.save(storage, (token_id, sequence), &record)?;
+
+        Ok(record)
+    }
+
+    /// Returns the number of stored records of the given token.
+    pub fn len(storage: &dyn Storage, token_id: &str) -> StdResult<u64> {
+        Ok(TX_HISTORY_COUNT
+            .may_load(storage, token_id)?
+            .unwrap_or_default())
+    }
+
+    /// Removes the record with the given sequence with its payloads and activity indexes.
+    /// Returns the removed record.
+    pub fn remove(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+    ) -> StdResult<TransactionRecord> {
+        let record = TX_HISTORY.load(storage, (token_id, sequence))?;
+        let count = len(storage, token_id)?;
+
+        TX_HISTORY.remove(storage, (token_id, sequence));
+        TX_PAYLOADS.remove(storage, (token_id, sequence));
+        TX_HISTORY_COUNT.save(storage, token_id, &count.saturating_sub(1))?;
+        ACTIVITY_FEED.remove(storage, record.activity_id);
+        if let Some(executor) = &record.executor {
+            EXECUTOR_ACTIVITY.remove(storage, (executor, record.activity_id));
+        }
+
+        Ok(record)
+    }
+
+    /// Removes the records of the given token that are not pending and whose sequence is
+    /// lower than the given one. Returns the removed records.
+    pub fn prune(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        before: u64,
+    ) -> StdResult<Vec<TransactionRecord>> {
+        let sequences = TX_HISTORY
+            .prefix(token_id)
+            .range(
+                storage,
+                None,
+                Some(Bound::exclusive(before)),
+                Order::Ascending,
+            )
+            .filter(|item| {
+                item.as_ref()
+                    .map_or(true, |(_, record)| !record.status.is_pending())
+            })
+            .map(|item| item.map(|(sequence, _)| sequence))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        sequences
+            .into_iter()
+            .map(|sequence| remove(storage, token_id, sequence))
+            .collect()
+    }
+
+    /// Evicts the oldest records of the given token that are not pending until the history
+    /// fits in the maximum size of the contract, if any. Returns the evicted records.
+    pub fn evict(storage: &mut dyn Storage, token_id: &str) -> StdResult<Vec<TransactionRecord>> {
+        let Some(max) = STATE.load(storage)?.max_history_per_token else {
+            return Ok(vec![]);
+        };
+        let excess = len(storage, token_id)?.saturating_sub(max);
+        if excess == 0 {
+            return Ok(vec![]);
+        }
+
+        let sequences = TX_HISTORY
+            .prefix(token_id)
+            .range(storage, None, None, Order::Ascending)
+            .filter(|item| {
+                item.as_ref()
+                    .map_or(true, |(_, record)| !record.status.is_pending())
             })
+            .take(excess as usize)
+            .map(|item| item.map(|(sequence, _)| sequence))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        sequences
+            .into_iter()
+            .map(|sequence| remove(storage, token_id, sequence))
+            .collect()
+    }
+
+    /// Returns the categories of the given type URLs, without duplicates.
+    pub fn categories(storage: &dyn Storage, type_urls: &[String]) -> StdResult<Vec<String>> {
+        let mut categories = Vec::new();
+        for type_url in type_urls {
+            if let Some(category) = MSG_CATEGORIES.may_load(storage, type_url)? {
+                if !categories.contains(&category) {
+                    categories.push(category);
+                }
+            }
+        }
+
+        Ok(categories)
+    }
+
+    /// Sets the decoded message results of the record with the given sequence.
+    pub fn set_results(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+        results: Vec<MsgResult>,
+    ) -> StdResult<()> {
+        let mut record = TX_HISTORY.load(storage, (token_id, sequence))?;
+        record.results = results;
+        TX_HISTORY.save(storage, (token_id, sequence), &record)
+    }
+
+    /// Stores the message sent by the record with the given sequence, if payloads are stored.
+    pub fn save_msg_payload(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+        msg: &IcaControllerExecuteMsg,
+    ) -> StdResult<()> {
+        let Some(max_size) = STATE.load(storage)?.max_payload_size else {
+            return Ok(());
+        };
+
+        let msg = to_json_binary(msg)?;
+        let mut payload = TX_PAYLOADS
+            .may_load(storage, (token_id, sequence))?
+            .unwrap_or_default();
+        if msg.len() as u64 <= max_size {
+            payload.msg = Some(msg);
+        } else {
+            payload.truncated = true;
+        }
+
+        TX_PAYLOADS.save(storage, (token_id, sequence), &payload)
+    }
+
+    /// Stores the acknowledgement of the record with the given sequence, if payloads are stored.
+    pub fn save_ack_payload(
+        storage: &mut dyn Storage,
+        token_id: &str,
+        sequence: u64,
+        ack: AckPayload,
+    ) -> StdResult<()> {
+        let Some(max_size) = STATE.load(storage)?.max_payload_size else {
+            return Ok(());
+        };
+
+        let mut payload = TX_PAYLOADS
+            .may_load(storage, (token_id, sequence))?
+            .unwrap_or_default();
+        if ack.len() as u64 <= max_size {
+            payload.ack = Some(ack);
+        } else {
+            payload.truncated = true;
+        }
+
+        TX_PAYLOADS.save(storage, (token_id, sequence), &payload)
+    }
+}
+
+/// This module contains the types used to store the auto-compound configurations.
+pub mod compound {
+    use super::*;
+    use cosmwasm_std::{Coin, Timestamp};
+
+    /// AutoCompoundConfig is the auto-compound configuration chosen by the owner of a token.
+    #[cw_serde]
+    pub struct AutoCompoundConfig {
+        /// The validator address on the host chain whose rewards are compounded.
+        pub validator: String,
+        /// The minimum number of seconds between two compounds.
+        pub frequency: u64,
+        /// The minimum amount of withdrawn rewards that are delegated, in the staking denom of
+        /// the host chain. Smaller rewards are left in the ICA.
+        pub min_amount: Coin,
+        /// The optional bounty paid to the keeper that triggers a compound.
+        #[serde(default)]
+        pub bounty: Option<Coin>,
+    }
+
+    /// AutoCompound is the stored auto-compound state of a token.
+    #[cw_serde]
+    pub struct AutoCompound {
+        /// The alias of the chain of the ICA.
+        pub chain: String,
+        /// The configuration chosen by the owner of the token.
+        pub config: AutoCompoundConfig,
+        /// The time of the last compound, if any.
+        pub last_compound: Option<Timestamp>,
+        /// The remaining balance of the keeper bounty, in the denom of the bounty.
+        pub bounty_balance: Uint128,
+        /// The owner of the token who deposited the bounty balance, refunded when
+        /// auto-compounding is disabled or the token is transferred.
+        pub depositor: Addr,
+    }
+
+    impl AutoCompound {
+        /// Returns the earliest time of the next compound.
+        pub fn next_compound(&self) -> Option<Timestamp> {
+            self.last_compound
+                .map(|last| last.plus_seconds(self.config.frequency))
+        }
+    }
+
+    /// CompoundStep is the step of a compound in progress.
+    #[cw_serde]
+    pub enum CompoundStep {
+        /// The rewards are being withdrawn.
+        Withdrawing,
+        /// The rewards were withdrawn, and are delegated by the next compound.
+        Withdrawn {
+            /// The withdrawn rewards in the denom of the minimum amount.
+            amount: Coin,
+        },
+        /// The withdrawn rewards are being delegated.
+        Delegating,
+    }
+
+    /// PendingCompound is a compound in progress. The rewards are withdrawn first, so that only
+    /// the withdrawn amount is delegated.
+    #[cw_serde]
+    pub struct PendingCompound {
+        /// The keeper that sent the current step, paid the reserved bounty once the rewards
+        /// are delegated.
+        pub keeper: Addr,
+        /// The depositor of the reserved bounty, refunded if auto-compounding is disabled.
+        pub depositor: Addr,
+        /// The bounty reserved from the bounty balance, if any.
+        pub bounty: Option<Coin>,
+        /// The sequence of the transaction record of the current step.
+        pub sequence: u64,
+        /// The current step.
+        pub step: CompoundStep,
+    }
+}
+
+/// This module contains the types used to store the contracts notified by the coordinator.
+pub mod hooks {
+    use super::*;
+
+    /// CallbackReceiver is a contract that receives the callbacks of the ICA transactions of
+    /// a token.
+    #[cw_serde]
+    pub struct CallbackReceiver {
+        /// The owner of the token who registered the receiver. Only the transactions sent while
+        /// this address owns the token are forwarded.
+        pub owner: Addr,
+        /// The address of the receiver contract.
+        pub addr: Addr,
+    }
+}
+
+/// This module contains the types used to pause the contract in an emergency.
+pub mod pause {
+    use cosmwasm_schema::cw_serde;
+
+    /// PausableOperation is an operation that can be paused in an emergency.
+    #[cw_serde]
+    #[derive(Copy)]
+    pub enum PausableOperation {
+        /// Minting ICA NFTs and creating additional ICAs for existing tokens.
+        Minting,
+        /// Sending messages and funds to the ICAs.
+        IcaExecution,
+        /// Transferring ICA NFTs through the cw721-ica-extension contract.
+        Transfers,
+    }
+
+    impl std::fmt::Display for PausableOperation {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Minting => write!(f, "minting"),
+                Self::IcaExecution => write!(f, "ica_execution"),
+                Self::Transfers => write!(f, "transfers"),
+            }
+        }
+    }
+
+    /// PauseFlags are the paused operations. The ICA callbacks are still processed while
+    /// paused, so that the history stays correct.
+    #[cw_serde]
+    #[derive(Default)]
+    pub struct PauseFlags {
+        /// Whether minting is paused.
+        pub minting: bool,
+        /// Whether sending messages to the ICAs is paused.
+        pub ica_execution: bool,
+        /// Whether transferring the ICA NFTs is paused.
+        pub transfers: bool,
+    }
+
+    impl PauseFlags {
+        /// Returns whether the given operation is paused.
+        pub fn is_paused(&self, operation: PausableOperation) -> bool {
+            match operation {
+                PausableOperation::Minting => self.minting,
+                PausableOperation::IcaExecution => self.ica_execution,
+                PausableOperation::Transfers => self.transfers,
+            }
+        }
+
+        /// Pauses or unpauses the given operation.
+        pub fn set(&mut self, operation: PausableOperation, paused: bool) {
+            match operation {
+                PausableOperation::Minting => self.minting = paused,
+                PausableOperation::IcaExecution => self.ica_execution = paused,
+                PausableOperation::Transfers => self.transfers = paused,
+            }
         }
     }
 }
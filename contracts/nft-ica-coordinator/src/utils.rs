@@ -1,10 +1,14 @@
 //! This module contains utilities for the contract.
 
+use cosmos_sdk_proto::cosmos::base::v1beta1::Coin as ProtoCoin;
 use cosmwasm_std::{
-    instantiate2_address, Addr, Api, Binary, CosmosMsg, Env, QuerierWrapper, WasmMsg,
+    instantiate2_address, Addr, Api, Binary, Coin, CosmosMsg, Env, HexBinary, QuerierWrapper,
+    WasmMsg,
 };
+use prost::Message;
+use sha2::{Digest, Sha256};
 
-use crate::types::ContractError;
+use crate::types::{state::fees::PacketFee, ContractError};
 
 /// Instantiate a contract using the instantiate2 pattern.
 /// Returns the instantiate2 message and the contract address.
@@ -40,11 +44,65 @@ pub fn instantiate2_contract(
     Ok((instantiate_msg.into(), contract_addr))
 }
 
+/// Verifies a sha256 merkle proof of the given data against the given root.
+/// Each pair of hashes is sorted before hashing, so the proof does not need to
+/// encode the position of the leaf in the tree.
+pub fn verify_merkle_proof(root: &[u8], data: &[u8], proof: &[HexBinary]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(Sha256::digest(data).to_vec(), |hash, sibling| {
+            let (left, right) = if hash.as_slice() <= sibling.as_slice() {
+                (hash.as_slice(), sibling.as_slice())
+            } else {
+                (sibling.as_slice(), hash.as_slice())
+            };
+
+            Sha256::new()
+                .chain_update(left)
+                .chain_update(right)
+                .finalize()
+                .to_vec()
+        });
+
+    computed == root
+}
+
+/// Converts the given coin to its protobuf representation.
+fn proto_coin(coin: Coin) -> ProtoCoin {
+    ProtoCoin {
+        denom: coin.denom,
+        amount: coin.amount.to_string(),
+    }
+}
+
+/// The packet fee of the ICS-29 fee middleware, also used by the Neutron feerefunder module.
+#[derive(Clone, PartialEq, Message)]
+struct Fee {
+    #[prost(message, repeated, tag = "1")]
+    recv_fee: Vec<ProtoCoin>,
+    #[prost(message, repeated, tag = "2")]
+    ack_fee: Vec<ProtoCoin>,
+    #[prost(message, repeated, tag = "3")]
+    timeout_fee: Vec<ProtoCoin>,
+}
+
+/// Converts the given packet fee to its protobuf representation.
+fn proto_fee(fee: &PacketFee) -> Fee {
+    let proto_coins =
+        |coins: &[Coin]| -> Vec<ProtoCoin> { coins.iter().cloned().map(proto_coin).collect() };
+
+    Fee {
+        recv_fee: proto_coins(&fee.recv_fee),
+        ack_fee: proto_coins(&fee.ack_fee),
+        timeout_fee: proto_coins(&fee.timeout_fee),
+    }
+}
+
 /// Contains the storage utilities.
 pub mod storage {
     use cosmwasm_schema::cw_serde;
-    use cosmwasm_std::{StdResult, Storage};
-    use cw_storage_plus::{Map, PrimaryKey};
+    use cosmwasm_std::{Order, StdResult, Storage};
+    use cw_storage_plus::{Bound, KeyDeserialize, Map, PrimaryKey};
 
     /// A set of keys.
     pub struct KeySet<'a, K>(Map<'a, K, NoValue>);
@@ -76,50 +134,116 @@ pub mod storage {
         pub fn remove(&self, store: &mut dyn Storage, key: K) {
             self.0.remove(store, key)
         }
+
+        /// Iterate over the keys in the given range.
+        pub fn keys<'c>(
+            &self,
+            store: &'c dyn Storage,
+            min: Option<Bound<'a, K>>,
+            max: Option<Bound<'a, K>>,
+            order: Order,
+        ) -> Box<dyn Iterator<Item = StdResult<K::Output>> + 'c>
+        where
+            K: KeyDeserialize,
+            K::Output: 'static,
+        {
+            self.0.keys(store, min, max, order)
+        }
     }
 
-    /// The bi-directional map between ICA addresses and NFT IDs.
-    pub struct NftIcaBiMap<'a, 'b>(Map<'a, &'b str, String>);
+    /// The bi-directional map between ICA controller addresses and (NFT ID, chain alias) pairs.
+    pub struct NftIcaBiMap<'a, 'b> {
+        controller_to_nft: Map<'a, &'b str, (String, String)>,
+        nft_to_controller: Map<'a, (&'b str, &'b str), String>,
+    }
 
     impl<'a, 'b> NftIcaBiMap<'a, 'b> {
-        /// Create a new bi-directional map between ICA addresses and NFT IDs.
-        pub const fn new(namespace: &'a str) -> Self {
-            Self(Map::new(namespace))
+        /// Create a new bi-directional map between ICA controller addresses and NFT IDs.
+        pub const fn new(controller_namespace: &'a str, nft_namespace: &'a str) -> Self {
+            Self {
+                controller_to_nft: Map::new(controller_namespace),
+                nft_to_controller: Map::new(nft_namespace),
+            }
         }
 
-        /// Insert a new ICA address and NFT ID pair.
+        /// Insert a new ICA controller address and (NFT ID, chain alias) pair.
         pub fn insert(
             &self,
             store: &mut dyn Storage,
-            ica_addr: impl Into<String>,
-            nft_id: impl Into<String>,
+            controller: &str,
+            nft_id: &str,
+            chain: &str,
         ) -> StdResult<()> {
-            let ica_addr = ica_addr.into();
-            let nft_id = nft_id.into();
-
-            self.0.save(store, &ica_addr, &nft_id)?;
-            self.0.save(store, &nft_id, &ica_addr)?;
+            self.controller_to_nft.save(
+                store,
+                controller,
+                &(nft_id.to_string(), chain.to_string()),
+            )?;
+            self.nft_to_controller
+                .save(store, (nft_id, chain), &controller.to_string())?;
 
             Ok(())
         }
 
-        /// Get the value associated with the given key.
-        pub fn load(&self, store: &dyn Storage, key: &str) -> StdResult<String> {
-            self.0.load(store, key)
+        /// Get the (NFT ID, chain alias) pair associated with the given ICA controller address.
+        pub fn load_nft(
+            &self,
+            store: &dyn Storage,
+            controller: &str,
+        ) -> StdResult<(String, String)> {
+            self.controller_to_nft.load(store, controller)
         }
 
-        /// Get the value associated with the given key if the key is present.
-        pub fn may_load(&self, store: &dyn Storage, key: &str) -> StdResult<Option<String>> {
-            self.0.may_load(store, key)
+        /// Get the (NFT ID, chain alias) pair associated with the given ICA controller address
+        /// if the address is present.
+        pub fn may_load_nft(
+            &self,
+            store: &dyn Storage,
+            controller: &str,
+        ) -> StdResult<Option<(String, String)>> {
+            self.controller_to_nft.may_load(store, controller)
         }
 
-        /// Remove the value associated with the given key and vice versa.
-        /// Does not return an error if the key does not exist.
-        /// Returns an error if there are issues parsing the value associated with the given key.
-        pub fn remove(&self, store: &mut dyn Storage, key: &str) -> StdResult<()> {
-            if let Some(value) = self.0.may_load(store, key)? {
-                self.0.remove(store, key);
-                self.0.remove(store, &value);
+        /// Get the ICA controller address associated with the given NFT ID and chain alias.
+        pub fn load_controller(
+            &self,
+            store: &dyn Storage,
+            nft_id: &str,
+            chain: &str,
+        ) -> StdResult<String> {
+            self.nft_to_controller.load(store, (nft_id, chain))
+        }
+
+        /// Get the ICA controller address associated with the given NFT ID and chain alias
+        /// if the pair is present.
+        pub fn may_load_controller(
+            &self,
+            store: &dyn Storage,
+            nft_id: &str,
+            chain: &str,
+        ) -> StdResult<Option<String>> {
+            self.nft_to_controller.may_load(store, (nft_id, chain))
+        }
+
+        /// Get all the (chain alias, ICA controller address) pairs associated with the given NFT ID.
+        pub fn controllers(
+            &self,
+            store: &dyn Storage,
+            nft_id: &str,
+        ) -> StdResult<Vec<(String, String)>> {
+            self.nft_to_controller
+                .prefix(nft_id)
+                .range(store, None, None, Order::Ascending)
+                .collect()
+        }
+
+        /// Remove the given ICA controller address and its associated (NFT ID, chain alias) pair.
+        /// Does not return an error if the controller address does not exist.
+        pub fn remove(&self, store: &mut dyn Storage, controller: &str) -> StdResult<()> {
+            if let Some((nft_id, chain)) = self.controller_to_nft.may_load(store, controller)? {
+                self.controller_to_nft.remove(store, controller);
+                self.nft_to_controller
+                    .remove(store, (nft_id.as_str(), chain.as_str()));
             }
 
             Ok(())
@@ -136,51 +260,1194 @@ pub mod storage {
         fn test_nft_ica_bi_map() {
             let mut storage = MockStorage::new();
 
-            let nft_ica_bi_map = NftIcaBiMap::new("nft_ica_bi_map");
+            let nft_ica_bi_map = NftIcaBiMap::new("controller_nft_map", "nft_controller_map");
 
             nft_ica_bi_map
-                .insert(&mut storage, "ica-addr-1", "nft-id-1")
+                .insert(&mut storage, "controller-1", "nft-id-1", "cosmoshub")
+                .unwrap();
+            nft_ica_bi_map
+                .insert(&mut storage, "controller-2", "nft-id-1", "osmosis")
                 .unwrap();
             nft_ica_bi_map
-                .insert(&mut storage, "ica-addr-2", "nft-id-2")
+                .insert(&mut storage, "controller-3", "nft-id-2", "cosmoshub")
                 .unwrap();
 
             assert_eq!(
-                nft_ica_bi_map.load(&storage, "ica-addr-1").unwrap(),
-                "nft-id-1"
+                nft_ica_bi_map.load_nft(&storage, "controller-1").unwrap(),
+                ("nft-id-1".to_string(), "cosmoshub".to_string())
+            );
+            assert_eq!(
+                nft_ica_bi_map.load_nft(&storage, "controller-2").unwrap(),
+                ("nft-id-1".to_string(), "osmosis".to_string())
+            );
+            assert_eq!(
+                nft_ica_bi_map
+                    .load_controller(&storage, "nft-id-1", "osmosis")
+                    .unwrap(),
+                "controller-2"
+            );
+            assert_eq!(
+                nft_ica_bi_map
+                    .load_controller(&storage, "nft-id-2", "cosmoshub")
+                    .unwrap(),
+                "controller-3"
+            );
+            assert_eq!(
+                nft_ica_bi_map.controllers(&storage, "nft-id-1").unwrap(),
+                vec![
+                    ("cosmoshub".to_string(), "controller-1".to_string()),
+                    ("osmosis".to_string(), "controller-2".to_string()),
+                ]
+            );
+
+            nft_ica_bi_map.remove(&mut storage, "controller-1").unwrap();
+
+            assert!(nft_ica_bi_map
+                .may_load_nft(&storage, "controller-1")
+                .unwrap()
+                .is_none());
+            assert!(nft_ica_bi_map
+                .may_load_controller(&storage, "nft-id-1", "cosmoshub")
+                .unwrap()
+                .is_none());
+            assert_eq!(
+                nft_ica_bi_map.controllers(&storage, "nft-id-1").unwrap(),
+                vec![("osmosis".to_string(), "controller-2".to_string())]
+            );
+
+            // removing a missing controller is a no-op
+            nft_ica_bi_map.remove(&mut storage, "controller-1").unwrap();
+            assert_eq!(
+                nft_ica_bi_map.load_nft(&storage, "controller-3").unwrap(),
+                ("nft-id-2".to_string(), "cosmoshub".to_string())
+            );
+        }
+    }
+}
+
+/// Contains the type URLs of the messages sent to the ICAs, independently of the ICA backend.
+pub mod type_url {
+    use cosmos_sdk_proto::Any;
+    use cosmwasm_std::{
+        from_json, BankMsg, CosmosMsg, DistributionMsg, GovMsg, IbcMsg, StakingMsg,
+    };
+    use prost::Message;
+    use serde::Deserialize;
+
+    /// The type URL of the bank send message.
+    pub const MSG_SEND_TYPE_URL: &str = "/cosmos.bank.v1beta1.MsgSend";
+    /// The type URL of the staking delegate message.
+    pub const MSG_DELEGATE_TYPE_URL: &str = "/cosmos.staking.v1beta1.MsgDelegate";
+    /// The type URL of the staking undelegate message.
+    pub const MSG_UNDELEGATE_TYPE_URL: &str = "/cosmos.staking.v1beta1.MsgUndelegate";
+    /// The type URL of the staking redelegate message.
+    pub const MSG_BEGIN_REDELEGATE_TYPE_URL: &str = "/cosmos.staking.v1beta1.MsgBeginRedelegate";
+    /// The type URL of the distribution set withdraw address message.
+    pub const MSG_SET_WITHDRAW_ADDRESS_TYPE_URL: &str =
+        "/cosmos.distribution.v1beta1.MsgSetWithdrawAddress";
+    /// The type URL of the distribution withdraw delegator reward message.
+    pub const MSG_WITHDRAW_DELEGATOR_REWARD_TYPE_URL: &str =
+        "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward";
+    /// The type URL of the governance vote message.
+    pub const MSG_VOTE_TYPE_URL: &str = "/cosmos.gov.v1beta1.MsgVote";
+    /// The type URL of the governance weighted vote message.
+    pub const MSG_VOTE_WEIGHTED_TYPE_URL: &str = "/cosmos.gov.v1beta1.MsgVoteWeighted";
+    /// The type URL of the ICS-20 transfer message.
+    pub const MSG_TRANSFER_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+
+    /// A proto3 encoded `CosmosTx`, the data of an ICA transaction.
+    #[derive(Clone, PartialEq, Message)]
+    pub struct CosmosTx {
+        /// The messages of the transaction.
+        #[prost(message, repeated, tag = "1")]
+        pub messages: Vec<Any>,
+    }
+
+    /// A JSON encoded message of a custom ICA transaction, identified by its type URL.
+    #[derive(Deserialize)]
+    struct JsonAny {
+        #[serde(rename = "@type")]
+        type_url: String,
+    }
+
+    /// A JSON encoded `CosmosTx` of a custom ICA transaction.
+    #[derive(Deserialize)]
+    struct JsonCosmosTx {
+        messages: Vec<JsonAny>,
+    }
+
+    /// Returns the type URL of the host message the given message is converted to, if supported.
+    pub fn msg_type_url(msg: &CosmosMsg) -> Option<&str> {
+        let type_url = match msg {
+            CosmosMsg::Stargate { type_url, .. } => type_url,
+            CosmosMsg::Bank(BankMsg::Send { .. }) => MSG_SEND_TYPE_URL,
+            CosmosMsg::Staking(StakingMsg::Delegate { .. }) => MSG_DELEGATE_TYPE_URL,
+            CosmosMsg::Staking(StakingMsg::Undelegate { .. }) => MSG_UNDELEGATE_TYPE_URL,
+            CosmosMsg::Staking(StakingMsg::Redelegate { .. }) => MSG_BEGIN_REDELEGATE_TYPE_URL,
+            CosmosMsg::Distribution(DistributionMsg::SetWithdrawAddress { .. }) => {
+                MSG_SET_WITHDRAW_ADDRESS_TYPE_URL
+            }
+            CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward { .. }) => {
+                MSG_WITHDRAW_DELEGATOR_REWARD_TYPE_URL
+            }
+            CosmosMsg::Gov(GovMsg::Vote { .. }) => MSG_VOTE_TYPE_URL,
+            CosmosMsg::Gov(GovMsg::VoteWeighted { .. }) => MSG_VOTE_WEIGHTED_TYPE_URL,
+            CosmosMsg::Ibc(IbcMsg::Transfer { .. }) => MSG_TRANSFER_TYPE_URL,
+            _ => return None,
+        };
+
+        Some(type_url)
+    }
+
+    /// Returns the type URLs of the messages of a custom ICA transaction. The messages are
+    /// either a JSON list of messages, a JSON `CosmosTx`, or a proto3 encoded `CosmosTx`.
+    /// Returns an empty list if the messages cannot be parsed.
+    pub fn custom_msg_type_urls(messages: &[u8]) -> Vec<String> {
+        let json_messages = from_json::<Vec<JsonAny>>(messages)
+            .or_else(|_| from_json::<JsonCosmosTx>(messages).map(|tx| tx.messages));
+        if let Ok(json_messages) = json_messages {
+            return json_messages.into_iter().map(|msg| msg.type_url).collect();
+        }
+
+        CosmosTx::decode(messages)
+            .map(|tx| tx.messages.into_iter().map(|msg| msg.type_url).collect())
+            .unwrap_or_default()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_custom_msg_type_urls() {
+            // JSON list of messages
+            let json_messages = br#"[
+                {"@type": "/cosmos.bank.v1beta1.MsgSend", "from_address": "cosmos1ica"},
+                {"@type": "/cosmos.staking.v1beta1.MsgDelegate"}
+            ]"#;
+            assert_eq!(
+                custom_msg_type_urls(json_messages),
+                vec![MSG_SEND_TYPE_URL, MSG_DELEGATE_TYPE_URL]
+            );
+
+            // JSON CosmosTx
+            let json_tx = br#"{"messages": [{"@type": "/cosmos.gov.v1beta1.MsgVote"}]}"#;
+            assert_eq!(custom_msg_type_urls(json_tx), vec![MSG_VOTE_TYPE_URL]);
+
+            // proto3 CosmosTx
+            let proto_tx = CosmosTx {
+                messages: vec![
+                    Any {
+                        type_url: MSG_TRANSFER_TYPE_URL.to_string(),
+                        value: vec![],
+                    },
+                    Any {
+                        type_url: "/osmosis.gamm.v1beta1.MsgSwapExactAmountIn".to_string(),
+                        value: vec![1, 2, 3],
+                    },
+                ],
+            };
+            assert_eq!(
+                custom_msg_type_urls(&proto_tx.encode_to_vec()),
+                vec![
+                    MSG_TRANSFER_TYPE_URL,
+                    "/osmosis.gamm.v1beta1.MsgSwapExactAmountIn"
+                ]
+            );
+
+            // messages that cannot be parsed
+            assert!(custom_msg_type_urls(b"not a transaction").is_empty());
+        }
+
+        #[test]
+        fn test_msg_type_url() {
+            let delegate = CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: "cosmosvaloper1".to_string(),
+                amount: cosmwasm_std::coin(1, "uatom"),
+            });
+            assert_eq!(msg_type_url(&delegate), Some(MSG_DELEGATE_TYPE_URL));
+
+            let stargate = CosmosMsg::Stargate {
+                type_url: "/cosmos.authz.v1beta1.MsgExec".to_string(),
+                value: Default::default(),
+            };
+            assert_eq!(
+                msg_type_url(&stargate),
+                Some("/cosmos.authz.v1beta1.MsgExec")
+            );
+
+            let wasm = CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                contract_addr: "contract".to_string(),
+            });
+            assert_eq!(msg_type_url(&wasm), None);
+        }
+    }
+}
+
+/// Contains the messages and helpers of the native ICA controller backend, which uses the
+/// interchaintxs module of Neutron.
+pub mod native_ica {
+    use cosmos_sdk_proto::{
+        cosmos::{
+            bank::v1beta1::MsgSend,
+            base::v1beta1::Coin as ProtoCoin,
+            distribution::v1beta1::{MsgSetWithdrawAddress, MsgWithdrawDelegatorReward},
+            gov::v1beta1::{
+                MsgVote, MsgVoteWeighted, VoteOption as ProtoVoteOption,
+                WeightedVoteOption as ProtoWeightedVoteOption,
+            },
+            staking::v1beta1::{MsgBeginRedelegate, MsgDelegate, MsgUndelegate},
+        },
+        Any,
+    };
+    use cosmwasm_std::{
+        from_json, Addr, BankMsg, Coin, CosmosMsg, DistributionMsg, GovMsg, HexBinary, IbcMsg,
+        StakingMsg, StdResult, VoteOption,
+    };
+    use prost::Message;
+    use serde::Deserialize;
+    use sha2::{Digest, Sha256};
+
+    use super::{
+        ics20, proto_coin, proto_fee,
+        type_url::{
+            CosmosTx, MSG_BEGIN_REDELEGATE_TYPE_URL, MSG_DELEGATE_TYPE_URL, MSG_SEND_TYPE_URL,
+            MSG_SET_WITHDRAW_ADDRESS_TYPE_URL, MSG_UNDELEGATE_TYPE_URL, MSG_VOTE_TYPE_URL,
+            MSG_VOTE_WEIGHTED_TYPE_URL, MSG_WITHDRAW_DELEGATOR_REWARD_TYPE_URL,
+        },
+        Fee,
+    };
+    use crate::types::{keys, state::fees::PacketFee, ContractError};
+
+    const MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL: &str =
+        "/neutron.interchaintxs.v1.MsgRegisterInterchainAccount";
+    const MSG_SUBMIT_TX_TYPE_URL: &str = "/neutron.interchaintxs.v1.MsgSubmitTx";
+
+    /// The `ORDER_ORDERED` channel ordering, so that the acknowledgements are received in the
+    /// order the transactions were sent.
+    const ORDER_ORDERED: i32 = 2;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgRegisterInterchainAccount {
+        #[prost(string, tag = "1")]
+        from_address: String,
+        #[prost(string, tag = "2")]
+        connection_id: String,
+        #[prost(string, tag = "3")]
+        interchain_account_id: String,
+        #[prost(message, repeated, tag = "4")]
+        register_fee: Vec<ProtoCoin>,
+        #[prost(int32, tag = "5")]
+        ordering: i32,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgSubmitTx {
+        #[prost(string, tag = "1")]
+        from_address: String,
+        #[prost(string, tag = "2")]
+        interchain_account_id: String,
+        #[prost(string, tag = "3")]
+        connection_id: String,
+        #[prost(message, repeated, tag = "4")]
+        msgs: Vec<Any>,
+        #[prost(string, tag = "5")]
+        memo: String,
+        #[prost(uint64, tag = "6")]
+        timeout: u64,
+        #[prost(message, optional, tag = "7")]
+        fee: Option<Fee>,
+    }
+
+    /// The part of the ICA channel version metadata needed by the contract.
+    #[derive(Deserialize)]
+    struct IcaMetadata {
+        address: String,
+    }
+
+    /// Returns the interchain account ID of the ICA of the given token on the given chain.
+    /// It is derived from a hash so that the port ID fits in its maximum length for any token ID.
+    pub fn interchain_account_id(token_id: &str, chain: &str) -> String {
+        let hash = Sha256::digest(format!("{}/{}", token_id, chain));
+
+        HexBinary::from(&hash[..16]).to_hex()
+    }
+
+    /// Returns the owner string of the ICA of the given token on the given chain, which is the
+    /// contract address and the interchain account ID separated by a dot, as in its port ID.
+    pub fn ica_owner(contract: &Addr, token_id: &str, chain: &str) -> String {
+        format!("{}.{}", contract, interchain_account_id(token_id, chain))
+    }
+
+    /// Returns the owner string of the ICA controlled through the given port.
+    pub fn owner_from_port(port_id: &str) -> Option<&str> {
+        port_id.strip_prefix(keys::ICA_CONTROLLER_PORT_PREFIX)
+    }
+
+    /// Splits the given owner string into the contract address and the interchain account ID.
+    fn split_owner(owner: &str) -> Result<(&str, &str), ContractError> {
+        owner
+            .split_once('.')
+            .ok_or_else(|| ContractError::UnsupportedIcaMsg(format!("invalid owner {}", owner)))
+    }
+
+    /// Returns the ICA address from the counterparty version of the ICA channel.
+    pub fn ica_address_from_version(version: &str) -> StdResult<String> {
+        Ok(from_json::<IcaMetadata>(version.as_bytes())?.address)
+    }
+
+    /// Returns the message that registers the ICA of the given owner string on the given
+    /// connection, paying the given registration fee from the contract.
+    pub fn register_interchain_account_msg(
+        owner: &str,
+        connection_id: &str,
+        register_fee: &[Coin],
+    ) -> Result<CosmosMsg, ContractError> {
+        let (from_address, interchain_account_id) = split_owner(owner)?;
+        let msg = MsgRegisterInterchainAccount {
+            from_address: from_address.to_string(),
+            connection_id: connection_id.to_string(),
+            interchain_account_id: interchain_account_id.to_string(),
+            register_fee: register_fee.iter().cloned().map(proto_coin).collect(),
+            ordering: ORDER_ORDERED,
+        };
+
+        Ok(CosmosMsg::Stargate {
+            type_url: MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL.to_string(),
+            value: msg.encode_to_vec().into(),
+        })
+    }
+
+    /// Returns the message that submits the given messages to the ICA of the given owner string.
+    /// The given fee is escrowed by the feerefunder module, and refunded to the contract like an
+    /// ICS-29 fee.
+    pub fn submit_tx_msg(
+        owner: &str,
+        connection_id: &str,
+        msgs: Vec<Any>,
+        memo: Option<String>,
+        timeout_seconds: Option<u64>,
+        fee: Option<&PacketFee>,
+    ) -> Result<CosmosMsg, ContractError> {
+        let (from_address, interchain_account_id) = split_owner(owner)?;
+        let msg = MsgSubmitTx {
+            from_address: from_address.to_string(),
+            interchain_account_id: interchain_account_id.to_string(),
+            connection_id: connection_id.to_string(),
+            msgs,
+            memo: memo.unwrap_or_default(),
+            timeout: timeout_seconds.unwrap_or(keys::DEFAULT_ICA_TIMEOUT_SECONDS),
+            fee: fee.map(proto_fee),
+        };
+
+        Ok(CosmosMsg::Stargate {
+            type_url: MSG_SUBMIT_TX_TYPE_URL.to_string(),
+            value: msg.encode_to_vec().into(),
+        })
+    }
+
+    /// Converts the given messages to protobuf [`Any`] messages executed by the given ICA address.
+    pub fn encode_msgs(
+        messages: Vec<CosmosMsg>,
+        ica_address: &str,
+    ) -> Result<Vec<Any>, ContractError> {
+        messages
+            .into_iter()
+            .map(|msg| to_any(msg, ica_address))
+            .collect()
+    }
+
+    /// Returns the messages of the given proto3 encoded `CosmosTx`.
+    pub fn decode_cosmos_tx(data: &[u8]) -> Result<Vec<Any>, ContractError> {
+        CosmosTx::decode(data)
+            .map(|tx| tx.messages)
+            .map_err(|err| ContractError::UnsupportedIcaMsg(format!("invalid CosmosTx: {}", err)))
+    }
+
+    /// Converts the given message to a protobuf [`Any`] signed by the given ICA address.
+    fn to_any(msg: CosmosMsg, ica_address: &str) -> Result<Any, ContractError> {
+        let signer = ica_address.to_string();
+        let any = match msg {
+            CosmosMsg::Stargate { type_url, value } => Any {
+                type_url,
+                value: value.to_vec(),
+            },
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => encode_any(
+                MSG_SEND_TYPE_URL,
+                MsgSend {
+                    from_address: signer,
+                    to_address,
+                    amount: amount.into_iter().map(proto_coin).collect(),
+                },
+            ),
+            CosmosMsg::Staking(StakingMsg::Delegate { validator, amount }) => encode_any(
+                MSG_DELEGATE_TYPE_URL,
+                MsgDelegate {
+                    delegator_address: signer,
+                    validator_address: validator,
+                    amount: Some(proto_coin(amount)),
+                },
+            ),
+            CosmosMsg::Staking(StakingMsg::Undelegate { validator, amount }) => encode_any(
+                MSG_UNDELEGATE_TYPE_URL,
+                MsgUndelegate {
+                    delegator_address: signer,
+                    validator_address: validator,
+                    amount: Some(proto_coin(amount)),
+                },
+            ),
+            CosmosMsg::Staking(StakingMsg::Redelegate {
+                src_validator,
+                dst_validator,
+                amount,
+            }) => encode_any(
+                MSG_BEGIN_REDELEGATE_TYPE_URL,
+                MsgBeginRedelegate {
+                    delegator_address: signer,
+                    validator_src_address: src_validator,
+                    validator_dst_address: dst_validator,
+                    amount: Some(proto_coin(amount)),
+                },
+            ),
+            CosmosMsg::Distribution(DistributionMsg::SetWithdrawAddress { address }) => encode_any(
+                MSG_SET_WITHDRAW_ADDRESS_TYPE_URL,
+                MsgSetWithdrawAddress {
+                    delegator_address: signer,
+                    withdraw_address: address,
+                },
+            ),
+            CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward { validator }) => {
+                encode_any(
+                    MSG_WITHDRAW_DELEGATOR_REWARD_TYPE_URL,
+                    MsgWithdrawDelegatorReward {
+                        delegator_address: signer,
+                        validator_address: validator,
+                    },
+                )
+            }
+            CosmosMsg::Gov(GovMsg::Vote { proposal_id, vote }) => encode_any(
+                MSG_VOTE_TYPE_URL,
+                MsgVote {
+                    proposal_id,
+                    voter: signer,
+                    option: proto_vote_option(vote),
+                },
+            ),
+            CosmosMsg::Gov(GovMsg::VoteWeighted {
+                proposal_id,
+                options,
+            }) => encode_any(
+                MSG_VOTE_WEIGHTED_TYPE_URL,
+                MsgVoteWeighted {
+                    proposal_id,
+                    voter: signer,
+                    options: options
+                        .into_iter()
+                        .map(|option| ProtoWeightedVoteOption {
+                            option: proto_vote_option(option.option),
+                            // sdk.Dec is encoded as an integer with 18 decimal places
+                            weight: option.weight.atomics().to_string(),
+                        })
+                        .collect(),
+                },
+            ),
+            CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id,
+                to_address,
+                amount,
+                timeout,
+            }) => ics20::transfer_any(&signer, channel_id, amount, to_address, timeout, None),
+            msg => return Err(ContractError::UnsupportedIcaMsg(format!("{:?}", msg))),
+        };
+
+        Ok(any)
+    }
+
+    fn encode_any(type_url: &str, msg: impl Message) -> Any {
+        Any {
+            type_url: type_url.to_string(),
+            value: msg.encode_to_vec(),
+        }
+    }
+
+    fn proto_vote_option(vote: VoteOption) -> i32 {
+        match vote {
+            VoteOption::Yes => ProtoVoteOption::Yes as i32,
+            VoteOption::No => ProtoVoteOption::No as i32,
+            VoteOption::Abstain => ProtoVoteOption::Abstain as i32,
+            VoteOption::NoWithVeto => ProtoVoteOption::NoWithVeto as i32,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use cosmwasm_std::{coin, Decimal, IbcTimeout, Timestamp, WasmMsg, WeightedVoteOption};
+
+        use super::{super::type_url::MSG_TRANSFER_TYPE_URL, *};
+
+        fn stargate_value(msg: CosmosMsg, type_url: &str) -> Vec<u8> {
+            match msg {
+                CosmosMsg::Stargate {
+                    type_url: msg_type_url,
+                    value,
+                } => {
+                    assert_eq!(msg_type_url, type_url);
+                    value.to_vec()
+                }
+                msg => panic!("unexpected message {:?}", msg),
+            }
+        }
+
+        #[test]
+        fn test_ica_owner() {
+            let contract = Addr::unchecked("neutron1coordinator");
+            let id = interchain_account_id("ica-token-0", "cosmoshub");
+            assert_eq!(id.len(), 32);
+            assert_ne!(id, interchain_account_id("ica-token-0", "osmosis"));
+
+            let owner = ica_owner(&contract, "ica-token-0", "cosmoshub");
+            assert_eq!(owner, format!("neutron1coordinator.{}", id));
+
+            let port_id = format!("{}{}", keys::ICA_CONTROLLER_PORT_PREFIX, owner);
+            assert_eq!(owner_from_port(&port_id), Some(owner.as_str()));
+            assert_eq!(owner_from_port("wasm.neutron1coordinator"), None);
+        }
+
+        #[test]
+        fn test_register_interchain_account_msg() {
+            let msg = register_interchain_account_msg(
+                "neutron1coordinator.abcd",
+                "connection-0",
+                &[coin(1000, "untrn")],
+            )
+            .unwrap();
+            let value = stargate_value(msg, MSG_REGISTER_INTERCHAIN_ACCOUNT_TYPE_URL);
+
+            let decoded = MsgRegisterInterchainAccount::decode(value.as_slice()).unwrap();
+            assert_eq!(
+                decoded,
+                MsgRegisterInterchainAccount {
+                    from_address: "neutron1coordinator".to_string(),
+                    connection_id: "connection-0".to_string(),
+                    interchain_account_id: "abcd".to_string(),
+                    register_fee: vec![proto_coin(coin(1000, "untrn"))],
+                    ordering: ORDER_ORDERED,
+                }
             );
+
+            assert!(matches!(
+                register_interchain_account_msg("neutron1coordinator", "connection-0", &[]),
+                Err(ContractError::UnsupportedIcaMsg(_))
+            ));
+        }
+
+        #[test]
+        fn test_submit_tx_msg() {
+            let msgs = encode_msgs(
+                vec![CosmosMsg::Staking(StakingMsg::Delegate {
+                    validator: "cosmosvaloper1".to_string(),
+                    amount: coin(100, "uatom"),
+                })],
+                "cosmos1ica",
+            )
+            .unwrap();
+            let fee = PacketFee {
+                recv_fee: vec![coin(1, "untrn")],
+                ack_fee: vec![coin(2, "untrn")],
+                timeout_fee: vec![coin(3, "untrn")],
+            };
+
+            let msg = submit_tx_msg(
+                "neutron1coordinator.abcd",
+                "connection-0",
+                msgs.clone(),
+                Some("memo".to_string()),
+                None,
+                Some(&fee),
+            )
+            .unwrap();
+            let value = stargate_value(msg, MSG_SUBMIT_TX_TYPE_URL);
+
+            let decoded = MsgSubmitTx::decode(value.as_slice()).unwrap();
             assert_eq!(
-                nft_ica_bi_map.load(&storage, "nft-id-1").unwrap(),
-                "ica-addr-1"
+                decoded,
+                MsgSubmitTx {
+                    from_address: "neutron1coordinator".to_string(),
+                    interchain_account_id: "abcd".to_string(),
+                    connection_id: "connection-0".to_string(),
+                    msgs,
+                    memo: "memo".to_string(),
+                    timeout: keys::DEFAULT_ICA_TIMEOUT_SECONDS,
+                    fee: Some(Fee {
+                        recv_fee: vec![proto_coin(coin(1, "untrn"))],
+                        ack_fee: vec![proto_coin(coin(2, "untrn"))],
+                        timeout_fee: vec![proto_coin(coin(3, "untrn"))],
+                    }),
+                }
             );
+
+            let delegate = MsgDelegate::decode(decoded.msgs[0].value.as_slice()).unwrap();
+            assert_eq!(decoded.msgs[0].type_url, MSG_DELEGATE_TYPE_URL);
+            assert_eq!(delegate.delegator_address, "cosmos1ica");
+            assert_eq!(delegate.validator_address, "cosmosvaloper1");
+            assert_eq!(delegate.amount, Some(proto_coin(coin(100, "uatom"))));
+        }
+
+        #[test]
+        fn test_to_any() {
+            let any = to_any(
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "cosmos1receiver".to_string(),
+                    amount: vec![coin(5, "uatom")],
+                }),
+                "cosmos1ica",
+            )
+            .unwrap();
+            assert_eq!(any.type_url, MSG_SEND_TYPE_URL);
             assert_eq!(
-                nft_ica_bi_map.load(&storage, "ica-addr-2").unwrap(),
-                "nft-id-2"
+                MsgSend::decode(any.value.as_slice()).unwrap(),
+                MsgSend {
+                    from_address: "cosmos1ica".to_string(),
+                    to_address: "cosmos1receiver".to_string(),
+                    amount: vec![proto_coin(coin(5, "uatom"))],
+                }
             );
+
+            let any = to_any(
+                CosmosMsg::Gov(GovMsg::VoteWeighted {
+                    proposal_id: 4,
+                    options: vec![WeightedVoteOption {
+                        option: VoteOption::Yes,
+                        weight: Decimal::percent(50),
+                    }],
+                }),
+                "cosmos1ica",
+            )
+            .unwrap();
+            assert_eq!(any.type_url, MSG_VOTE_WEIGHTED_TYPE_URL);
+            let vote = MsgVoteWeighted::decode(any.value.as_slice()).unwrap();
+            assert_eq!(vote.proposal_id, 4);
+            assert_eq!(vote.options[0].option, ProtoVoteOption::Yes as i32);
+            assert_eq!(vote.options[0].weight, "500000000000000000");
+
+            let any = to_any(
+                CosmosMsg::Ibc(IbcMsg::Transfer {
+                    channel_id: "channel-1".to_string(),
+                    to_address: "neutron1receiver".to_string(),
+                    amount: coin(7, "uatom"),
+                    timeout: IbcTimeout::with_timestamp(Timestamp::from_seconds(10)),
+                }),
+                "cosmos1ica",
+            )
+            .unwrap();
+            assert_eq!(any.type_url, MSG_TRANSFER_TYPE_URL);
+
+            assert!(matches!(
+                to_any(
+                    CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                        contract_addr: "contract".to_string(),
+                    }),
+                    "cosmos1ica",
+                ),
+                Err(ContractError::UnsupportedIcaMsg(_))
+            ));
+        }
+
+        #[test]
+        fn test_decode_cosmos_tx() {
+            let messages = vec![Any {
+                type_url: MSG_VOTE_TYPE_URL.to_string(),
+                value: vec![1, 2, 3],
+            }];
+            let data = CosmosTx {
+                messages: messages.clone(),
+            }
+            .encode_to_vec();
+            assert_eq!(decode_cosmos_tx(&data).unwrap(), messages);
+
+            assert!(matches!(
+                decode_cosmos_tx(&[0xff]),
+                Err(ContractError::UnsupportedIcaMsg(_))
+            ));
+        }
+    }
+}
+
+/// Contains the messages of the ICS-20 transfer module.
+pub mod ics20 {
+    use cosmos_sdk_proto::{cosmos::base::v1beta1::Coin as ProtoCoin, Any};
+    use cosmwasm_std::{
+        to_json_string, Addr, Api, CanonicalAddr, Coin, IbcTimeout, StdError, StdResult,
+    };
+    use prost::Message;
+    use serde::Serialize;
+    use sha2::{Digest, Sha256};
+
+    use super::{proto_coin, type_url::MSG_TRANSFER_TYPE_URL};
+    use crate::types::keys;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgTransfer {
+        #[prost(string, tag = "1")]
+        source_port: String,
+        #[prost(string, tag = "2")]
+        source_channel: String,
+        #[prost(message, optional, tag = "3")]
+        token: Option<ProtoCoin>,
+        #[prost(string, tag = "4")]
+        sender: String,
+        #[prost(string, tag = "5")]
+        receiver: String,
+        #[prost(message, optional, tag = "6")]
+        timeout_height: Option<Height>,
+        #[prost(uint64, tag = "7")]
+        timeout_timestamp: u64,
+        #[prost(string, tag = "8")]
+        memo: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Height {
+        #[prost(uint64, tag = "1")]
+        revision_number: u64,
+        #[prost(uint64, tag = "2")]
+        revision_height: u64,
+    }
+
+    /// The proto3 encoded response of an ICS-20 transfer.
+    #[derive(Clone, PartialEq, Message)]
+    pub struct MsgTransferResponse {
+        /// The packet sequence of the transfer.
+        #[prost(uint64, tag = "1")]
+        pub sequence: u64,
+    }
+
+    /// The ibc-hooks memo executing a contract with the received funds.
+    #[derive(Serialize)]
+    struct WasmHookMemo<'a, T> {
+        wasm: WasmHook<'a, T>,
+    }
+
+    #[derive(Serialize)]
+    struct WasmHook<'a, T> {
+        contract: &'a Addr,
+        msg: &'a T,
+    }
+
+    /// Returns the proto3 encoded `MsgTransfer` sending the given amount from the given sender.
+    pub fn transfer_any(
+        sender: &str,
+        channel_id: String,
+        amount: Coin,
+        receiver: String,
+        timeout: IbcTimeout,
+        memo: Option<String>,
+    ) -> Any {
+        let msg = MsgTransfer {
+            source_port: keys::ICS20_PORT_ID.to_string(),
+            source_channel: channel_id,
+            token: Some(proto_coin(amount)),
+            sender: sender.to_string(),
+            receiver,
+            timeout_height: timeout.block().map(|block| Height {
+                revision_number: block.revision,
+                revision_height: block.height,
+            }),
+            timeout_timestamp: timeout.timestamp().map(|t| t.nanos()).unwrap_or_default(),
+            memo: memo.unwrap_or_default(),
+        };
+
+        Any {
+            type_url: MSG_TRANSFER_TYPE_URL.to_string(),
+            value: msg.encode_to_vec(),
+        }
+    }
+
+    /// Returns the ibc-hooks memo executing the given message on the given contract when the
+    /// transfer is received.
+    pub fn wasm_hook_memo<T: Serialize>(contract: &Addr, msg: &T) -> StdResult<String> {
+        to_json_string(&WasmHookMemo {
+            wasm: WasmHook { contract, msg },
+        })
+    }
+
+    /// Returns the address that ibc-hooks uses as the sender of the contract execution of a
+    /// transfer received on the given channel of this chain from the given original sender.
+    pub fn ibc_hooks_sender(
+        api: &dyn Api,
+        channel_id: &str,
+        original_sender: &str,
+    ) -> StdResult<Addr> {
+        let hash = Sha256::new()
+            .chain_update(Sha256::digest(keys::IBC_HOOKS_SENDER_PREFIX))
+            .chain_update(format!("{}/{}", channel_id, original_sender))
+            .finalize();
+
+        api.addr_humanize(&CanonicalAddr::from(hash.to_vec()))
+    }
+
+    /// Returns the packet sequence from the response data of an ICS-20 transfer.
+    pub fn transfer_sequence(data: &[u8]) -> StdResult<u64> {
+        MsgTransferResponse::decode(data)
+            .map(|response| response.sequence)
+            .map_err(|err| StdError::generic_err(format!("invalid transfer response: {}", err)))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use cosmwasm_std::{coin, Timestamp};
+
+        use super::*;
+
+        #[test]
+        fn test_transfer_any() {
+            let memo = wasm_hook_memo(
+                &Addr::unchecked("coordinator"),
+                &crate::types::msg::ExecuteMsg::CompleteWithdrawal {
+                    token_id: "ica-token-0".to_string(),
+                    sequence: 3,
+                },
+            )
+            .unwrap();
             assert_eq!(
-                nft_ica_bi_map.load(&storage, "nft-id-2").unwrap(),
-                "ica-addr-2"
+                memo,
+                r#"{"wasm":{"contract":"coordinator","msg":{"complete_withdrawal":{"token_id":"ica-token-0","sequence":3}}}}"#
+            );
+
+            let any = transfer_any(
+                "ica",
+                "channel-1".to_string(),
+                coin(100, "uatom"),
+                "coordinator".to_string(),
+                Timestamp::from_nanos(42).into(),
+                Some(memo.clone()),
             );
+            assert_eq!(any.type_url, MSG_TRANSFER_TYPE_URL);
+            let msg = MsgTransfer::decode(any.value.as_slice()).unwrap();
+            assert_eq!(msg.source_port, keys::ICS20_PORT_ID);
+            assert_eq!(msg.source_channel, "channel-1");
+            assert_eq!(msg.sender, "ica");
+            assert_eq!(msg.receiver, "coordinator");
+            assert_eq!(msg.timeout_height, None);
+            assert_eq!(msg.timeout_timestamp, 42);
+            assert_eq!(msg.memo, memo);
+        }
+    }
+}
+
+/// Contains the decoding of the acknowledgement results of the ICA host.
+pub mod ica_ack {
+    use cosmos_sdk_proto::{cosmos::base::v1beta1::Coin as ProtoCoin, Any};
+    use cosmwasm_std::{Coin, StdError, StdResult, Timestamp, Uint128};
+    use prost::Message;
+
+    use super::ics20::MsgTransferResponse;
+    use crate::types::state::history::MsgResult;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct TxMsgData {
+        /// The responses of the messages before Cosmos SDK v0.46.
+        #[prost(message, repeated, tag = "1")]
+        data: Vec<MsgData>,
+        #[prost(message, repeated, tag = "2")]
+        msg_responses: Vec<Any>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgData {
+        #[prost(string, tag = "1")]
+        msg_type: String,
+        #[prost(bytes = "vec", tag = "2")]
+        data: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ProtoTimestamp {
+        #[prost(int64, tag = "1")]
+        seconds: i64,
+        #[prost(int32, tag = "2")]
+        nanos: i32,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgUndelegateResponse {
+        #[prost(message, optional, tag = "1")]
+        completion_time: Option<ProtoTimestamp>,
+        /// Only set since Cosmos SDK v0.50.
+        #[prost(message, optional, tag = "2")]
+        amount: Option<ProtoCoin>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgBeginRedelegateResponse {
+        #[prost(message, optional, tag = "1")]
+        completion_time: Option<ProtoTimestamp>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgWithdrawDelegatorRewardResponse {
+        #[prost(message, repeated, tag = "1")]
+        amount: Vec<ProtoCoin>,
+    }
+
+    /// Decodes the results of the messages of an ICA transaction from the `TxMsgData` of its
+    /// successful acknowledgement.
+    pub fn decode_msg_results(data: &[u8]) -> StdResult<Vec<MsgResult>> {
+        let tx_msg_data = TxMsgData::decode(data).map_err(invalid_response)?;
+
+        if !tx_msg_data.msg_responses.is_empty() {
+            return tx_msg_data
+                .msg_responses
+                .iter()
+                .map(|any| decode_msg_result(&any.type_url, &any.value))
+                .collect();
+        }
+
+        // Before Cosmos SDK v0.46, the responses are identified by the type URL of their message.
+        tx_msg_data
+            .data
+            .iter()
+            .map(|msg_data| decode_msg_result(&msg_data.msg_type, &msg_data.data))
+            .collect()
+    }
+
+    /// Decodes the result of a message from its response and the type URL of either the
+    /// message or the response.
+    fn decode_msg_result(type_url: &str, value: &[u8]) -> StdResult<MsgResult> {
+        let result = match type_url.trim_end_matches("Response") {
+            "/cosmos.staking.v1beta1.MsgDelegate" => MsgResult::Delegate,
+            "/cosmos.staking.v1beta1.MsgUndelegate" => {
+                let response = MsgUndelegateResponse::decode(value).map_err(invalid_response)?;
+                MsgResult::Undelegate {
+                    completion_time: response.completion_time.and_then(timestamp),
+                    amount: response.amount.map(coin).transpose()?,
+                }
+            }
+            "/cosmos.staking.v1beta1.MsgBeginRedelegate" => {
+                let response =
+                    MsgBeginRedelegateResponse::decode(value).map_err(invalid_response)?;
+                MsgResult::Redelegate {
+                    completion_time: response.completion_time.and_then(timestamp),
+                }
+            }
+            "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward" => {
+                let response =
+                    MsgWithdrawDelegatorRewardResponse::decode(value).map_err(invalid_response)?;
+                MsgResult::WithdrawRewards {
+                    amount: response
+                        .amount
+                        .into_iter()
+                        .map(coin)
+                        .collect::<StdResult<_>>()?,
+                }
+            }
+            "/ibc.applications.transfer.v1.MsgTransfer" => {
+                let response = MsgTransferResponse::decode(value).map_err(invalid_response)?;
+                MsgResult::Transfer {
+                    sequence: response.sequence,
+                }
+            }
+            _ => MsgResult::Unknown {
+                type_url: type_url.to_string(),
+            },
+        };
+
+        Ok(result)
+    }
+
+    /// Returns `None` if the timestamp cannot be represented in nanoseconds.
+    fn timestamp(timestamp: ProtoTimestamp) -> Option<Timestamp> {
+        let nanos = u64::try_from(timestamp.seconds.max(0))
+            .ok()?
+            .checked_mul(1_000_000_000)?
+            .checked_add(timestamp.nanos.max(0) as u64)?;
+        Some(Timestamp::from_nanos(nanos))
+    }
 
-            nft_ica_bi_map.remove(&mut storage, "ica-addr-1").unwrap();
+    fn coin(coin: ProtoCoin) -> StdResult<Coin> {
+        Ok(Coin {
+            denom: coin.denom,
+            amount: coin.amount.parse::<Uint128>()?,
+        })
+    }
+
+    fn invalid_response(err: prost::DecodeError) -> StdError {
+        StdError::generic_err(format!("invalid acknowledgement result: {}", err))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_decode_msg_results() {
+            let undelegate = MsgUndelegateResponse {
+                completion_time: Some(ProtoTimestamp {
+                    seconds: 1_700_000_000,
+                    nanos: 5,
+                }),
+                amount: None,
+            };
+            let transfer = MsgTransferResponse { sequence: 7 };
+            let data = TxMsgData {
+                data: vec![],
+                msg_responses: vec![
+                    Any {
+                        type_url: "/cosmos.staking.v1beta1.MsgDelegateResponse".to_string(),
+                        value: vec![],
+                    },
+                    Any {
+                        type_url: "/cosmos.staking.v1beta1.MsgUndelegateResponse".to_string(),
+                        value: undelegate.encode_to_vec(),
+                    },
+                    Any {
+                        type_url: "/ibc.applications.transfer.v1.MsgTransferResponse".to_string(),
+                        value: transfer.encode_to_vec(),
+                    },
+                    Any {
+                        type_url: "/cosmos.bank.v1beta1.MsgSendResponse".to_string(),
+                        value: vec![],
+                    },
+                ],
+            };
 
-            assert!(nft_ica_bi_map.load(&storage, "ica-addr-1").is_err());
-            assert!(nft_ica_bi_map.load(&storage, "nft-id-1").is_err());
             assert_eq!(
-                nft_ica_bi_map.load(&storage, "ica-addr-2").unwrap(),
-                "nft-id-2"
+                decode_msg_results(&data.encode_to_vec()).unwrap(),
+                vec![
+                    MsgResult::Delegate,
+                    MsgResult::Undelegate {
+                        completion_time: Some(Timestamp::from_nanos(1_700_000_000_000_000_005)),
+                        amount: None,
+                    },
+                    MsgResult::Transfer { sequence: 7 },
+                    MsgResult::Unknown {
+                        type_url: "/cosmos.bank.v1beta1.MsgSendResponse".to_string(),
+                    },
+                ]
             );
+
+            // responses before Cosmos SDK v0.46
+            let legacy = TxMsgData {
+                data: vec![MsgData {
+                    msg_type: "/ibc.applications.transfer.v1.MsgTransfer".to_string(),
+                    data: transfer.encode_to_vec(),
+                }],
+                msg_responses: vec![],
+            };
+            assert_eq!(
+                decode_msg_results(&legacy.encode_to_vec()).unwrap(),
+                vec![MsgResult::Transfer { sequence: 7 }]
+            );
+        }
+
+        #[test]
+        fn test_decode_msg_results_timestamp_overflow() {
+            let undelegate = MsgUndelegateResponse {
+                completion_time: Some(ProtoTimestamp {
+                    seconds: i64::MAX,
+                    nanos: 0,
+                }),
+                amount: None,
+            };
+            let data = TxMsgData {
+                data: vec![],
+                msg_responses: vec![Any {
+                    type_url: "/cosmos.staking.v1beta1.MsgUndelegateResponse".to_string(),
+                    value: undelegate.encode_to_vec(),
+                }],
+            };
+
             assert_eq!(
-                nft_ica_bi_map.load(&storage, "nft-id-2").unwrap(),
-                "ica-addr-2"
+                decode_msg_results(&data.encode_to_vec()).unwrap(),
+                vec![MsgResult::Undelegate {
+                    completion_time: None,
+                    amount: None,
+                }]
             );
+        }
+    }
+}
+
+/// Contains the messages of the ICS-29 fee middleware.
+pub mod ics29 {
+    use cosmwasm_std::CosmosMsg;
+    use prost::Message;
 
-            nft_ica_bi_map.remove(&mut storage, "nft-id-2").unwrap();
+    use super::{proto_fee, Fee};
+    use crate::types::state::fees::PacketFee;
 
-            assert!(nft_ica_bi_map.load(&storage, "ica-addr-1").is_err());
-            assert!(nft_ica_bi_map.load(&storage, "nft-id-1").is_err());
-            assert!(nft_ica_bi_map.load(&storage, "ica-addr-2").is_err());
-            assert!(nft_ica_bi_map.load(&storage, "nft-id-2").is_err());
+    const MSG_PAY_PACKET_FEE_TYPE_URL: &str = "/ibc.applications.fee.v1.MsgPayPacketFee";
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MsgPayPacketFee {
+        #[prost(message, optional, tag = "1")]
+        fee: Option<Fee>,
+        #[prost(string, tag = "2")]
+        source_port_id: String,
+        #[prost(string, tag = "3")]
+        source_channel_id: String,
+        #[prost(string, tag = "4")]
+        signer: String,
+        #[prost(string, repeated, tag = "5")]
+        relayers: Vec<String>,
+    }
+
+    /// Returns the message that escrows the given fee for the next packet sent on the given channel.
+    /// It must be dispatched right before the message that sends the packet.
+    pub fn pay_packet_fee_msg(
+        signer: &str,
+        port_id: &str,
+        channel_id: &str,
+        fee: &PacketFee,
+    ) -> CosmosMsg {
+        let msg = MsgPayPacketFee {
+            fee: Some(proto_fee(fee)),
+            source_port_id: port_id.to_string(),
+            source_channel_id: channel_id.to_string(),
+            signer: signer.to_string(),
+            relayers: vec![],
+        };
+
+        CosmosMsg::Stargate {
+            type_url: MSG_PAY_PACKET_FEE_TYPE_URL.to_string(),
+            value: msg.encode_to_vec().into(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The merkle tree of the sha256 hashes of "alice", "bob", "carol" and "dave".
+    const ROOT: &str = "1f2829c665271766c82831f6b472b40aad1ac9d27aa91aa1523f1f84984485f9";
+
+    fn proof(hashes: &[&str]) -> Vec<HexBinary> {
+        hashes
+            .iter()
+            .map(|h| HexBinary::from_hex(h).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_merkle_proof() {
+        let root = HexBinary::from_hex(ROOT).unwrap();
+
+        let alice_proof = proof(&[
+            "81b637d8fcd2c6da6359e6963113a1170de795e4b725b84d1e0b4cfd9ec58ce9",
+            "e1b219f95e3293ebe8f25e39d2b5ba02b7bb68daa4d3a0cc3323761fab6a9efc",
+        ]);
+        let carol_proof = proof(&[
+            "61ea0803f8853523b777d414ace3130cd4d3f92de2cd7ff8695c337d79c2eeee",
+            "cb57721dc3aa8df0eef91989560b053a86be98131f45650bd1c3955e0167ef17",
+        ]);
+
+        assert!(verify_merkle_proof(&root, b"alice", &alice_proof));
+        assert!(verify_merkle_proof(&root, b"carol", &carol_proof));
+
+        assert!(!verify_merkle_proof(&root, b"bob", &alice_proof));
+        assert!(!verify_merkle_proof(&root, b"alice", &carol_proof));
+        assert!(!verify_merkle_proof(&root, b"alice", &[]));
+        assert!(!verify_merkle_proof(&root, b"eve", &alice_proof));
+    }
+}